@@ -0,0 +1,151 @@
+// Copyright 2019 click2stream, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! HTTP server codec for the local management API.
+
+use bytes::BytesMut;
+
+use tokio::codec::{Decoder, Encoder};
+
+use crate::net::http::generic::{
+    Error, FixedSizeBodyDecoder, Request, RequestHeader, RequestHeaderDecoder, Response,
+};
+
+/// Maximum length of a request header line.
+const MAX_LINE_LENGTH: usize = 4096;
+
+/// Maximum number of request header lines.
+const MAX_HEADER_LINES: usize = 64;
+
+/// Maximum size of a request body.
+const MAX_BODY_SIZE: usize = 65536;
+
+/// HTTP server codec. It decodes requests and encodes responses.
+pub struct ApiCodec {
+    hdecoder: RequestHeaderDecoder,
+    bdecoder: Option<FixedSizeBodyDecoder>,
+    header: Option<RequestHeader>,
+}
+
+impl ApiCodec {
+    /// Create a new codec.
+    pub fn new() -> ApiCodec {
+        ApiCodec {
+            hdecoder: RequestHeaderDecoder::new(MAX_LINE_LENGTH, MAX_HEADER_LINES),
+            bdecoder: None,
+            header: None,
+        }
+    }
+}
+
+impl Decoder for ApiCodec {
+    type Item = Request;
+    type Error = Error;
+
+    fn decode(&mut self, data: &mut BytesMut) -> Result<Option<Request>, Error> {
+        if self.header.is_none() {
+            let header = match self.hdecoder.decode(data)? {
+                Some(header) => header,
+                None => return Ok(None),
+            };
+
+            if header.get_header_field_value("transfer-encoding").is_some() {
+                return Err(Error::from("transfer encoding is not supported"));
+            }
+
+            let length = header
+                .get_header_field_value("content-length")
+                .map(|length| length.trim().parse::<usize>())
+                .unwrap_or(Ok(0))
+                .map_err(|_| Error::from("invalid content length"))?;
+
+            if length > MAX_BODY_SIZE {
+                return Err(Error::from("request body is too large"));
+            }
+
+            self.bdecoder = Some(FixedSizeBodyDecoder::new(length, false));
+            self.header = Some(header);
+        }
+
+        let body = match self.bdecoder.as_mut() {
+            Some(bdecoder) => bdecoder.decode(data)?,
+            None => None,
+        };
+
+        if let Some(body) = body {
+            let header = self.header.take().expect("missing request header");
+
+            self.bdecoder = None;
+
+            Ok(Some(Request::from_parts(header, body)))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+impl Encoder for ApiCodec {
+    type Item = Response;
+    type Error = Error;
+
+    fn encode(&mut self, response: Response, data: &mut BytesMut) -> Result<(), Error> {
+        let header = format!("{}", response.header());
+        let body = response.body();
+
+        data.extend_from_slice(header.as_bytes());
+        data.extend_from_slice(body);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_request_decoding() {
+        let mut codec = ApiCodec::new();
+
+        let mut data = BytesMut::from(
+            &b"POST /scan HTTP/1.1\r\nHost: localhost\r\nContent-Length: 4\r\n\r\nbo"[..],
+        );
+
+        assert!(codec.decode(&mut data).unwrap().is_none());
+
+        data.extend_from_slice(b"dyGET /status HTTP/1.1\r\n\r\n");
+
+        let request = codec.decode(&mut data).unwrap().unwrap();
+
+        assert_eq!(request.header().method(), "POST");
+        assert_eq!(request.header().path(), "/scan");
+        assert_eq!(request.header().version(), "1.1");
+        assert_eq!(request.body(), b"body");
+
+        let request = codec.decode(&mut data).unwrap().unwrap();
+
+        assert_eq!(request.header().method(), "GET");
+        assert_eq!(request.header().path(), "/status");
+        assert!(request.body().is_empty());
+    }
+
+    #[test]
+    fn test_invalid_request() {
+        let mut codec = ApiCodec::new();
+
+        let mut data = BytesMut::from(&b"GET /status HTTP/1.1\r\nContent-Length: foo\r\n\r\n"[..]);
+
+        assert!(codec.decode(&mut data).is_err());
+    }
+}
@@ -0,0 +1,306 @@
+// Copyright 2019 click2stream, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Request handler for the local management API.
+
+use std::str;
+
+use std::net::{IpAddr, SocketAddr};
+
+use json;
+
+use json::JsonValue;
+
+use crate::cmd_handler::{Command, CommandChannel};
+use crate::context::ApplicationContext;
+use crate::net::http::generic::{Request, Response, ResponseBuilder};
+use crate::utils::json::ToJson;
+use crate::utils::logger::filter::LogFilter;
+
+/// Management API request handler.
+#[derive(Clone)]
+pub struct ApiHandler {
+    app_context: ApplicationContext,
+    cmd_channel: CommandChannel,
+    bound_addr: Option<SocketAddr>,
+}
+
+impl ApiHandler {
+    /// Create a new request handler. The bound address should be given for
+    /// TCP listeners, the Host header of all requests is checked in such
+    /// case.
+    pub fn new(
+        app_context: ApplicationContext,
+        cmd_channel: CommandChannel,
+        bound_addr: Option<SocketAddr>,
+    ) -> ApiHandler {
+        ApiHandler {
+            app_context,
+            cmd_channel,
+            bound_addr,
+        }
+    }
+
+    /// Handle a given request.
+    pub fn handle(&self, request: &Request) -> Response {
+        let header = request.header();
+
+        let method = header.method();
+        let path = header.path().split('?').next().unwrap_or("");
+
+        // NOTE: a web page can reach the API using DNS rebinding; the Host
+        // header contains the name of the attacker's domain in such case
+        if let Some(addr) = self.bound_addr {
+            let host = header.get_header_field_value("host").unwrap_or("");
+
+            if !is_allowed_host(host, addr) {
+                return error_response(403, "Forbidden", "invalid host");
+            }
+        }
+
+        // NOTE: web browsers send the Origin header with all cross-origin
+        // POST requests; we do not want any web page to trigger our actions
+        if method == "POST" && header.get_header_field_value("origin").is_some() {
+            return error_response(403, "Forbidden", "cross-origin requests are not allowed");
+        }
+
+        match (method, path) {
+            ("GET", "/status") => self.status(),
+            ("GET", "/metrics") => self.metrics(),
+            ("GET", "/services") => self.services(),
+            ("GET", "/scan") => self.scan_report(),
+            ("POST", "/scan") => self.scan_network(),
+            ("POST", "/services/reset") => self.reset_service_table(),
+            ("POST", "/pairing-window") => self.reset_pairing_window(),
+            ("GET", "/log-level") => self.log_level(),
+            ("POST", "/log-level") => self.set_log_level(request.body()),
+            (_, "/status")
+            | (_, "/metrics")
+            | (_, "/services")
+            | (_, "/scan")
+            | (_, "/services/reset")
+            | (_, "/pairing-window")
+            | (_, "/log-level") => error_response(405, "Method Not Allowed", "method not allowed"),
+            _ => error_response(404, "Not Found", "not found"),
+        }
+    }
+
+    /// Get the client status.
+    fn status(&self) -> Response {
+        let status = object! {
+            "state" => self.app_context.get_connection_state().to_string(),
+            "state_since" => self.app_context.get_connection_state_since() as u64,
+            "last_error" => self.app_context.get_last_error(),
+            "arrow_service" => self.app_context.get_arrow_service_address(),
+            "arrow_endpoint" => self.app_context.get_arrow_endpoint(),
+            "uuid" => format!("{}", self.app_context.get_arrow_uuid().to_hyphenated_ref()),
+            "mac" => format!("{}", self.app_context.get_arrow_mac_address()),
+            "discovery" => self.app_context.get_discovery(),
+            "scanning" => self.app_context.is_scanning(),
+            "pairing_mode" => self.app_context.is_pairing_mode(),
+            "log_level" => self.app_context.get_log_level().as_str()
+        };
+
+        json_response(200, "OK", status)
+    }
+
+    /// Get application metrics in the Prometheus text format.
+    fn metrics(&self) -> Response {
+        let conn_state = self.app_context.get_connection_state();
+
+        let body = self.app_context.get_metrics().render(conn_state);
+
+        ResponseBuilder::new("HTTP", "1.1", 200, "OK")
+            .set_header_field(("Content-Type", "text/plain; version=0.0.4"))
+            .set_header_field(("Content-Length", body.len()))
+            .set_header_field(("Connection", "close"))
+            .set_body(body)
+            .build()
+    }
+
+    /// Get the service table.
+    fn services(&self) -> Response {
+        let services = self
+            .app_context
+            .get_service_table()
+            .entries()
+            .iter()
+            .map(|entry| entry.to_json())
+            .collect::<Vec<_>>();
+
+        let res = object! {
+            "services" => services
+        };
+
+        json_response(200, "OK", res)
+    }
+
+    /// Get the last network scan report.
+    fn scan_report(&self) -> Response {
+        let res = object! {
+            "scanning" => self.app_context.is_scanning(),
+            "result" => self.app_context.get_scan_result().to_json()
+        };
+
+        json_response(200, "OK", res)
+    }
+
+    /// Trigger a network scan.
+    fn scan_network(&self) -> Response {
+        if !self.app_context.get_discovery() {
+            return error_response(409, "Conflict", "network discovery is disabled");
+        }
+
+        self.cmd_channel.send(Command::ScanNetwork);
+
+        accepted_response()
+    }
+
+    /// Reset the service table.
+    fn reset_service_table(&self) -> Response {
+        self.cmd_channel.send(Command::ResetServiceTable);
+
+        accepted_response()
+    }
+
+    /// Restart the pairing window.
+    fn reset_pairing_window(&self) -> Response {
+        self.cmd_channel.send(Command::ResetPairingWindow);
+
+        accepted_response()
+    }
+
+    /// Get the current log level and the current log filter.
+    fn log_level(&self) -> Response {
+        let filter = self.app_context.get_log_filter();
+
+        let res = object! {
+            "level" => filter.level().as_str(),
+            "filter" => filter.to_string()
+        };
+
+        json_response(200, "OK", res)
+    }
+
+    /// Change the log level. The request body is expected to be a JSON
+    /// object containing the "level" field. The field may contain either
+    /// a log level or a log filter (e.g. "info,net::arrow::session=debug").
+    fn set_log_level(&self, body: &[u8]) -> Response {
+        let level = str::from_utf8(body)
+            .ok()
+            .and_then(|body| json::parse(body).ok())
+            .and_then(|body| {
+                body["level"]
+                    .as_str()
+                    .map(|level| level.parse::<LogFilter>())
+            });
+
+        match level {
+            Some(Ok(filter)) => {
+                self.cmd_channel.send(Command::SetLogFilter(filter));
+
+                accepted_response()
+            }
+            Some(Err(err)) => error_response(400, "Bad Request", &format!("{}", err)),
+            None => error_response(400, "Bad Request", "missing log level"),
+        }
+    }
+}
+
+/// Create a new JSON response.
+fn json_response(status_code: u16, status_line: &str, body: JsonValue) -> Response {
+    let body = body.dump();
+
+    ResponseBuilder::new("HTTP", "1.1", status_code, status_line)
+        .set_header_field(("Content-Type", "application/json"))
+        .set_header_field(("Content-Length", body.len()))
+        .set_header_field(("Connection", "close"))
+        .set_body(body)
+        .build()
+}
+
+/// Create a response for an accepted action.
+fn accepted_response() -> Response {
+    let body = object! {
+        "result" => "accepted"
+    };
+
+    json_response(202, "Accepted", body)
+}
+
+/// Check if a given value of the Host header is allowed for a management API
+/// listener bound to a given address. Only "localhost" and the loopback
+/// addresses are allowed. The port (if present) must be equal to the bound
+/// port.
+fn is_allowed_host(host: &str, addr: SocketAddr) -> bool {
+    let host = host.trim();
+
+    let (name, port) = if let Some(end) = host.find(']').filter(|_| host.starts_with('[')) {
+        (&host[..=end], &host[end + 1..])
+    } else if let Some(pos) = host.rfind(':') {
+        (&host[..pos], &host[pos..])
+    } else {
+        (host, "")
+    };
+
+    if !port.is_empty() && port != format!(":{}", addr.port()) {
+        return false;
+    }
+
+    let name = name.to_ascii_lowercase();
+
+    let bound = match addr.ip() {
+        IpAddr::V4(ip) => ip.to_string(),
+        IpAddr::V6(ip) => format!("[{}]", ip),
+    };
+
+    name == "localhost" || name == "127.0.0.1" || name == "[::1]" || name == bound
+}
+
+/// Create a new error response.
+pub fn error_response(status_code: u16, status_line: &str, msg: &str) -> Response {
+    let body = object! {
+        "error" => msg
+    };
+
+    json_response(status_code, status_line, body)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_allowed_hosts() {
+        let addr = "127.0.0.2:8901".parse().unwrap();
+
+        assert!(is_allowed_host("localhost", addr));
+        assert!(is_allowed_host("LocalHost:8901", addr));
+        assert!(is_allowed_host("127.0.0.1:8901", addr));
+        assert!(is_allowed_host("127.0.0.2", addr));
+        assert!(is_allowed_host("[::1]:8901", addr));
+
+        assert!(!is_allowed_host("", addr));
+        assert!(!is_allowed_host("localhost:8902", addr));
+        assert!(!is_allowed_host("attacker.example:8901", addr));
+        assert!(!is_allowed_host("localhost.attacker.example", addr));
+        assert!(!is_allowed_host("[::1]8901", addr));
+
+        let addr = "[::1]:8901".parse().unwrap();
+
+        assert!(is_allowed_host("[::1]:8901", addr));
+        assert!(is_allowed_host("localhost:8901", addr));
+        assert!(!is_allowed_host("[::2]:8901", addr));
+    }
+}
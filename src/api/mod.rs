@@ -0,0 +1,219 @@
+// Copyright 2019 click2stream, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Local management HTTP API.
+//!
+//! The API is disabled by default. It can listen either on a loopback TCP
+//! address or on a Unix socket. The following endpoints are available:
+//!
+//! * `GET /status` - connection state, Arrow Service endpoint and public
+//!   client identity
+//! * `GET /metrics` - application metrics in the Prometheus text format
+//! * `GET /services` - service table including visibility flags
+//! * `GET /scan` - the last network scan result
+//! * `POST /scan` - trigger a network scan
+//! * `POST /services/reset` - reset the service table
+//! * `POST /pairing-window` - restart the pairing window
+//! * `GET /log-level` - get the current log level
+//! * `POST /log-level` - change the log level (e.g. `{"level": "debug"}`)
+
+mod codec;
+mod handler;
+
+use std::fmt;
+use std::fs;
+use std::io;
+
+use std::fmt::{Display, Formatter};
+use std::net::SocketAddr;
+use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use futures::{Future, Poll, Sink, Stream};
+
+use tokio;
+
+use tokio::codec::Decoder;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::{TcpListener, UnixListener};
+use tokio::timer::Timeout;
+
+use crate::cmd_handler::CommandChannel;
+use crate::context::ApplicationContext;
+use crate::utils::logger::{BoxLogger, Logger};
+
+use self::codec::ApiCodec;
+use self::handler::ApiHandler;
+
+/// Maximum time for receiving a request.
+const REQUEST_TIMEOUT: u64 = 10;
+
+/// Listening address of the local management API.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum ApiAddress {
+    Tcp(SocketAddr),
+    Unix(PathBuf),
+}
+
+impl Display for ApiAddress {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
+        match self {
+            ApiAddress::Tcp(addr) => write!(f, "{}", addr),
+            ApiAddress::Unix(path) => write!(f, "unix:{}", path.display()),
+        }
+    }
+}
+
+/// Management API server. It is a future that accepts incoming connections
+/// until it is dropped.
+pub struct ApiServer {
+    inner: Box<dyn Future<Item = (), Error = ()> + Send>,
+    _socket: Option<SocketFile>,
+}
+
+impl Future for ApiServer {
+    type Item = ();
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<(), ()> {
+        self.inner.poll()
+    }
+}
+
+/// Create a new management API server listening on a given address.
+pub fn new(
+    app_context: ApplicationContext,
+    cmd_channel: CommandChannel,
+    addr: &ApiAddress,
+) -> Result<ApiServer, io::Error> {
+    let logger = app_context.get_logger();
+
+    let server = match addr {
+        ApiAddress::Tcp(addr) => {
+            let listener = TcpListener::bind(addr)?;
+
+            let handler = ApiHandler::new(app_context, cmd_channel, Some(*addr));
+
+            let inner = accept_connections(listener.incoming(), handler, logger);
+
+            ApiServer {
+                inner: Box::new(inner),
+                _socket: None,
+            }
+        }
+        ApiAddress::Unix(path) => {
+            let socket = SocketFile::new(path)?;
+            let listener = UnixListener::bind(path)?;
+
+            fs::set_permissions(path, fs::Permissions::from_mode(0o660))?;
+
+            // web browsers cannot connect to Unix sockets
+            let handler = ApiHandler::new(app_context, cmd_channel, None);
+
+            let inner = accept_connections(listener.incoming(), handler, logger);
+
+            ApiServer {
+                inner: Box::new(inner),
+                _socket: Some(socket),
+            }
+        }
+    };
+
+    Ok(server)
+}
+
+/// Handle all connections from a given stream of incoming connections.
+fn accept_connections<I, S>(
+    incoming: I,
+    handler: ApiHandler,
+    mut logger: BoxLogger,
+) -> impl Future<Item = (), Error = ()>
+where
+    I: Stream<Item = S, Error = io::Error>,
+    S: 'static + AsyncRead + AsyncWrite + Send,
+{
+    incoming.then(Ok).for_each(move |res| {
+        match res {
+            Ok(stream) => {
+                tokio::spawn(handle_connection(stream, handler.clone()));
+            }
+            Err(err) => log_warn!(logger, "unable to accept API connection: {}", err),
+        }
+
+        Ok(())
+    })
+}
+
+/// Handle a single request from a given connection and close the connection.
+fn handle_connection<S>(stream: S, handler: ApiHandler) -> impl Future<Item = (), Error = ()>
+where
+    S: 'static + AsyncRead + AsyncWrite + Send,
+{
+    let (sink, stream) = ApiCodec::new().framed(stream).split();
+
+    let request = stream
+        .into_future()
+        .map(|(request, _)| request)
+        .map_err(|(err, _)| err);
+
+    Timeout::new(request, Duration::from_secs(REQUEST_TIMEOUT))
+        .then(move |res| match res {
+            Ok(Some(request)) => Ok(handler.handle(&request)),
+            Ok(None) => Err(()),
+            Err(err) => match err.into_inner() {
+                Some(err) => Ok(handler::error_response(
+                    400,
+                    "Bad Request",
+                    &format!("{}", err),
+                )),
+                None => Err(()),
+            },
+        })
+        .and_then(move |response| sink.send(response).map(|_| ()).map_err(|_| ()))
+        .or_else(|_| Ok(()))
+}
+
+/// Unix socket file. The file is removed when dropped.
+struct SocketFile {
+    path: PathBuf,
+}
+
+impl SocketFile {
+    /// Remove a stale socket file at a given path (if any).
+    fn new(path: &Path) -> Result<SocketFile, io::Error> {
+        match fs::symlink_metadata(path) {
+            Ok(ref metadata) if metadata.file_type().is_socket() => fs::remove_file(path)?,
+            Ok(_) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    format!("\"{}\" exists and it is not a socket", path.display()),
+                ))
+            }
+            Err(_) => (),
+        }
+
+        let res = SocketFile {
+            path: path.to_path_buf(),
+        };
+
+        Ok(res)
+    }
+}
+
+impl Drop for SocketFile {
+    fn drop(&mut self) {
+        fs::remove_file(&self.path).unwrap_or(());
+    }
+}
@@ -12,7 +12,6 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-#[cfg(feature = "discovery")]
 use std::thread;
 
 use std::thread::JoinHandle;
@@ -28,28 +27,30 @@ use time;
 #[cfg(feature = "discovery")]
 use crate::scanner::discovery;
 
+#[cfg(feature = "discovery")]
+use crate::hooks;
 #[cfg(feature = "discovery")]
 use crate::utils;
-
-use crate::context::ApplicationContext;
-use crate::utils::logger::{BoxLogger, Logger};
-
 #[cfg(feature = "discovery")]
 use crate::utils::logger::Severity;
 
-/// Network scan period.
-const NETWORK_SCAN_PERIOD: f64 = 300.0;
+use crate::context::ApplicationContext;
+use crate::utils::logger::filter::LogFilter;
+use crate::utils::logger::{BoxLogger, Logger};
 
 /// Different command types that the command handler can receive.
-#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[derive(Debug, Clone, Eq, PartialEq)]
 pub enum Command {
     ResetServiceTable,
     ScanNetwork,
     PeriodicNetworkScan,
+    ReloadConfig,
+    ResetPairingWindow,
+    SetLogFilter(LogFilter),
 }
 
 /// Command handler event.
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Clone)]
 enum Event {
     Command(Command),
     ScanCompleted,
@@ -119,19 +120,62 @@ impl CommandHandlerContext {
             Command::ResetServiceTable => self.reset_service_table(),
             Command::ScanNetwork => self.scan_network(),
             Command::PeriodicNetworkScan => self.periodic_network_scan(),
+            Command::ReloadConfig => self.reload_config(),
+            Command::ResetPairingWindow => self.reset_pairing_window(),
+            Command::SetLogFilter(filter) => self.set_log_filter(filter),
         }
     }
 
+    /// Reload application configuration. The configuration is loaded in a
+    /// separate thread because it may involve DNS lookups.
+    fn reload_config(&mut self) {
+        let mut app_context = self.app_context.clone();
+        let mut logger = self.logger.clone();
+
+        thread::spawn(move || {
+            log_info!(logger, "reloading configuration...");
+
+            if let Err(err) = app_context.reload_config() {
+                log_warn!(logger, "unable to reload configuration: {}", err);
+            }
+        });
+    }
+
     /// Reset service table.
     fn reset_service_table(&mut self) {
         self.app_context.reset_service_table()
     }
 
+    /// Restart the pairing window.
+    fn reset_pairing_window(&mut self) {
+        log_info!(self.logger, "pairing window reopened");
+
+        self.app_context.reset_pairing_window()
+    }
+
+    /// Change the log level (i.e. the log filter).
+    fn set_log_filter(&mut self, filter: LogFilter) {
+        let current = self.app_context.get_log_filter();
+
+        if current != filter {
+            log_info!(
+                self.logger,
+                "log level changed from {} to {}",
+                current,
+                filter
+            );
+
+            self.app_context.set_log_filter(filter);
+        }
+    }
+
     /// Trigger periodic netork scan.
     fn periodic_network_scan(&mut self) {
         let t = time::precise_time_s();
 
-        if (self.last_nw_scan + NETWORK_SCAN_PERIOD) < t {
+        let period = self.app_context.get_scan_period().as_secs() as f64;
+
+        if (self.last_nw_scan + period) < t {
             self.scan_network();
         }
     }
@@ -139,10 +183,17 @@ impl CommandHandlerContext {
     #[cfg(feature = "discovery")]
     /// Trigger a network scan.
     fn scan_network(&mut self) {
-        if !self.app_context.get_discovery() || self.scanner.is_some() {
+        if !self.app_context.get_discovery()
+            || self.app_context.is_shutting_down()
+            || self.scanner.is_some()
+        {
             return;
         }
 
+        // the flag needs to be set before the thread is started, otherwise
+        // a shutdown could miss a freshly started scanner
+        self.app_context.set_scanning(true);
+
         let app_context = self.app_context.clone();
         let cmd_sender = self.cmd_sender.clone();
 
@@ -230,15 +281,24 @@ fn network_scanner_thread(mut app_context: ApplicationContext) {
     let rtsp_paths_file = app_context.get_rtsp_paths_file();
     let mjpeg_paths_file = app_context.get_mjpeg_paths_file();
 
-    app_context.set_scanning(true);
+    let rtsp_ports = app_context.get_rtsp_port_candidates();
+    let http_ports = app_context.get_http_port_candidates();
 
     log_info!(logger, "looking for local services...");
 
+    let start = time::precise_time_s();
+
     let result = utils::result_or_log(
         &mut logger,
         Severity::WARN,
         "network scanner error",
-        discovery::scan_network(slogger, &rtsp_paths_file, &mjpeg_paths_file),
+        discovery::scan_network(
+            slogger,
+            &rtsp_paths_file,
+            &mjpeg_paths_file,
+            &rtsp_ports,
+            &http_ports,
+        ),
     );
 
     if let Some(result) = result {
@@ -246,9 +306,24 @@ fn network_scanner_thread(mut app_context: ApplicationContext) {
 
         let count = services.len();
 
+        let duration = time::precise_time_s() - start;
+        let hosts = result.hosts().count();
+
+        app_context
+            .get_metrics()
+            .scan_completed(duration, hosts, count);
+
         app_context.update_service_table(services);
         app_context.set_scan_result(result);
 
+        app_context
+            .get_event_hooks()
+            .emit(hooks::Event::ScanCompleted {
+                duration,
+                hosts,
+                services: count,
+            });
+
         log_info!(
             logger,
             "{} services found, current service table: {}",
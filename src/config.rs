@@ -12,20 +12,32 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+pub mod identity;
+
+mod options;
+mod schema;
+mod secrets;
+
 use std;
+use std::cmp;
 
+use std::env;
 use std::fmt;
+use std::iter;
 use std::process;
 use std::str;
 
-use std::env::Args;
+use std::collections::HashSet;
 use std::error::Error;
 use std::fmt::{Display, Formatter};
+use std::fs;
+
 use std::fs::File;
 use std::io::{Read, Write};
 use std::net::{SocketAddr, SocketAddrV4, SocketAddrV6};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::time::Duration;
 
 use fs2::FileExt;
 
@@ -39,17 +51,37 @@ use uuid::Uuid;
 
 use crate::net;
 use crate::utils;
+
+use crate::api::ApiAddress;
+use crate::hooks::{Event, EventHooks, DEFAULT_HOOK_TIMEOUT, DEFAULT_MAX_RUNNING_HOOKS};
+use crate::metrics::Metrics;
+use crate::scanner::{HTTP_PORT_CANDIDATES, RTSP_PORT_CANDIDATES};
 use crate::utils::logger;
 
 use crate::net::raw::devices::EthernetDevice;
 use crate::net::raw::ether::MacAddr;
 use crate::net::tls::TlsConnector;
 use crate::net::url::Url;
-use crate::svc_table::{Service, SharedServiceTable, SharedServiceTableRef};
+use crate::svc_table::policy::POLICY_SERVICE_TYPES;
+use crate::svc_table::{
+    DestinationPolicy, HttpPathPolicy, HttpPathRule, IpNetwork, Service, ServiceTableEntry,
+    SharedServiceTable, SharedServiceTableRef,
+};
 use crate::utils::json::{FromJson, ParseError, ToJson};
+use crate::utils::logger::file::{FileLogger, RotationPeriod};
+use crate::utils::logger::filter::LogFilter;
+use crate::utils::logger::format::LogFormat;
+use crate::utils::logger::remote_syslog::{self, Facility, Protocol, Transport};
+use crate::utils::logger::ring::{LogRingBuffer, RingBufferLogger};
 use crate::utils::logger::{BoxLogger, Logger, Severity};
 use crate::utils::RuntimeError;
 
+use self::options::Options;
+use self::secrets::{KeySource, SecretsKey};
+
+/// Command line argument iterator.
+type Args = std::vec::IntoIter<String>;
+
 /*const EXIT_CODE_USAGE:         i32 = 1;
 const EXIT_CODE_NETWORK_ERROR: i32 = 2;
 const EXIT_CODE_CONFIG_ERROR:  i32 = 3;
@@ -73,12 +105,64 @@ const RTSP_PATHS_FILE: &'static str = "/etc/arrow/rtsp-paths";
 /// line).
 const MJPEG_PATHS_FILE: &'static str = "/etc/arrow/mjpeg-paths";
 
+/// Default path to the control socket.
+pub const CONTROL_SOCKET: &str = "/var/run/arrow/control.sock";
+
+/// Environment variable containing path to the options file.
+const OPTIONS_FILE_ENV: &str = "ARROW_OPTIONS_FILE";
+
+/// Default number of kept previous configuration file generations.
+const DEFAULT_CONFIG_GENERATIONS: usize = 3;
+
 /// Default port number for connecting to an Arrow Service.
 const DEFAULT_ARROW_SERVICE_PORT: u16 = 8900;
 
+/// Default network scan period (in seconds).
+const DEFAULT_SCAN_PERIOD: u64 = 300;
+
+/// Default timeout for connecting to an Arrow Service (in seconds).
+const DEFAULT_CONNECTION_TIMEOUT: u64 = 20;
+
+/// Default minimum time between two connection attempts (in seconds).
+const DEFAULT_RETRY_TIMEOUT: u64 = 60;
+
+/// Default length of the pairing window (in seconds).
+const DEFAULT_PAIRING_WINDOW: u64 = 1200;
+
 /// List of cipher that can be used for TLS connections to Arrow services.
 const SSL_CIPHER_LIST: &'static str = "HIGH:!aNULL:!kRSA:!PSK:!MD5:!RC4";
 
+/// Format of the connection state file.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum StateFileFormat {
+    /// JSON object containing the connection state and other details.
+    Json,
+    /// A single word representing the connection state.
+    Plain,
+}
+
+impl StateFileFormat {
+    /// Get string representation of the format.
+    fn as_str(self) -> &'static str {
+        match self {
+            StateFileFormat::Json => "json",
+            StateFileFormat::Plain => "plain",
+        }
+    }
+}
+
+impl FromStr for StateFileFormat {
+    type Err = ConfigError;
+
+    fn from_str(s: &str) -> Result<StateFileFormat, ConfigError> {
+        match s {
+            "json" => Ok(StateFileFormat::Json),
+            "plain" => Ok(StateFileFormat::Plain),
+            _ => Err(ConfigError::from("\"json\" or \"plain\" expected")),
+        }
+    }
+}
+
 /// Arrow configuration loading/parsing/saving error.
 #[derive(Debug, Clone)]
 pub struct ConfigError {
@@ -139,28 +223,72 @@ enum LoggerType {
     Stderr,
     StderrPretty,
     FileLogger,
+    RemoteSyslog,
 }
 
 /// Builder for application configuration.
 struct ApplicationConfigBuilder {
     arrow_mac: MacAddr,
     arrow_svc_addr: String,
+    interface: Option<String>,
     ca_certificates: Vec<String>,
-    services: Vec<Service>,
+    rtsp_services: Vec<String>,
+    mjpeg_services: Vec<String>,
+    http_services: Vec<String>,
+    tcp_services: Vec<String>,
     logger_type: LoggerType,
     config_file: String,
     config_file_skel: String,
+    config_generations: usize,
+    secrets_key: Option<KeySource>,
+    options_file: Option<String>,
     identity_file: Option<String>,
     state_file: String,
+    state_file_format: StateFileFormat,
     rtsp_paths_file: String,
     mjpeg_paths_file: String,
     log_file: String,
     discovery: bool,
-    verbose: bool,
+    log_level: Option<LogFilter>,
     diagnostic_mode: bool,
     log_file_size: usize,
     log_file_rotations: usize,
+    log_file_period: Option<RotationPeriod>,
+    log_file_compress: bool,
+    log_file_budget: Option<u64>,
+    log_format: LogFormat,
+    log_buffer_size: usize,
+    syslog_host: String,
+    syslog_port: Option<u16>,
+    syslog_transport: Transport,
+    syslog_protocol: Protocol,
+    syslog_facility: Facility,
+    syslog_app_name: String,
+    syslog_queue_size: usize,
+    syslog_ca_certificates: Vec<String>,
     lock_file: Option<String>,
+    api_addresses: Vec<ApiAddress>,
+    api_sockets: Vec<ApiAddress>,
+    control_socket: Option<PathBuf>,
+    event_hooks: Vec<String>,
+    event_hook_timeout: u64,
+    event_hook_concurrency: usize,
+    scan_period: u64,
+    rtsp_ports: Vec<u16>,
+    http_ports: Vec<u16>,
+    connection_timeout: u64,
+    retry_timeout: u64,
+    proxy: Option<String>,
+    pairing_window: u64,
+    pairing_trigger: Option<String>,
+    pairing_code: bool,
+    pairing_code_file: Option<String>,
+    allowed_networks: Vec<IpNetwork>,
+    allowed_ports: Vec<u16>,
+    allowed_service_types: Vec<String>,
+    http_path_rules: Vec<HttpPathRule>,
+    cli_lists: HashSet<&'static str>,
+    print_config: bool,
 }
 
 impl ApplicationConfigBuilder {
@@ -172,27 +300,601 @@ impl ApplicationConfigBuilder {
         let builder = ApplicationConfigBuilder {
             arrow_mac: default_mac_addr,
             arrow_svc_addr: String::new(),
+            interface: None,
             ca_certificates: Vec::new(),
-            services: Vec::new(),
+            rtsp_services: Vec::new(),
+            mjpeg_services: Vec::new(),
+            http_services: Vec::new(),
+            tcp_services: Vec::new(),
             logger_type: LoggerType::Syslog,
             config_file: CONFIG_FILE.to_string(),
             config_file_skel: CONFIG_FILE_SKELETON.to_string(),
+            config_generations: DEFAULT_CONFIG_GENERATIONS,
+            secrets_key: None,
+            options_file: None,
             identity_file: None,
             state_file: STATE_FILE.to_string(),
+            state_file_format: StateFileFormat::Json,
             rtsp_paths_file: RTSP_PATHS_FILE.to_string(),
             mjpeg_paths_file: MJPEG_PATHS_FILE.to_string(),
             log_file: String::new(),
             discovery: false,
-            verbose: false,
+            log_level: None,
             diagnostic_mode: false,
             log_file_size: 10 * 1024,
             log_file_rotations: 1,
+            log_file_period: None,
+            log_file_compress: false,
+            log_file_budget: None,
+            log_format: LogFormat::Text,
+            log_buffer_size: 64,
+            syslog_host: String::new(),
+            syslog_port: None,
+            syslog_transport: Transport::Udp,
+            syslog_protocol: Protocol::Rfc5424,
+            syslog_facility: Facility::USER,
+            syslog_app_name: remote_syslog::DEFAULT_APP_NAME.to_string(),
+            syslog_queue_size: remote_syslog::DEFAULT_QUEUE_SIZE,
+            syslog_ca_certificates: Vec::new(),
             lock_file: None,
+            api_addresses: Vec::new(),
+            api_sockets: Vec::new(),
+            control_socket: None,
+            event_hooks: Vec::new(),
+            event_hook_timeout: DEFAULT_HOOK_TIMEOUT,
+            event_hook_concurrency: DEFAULT_MAX_RUNNING_HOOKS,
+            scan_period: DEFAULT_SCAN_PERIOD,
+            rtsp_ports: RTSP_PORT_CANDIDATES.to_vec(),
+            http_ports: HTTP_PORT_CANDIDATES.to_vec(),
+            connection_timeout: DEFAULT_CONNECTION_TIMEOUT,
+            retry_timeout: DEFAULT_RETRY_TIMEOUT,
+            proxy: None,
+            pairing_window: DEFAULT_PAIRING_WINDOW,
+            pairing_trigger: None,
+            pairing_code: false,
+            pairing_code_file: None,
+            allowed_networks: Vec::new(),
+            allowed_ports: Vec::new(),
+            allowed_service_types: Vec::new(),
+            http_path_rules: Vec::new(),
+            cli_lists: HashSet::new(),
+            print_config: false,
         };
 
         Ok(builder)
     }
 
+    /// Apply the options file, environment variables and given command line
+    /// arguments (in this order, i.e. command line arguments have the
+    /// highest priority). A list option given in a higher priority source
+    /// replaces the whole list from lower priority sources.
+    fn configure(mut self, args: Vec<String>) -> Result<ApplicationConfigBuilder, ConfigError> {
+        // the path to the options file must be known in advance
+        let options_file = args
+            .iter()
+            .filter_map(|arg| arg.strip_prefix("--options-file="))
+            .next_back()
+            .map(|path| path.to_string())
+            .or_else(|| env::var(OPTIONS_FILE_ENV).ok());
+
+        if let Some(options_file) = options_file {
+            let options = load_options_file(&options_file)?;
+
+            self.apply_options(&options, false).map_err(|err| {
+                ConfigError::from(format!(
+                    "invalid options file \"{}\": {}",
+                    options_file, err
+                ))
+            })?;
+
+            self.options_file = Some(options_file);
+        }
+
+        let options = Options::from_env()?;
+
+        self.apply_options(&options, true)?;
+
+        self.parse(args.into_iter())
+    }
+
+    /// Apply given options. The reloadable options (i.e. CA certificates,
+    /// static services, log level, destination policy and HTTP path rules)
+    /// are applied only if requested. Lists
+    /// given in the options replace the current lists.
+    fn apply_options(&mut self, options: &Options, reloadable: bool) -> Result<(), ConfigError> {
+        let invalid = |key: &str, err: ConfigError| {
+            ConfigError::from(format!("invalid value of \"{}\", {}", key, err))
+        };
+
+        if reloadable {
+            replace_list(&mut self.ca_certificates, options.ca_certificates());
+            replace_list(
+                &mut self.rtsp_services,
+                options.get_strings("rtsp_services"),
+            );
+            replace_list(
+                &mut self.mjpeg_services,
+                options.get_strings("mjpeg_services"),
+            );
+            replace_list(
+                &mut self.http_services,
+                options.get_strings("http_services"),
+            );
+            replace_list(&mut self.tcp_services, options.get_strings("tcp_services"));
+            replace_list(&mut self.allowed_networks, get_allowed_networks(options)?);
+
+            if let Some(ports) = options.get_ports("allowed_ports") {
+                self.allowed_ports = ports;
+            }
+
+            replace_list(
+                &mut self.allowed_service_types,
+                get_allowed_service_types(options)?,
+            );
+            replace_list(&mut self.http_path_rules, get_http_path_rules(options)?);
+
+            if let Some(level) = options.log_level() {
+                self.log_level = Some(level);
+            }
+        }
+
+        if let Some(addr) = options.get_str("service_address") {
+            self.set_arrow_service_address(addr);
+        }
+
+        if let Some(discovery) = options.get_bool("discovery") {
+            if discovery {
+                self.discovery()?;
+            } else {
+                self.discovery = false;
+            }
+        }
+
+        if let Some(iface) = options.get_str("interface") {
+            self.set_interface(iface)?;
+        }
+
+        if let Some(diagnostic_mode) = options.get_bool("diagnostic_mode") {
+            self.diagnostic_mode = diagnostic_mode;
+        }
+
+        if let Some(path) = options.get_str("config_file") {
+            self.config_file = path.to_string();
+        }
+
+        if let Some(path) = options.get_str("config_file_skel") {
+            self.config_file_skel = path.to_string();
+        }
+
+        if let Some(generations) = options.get_u64("config_generations") {
+            self.config_generations = generations as usize;
+        }
+
+        if let Some(source) = options.get_str("secrets_key") {
+            self.secrets_key = Some(source.parse().map_err(|err| invalid("secrets_key", err))?);
+        }
+
+        if let Some(path) = options.get_str("identity_file") {
+            self.identity_file = Some(path.to_string());
+        }
+
+        if let Some(path) = options.get_str("conn_state_file") {
+            self.state_file = path.to_string();
+        }
+
+        if let Some(format) = options.get_str("conn_state_format") {
+            self.state_file_format = format
+                .parse()
+                .map_err(|err| invalid("conn_state_format", err))?;
+        }
+
+        if let Some(path) = options.get_str("rtsp_paths") {
+            self.rtsp_paths_file = path.to_string();
+        }
+
+        if let Some(path) = options.get_str("mjpeg_paths") {
+            self.mjpeg_paths_file = path.to_string();
+        }
+
+        if options.get_bool("log_stderr") == Some(true) {
+            self.log_stderr();
+        }
+
+        if options.get_bool("log_stderr_pretty") == Some(true) {
+            self.log_stderr_pretty();
+        }
+
+        if let Some(path) = options.get_str("log_file") {
+            self.logger_type = LoggerType::FileLogger;
+            self.log_file = path.to_string();
+        }
+
+        if let Some(size) = options.get_u64("log_file_size") {
+            self.log_file_size = size as usize;
+        }
+
+        if let Some(rotations) = options.get_u64("log_file_rotations") {
+            self.log_file_rotations = rotations as usize;
+        }
+
+        if let Some(period) = options.get_str("log_file_period") {
+            let period = period
+                .parse()
+                .map_err(|err| invalid("log_file_period", ConfigError::from(format!("{}", err))))?;
+
+            self.log_file_period = Some(period);
+        }
+
+        if let Some(compress) = options.get_bool("log_file_compress") {
+            self.log_file_compress = compress;
+        }
+
+        if let Some(budget) = options.get_u64("log_file_budget") {
+            self.log_file_budget = Some(budget);
+        }
+
+        if let Some(format) = options.get_str("log_format") {
+            self.log_format = format
+                .parse()
+                .map_err(|err| invalid("log_format", ConfigError::from(format!("{}", err))))?;
+        }
+
+        if let Some(size) = options.get_u64("log_buffer_size") {
+            self.log_buffer_size = size as usize;
+        }
+
+        if let Some(host) = options.get_str("syslog_host") {
+            self.logger_type = LoggerType::RemoteSyslog;
+            self.syslog_host = host.to_string();
+        }
+
+        if let Some(port) = options.get_u64("syslog_port") {
+            if port > u64::from(u16::MAX) {
+                return Err(invalid(
+                    "syslog_port",
+                    ConfigError::from("port number expected"),
+                ));
+            }
+
+            self.syslog_port = Some(port as u16);
+        }
+
+        if let Some(transport) = options.get_str("syslog_transport") {
+            self.syslog_transport = transport.parse().map_err(|err| {
+                invalid("syslog_transport", ConfigError::from(format!("{}", err)))
+            })?;
+        }
+
+        if let Some(protocol) = options.get_str("syslog_protocol") {
+            self.syslog_protocol = protocol
+                .parse()
+                .map_err(|err| invalid("syslog_protocol", ConfigError::from(format!("{}", err))))?;
+        }
+
+        if let Some(facility) = options.get_str("syslog_facility") {
+            self.syslog_facility = facility
+                .parse()
+                .map_err(|err| invalid("syslog_facility", ConfigError::from(format!("{}", err))))?;
+        }
+
+        if let Some(name) = options.get_str("syslog_app_name") {
+            self.syslog_app_name = name.to_string();
+        }
+
+        if let Some(size) = options.get_u64("syslog_queue_size") {
+            self.syslog_queue_size = size as usize;
+        }
+
+        let syslog_ca_certificates = options.get_strings("syslog_ca_certificates");
+
+        if !syslog_ca_certificates.is_empty() {
+            self.syslog_ca_certificates = syslog_ca_certificates;
+        }
+
+        if let Some(path) = options.get_str("lock_file") {
+            self.lock_file = Some(path.to_string());
+        }
+
+        let api_addresses = options
+            .get_strings("api_addresses")
+            .iter()
+            .map(|addr| parse_api_address(addr))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|err| invalid("api_addresses", err))?;
+
+        replace_list(&mut self.api_addresses, api_addresses);
+
+        let api_sockets = options
+            .get_strings("api_sockets")
+            .iter()
+            .map(|path| parse_api_socket(path))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|err| invalid("api_sockets", err))?;
+
+        replace_list(&mut self.api_sockets, api_sockets);
+
+        if let Some(path) = options.get_str("control_socket") {
+            self.set_control_socket(path);
+        }
+
+        replace_list(&mut self.event_hooks, options.get_strings("event_hooks"));
+
+        if let Some(timeout) = options.get_u64("event_hook_timeout") {
+            self.event_hook_timeout = timeout;
+        }
+
+        if let Some(concurrency) = options.get_u64("event_hook_concurrency") {
+            if concurrency == 0 {
+                return Err(invalid(
+                    "event_hook_concurrency",
+                    ConfigError::from("positive number expected"),
+                ));
+            }
+
+            self.event_hook_concurrency = concurrency as usize;
+        }
+
+        if let Some(period) = options.get_u64("scan_period") {
+            self.scan_period = period;
+        }
+
+        if let Some(ports) = options.get_ports("rtsp_ports") {
+            self.rtsp_ports = ports;
+        }
+
+        if let Some(ports) = options.get_ports("http_ports") {
+            self.http_ports = ports;
+        }
+
+        if let Some(timeout) = options.get_u64("connection_timeout") {
+            self.connection_timeout = timeout;
+        }
+
+        if let Some(timeout) = options.get_u64("retry_timeout") {
+            self.retry_timeout = timeout;
+        }
+
+        if let Some(proxy) = options.get_str("proxy") {
+            self.set_proxy(proxy).map_err(|err| invalid("proxy", err))?;
+        }
+
+        if let Some(window) = options.get_u64("pairing_window") {
+            self.pairing_window = window;
+        }
+
+        if let Some(path) = options.get_str("pairing_trigger") {
+            self.pairing_trigger = Some(path.to_string());
+        }
+
+        if let Some(pairing_code) = options.get_bool("pairing_code") {
+            self.pairing_code = pairing_code;
+        }
+
+        if let Some(path) = options.get_str("pairing_code_file") {
+            self.pairing_code_file = Some(path.to_string());
+        }
+
+        Ok(())
+    }
+
+    /// Get the effective options, i.e. the options file, environment
+    /// variables, command line arguments and default values merged together.
+    fn effective_options(&self) -> Result<Options, ConfigError> {
+        let file = self
+            .options_file
+            .as_ref()
+            .map(|path| load_options_file(path))
+            .transpose()?
+            .unwrap_or_default();
+
+        // lists given as command line arguments or environment variables
+        // replace lists from the options file
+        let merge = |key: &str, list: &[String]| {
+            if list.is_empty() {
+                file.get_strings(key)
+            } else {
+                list.to_vec()
+            }
+        };
+
+        let log_level = self
+            .log_level
+            .clone()
+            .or_else(|| file.log_level())
+            .unwrap_or_else(|| LogFilter::new(Severity::INFO));
+
+        let mut res = Options::default();
+
+        res.set("service_address", self.arrow_svc_addr.as_str())?;
+        res.set(
+            "ca_certificates",
+            merge("ca_certificates", &self.ca_certificates),
+        )?;
+        res.set("discovery", self.discovery)?;
+
+        if let Some(iface) = self.interface.as_ref() {
+            res.set("interface", iface.as_str())?;
+        }
+
+        res.set("rtsp_services", merge("rtsp_services", &self.rtsp_services))?;
+        res.set(
+            "mjpeg_services",
+            merge("mjpeg_services", &self.mjpeg_services),
+        )?;
+        res.set("http_services", merge("http_services", &self.http_services))?;
+        res.set("tcp_services", merge("tcp_services", &self.tcp_services))?;
+        res.set("log_level", log_level.to_string())?;
+        res.set("diagnostic_mode", self.diagnostic_mode)?;
+        res.set("config_file", self.config_file.as_str())?;
+        res.set("config_file_skel", self.config_file_skel.as_str())?;
+        res.set("config_generations", self.config_generations)?;
+
+        if let Some(source) = self.secrets_key.as_ref() {
+            res.set("secrets_key", source.to_string())?;
+        }
+
+        if let Some(path) = self.identity_file.as_ref() {
+            res.set("identity_file", path.as_str())?;
+        }
+
+        res.set("conn_state_file", self.state_file.as_str())?;
+        res.set("conn_state_format", self.state_file_format.as_str())?;
+        res.set("rtsp_paths", self.rtsp_paths_file.as_str())?;
+        res.set("mjpeg_paths", self.mjpeg_paths_file.as_str())?;
+
+        match self.logger_type {
+            LoggerType::Syslog => (),
+            LoggerType::Stderr => res.set("log_stderr", true)?,
+            LoggerType::StderrPretty => res.set("log_stderr_pretty", true)?,
+            LoggerType::FileLogger => res.set("log_file", self.log_file.as_str())?,
+            LoggerType::RemoteSyslog => {
+                res.set("syslog_host", self.syslog_host.as_str())?;
+                res.set(
+                    "syslog_port",
+                    self.syslog_port
+                        .unwrap_or_else(|| self.syslog_transport.default_port()),
+                )?;
+                res.set("syslog_transport", self.syslog_transport.as_str())?;
+                res.set("syslog_protocol", self.syslog_protocol.as_str())?;
+                res.set("syslog_facility", self.syslog_facility.to_string())?;
+                res.set("syslog_app_name", self.syslog_app_name.as_str())?;
+                res.set("syslog_queue_size", self.syslog_queue_size)?;
+
+                if !self.syslog_ca_certificates.is_empty() {
+                    res.set(
+                        "syslog_ca_certificates",
+                        self.syslog_ca_certificates.clone(),
+                    )?;
+                }
+            }
+        }
+
+        res.set("log_file_size", self.log_file_size)?;
+        res.set("log_file_rotations", self.log_file_rotations)?;
+
+        if let Some(period) = self.log_file_period {
+            res.set("log_file_period", period.as_str())?;
+        }
+
+        res.set("log_file_compress", self.log_file_compress)?;
+
+        if let Some(budget) = self.log_file_budget {
+            res.set("log_file_budget", budget)?;
+        }
+
+        res.set("log_format", self.log_format.as_str())?;
+        res.set("log_buffer_size", self.log_buffer_size)?;
+
+        if let Some(path) = self.lock_file.as_ref() {
+            res.set("lock_file", path.as_str())?;
+        }
+
+        let api_addresses = self
+            .api_addresses
+            .iter()
+            .map(|addr| addr.to_string())
+            .collect::<Vec<_>>();
+
+        let api_sockets = self
+            .api_sockets
+            .iter()
+            .filter_map(|addr| match addr {
+                ApiAddress::Unix(path) => Some(path.to_string_lossy().to_string()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        res.set("api_addresses", api_addresses)?;
+        res.set("api_sockets", api_sockets)?;
+
+        if let Some(path) = self.control_socket.as_ref() {
+            res.set("control_socket", path.to_string_lossy().to_string())?;
+        }
+
+        res.set("event_hooks", self.event_hooks.clone())?;
+        res.set("event_hook_timeout", self.event_hook_timeout)?;
+        res.set("event_hook_concurrency", self.event_hook_concurrency)?;
+        res.set("scan_period", self.scan_period)?;
+        res.set("rtsp_ports", self.rtsp_ports.clone())?;
+        res.set("http_ports", self.http_ports.clone())?;
+        res.set("connection_timeout", self.connection_timeout)?;
+        res.set("retry_timeout", self.retry_timeout)?;
+
+        if let Some(proxy) = self.proxy.as_ref() {
+            res.set("proxy", proxy.as_str())?;
+        }
+
+        res.set("pairing_window", self.pairing_window)?;
+
+        if let Some(path) = self.pairing_trigger.as_ref() {
+            res.set("pairing_trigger", path.as_str())?;
+        }
+
+        res.set("pairing_code", self.pairing_code)?;
+
+        if let Some(path) = self.pairing_code_file.as_ref() {
+            res.set("pairing_code_file", path.as_str())?;
+        }
+
+        let allowed_networks = self
+            .allowed_networks
+            .iter()
+            .map(|network| network.to_string())
+            .collect::<Vec<_>>();
+
+        let allowed_ports = if self.allowed_ports.is_empty() {
+            file.get_ports("allowed_ports").unwrap_or_default()
+        } else {
+            self.allowed_ports.clone()
+        };
+
+        let http_path_rules = self
+            .http_path_rules
+            .iter()
+            .map(|rule| rule.to_string())
+            .collect::<Vec<_>>();
+
+        res.set(
+            "allowed_networks",
+            merge("allowed_networks", &allowed_networks),
+        )?;
+        res.set("allowed_ports", allowed_ports)?;
+        res.set(
+            "allowed_service_types",
+            merge("allowed_service_types", &self.allowed_service_types),
+        )?;
+        res.set(
+            "http_path_rules",
+            merge("http_path_rules", &http_path_rules),
+        )?;
+
+        Ok(res)
+    }
+
+    /// Get all static services given as command line arguments or
+    /// environment variables.
+    fn get_services(&self) -> Result<Vec<Service>, ConfigError> {
+        let mut res = Vec::new();
+
+        for url in &self.rtsp_services {
+            res.push(parse_rtsp_url(url)?);
+        }
+
+        for url in &self.mjpeg_services {
+            res.push(parse_mjpeg_url(url)?);
+        }
+
+        for addr in &self.http_services {
+            res.push(parse_http_address(addr)?);
+        }
+
+        for addr in &self.tcp_services {
+            res.push(parse_tcp_address(addr)?);
+        }
+
+        Ok(res)
+    }
+
     /// Create a lock file (if specified).
     fn create_lock_file(&self) -> Result<Option<File>, ConfigError> {
         self.lock_file
@@ -217,15 +919,56 @@ impl ApplicationConfigBuilder {
             .transpose()
     }
 
+    /// Create a new remote syslog logger.
+    fn create_remote_syslog(&self) -> Result<BoxLogger, ConfigError> {
+        let mut builder = remote_syslog::builder(&self.syslog_host)
+            .transport(self.syslog_transport)
+            .protocol(self.syslog_protocol)
+            .facility(self.syslog_facility)
+            .app_name(&self.syslog_app_name)
+            .queue_size(self.syslog_queue_size);
+
+        if let Some(port) = self.syslog_port {
+            builder = builder.port(port);
+        }
+
+        if self.syslog_transport == Transport::Tls && !self.syslog_ca_certificates.is_empty() {
+            let mut connector = SslConnector::builder(SslMethod::tls()).map_err(|err| {
+                ConfigError::from(format!(
+                    "unable to create a TLS connection builder: {}",
+                    err
+                ))
+            })?;
+
+            for ca_cert in &self.syslog_ca_certificates {
+                connector
+                    .load_ca_certificates(ca_cert)
+                    .map_err(|err| ConfigError::from(format!("{}", err)))?;
+            }
+
+            builder = builder.tls_connector(connector.build());
+        }
+
+        let logger = builder
+            .build()
+            .map_err(|err| ConfigError::from(format!("{}", err)))?;
+
+        Ok(BoxLogger::new(logger))
+    }
+
     /// Create a new logger.
     fn create_logger(&self) -> Result<BoxLogger, ConfigError> {
         let logger = match self.logger_type {
             LoggerType::Syslog => BoxLogger::new(logger::syslog::new()),
-            LoggerType::Stderr => BoxLogger::new(logger::stderr::new()),
-            LoggerType::StderrPretty => BoxLogger::new(logger::stderr::new_pretty()),
+            LoggerType::Stderr => {
+                BoxLogger::new(logger::stderr::new().with_format(self.log_format))
+            }
+            LoggerType::StderrPretty => {
+                BoxLogger::new(logger::stderr::new_pretty().with_format(self.log_format))
+            }
             LoggerType::FileLogger => {
                 logger::file::new(&self.log_file, self.log_file_size, self.log_file_rotations)
-                    .map(|logger| BoxLogger::new(logger))
+                    .map(|logger| self.configure_file_logger(logger))
                     .map_err(|_| {
                         ConfigError::from(format!(
                             "unable to open the given log file: \"{}\"",
@@ -233,17 +976,100 @@ impl ApplicationConfigBuilder {
                         ))
                     })?
             }
+            LoggerType::RemoteSyslog => self.create_remote_syslog()?,
         };
 
         Ok(logger)
     }
 
+    /// Apply the log format and rotation options to a given file logger.
+    fn configure_file_logger(&self, logger: FileLogger) -> BoxLogger {
+        let mut logger = logger
+            .with_format(self.log_format)
+            .with_compression(self.log_file_compress);
+
+        if let Some(period) = self.log_file_period {
+            logger = logger.with_period(period);
+        }
+
+        if let Some(budget) = self.log_file_budget {
+            logger = logger.with_budget(budget);
+        }
+
+        BoxLogger::new(logger)
+    }
+
+    /// Get names of the reloadable list options that were given as command
+    /// line arguments or environment variables.
+    fn get_overridden_lists(&self) -> Vec<&'static str> {
+        let lists = [
+            ("ca_certificates", self.ca_certificates.is_empty()),
+            ("rtsp_services", self.rtsp_services.is_empty()),
+            ("mjpeg_services", self.mjpeg_services.is_empty()),
+            ("http_services", self.http_services.is_empty()),
+            ("tcp_services", self.tcp_services.is_empty()),
+            ("allowed_networks", self.allowed_networks.is_empty()),
+            ("allowed_ports", self.allowed_ports.is_empty()),
+            (
+                "allowed_service_types",
+                self.allowed_service_types.is_empty(),
+            ),
+            ("http_path_rules", self.http_path_rules.is_empty()),
+        ];
+
+        lists
+            .iter()
+            .filter(|(_, empty)| !empty)
+            .map(|(key, _)| *key)
+            .collect()
+    }
+
     /// Build application configuration.
     fn build(self) -> Result<ApplicationConfig, ConfigError> {
+        if self.arrow_svc_addr.is_empty() {
+            return Err(ConfigError::from("missing Angelcam Arrow Service address"));
+        }
+
+        let services = self.get_services()?;
+
         let lock_file = self.create_lock_file()?;
 
         let mut logger = self.create_logger()?;
 
+        let log_buffer = LogRingBuffer::new(self.log_buffer_size * 1024);
+
+        if self.log_buffer_size > 0 {
+            logger = BoxLogger::new(RingBufferLogger::new(logger, log_buffer.clone()));
+        }
+
+        let secrets_key = self
+            .secrets_key
+            .as_ref()
+            .map(SecretsKey::load)
+            .transpose()?;
+
+        let loader = ConfigLoader {
+            config_file: self.config_file.clone(),
+            secrets_key,
+            options_file: self.options_file.clone(),
+            ca_certificates: self.ca_certificates.clone(),
+            services,
+            allowed_networks: self.allowed_networks.clone(),
+            allowed_ports: self.allowed_ports.clone(),
+            allowed_service_types: self.allowed_service_types.clone(),
+            http_path_rules: self.http_path_rules.clone(),
+            overridden: self.get_overridden_lists(),
+            log_level: self.log_level,
+        };
+
+        let options = loader.load_options()?;
+
+        logger.set_filter(loader.get_log_level(&options));
+
+        // never replace credentials that cannot be decrypted with new ones
+        check_secrets(&self.config_file_skel, loader.secrets_key.as_ref())?;
+        check_secrets(&self.config_file, loader.secrets_key.as_ref())?;
+
         // read config skeleton
         let config_skeleton = utils::result_or_log(
             &mut logger,
@@ -252,15 +1078,22 @@ impl ApplicationConfigBuilder {
                 "unable to read configuration file skeleton\"{}\"",
                 self.config_file_skel
             ),
-            PersistentConfig::load(&self.config_file_skel),
+            PersistentConfig::load(&self.config_file_skel, loader.secrets_key.as_ref()),
+        );
+
+        // read config (or its latest valid generation)
+        let config = PersistentConfig::load_or_recover(
+            &mut logger,
+            &self.config_file,
+            self.config_generations,
+            loader.secrets_key.as_ref(),
         );
 
-        // read config
         let config = utils::result_or_log(
             &mut logger,
             Severity::WARN,
             format!("unable to read configuration file \"{}\"", self.config_file),
-            PersistentConfig::load(&self.config_file),
+            config,
         );
 
         let config_skeleton_exists = config_skeleton.is_some();
@@ -271,6 +1104,24 @@ impl ApplicationConfigBuilder {
             .or(config_skeleton)
             .unwrap_or(PersistentConfig::new());
 
+        logger.set_secrets(get_secrets(&config.passwd));
+
+        if config.schema_version < schema::SCHEMA_VERSION {
+            log_info!(
+                &mut logger,
+                "upgrading configuration from schema version {} to {}",
+                config.schema_version,
+                schema::SCHEMA_VERSION
+            );
+        } else if config.schema_version > schema::SCHEMA_VERSION {
+            log_warn!(
+                &mut logger,
+                "configuration schema version {} is newer than the supported version {}, unknown fields will be preserved",
+                config.schema_version,
+                schema::SCHEMA_VERSION
+            );
+        }
+
         // if there is no skeleton, create one from the config
         if !config_skeleton_exists {
             let config_skeleton = config.to_skeleton();
@@ -288,7 +1139,7 @@ impl ApplicationConfigBuilder {
                     "unable to create configuration file skeleton \"{}\"",
                     self.config_file_skel
                 ),
-                config_skeleton.save(&self.config_file_skel),
+                config_skeleton.save(&self.config_file_skel, loader.secrets_key.as_ref()),
             );
         }
 
@@ -304,15 +1155,31 @@ impl ApplicationConfigBuilder {
             );
         }
 
-        let mut config = ApplicationConfig {
-            version: config.version,
-            uuid: config.uuid,
+        let mut api_addresses = self.api_addresses;
+
+        api_addresses.extend(self.api_sockets);
+        api_addresses.extend(self.control_socket.map(ApiAddress::Unix));
+
+        let event_hooks = EventHooks::new(
+            logger.clone(),
+            self.event_hooks,
+            Duration::from_secs(self.event_hook_timeout),
+            self.event_hook_concurrency,
+        );
+
+        let mut config = ApplicationConfig {
+            version: config.version,
+            uuid: config.uuid,
             passwd: config.passwd,
             arrow_mac: self.arrow_mac,
             arrow_svc_addr: self.arrow_svc_addr,
-            ca_certificates: self.ca_certificates,
+            ca_certificates: loader.get_ca_certificates(&options),
             config_file: self.config_file,
+            config_generations: self.config_generations,
+            schema_version: config.schema_version,
+            extra: config.extra,
             state_file: self.state_file,
+            state_file_format: self.state_file_format,
             rtsp_paths_file: self.rtsp_paths_file,
             mjpeg_paths_file: self.mjpeg_paths_file,
             diagnostic_mode: self.diagnostic_mode,
@@ -320,14 +1187,33 @@ impl ApplicationConfigBuilder {
             default_svc_table: config.svc_table.clone(),
             svc_table: config.svc_table,
             logger: logger,
+            log_buffer,
+            loader: loader.clone(),
+            api_addresses,
+            metrics: Metrics::new(),
+            event_hooks,
+            scan_period: Duration::from_secs(self.scan_period),
+            rtsp_ports: self.rtsp_ports,
+            http_ports: self.http_ports,
+            connection_timeout: Duration::from_secs(self.connection_timeout),
+            retry_timeout: Duration::from_secs(self.retry_timeout),
+            proxy: self.proxy,
+            pairing_window: Duration::from_secs(self.pairing_window),
+            pairing_trigger: self.pairing_trigger,
+            pairing_code: self.pairing_code || self.pairing_code_file.is_some(),
+            pairing_code_file: self.pairing_code_file,
+            destination_policy: loader.get_destination_policy(&options)?,
+            http_path_policy: loader.get_http_path_policy(&options)?,
             _lock_file: lock_file,
         };
 
-        if self.verbose {
-            config.logger.set_level(Severity::DEBUG);
-        }
+        // the persisted service table might have been created using
+        // a different policy
+        config.remove_denied_services();
+
+        let services = loader.get_services(&options)?;
 
-        for svc in self.services {
+        for svc in config.filter_allowed_services(services) {
             config.svc_table.add_static(svc.clone());
             config.default_svc_table.add_static(svc);
         }
@@ -347,7 +1233,15 @@ impl ApplicationConfigBuilder {
         // skip the application name
         args.next();
 
-        self.arrow_service_address(&mut args)?;
+        // the Arrow Service address is optional if it is given in the
+        // options file or as an environment variable
+        let mut args = args.peekable();
+
+        if let Some(addr) = args.next_if(|arg| !arg.starts_with('-')) {
+            self.set_arrow_service_address(&addr);
+        }
+
+        let mut args = args.collect::<Vec<_>>().into_iter();
 
         while let Some(ref arg) = args.next() {
             match arg as &str {
@@ -363,14 +1257,25 @@ impl ApplicationConfigBuilder {
                 "--diagnostic-mode" => self.diagnostic_mode(),
                 "--log-stderr" => self.log_stderr(),
                 "--log-stderr-pretty" => self.log_stderr_pretty(),
+                "--log-file-compress" => self.log_file_compress = true,
+                "--print-config" => self.print_config = true,
+                "--pairing-code" => self.pairing_code = true,
 
                 arg => {
                     if arg.starts_with("--config-file=") {
                         self.config_file(arg);
                     } else if arg.starts_with("--config-file-skel=") {
                         self.config_file_skel(arg);
+                    } else if arg.starts_with("--config-generations=") {
+                        self.config_generations(arg)?;
+                    } else if arg.starts_with("--secrets-key=") {
+                        self.secrets_key(arg)?;
+                    } else if arg.starts_with("--options-file=") {
+                        self.options_file(arg);
                     } else if arg.starts_with("--conn-state-file=") {
                         self.conn_state_file(arg);
+                    } else if arg.starts_with("--conn-state-format=") {
+                        self.conn_state_format(arg)?;
                     } else if arg.starts_with("--identity-file=") {
                         self.identity_file(arg);
                     } else if arg.starts_with("--rtsp-paths=") {
@@ -383,8 +1288,72 @@ impl ApplicationConfigBuilder {
                         self.log_file_size(arg)?;
                     } else if arg.starts_with("--log-file-rotations=") {
                         self.log_file_rotations(arg)?;
+                    } else if arg.starts_with("--log-file-period=") {
+                        self.log_file_period(arg)?;
+                    } else if arg.starts_with("--log-file-budget=") {
+                        self.log_file_budget(arg)?;
+                    } else if arg.starts_with("--log-format=") {
+                        self.log_format(arg)?;
+                    } else if arg.starts_with("--log-buffer-size=") {
+                        self.log_buffer_size(arg)?;
+                    } else if arg.starts_with("--syslog-host=") {
+                        self.syslog_host(arg);
+                    } else if arg.starts_with("--syslog-port=") {
+                        self.syslog_port(arg)?;
+                    } else if arg.starts_with("--syslog-transport=") {
+                        self.syslog_transport(arg)?;
+                    } else if arg.starts_with("--syslog-protocol=") {
+                        self.syslog_protocol(arg)?;
+                    } else if arg.starts_with("--syslog-facility=") {
+                        self.syslog_facility(arg)?;
+                    } else if arg.starts_with("--syslog-app-name=") {
+                        self.syslog_app_name(arg);
+                    } else if arg.starts_with("--syslog-queue-size=") {
+                        self.syslog_queue_size(arg)?;
+                    } else if arg.starts_with("--syslog-ca=") {
+                        self.syslog_ca(arg);
                     } else if arg.starts_with("--lock-file=") {
                         self.lock_file(arg)?
+                    } else if arg.starts_with("--api-addr=") {
+                        self.api_addr(arg)?
+                    } else if arg.starts_with("--api-socket=") {
+                        self.api_socket(arg)?
+                    } else if arg == "--control-socket" || arg.starts_with("--control-socket=") {
+                        self.control_socket(arg)
+                    } else if arg.starts_with("--event-hook=") {
+                        self.event_hook(arg)
+                    } else if arg.starts_with("--event-hook-timeout=") {
+                        self.event_hook_timeout(arg)?
+                    } else if arg.starts_with("--event-hook-concurrency=") {
+                        self.event_hook_concurrency(arg)?
+                    } else if arg.starts_with("--log-level=") {
+                        self.log_level(arg)?
+                    } else if arg.starts_with("--scan-period=") {
+                        self.scan_period(arg)?
+                    } else if arg.starts_with("--rtsp-ports=") {
+                        self.rtsp_ports(arg)?
+                    } else if arg.starts_with("--http-ports=") {
+                        self.http_ports(arg)?
+                    } else if arg.starts_with("--connection-timeout=") {
+                        self.connection_timeout(arg)?
+                    } else if arg.starts_with("--retry-timeout=") {
+                        self.retry_timeout(arg)?
+                    } else if arg.starts_with("--proxy=") {
+                        self.proxy(arg)?
+                    } else if arg.starts_with("--pairing-window=") {
+                        self.pairing_window(arg)?
+                    } else if arg.starts_with("--pairing-trigger=") {
+                        self.pairing_trigger(arg)
+                    } else if arg.starts_with("--pairing-code-file=") {
+                        self.pairing_code_file(arg)
+                    } else if arg.starts_with("--allowed-networks=") {
+                        self.allowed_networks(arg)?
+                    } else if arg.starts_with("--allowed-ports=") {
+                        self.allowed_ports(arg)?
+                    } else if arg.starts_with("--allowed-service-types=") {
+                        self.allowed_service_types(arg)?
+                    } else if arg.starts_with("--http-path-rule=") {
+                        self.http_path_rule(arg)?
                     } else {
                         return Err(ConfigError::from(format!("unknown argument: \"{}\"", arg)));
                     }
@@ -395,20 +1364,14 @@ impl ApplicationConfigBuilder {
         Ok(self)
     }
 
-    /// Process the Arrow Service address argument.
-    fn arrow_service_address(&mut self, args: &mut Args) -> Result<(), ConfigError> {
-        let addr = args
-            .next()
-            .ok_or(ConfigError::from("missing Angelcam Arrow Service address"))?;
-
+    /// Set the Arrow Service address.
+    fn set_arrow_service_address(&mut self, addr: &str) {
         // add the default port number if the given address has no port
         if addr.ends_with(']') || !addr.contains(':') {
             self.arrow_svc_addr = format!("{}:{}", addr, DEFAULT_ARROW_SERVICE_PORT);
         } else {
-            self.arrow_svc_addr = addr;
+            self.arrow_svc_addr = addr.to_string();
         }
-
-        Ok(())
     }
 
     /// Process the CA certificate argument.
@@ -417,6 +1380,12 @@ impl ApplicationConfigBuilder {
             .next()
             .ok_or(ConfigError::from("CA certificate path expected"))?;
 
+        override_list(
+            &mut self.cli_lists,
+            "ca_certificates",
+            &mut self.ca_certificates,
+        );
+
         self.ca_certificates.push(path);
 
         Ok(())
@@ -439,7 +1408,13 @@ impl ApplicationConfigBuilder {
             .next()
             .ok_or(ConfigError::from("network interface name expected"))?;
 
-        self.arrow_mac = get_mac(&iface)?;
+        self.set_interface(&iface)
+    }
+
+    /// Set the network interface used for client identification.
+    fn set_interface(&mut self, iface: &str) -> Result<(), ConfigError> {
+        self.arrow_mac = get_mac(iface)?;
+        self.interface = Some(iface.to_string());
 
         Ok(())
     }
@@ -448,9 +1423,13 @@ impl ApplicationConfigBuilder {
     fn rtsp_service(&mut self, args: &mut Args) -> Result<(), ConfigError> {
         let url = args.next().ok_or(ConfigError::from("RTSP URL expected"))?;
 
-        let service = parse_rtsp_url(&url)?;
+        override_list(
+            &mut self.cli_lists,
+            "rtsp_services",
+            &mut self.rtsp_services,
+        );
 
-        self.services.push(service);
+        self.rtsp_services.push(url);
 
         Ok(())
     }
@@ -459,9 +1438,13 @@ impl ApplicationConfigBuilder {
     fn mjpeg_service(&mut self, args: &mut Args) -> Result<(), ConfigError> {
         let url = args.next().ok_or(ConfigError::from("HTTP URL expected"))?;
 
-        let service = parse_mjpeg_url(&url)?;
+        override_list(
+            &mut self.cli_lists,
+            "mjpeg_services",
+            &mut self.mjpeg_services,
+        );
 
-        self.services.push(service);
+        self.mjpeg_services.push(url);
 
         Ok(())
     }
@@ -472,13 +1455,13 @@ impl ApplicationConfigBuilder {
             .next()
             .ok_or(ConfigError::from("TCP socket address expected"))?;
 
-        let addr = net::utils::get_socket_address(addr.as_str()).map_err(|_| {
-            ConfigError::from(format!("unable to resolve socket address: {}", addr))
-        })?;
-
-        let mac = get_fake_mac(0xffff, &addr);
+        override_list(
+            &mut self.cli_lists,
+            "http_services",
+            &mut self.http_services,
+        );
 
-        self.services.push(Service::http(mac, addr));
+        self.http_services.push(addr);
 
         Ok(())
     }
@@ -489,20 +1472,16 @@ impl ApplicationConfigBuilder {
             .next()
             .ok_or(ConfigError::from("TCP socket address expected"))?;
 
-        let addr = net::utils::get_socket_address(addr.as_str()).map_err(|_| {
-            ConfigError::from(format!("unable to resolve socket address: {}", addr))
-        })?;
-
-        let mac = get_fake_mac(0xffff, &addr);
+        override_list(&mut self.cli_lists, "tcp_services", &mut self.tcp_services);
 
-        self.services.push(Service::tcp(mac, addr));
+        self.tcp_services.push(addr);
 
         Ok(())
     }
 
     /// Process the verbose argument.
     fn verbose(&mut self) {
-        self.verbose = true;
+        self.log_level = Some(LogFilter::new(Severity::DEBUG));
     }
 
     /// Process the diagnostic mode argument.
@@ -556,6 +1535,139 @@ impl ApplicationConfigBuilder {
         Ok(())
     }
 
+    /// Process the log-file-period argument.
+    fn log_file_period(&mut self, arg: &str) -> Result<(), ConfigError> {
+        // skip "--log-file-period=" length
+        let period = arg[18..].parse().map_err(|err| {
+            ConfigError::from(format!("invalid value given for {}, {}", arg, err))
+        })?;
+
+        self.log_file_period = Some(period);
+
+        Ok(())
+    }
+
+    /// Process the log-file-budget argument.
+    fn log_file_budget(&mut self, arg: &str) -> Result<(), ConfigError> {
+        // skip "--log-file-budget=" length
+        let budget = &arg[18..];
+
+        let budget = budget.parse().map_err(|_| {
+            ConfigError::from(format!("invalid value given for {}, number expeced", arg))
+        })?;
+
+        self.log_file_budget = Some(budget);
+
+        Ok(())
+    }
+
+    /// Process the log-format argument.
+    fn log_format(&mut self, arg: &str) -> Result<(), ConfigError> {
+        // skip "--log-format=" length
+        self.log_format = arg[13..].parse().map_err(|err| {
+            ConfigError::from(format!("invalid value given for {}, {}", arg, err))
+        })?;
+
+        Ok(())
+    }
+
+    /// Process the log-buffer-size argument.
+    fn log_buffer_size(&mut self, arg: &str) -> Result<(), ConfigError> {
+        // skip "--log-buffer-size=" length
+        let size = &arg[18..];
+
+        self.log_buffer_size = size.parse().map_err(|_| {
+            ConfigError::from(format!("invalid value given for {}, number expeced", arg))
+        })?;
+
+        Ok(())
+    }
+
+    /// Process the syslog-host argument.
+    fn syslog_host(&mut self, arg: &str) {
+        self.logger_type = LoggerType::RemoteSyslog;
+
+        // skip "--syslog-host=" length
+        self.syslog_host = arg[14..].to_string();
+    }
+
+    /// Process the syslog-port argument.
+    fn syslog_port(&mut self, arg: &str) -> Result<(), ConfigError> {
+        // skip "--syslog-port=" length
+        let port = &arg[14..];
+
+        let port = port.parse().map_err(|_| {
+            ConfigError::from(format!(
+                "invalid value given for {}, port number expected",
+                arg
+            ))
+        })?;
+
+        self.syslog_port = Some(port);
+
+        Ok(())
+    }
+
+    /// Process the syslog-transport argument.
+    fn syslog_transport(&mut self, arg: &str) -> Result<(), ConfigError> {
+        // skip "--syslog-transport=" length
+        self.syslog_transport = arg[19..].parse().map_err(|err| {
+            ConfigError::from(format!("invalid value given for {}, {}", arg, err))
+        })?;
+
+        Ok(())
+    }
+
+    /// Process the syslog-protocol argument.
+    fn syslog_protocol(&mut self, arg: &str) -> Result<(), ConfigError> {
+        // skip "--syslog-protocol=" length
+        self.syslog_protocol = arg[18..].parse().map_err(|err| {
+            ConfigError::from(format!("invalid value given for {}, {}", arg, err))
+        })?;
+
+        Ok(())
+    }
+
+    /// Process the syslog-facility argument.
+    fn syslog_facility(&mut self, arg: &str) -> Result<(), ConfigError> {
+        // skip "--syslog-facility=" length
+        self.syslog_facility = arg[18..].parse().map_err(|err| {
+            ConfigError::from(format!("invalid value given for {}, {}", arg, err))
+        })?;
+
+        Ok(())
+    }
+
+    /// Process the syslog-app-name argument.
+    fn syslog_app_name(&mut self, arg: &str) {
+        // skip "--syslog-app-name=" length
+        self.syslog_app_name = arg[18..].to_string();
+    }
+
+    /// Process the syslog-queue-size argument.
+    fn syslog_queue_size(&mut self, arg: &str) -> Result<(), ConfigError> {
+        // skip "--syslog-queue-size=" length
+        let size = &arg[20..];
+
+        self.syslog_queue_size = size.parse().map_err(|_| {
+            ConfigError::from(format!("invalid value given for {}, number expeced", arg))
+        })?;
+
+        Ok(())
+    }
+
+    /// Process the syslog-ca argument.
+    fn syslog_ca(&mut self, arg: &str) {
+        override_list(
+            &mut self.cli_lists,
+            "syslog_ca_certificates",
+            &mut self.syslog_ca_certificates,
+        );
+
+        // skip "--syslog-ca=" length
+        self.syslog_ca_certificates.push(arg[12..].to_string());
+    }
+
     /// Process the config-file argument.
     fn config_file(&mut self, arg: &str) {
         // skip "--config-file=" length
@@ -568,6 +1680,36 @@ impl ApplicationConfigBuilder {
         self.config_file_skel = arg[19..].to_string()
     }
 
+    /// Process the config-generations argument.
+    fn config_generations(&mut self, arg: &str) -> Result<(), ConfigError> {
+        // skip "--config-generations=" length
+        let generations = &arg[21..];
+
+        self.config_generations = generations.parse().map_err(|_| {
+            ConfigError::from(format!("invalid value given for {}, number expeced", arg))
+        })?;
+
+        Ok(())
+    }
+
+    /// Process the secrets-key argument.
+    fn secrets_key(&mut self, arg: &str) -> Result<(), ConfigError> {
+        // skip "--secrets-key=" length
+        let source = arg[14..].parse().map_err(|err| {
+            ConfigError::from(format!("invalid value given for {}, {}", arg, err))
+        })?;
+
+        self.secrets_key = Some(source);
+
+        Ok(())
+    }
+
+    /// Process the options-file argument.
+    fn options_file(&mut self, arg: &str) {
+        // skip "--options-file=" length
+        self.options_file = Some(arg[15..].to_string())
+    }
+
     /// Process the identity-file argument.
     fn identity_file(&mut self, arg: &str) {
         // skip "--identity-file=" length
@@ -580,6 +1722,16 @@ impl ApplicationConfigBuilder {
         self.state_file = arg[18..].to_string()
     }
 
+    /// Process the conn-state-format argument.
+    fn conn_state_format(&mut self, arg: &str) -> Result<(), ConfigError> {
+        // skip "--conn-state-format=" length
+        self.state_file_format = arg[20..].parse().map_err(|err| {
+            ConfigError::from(format!("invalid value given for {}, {}", arg, err))
+        })?;
+
+        Ok(())
+    }
+
     /// Process the rtsp-paths argument.
     fn rtsp_paths(&mut self, arg: &str) -> Result<(), ConfigError> {
         if !cfg!(feature = "discovery") {
@@ -617,6 +1769,338 @@ impl ApplicationConfigBuilder {
 
         Ok(())
     }
+
+    /// Process the api-addr argument.
+    fn api_addr(&mut self, arg: &str) -> Result<(), ConfigError> {
+        // skip "--api-addr=" length
+        let addr = parse_api_address(&arg[11..]).map_err(|err| {
+            ConfigError::from(format!("invalid value given for {}, {}", arg, err))
+        })?;
+
+        override_list(
+            &mut self.cli_lists,
+            "api_addresses",
+            &mut self.api_addresses,
+        );
+
+        self.api_addresses.push(addr);
+
+        Ok(())
+    }
+
+    /// Process the api-socket argument.
+    fn api_socket(&mut self, arg: &str) -> Result<(), ConfigError> {
+        // skip "--api-socket=" length
+        let socket = parse_api_socket(&arg[13..]).map_err(|err| {
+            ConfigError::from(format!("invalid value given for {}, {}", arg, err))
+        })?;
+
+        override_list(&mut self.cli_lists, "api_sockets", &mut self.api_sockets);
+
+        self.api_sockets.push(socket);
+
+        Ok(())
+    }
+
+    /// Process the control-socket argument.
+    fn control_socket(&mut self, arg: &str) {
+        // skip "--control-socket=" length
+        self.set_control_socket(arg.get(17..).unwrap_or(""))
+    }
+
+    /// Set the control socket path. The default path is used if the given
+    /// path is empty.
+    fn set_control_socket(&mut self, path: &str) {
+        let path = if path.is_empty() {
+            PathBuf::from(CONTROL_SOCKET)
+        } else {
+            PathBuf::from(path)
+        };
+
+        self.control_socket = Some(path);
+    }
+
+    /// Process the event-hook argument.
+    fn event_hook(&mut self, arg: &str) {
+        // skip "--event-hook=" length
+        let hook = &arg[13..];
+
+        override_list(&mut self.cli_lists, "event_hooks", &mut self.event_hooks);
+
+        self.event_hooks.push(hook.to_string());
+    }
+
+    /// Process the event-hook-timeout argument.
+    fn event_hook_timeout(&mut self, arg: &str) -> Result<(), ConfigError> {
+        // skip "--event-hook-timeout=" length
+        let timeout = &arg[21..];
+
+        self.event_hook_timeout = timeout.parse().map_err(|_| {
+            ConfigError::from(format!("invalid value given for {}, number expeced", arg))
+        })?;
+
+        Ok(())
+    }
+
+    /// Process the event-hook-concurrency argument.
+    fn event_hook_concurrency(&mut self, arg: &str) -> Result<(), ConfigError> {
+        // skip "--event-hook-concurrency=" length
+        let concurrency = &arg[25..];
+
+        self.event_hook_concurrency = match concurrency.parse() {
+            Ok(n) if n > 0 => n,
+            _ => {
+                return Err(ConfigError::from(format!(
+                    "invalid value given for {}, positive number expected",
+                    arg
+                )))
+            }
+        };
+
+        Ok(())
+    }
+
+    /// Process the log-level argument.
+    fn log_level(&mut self, arg: &str) -> Result<(), ConfigError> {
+        // skip "--log-level=" length
+        let level = arg[12..].parse().map_err(|err| {
+            ConfigError::from(format!("invalid value given for {}, {}", arg, err))
+        })?;
+
+        self.log_level = Some(level);
+
+        Ok(())
+    }
+
+    /// Process the scan-period argument.
+    fn scan_period(&mut self, arg: &str) -> Result<(), ConfigError> {
+        // skip "--scan-period=" length
+        let period = &arg[14..];
+
+        self.scan_period = period.parse().map_err(|_| {
+            ConfigError::from(format!("invalid value given for {}, number expeced", arg))
+        })?;
+
+        Ok(())
+    }
+
+    /// Process the rtsp-ports argument.
+    fn rtsp_ports(&mut self, arg: &str) -> Result<(), ConfigError> {
+        // skip "--rtsp-ports=" length
+        self.rtsp_ports = parse_port_list(&arg[13..]).map_err(|err| {
+            ConfigError::from(format!("invalid value given for {}, {}", arg, err))
+        })?;
+
+        Ok(())
+    }
+
+    /// Process the http-ports argument.
+    fn http_ports(&mut self, arg: &str) -> Result<(), ConfigError> {
+        // skip "--http-ports=" length
+        self.http_ports = parse_port_list(&arg[13..]).map_err(|err| {
+            ConfigError::from(format!("invalid value given for {}, {}", arg, err))
+        })?;
+
+        Ok(())
+    }
+
+    /// Process the connection-timeout argument.
+    fn connection_timeout(&mut self, arg: &str) -> Result<(), ConfigError> {
+        // skip "--connection-timeout=" length
+        let timeout = &arg[21..];
+
+        self.connection_timeout = timeout.parse().map_err(|_| {
+            ConfigError::from(format!("invalid value given for {}, number expeced", arg))
+        })?;
+
+        Ok(())
+    }
+
+    /// Process the retry-timeout argument.
+    fn retry_timeout(&mut self, arg: &str) -> Result<(), ConfigError> {
+        // skip "--retry-timeout=" length
+        let timeout = &arg[16..];
+
+        self.retry_timeout = timeout.parse().map_err(|_| {
+            ConfigError::from(format!("invalid value given for {}, number expeced", arg))
+        })?;
+
+        Ok(())
+    }
+
+    /// Process the proxy argument.
+    fn proxy(&mut self, arg: &str) -> Result<(), ConfigError> {
+        // skip "--proxy=" length
+        self.set_proxy(&arg[8..])
+            .map_err(|err| ConfigError::from(format!("invalid value given for {}, {}", arg, err)))
+    }
+
+    /// Process the pairing-window argument.
+    fn pairing_window(&mut self, arg: &str) -> Result<(), ConfigError> {
+        // skip "--pairing-window=" length
+        let window = &arg[17..];
+
+        self.pairing_window = window.parse().map_err(|_| {
+            ConfigError::from(format!("invalid value given for {}, number expeced", arg))
+        })?;
+
+        Ok(())
+    }
+
+    /// Process the pairing-trigger argument.
+    fn pairing_trigger(&mut self, arg: &str) {
+        // skip "--pairing-trigger=" length
+        self.pairing_trigger = Some(arg[18..].to_string())
+    }
+
+    /// Process the pairing-code-file argument.
+    fn pairing_code_file(&mut self, arg: &str) {
+        // skip "--pairing-code-file=" length
+        self.pairing_code_file = Some(arg[20..].to_string())
+    }
+
+    /// Process the allowed-networks argument.
+    fn allowed_networks(&mut self, arg: &str) -> Result<(), ConfigError> {
+        // skip "--allowed-networks=" length
+        self.allowed_networks = parse_networks(arg[19..].split(',')).map_err(|err| {
+            ConfigError::from(format!("invalid value given for {}, {}", arg, err))
+        })?;
+
+        Ok(())
+    }
+
+    /// Process the allowed-ports argument.
+    fn allowed_ports(&mut self, arg: &str) -> Result<(), ConfigError> {
+        // skip "--allowed-ports=" length
+        self.allowed_ports = parse_port_list(&arg[16..]).map_err(|err| {
+            ConfigError::from(format!("invalid value given for {}, {}", arg, err))
+        })?;
+
+        Ok(())
+    }
+
+    /// Process the allowed-service-types argument.
+    fn allowed_service_types(&mut self, arg: &str) -> Result<(), ConfigError> {
+        // skip "--allowed-service-types=" length
+        self.allowed_service_types =
+            parse_policy_service_types(arg[24..].split(',')).map_err(|err| {
+                ConfigError::from(format!("invalid value given for {}, {}", arg, err))
+            })?;
+
+        Ok(())
+    }
+
+    /// Process the http-path-rule argument.
+    fn http_path_rule(&mut self, arg: &str) -> Result<(), ConfigError> {
+        // skip "--http-path-rule=" length
+        let rule = arg[17..].parse().map_err(|err| {
+            ConfigError::from(format!("invalid value given for {}, {}", arg, err))
+        })?;
+
+        override_list(
+            &mut self.cli_lists,
+            "http_path_rules",
+            &mut self.http_path_rules,
+        );
+
+        self.http_path_rules.push(rule);
+
+        Ok(())
+    }
+
+    /// Encrypt secrets in all existing configuration files.
+    fn encrypt_secrets(&self) -> Result<(), ConfigError> {
+        let source = self
+            .secrets_key
+            .as_ref()
+            .ok_or_else(|| ConfigError::from("no secrets key given (see --secrets-key)"))?;
+
+        let key = SecretsKey::load(source)?;
+
+        let mut files = vec![self.config_file.clone(), self.config_file_skel.clone()];
+
+        for generation in 1..=self.config_generations {
+            files.push(get_generation_path(&self.config_file, generation));
+        }
+
+        let mut failed = 0;
+
+        for file in files {
+            if !Path::new(&file).exists() {
+                continue;
+            }
+
+            let res = PersistentConfig::load(&file, Some(&key))
+                .and_then(|config| config.save(&file, Some(&key)));
+
+            match res {
+                Ok(()) => println!("secrets encrypted in \"{}\"", file),
+                Err(err) => {
+                    eprintln!("unable to encrypt secrets in \"{}\": {}", file, err);
+
+                    failed += 1;
+                }
+            }
+        }
+
+        if failed > 0 {
+            Err(ConfigError::from(format!(
+                "unable to encrypt secrets in {} file(s)",
+                failed
+            )))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Set HTTP proxy used for connecting to the Arrow Service.
+    fn set_proxy(&mut self, proxy: &str) -> Result<(), ConfigError> {
+        let proxy = proxy.strip_prefix("http://").unwrap_or(proxy);
+        let proxy = proxy.trim_end_matches('/');
+
+        let port = proxy
+            .rsplit(':')
+            .next()
+            .and_then(|port| port.parse::<u16>().ok());
+
+        if !proxy.contains(':') || port.is_none() {
+            return Err(ConfigError::from("\"host:port\" expected"));
+        }
+
+        self.proxy = Some(proxy.to_string());
+
+        Ok(())
+    }
+}
+
+/// Check that secrets in a given configuration file can be decrypted. Files
+/// that do not exist or cannot be parsed are ignored.
+fn check_secrets(path: &str, key: Option<&SecretsKey>) -> Result<(), ConfigError> {
+    let mut config = match fs::read_to_string(path).map(|data| json::parse(&data)) {
+        Ok(Ok(config)) => config,
+        _ => return Ok(()),
+    };
+
+    secrets::decrypt_fields(&mut config, key).map_err(|err| {
+        ConfigError::from(format!(
+            "unable to read configuration file \"{}\": {}",
+            path, err
+        ))
+    })
+}
+
+/// Get path of a given generation of a given configuration file.
+fn get_generation_path(path: &str, generation: usize) -> String {
+    format!("{}.{}", path, generation)
+}
+
+/// Get all representations of a given client password that must not appear
+/// in log messages.
+fn get_secrets(passwd: &Uuid) -> Vec<String> {
+    vec![
+        passwd.to_hyphenated_ref().to_string(),
+        passwd.to_simple_ref().to_string(),
+    ]
 }
 
 /// Client identification that can be publicly available.
@@ -627,9 +2111,7 @@ struct PublicIdentity {
 impl PublicIdentity {
     /// Save identity into a given file.
     fn save(&self, path: &str) -> Result<(), ConfigError> {
-        let mut file = File::create(path)?;
-
-        self.to_json().write(&mut file)?;
+        utils::write_file_atomically(path, self.to_json().dump().as_bytes())?;
 
         Ok(())
     }
@@ -645,39 +2127,85 @@ impl ToJson for PublicIdentity {
 
 /// Persistent part of application configuration.
 struct PersistentConfig {
+    schema_version: u64,
     uuid: Uuid,
     passwd: Uuid,
     version: usize,
     svc_table: SharedServiceTable,
+    extra: JsonValue,
 }
 
 impl PersistentConfig {
     /// Create a new instance of persistent configuration.
     fn new() -> PersistentConfig {
         PersistentConfig {
+            schema_version: schema::SCHEMA_VERSION,
             uuid: Uuid::new_v4(),
             passwd: Uuid::new_v4(),
             version: 0,
             svc_table: SharedServiceTable::new(),
+            extra: JsonValue::new_object(),
         }
     }
 
-    /// Load configuration from a given file.
-    fn load(path: &str) -> Result<PersistentConfig, ConfigError> {
+    /// Load configuration from a given file. Encrypted secrets are
+    /// decrypted using a given key.
+    fn load(path: &str, key: Option<&SecretsKey>) -> Result<PersistentConfig, ConfigError> {
         let mut file = File::open(path)?;
         let mut data = String::new();
 
         file.read_to_string(&mut data)?;
 
-        let object = json::parse(&data).map_err(|err| {
+        let mut object = json::parse(&data).map_err(|err| {
             utils::json::ParseError::from(format!("unable to parse configuration: {}", err))
         })?;
 
+        secrets::decrypt_fields(&mut object, key)?;
+
         let config = PersistentConfig::from_json(object)?;
 
         Ok(config)
     }
 
+    /// Load configuration from a given file. If the file cannot be loaded,
+    /// try to load the newest valid previous generation of the file. The
+    /// original error is returned if there is no valid generation.
+    fn load_or_recover<L>(
+        logger: &mut L,
+        path: &str,
+        generations: usize,
+        key: Option<&SecretsKey>,
+    ) -> Result<PersistentConfig, ConfigError>
+    where
+        L: Logger,
+    {
+        let err = match PersistentConfig::load(path, key) {
+            Ok(config) => return Ok(config),
+            Err(err) => err,
+        };
+
+        for generation in 1..=generations {
+            let generation_path = get_generation_path(path, generation);
+
+            if let Ok(config) = PersistentConfig::load(&generation_path, key) {
+                log_warn!(
+                    logger,
+                    "unable to read configuration file \"{}\" ({}), configuration recovered from \"{}\" (uuid: {}, version: {}, services: {})",
+                    path,
+                    err,
+                    generation_path,
+                    config.uuid.to_hyphenated_ref(),
+                    config.version,
+                    config.svc_table.get_ref().visible().count()
+                );
+
+                return Ok(config);
+            }
+        }
+
+        Err(err)
+    }
+
     /// Get client public identity.
     fn to_identity(&self) -> PublicIdentity {
         PublicIdentity { uuid: self.uuid }
@@ -686,36 +2214,85 @@ impl PersistentConfig {
     /// Create a configuration skeleton from this persistent config.
     fn to_skeleton(&self) -> PersistentConfig {
         PersistentConfig {
+            schema_version: self.schema_version,
             uuid: self.uuid.clone(),
             passwd: self.passwd.clone(),
             version: 0,
             svc_table: SharedServiceTable::new(),
+            extra: self.extra.clone(),
         }
     }
 
-    /// Save configuration into a given file.
-    fn save(&self, path: &str) -> Result<(), ConfigError> {
-        let mut file = File::create(path)?;
+    /// Save configuration into a given file. Secrets are encrypted if a key
+    /// is given.
+    fn save(&self, path: &str, key: Option<&SecretsKey>) -> Result<(), ConfigError> {
+        let mut config = self.to_json();
+
+        if let Some(key) = key {
+            secrets::encrypt_fields(&mut config, key)?;
+        }
 
-        self.to_json().write(&mut file)?;
+        utils::write_file_atomically(path, config.dump().as_bytes())?;
 
         Ok(())
     }
+
+    /// Save configuration into a given file and keep a given number of its
+    /// previous generations (named "<path>.1" (the newest) to "<path>.N").
+    /// The current file is not kept if it is not a valid configuration.
+    fn save_with_generations(
+        &self,
+        path: &str,
+        generations: usize,
+        key: Option<&SecretsKey>,
+    ) -> Result<(), ConfigError> {
+        if generations > 0 && PersistentConfig::load(path, key).is_ok() {
+            for generation in (1..generations).rev() {
+                let src = get_generation_path(path, generation);
+
+                if Path::new(&src).exists() {
+                    fs::rename(&src, get_generation_path(path, generation + 1))?;
+                }
+            }
+
+            let dst = get_generation_path(path, 1);
+
+            fs::remove_file(&dst).unwrap_or(());
+
+            // the current file will be replaced by a new one, so it is enough
+            // to create a hard link (if supported by the filesystem)
+            fs::hard_link(path, &dst).or_else(|_| fs::copy(path, &dst).map(|_| ()))?;
+        }
+
+        self.save(path, key)
+    }
 }
 
 impl ToJson for PersistentConfig {
     fn to_json(&self) -> JsonValue {
-        object! {
+        let mut res = object! {
+            "schema_version" => cmp::max(self.schema_version, schema::SCHEMA_VERSION),
             "uuid" => format!("{}", self.uuid.to_hyphenated_ref()),
             "passwd" => format!("{}", self.passwd.to_hyphenated_ref()),
             "version" => self.version,
             "svc_table" => self.svc_table.to_json()
+        };
+
+        // keep fields written by newer versions of the application
+        for (key, value) in self.extra.entries() {
+            if !res.has_key(key) {
+                res[key] = value.clone();
+            }
         }
+
+        res
     }
 }
 
 impl FromJson for PersistentConfig {
-    fn from_json(value: JsonValue) -> Result<Self, ParseError> {
+    fn from_json(mut value: JsonValue) -> Result<Self, ParseError> {
+        let schema_version = schema::upgrade(&mut value)?;
+
         let mut config;
 
         if let JsonValue::Object(cfg) = value {
@@ -724,6 +2301,8 @@ impl FromJson for PersistentConfig {
             return Err(ParseError::from("JSON object expected"));
         }
 
+        config.remove("schema_version");
+
         let svc_table = config
             .remove("svc_table")
             .ok_or(ParseError::from("missing field \"svc_table\""))?;
@@ -732,31 +2311,174 @@ impl FromJson for PersistentConfig {
             .map_err(|err| ParseError::from(format!("unable to parse service table: {}", err)))?;
 
         let uuid = config
-            .get("uuid")
-            .and_then(|v| v.as_str())
+            .remove("uuid")
             .ok_or(ParseError::from("missing field \"uuid\""))?;
         let passwd = config
-            .get("passwd")
-            .and_then(|v| v.as_str())
+            .remove("passwd")
             .ok_or(ParseError::from("missing field \"passwd\""))?;
         let version = config
-            .get("version")
+            .remove("version")
             .and_then(|v| v.as_usize())
             .ok_or(ParseError::from("missing field \"version\""))?;
 
-        let uuid = Uuid::from_str(uuid).map_err(|_| ParseError::from("unable to parse UUID"))?;
-        let passwd =
-            Uuid::from_str(passwd).map_err(|_| ParseError::from("unable to parse UUID"))?;
+        let uuid = uuid
+            .as_str()
+            .and_then(|uuid| Uuid::from_str(uuid).ok())
+            .ok_or(ParseError::from("unable to parse UUID"))?;
+        let passwd = passwd
+            .as_str()
+            .and_then(|passwd| Uuid::from_str(passwd).ok())
+            .ok_or(ParseError::from("unable to parse UUID"))?;
 
         let res = PersistentConfig {
-            uuid: uuid,
-            passwd: passwd,
-            version: version,
-            svc_table: svc_table,
+            schema_version,
+            uuid,
+            passwd,
+            version,
+            svc_table,
+            extra: JsonValue::Object(config),
+        };
+
+        Ok(res)
+    }
+}
+
+/// Helper for loading the reloadable part of the application configuration.
+/// It does not keep any reference to the application configuration, so that
+/// the (potentially slow) loading does not block other users of the config.
+#[derive(Clone)]
+pub struct ConfigLoader {
+    config_file: String,
+    secrets_key: Option<SecretsKey>,
+    options_file: Option<String>,
+    ca_certificates: Vec<String>,
+    services: Vec<Service>,
+    allowed_networks: Vec<IpNetwork>,
+    allowed_ports: Vec<u16>,
+    allowed_service_types: Vec<String>,
+    http_path_rules: Vec<HttpPathRule>,
+    overridden: Vec<&'static str>,
+    log_level: Option<LogFilter>,
+}
+
+impl ConfigLoader {
+    /// Load the configuration file and the options file.
+    pub fn load(&self) -> Result<ReloadableConfig, ConfigError> {
+        let config = PersistentConfig::load(&self.config_file, self.secrets_key.as_ref()).map_err(
+            |err| {
+                ConfigError::from(format!(
+                    "unable to read configuration file \"{}\": {}",
+                    self.config_file, err
+                ))
+            },
+        )?;
+
+        let options = self.load_options()?;
+
+        let ca_certificates = self.get_ca_certificates(&options);
+
+        // make sure that we will be able to create a TLS connector
+        create_tls_connector(&ca_certificates)
+            .map_err(|err| ConfigError::from(format!("unable to load CA certificates: {}", err)))?;
+
+        let res = ReloadableConfig {
+            schema_version: config.schema_version,
+            uuid: config.uuid,
+            passwd: config.passwd,
+            extra: config.extra,
+            ca_certificates,
+            services: self.get_services(&options)?,
+            destination_policy: self.get_destination_policy(&options)?,
+            http_path_policy: self.get_http_path_policy(&options)?,
+            log_level: self.get_log_level(&options),
+        };
+
+        Ok(res)
+    }
+
+    /// Load the options file (if specified). Reloadable lists given as
+    /// command line arguments or environment variables are removed from the
+    /// loaded options because they replace the lists from the options file.
+    fn load_options(&self) -> Result<Options, ConfigError> {
+        let mut options = if let Some(ref options_file) = self.options_file {
+            load_options_file(options_file)?
+        } else {
+            Options::default()
         };
 
+        for key in &self.overridden {
+            options.remove(key);
+        }
+
+        Ok(options)
+    }
+
+    /// Get all CA certificates from the command line (or environment
+    /// variables) or from given options.
+    fn get_ca_certificates(&self, options: &Options) -> Vec<String> {
+        let mut res = self.ca_certificates.clone();
+
+        res.extend(options.ca_certificates());
+
+        res
+    }
+
+    /// Get all static services from the command line (or environment
+    /// variables) and from given options.
+    fn get_services(&self, options: &Options) -> Result<Vec<Service>, ConfigError> {
+        let mut res = self.services.clone();
+
+        res.extend(options.services()?);
+
         Ok(res)
     }
+
+    /// Get the destination policy from the command line (or environment
+    /// variables) and from given options.
+    fn get_destination_policy(&self, options: &Options) -> Result<DestinationPolicy, ConfigError> {
+        let mut networks = self.allowed_networks.clone();
+        let mut ports = self.allowed_ports.clone();
+        let mut service_types = self.allowed_service_types.clone();
+
+        networks.extend(get_allowed_networks(options)?);
+        ports.extend(options.get_ports("allowed_ports").unwrap_or_default());
+        service_types.extend(get_allowed_service_types(options)?);
+
+        Ok(DestinationPolicy::new(networks, ports, service_types))
+    }
+
+    /// Get the HTTP path policy from the command line (or environment
+    /// variables) and from given options.
+    fn get_http_path_policy(&self, options: &Options) -> Result<HttpPathPolicy, ConfigError> {
+        let mut rules = self.http_path_rules.clone();
+
+        rules.extend(get_http_path_rules(options)?);
+
+        Ok(HttpPathPolicy::new(rules))
+    }
+
+    /// Get log level (i.e. the log filter). The log level given as
+    /// a command line argument or an environment variable has higher
+    /// priority than the options file.
+    fn get_log_level(&self, options: &Options) -> LogFilter {
+        self.log_level
+            .clone()
+            .or_else(|| options.log_level())
+            .unwrap_or_else(|| LogFilter::new(Severity::INFO))
+    }
+}
+
+/// Reloadable part of the application configuration.
+pub struct ReloadableConfig {
+    schema_version: u64,
+    uuid: Uuid,
+    passwd: Uuid,
+    extra: JsonValue,
+    ca_certificates: Vec<String>,
+    services: Vec<Service>,
+    destination_policy: DestinationPolicy,
+    http_path_policy: HttpPathPolicy,
+    log_level: LogFilter,
 }
 
 /// Struct holding application configuration loaded from a configuration file and passed as
@@ -769,7 +2491,11 @@ pub struct ApplicationConfig {
     arrow_svc_addr: String,
     ca_certificates: Vec<String>,
     config_file: String,
+    config_generations: usize,
+    schema_version: u64,
+    extra: JsonValue,
     state_file: String,
+    state_file_format: StateFileFormat,
     rtsp_paths_file: String,
     mjpeg_paths_file: String,
     diagnostic_mode: bool,
@@ -777,6 +2503,23 @@ pub struct ApplicationConfig {
     svc_table: SharedServiceTable,
     default_svc_table: SharedServiceTable,
     logger: BoxLogger,
+    log_buffer: LogRingBuffer,
+    loader: ConfigLoader,
+    api_addresses: Vec<ApiAddress>,
+    metrics: Metrics,
+    event_hooks: EventHooks,
+    scan_period: Duration,
+    rtsp_ports: Vec<u16>,
+    http_ports: Vec<u16>,
+    connection_timeout: Duration,
+    retry_timeout: Duration,
+    proxy: Option<String>,
+    pairing_window: Duration,
+    pairing_trigger: Option<String>,
+    pairing_code: bool,
+    pairing_code_file: Option<String>,
+    destination_policy: DestinationPolicy,
+    http_path_policy: HttpPathPolicy,
     _lock_file: Option<File>,
 }
 
@@ -784,9 +2527,54 @@ impl ApplicationConfig {
     /// Create a new application configuration. The methods reads all command line arguments and
     /// loads the configuration file.
     pub fn create() -> Result<ApplicationConfig, ConfigError> {
+        let builder = ApplicationConfigBuilder::new()?.configure(env::args().collect())?;
+
+        if builder.print_config {
+            println!("{}", builder.effective_options()?.to_json().pretty(4));
+            process::exit(0);
+        }
+
+        builder.build()
+    }
+
+    /// Encrypt secrets in the configuration file (including its previous
+    /// generations) and in the configuration file skeleton. The arguments
+    /// are the same as the application arguments (excluding the program
+    /// name).
+    pub fn encrypt_secrets<I>(args: I) -> Result<(), ConfigError>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        // the program name is expected by the argument parser
+        let args = iter::once(String::new()).chain(args).collect();
+
         ApplicationConfigBuilder::new()?
-            .parse(std::env::args())?
-            .build()
+            .configure(args)?
+            .encrypt_secrets()
+    }
+
+    /// Create a new application configuration for the self-test diagnostics.
+    /// The arguments are the same as the application arguments (excluding
+    /// the program name). The diagnostic mode is always enabled, log messages
+    /// are sent into stderr and no lock file is created.
+    pub fn create_diagnostic<I>(args: I) -> Result<ApplicationConfig, ConfigError>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        // the program name is expected by the argument parser
+        let args = iter::once(String::new()).chain(args).collect();
+
+        let mut builder = ApplicationConfigBuilder::new()?.configure(args)?;
+
+        builder.diagnostic_mode = true;
+        builder.logger_type = LoggerType::Stderr;
+        builder.lock_file = None;
+
+        if builder.log_level.is_none() {
+            builder.log_level = Some(LogFilter::new(Severity::WARN));
+        }
+
+        builder.build()
     }
 
     /// Get address of the remote Arrow Service.
@@ -824,6 +2612,11 @@ impl ApplicationConfig {
         &self.state_file
     }
 
+    /// Get format of the connection state file.
+    pub fn get_connection_state_format(&self) -> StateFileFormat {
+        self.state_file_format
+    }
+
     /// Get path to a file containing RTSP paths for the network scanner.
     pub fn get_rtsp_paths_file(&self) -> &str {
         &self.rtsp_paths_file
@@ -834,42 +2627,212 @@ impl ApplicationConfig {
         &self.mjpeg_paths_file
     }
 
+    /// Get listening addresses of the local management API.
+    pub fn get_api_addresses(&self) -> &[ApiAddress] {
+        &self.api_addresses
+    }
+
     /// Get logger.
     pub fn get_logger(&self) -> BoxLogger {
         self.logger.clone()
     }
 
+    /// Get the in-memory log ring buffer.
+    pub fn get_log_buffer(&self) -> LogRingBuffer {
+        self.log_buffer.clone()
+    }
+
+    /// Get metrics registry.
+    pub fn get_metrics(&self) -> Metrics {
+        self.metrics.clone()
+    }
+
+    /// Get event hooks.
+    pub fn get_event_hooks(&self) -> EventHooks {
+        self.event_hooks.clone()
+    }
+
+    /// Get network scan period.
+    pub fn get_scan_period(&self) -> Duration {
+        self.scan_period
+    }
+
+    /// Get RTSP port candidates for the network scanner.
+    pub fn get_rtsp_port_candidates(&self) -> &[u16] {
+        &self.rtsp_ports
+    }
+
+    /// Get HTTP port candidates for the network scanner.
+    pub fn get_http_port_candidates(&self) -> &[u16] {
+        &self.http_ports
+    }
+
+    /// Get timeout for connecting to the Arrow Service.
+    pub fn get_connection_timeout(&self) -> Duration {
+        self.connection_timeout
+    }
+
+    /// Get minimum time between two connection attempts.
+    pub fn get_retry_timeout(&self) -> Duration {
+        self.retry_timeout
+    }
+
+    /// Get HTTP proxy (in the "host:port" format) used for connecting to
+    /// the Arrow Service.
+    pub fn get_proxy(&self) -> Option<&str> {
+        self.proxy.as_deref()
+    }
+
+    /// Get length of the pairing window.
+    pub fn get_pairing_window(&self) -> Duration {
+        self.pairing_window
+    }
+
+    /// Get path to the pairing trigger file (if any).
+    pub fn get_pairing_trigger(&self) -> Option<&str> {
+        self.pairing_trigger.as_deref()
+    }
+
+    /// Check if the pairing code should be displayed.
+    pub fn is_pairing_code_enabled(&self) -> bool {
+        self.pairing_code
+    }
+
+    /// Get path to the file where the pairing code should be written (if
+    /// any).
+    pub fn get_pairing_code_file(&self) -> Option<&str> {
+        self.pairing_code_file.as_deref()
+    }
+
+    /// Get the local policy of allowed service destinations.
+    pub fn get_destination_policy(&self) -> &DestinationPolicy {
+        &self.destination_policy
+    }
+
+    /// Get the local policy of HTTP paths accessible through HTTP services.
+    pub fn get_http_path_policy(&self) -> &HttpPathPolicy {
+        &self.http_path_policy
+    }
+
     /// Get TLS connector for a given server hostname.
     pub fn get_tls_connector(&self) -> Result<TlsConnector, RuntimeError> {
-        let mut builder = SslConnector::builder(SslMethod::tls()).map_err(|err| {
-            RuntimeError::from(format!(
-                "unable to create a TLS connection builder: {}",
-                err
-            ))
-        })?;
+        create_tls_connector(&self.ca_certificates)
+    }
+
+    /// Get loader for the reloadable part of the configuration.
+    pub fn get_loader(&self) -> ConfigLoader {
+        self.loader.clone()
+    }
+
+    /// Apply a given reloaded configuration and log all changes. Static
+    /// services are diffed into the service table, discovered services are
+    /// not affected.
+    pub fn reload(&mut self, config: ReloadableConfig) {
+        let mut changed = false;
+
+        if self.uuid != config.uuid || self.passwd != config.passwd {
+            log_info!(
+                self.logger,
+                "client credentials changed (uuid: {})",
+                config.uuid.to_hyphenated_ref()
+            );
+
+            self.uuid = config.uuid;
+            self.passwd = config.passwd;
+
+            self.logger.set_secrets(get_secrets(&self.passwd));
+
+            changed = true;
+        }
+
+        self.schema_version = config.schema_version;
+        self.extra = config.extra;
+
+        if self.ca_certificates != config.ca_certificates {
+            log_info!(
+                self.logger,
+                "CA certificates changed (the new ones will be used for the next connection): {}",
+                config.ca_certificates.join(", ")
+            );
+
+            self.ca_certificates = config.ca_certificates;
+
+            changed = true;
+        }
+
+        let visible = self.get_visible_services();
+
+        if self.destination_policy != config.destination_policy {
+            log_info!(
+                self.logger,
+                "destination policy changed (the new one will be used for new sessions)"
+            );
+
+            self.destination_policy = config.destination_policy;
+
+            changed = true;
+        }
+
+        if self.http_path_policy != config.http_path_policy {
+            log_info!(
+                self.logger,
+                "HTTP path policy changed (the new one will be used for new sessions)"
+            );
 
-        let mut options = builder.options();
+            self.http_path_policy = config.http_path_policy;
 
-        options.insert(SslOptions::NO_COMPRESSION);
-        options.insert(SslOptions::NO_SSLV2);
-        options.insert(SslOptions::NO_SSLV3);
-        options.insert(SslOptions::NO_TLSV1);
-        options.insert(SslOptions::NO_TLSV1_1);
+            changed = true;
+        }
+
+        if self.remove_denied_services() {
+            self.version += 1;
+
+            changed = true;
+        }
+
+        let services = self.filter_allowed_services(config.services);
+
+        let (added, removed) = self.svc_table.set_static_services(services.clone());
+
+        self.emit_service_changes(visible);
+
+        self.default_svc_table.set_static_services(services);
+
+        for svc in &added {
+            log_info!(self.logger, "static service added: {}", svc);
+        }
+
+        for svc in &removed {
+            log_info!(self.logger, "static service removed: {}", svc);
+        }
+
+        if !added.is_empty() || !removed.is_empty() {
+            self.version += 1;
+
+            changed = true;
+        }
+
+        let log_level = self.logger.get_filter();
 
-        builder.set_options(options);
+        if log_level != config.log_level {
+            log_info!(
+                self.logger,
+                "log level changed from {} to {}",
+                log_level,
+                config.log_level
+            );
 
-        builder.set_verify(SslVerifyMode::PEER);
-        builder
-            .set_cipher_list(SSL_CIPHER_LIST)
-            .map_err(|err| RuntimeError::from(format!("unable to set TLS cipher list: {}", err)))?;
+            self.logger.set_filter(config.log_level);
 
-        for ca_cert in &self.ca_certificates {
-            builder.load_ca_certificates(&ca_cert)?;
+            changed = true;
         }
 
-        let connector = TlsConnector::from(builder.build());
+        if !changed {
+            log_info!(self.logger, "configuration reloaded, no changes");
+            return;
+        }
 
-        Ok(connector)
+        self.save_or_log();
     }
 
     /// Get read-only reference to the shared service table.
@@ -879,18 +2842,15 @@ impl ApplicationConfig {
 
     /// Reset the service table.
     pub fn reset_service_table(&mut self) {
+        let visible = self.get_visible_services();
+
         self.svc_table = self.default_svc_table.clone();
 
-        self.version += 1;
+        self.emit_service_changes(visible);
 
-        let res = self.save();
+        self.version += 1;
 
-        utils::result_or_log(
-            &mut self.logger,
-            Severity::WARN,
-            format!("unable to save config file \"{}\"", self.config_file),
-            res,
-        );
+        self.save_or_log();
     }
 
     /// Update service table. Add all given services into the table and update active services.
@@ -900,7 +2860,9 @@ impl ApplicationConfig {
     {
         let old_version = self.svc_table.version();
 
-        for svc in services {
+        let visible = self.get_visible_services();
+
+        for svc in self.filter_allowed_services(services) {
             self.svc_table.add(svc);
         }
 
@@ -910,10 +2872,101 @@ impl ApplicationConfig {
             return;
         }
 
+        self.emit_service_changes(visible);
+
         self.version += 1;
 
+        self.save_or_log();
+    }
+
+    /// Remove all services that are not allowed by the destination policy
+    /// from given services. Every removed service is logged.
+    fn filter_allowed_services<I>(&mut self, services: I) -> Vec<Service>
+    where
+        I: IntoIterator<Item = Service>,
+    {
+        let mut res = Vec::new();
+
+        for svc in services {
+            if let Err(err) = self.destination_policy.check_service(&svc) {
+                log_warn!(
+                    self.logger,
+                    "service {} denied by the destination policy: {}",
+                    svc,
+                    err
+                );
+            } else {
+                res.push(svc);
+            }
+        }
+
+        res
+    }
+
+    /// Remove all services that are not allowed by the destination policy
+    /// from the service table (including static and discovered services
+    /// loaded from the configuration file). Every removed service is logged.
+    /// The method returns true if any service has been removed.
+    fn remove_denied_services(&mut self) -> bool {
+        let policy = &self.destination_policy;
+
+        let removed = self
+            .svc_table
+            .remove_services(|svc| policy.check_service(svc).is_err());
+
+        self.default_svc_table
+            .remove_services(|svc| policy.check_service(svc).is_err());
+
+        for svc in &removed {
+            log_warn!(
+                self.logger,
+                "service {} removed, it is denied by the destination policy",
+                svc
+            );
+        }
+
+        !removed.is_empty()
+    }
+
+    /// Get all visible services from the service table.
+    fn get_visible_services(&self) -> Vec<ServiceTableEntry> {
+        self.svc_table
+            .get_ref()
+            .entries()
+            .into_iter()
+            .filter(|entry| entry.visible)
+            .collect()
+    }
+
+    /// Compare a given list of previously visible services with the
+    /// currently visible services and emit the corresponding events.
+    fn emit_service_changes(&self, old: Vec<ServiceTableEntry>) {
+        let new = self.get_visible_services();
+
+        let contains = |entries: &[ServiceTableEntry], id| entries.iter().any(|e| e.id == id);
+
+        for entry in &old {
+            if !contains(&new, entry.id) {
+                self.event_hooks.emit(Event::ServiceRemoved(entry.clone()));
+            }
+        }
+
+        for entry in new {
+            if !contains(&old, entry.id) {
+                self.event_hooks.emit(Event::ServiceAdded(entry));
+            }
+        }
+    }
+
+    /// Save the current configuration into the configuration file and log
+    /// an error (if any).
+    fn save_or_log(&mut self) {
         let res = self.save();
 
+        if res.is_err() {
+            self.metrics.config_save_failed();
+        }
+
         utils::result_or_log(
             &mut self.logger,
             Severity::WARN,
@@ -924,18 +2977,184 @@ impl ApplicationConfig {
 
     /// Save the current configuration into the configuration file.
     fn save(&self) -> Result<(), ConfigError> {
-        self.to_persistent_config().save(&self.config_file)
+        self.to_persistent_config().save_with_generations(
+            &self.config_file,
+            self.config_generations,
+            self.loader.secrets_key.as_ref(),
+        )
     }
 
     /// Create persistent configuration.
     fn to_persistent_config(&self) -> PersistentConfig {
         PersistentConfig {
+            schema_version: self.schema_version,
             uuid: self.uuid,
             passwd: self.passwd,
             version: self.version,
             svc_table: self.svc_table.clone(),
+            extra: self.extra.clone(),
+        }
+    }
+}
+
+/// Load a given options file.
+fn load_options_file(path: &str) -> Result<Options, ConfigError> {
+    Options::load(path).map_err(|err| {
+        ConfigError::from(format!("unable to read options file \"{}\": {}", path, err))
+    })
+}
+
+/// Parse given networks in the CIDR notation.
+fn parse_networks<'a, I>(networks: I) -> Result<Vec<IpNetwork>, ConfigError>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    networks
+        .into_iter()
+        .map(|network| network.trim().parse())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| ConfigError::from(format!("{}", err)))
+}
+
+/// Get allowed networks from given options.
+fn get_allowed_networks(options: &Options) -> Result<Vec<IpNetwork>, ConfigError> {
+    let networks = options.get_strings("allowed_networks");
+
+    parse_networks(networks.iter().map(|n| n.as_str()))
+        .map_err(|err| ConfigError::from(format!("invalid value of \"allowed_networks\", {}", err)))
+}
+
+/// Get allowed service types from given options.
+fn get_allowed_service_types(options: &Options) -> Result<Vec<String>, ConfigError> {
+    let types = options.get_strings("allowed_service_types");
+
+    parse_policy_service_types(types.iter().map(|t| t.as_str())).map_err(|err| {
+        ConfigError::from(format!(
+            "invalid value of \"allowed_service_types\", {}",
+            err
+        ))
+    })
+}
+
+/// Get HTTP path rules from given options.
+fn get_http_path_rules(options: &Options) -> Result<Vec<HttpPathRule>, ConfigError> {
+    options
+        .get_strings("http_path_rules")
+        .iter()
+        .map(|rule| rule.parse())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| ConfigError::from(format!("invalid value of \"http_path_rules\", {}", err)))
+}
+
+/// Parse given service type names used by the destination policy.
+fn parse_policy_service_types<'a, I>(types: I) -> Result<Vec<String>, ConfigError>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let mut res = Vec::new();
+
+    for t in types {
+        let t = t.trim();
+
+        if !POLICY_SERVICE_TYPES.contains(&t) {
+            return Err(ConfigError::from(format!(
+                "unknown service type \"{}\" (expected one of: {})",
+                t,
+                POLICY_SERVICE_TYPES.join(", ")
+            )));
         }
+
+        res.push(t.to_string());
+    }
+
+    Ok(res)
+}
+
+/// Parse a given management API address. Only loopback addresses are
+/// allowed.
+fn parse_api_address(addr: &str) -> Result<ApiAddress, ConfigError> {
+    let addr = addr
+        .parse::<SocketAddr>()
+        .map_err(|_| ConfigError::from("socket address expected"))?;
+
+    if !addr.ip().is_loopback() {
+        return Err(ConfigError::from(
+            "the management API can listen only on a loopback address",
+        ));
+    }
+
+    Ok(ApiAddress::Tcp(addr))
+}
+
+/// Parse a given management API socket path. Only absolute paths are
+/// allowed.
+fn parse_api_socket(path: &str) -> Result<ApiAddress, ConfigError> {
+    let path = Path::new(path);
+
+    if !path.is_absolute() || path.file_name().is_none() {
+        return Err(ConfigError::from("absolute path of a socket file expected"));
     }
+
+    Ok(ApiAddress::Unix(path.to_path_buf()))
+}
+
+/// Replace a given list with a given non-empty list. The list is kept if
+/// the new one is empty.
+fn replace_list<T>(list: &mut Vec<T>, new: Vec<T>) {
+    if !new.is_empty() {
+        *list = new;
+    }
+}
+
+/// Clear a given list when the corresponding command line argument is used
+/// for the first time, so that the command line arguments replace the list
+/// from the options file and environment variables.
+fn override_list<T>(overridden: &mut HashSet<&'static str>, key: &'static str, list: &mut Vec<T>) {
+    if overridden.insert(key) {
+        list.clear();
+    }
+}
+
+/// Parse a given comma-separated list of port numbers.
+fn parse_port_list(ports: &str) -> Result<Vec<u16>, ConfigError> {
+    ports
+        .split(',')
+        .map(|port| port.trim().parse())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| ConfigError::from("comma-separated list of port numbers expected"))
+}
+
+/// Create a new TLS connector using given CA certificates.
+fn create_tls_connector(ca_certificates: &[String]) -> Result<TlsConnector, RuntimeError> {
+    let mut builder = SslConnector::builder(SslMethod::tls()).map_err(|err| {
+        RuntimeError::from(format!(
+            "unable to create a TLS connection builder: {}",
+            err
+        ))
+    })?;
+
+    let mut options = builder.options();
+
+    options.insert(SslOptions::NO_COMPRESSION);
+    options.insert(SslOptions::NO_SSLV2);
+    options.insert(SslOptions::NO_SSLV3);
+    options.insert(SslOptions::NO_TLSV1);
+    options.insert(SslOptions::NO_TLSV1_1);
+
+    builder.set_options(options);
+
+    builder.set_verify(SslVerifyMode::PEER);
+    builder
+        .set_cipher_list(SSL_CIPHER_LIST)
+        .map_err(|err| RuntimeError::from(format!("unable to set TLS cipher list: {}", err)))?;
+
+    for ca_cert in ca_certificates {
+        builder.load_ca_certificates(&ca_cert)?;
+    }
+
+    let connector = TlsConnector::from(builder.build());
+
+    Ok(connector)
 }
 
 /// Get MAC address of the first configured ethernet device.
@@ -1124,11 +3343,40 @@ fn parse_mjpeg_url(url: &str) -> Result<Service, ConfigError> {
     }
 }
 
+/// Parse a given socket address and return an HTTP service or an error.
+fn parse_http_address(addr: &str) -> Result<Service, ConfigError> {
+    let addr = net::utils::get_socket_address(addr)
+        .map_err(|_| ConfigError::from(format!("unable to resolve socket address: {}", addr)))?;
+
+    let mac = get_fake_mac(0xffff, &addr);
+
+    Ok(Service::http(mac, addr))
+}
+
+/// Parse a given socket address and return a TCP service or an error.
+fn parse_tcp_address(addr: &str) -> Result<Service, ConfigError> {
+    let addr = net::utils::get_socket_address(addr)
+        .map_err(|_| ConfigError::from(format!("unable to resolve socket address: {}", addr)))?;
+
+    let mac = get_fake_mac(0xffff, &addr);
+
+    Ok(Service::tcp(mac, addr))
+}
+
 /// Print usage and exit the process with a given exit code.
 pub fn usage(exit_code: i32) -> ! {
-    println!("USAGE: arrow-client arr-host[:arr-port] [OPTIONS]\n");
+    println!("USAGE: arrow-client arr-host[:arr-port] [OPTIONS]");
+    println!("       arrow-client ctl [CTL-OPTIONS] command [args]");
+    println!("       arrow-client diagnose [--json] arr-host[:arr-port] [OPTIONS]");
+    println!("       arrow-client encrypt-secrets --secrets-key=src [OPTIONS]");
+    println!("       arrow-client identity command [args] [OPTIONS]\n");
     println!("    arr-host  Angelcam Arrow Service host");
     println!("    arr-port  Angelcam Arrow Service port\n");
+    println!("The Arrow Service address can be omitted if it is given in the options file");
+    println!("or using the ARROW_SERVICE_ADDRESS environment variable. All options can be");
+    println!("also set in the options file or using ARROW_* environment variables. Command");
+    println!("line arguments take precedence over environment variables and environment");
+    println!("variables take precedence over the options file.\n");
     println!("OPTIONS:\n");
     println!("    -i iface  ethernet interface used for client identification (the first");
     println!("              configured network interface is used by default)");
@@ -1148,22 +3396,49 @@ pub fn usage(exit_code: i32) -> ! {
     println!("              format)");
     println!("    -t addr   add a given TCP service (addr must be in the \"host:port\"");
     println!("              format)");
-    println!("    -v        enable debug logs\n");
+    println!("    -v        enable debug logs (the same as --log-level=debug)\n");
     println!("    --config-file=path  alternative path to the client configuration file");
     println!("                        (default value: /etc/arrow/config.json)");
     println!("    --config-file-skel=path  the client will use this file as a backup for");
     println!("                        its credentials (default value:");
     println!("                        /etc/arrow/config-skel.json)");
+    println!("    --config-generations=n  number of kept previous versions of the");
+    println!("                        configuration file; they are used if the");
+    println!("                        configuration file is damaged (default value: 3)");
+    println!("    --secrets-key=src   encrypt secrets in the configuration file using a key");
+    println!("                        from a given source; \"file:path\" (content of a given");
+    println!("                        file), \"env:VARIABLE\" (value of a given environment");
+    println!("                        variable) or \"machine-id\" (the machine ID combined");
+    println!("                        with a salt from the root-only file");
+    println!("                        /etc/arrow/machine-id.salt; the configuration can be");
+    println!("                        decrypted only on this machine); use the");
+    println!("                        \"encrypt-secrets\" command to encrypt existing");
+    println!("                        configuration files");
+    println!("    --options-file=path  a JSON file containing options (the path can be also");
+    println!("                        given using the ARROW_OPTIONS_FILE environment");
+    println!("                        variable); CA certificates, static services, log");
+    println!("                        level, destination policy and HTTP path rules are");
+    println!("                        re-read together with the configuration file on");
+    println!("                        SIGHUP");
+    println!("    --print-config      print the effective configuration in the options file");
+    println!("                        format and exit");
     println!("    --identity-file=path  a file that will contain only the public part of");
     println!("                        the client identification (i.e. there will be no");
     println!("                        secret in the file)");
     println!("    --conn-state-file=path  alternative path to the client connection state");
     println!("                        file (default value: /var/lib/arrow/state)");
+    println!("    --conn-state-format=fmt  format of the connection state file; \"json\"");
+    println!("                        (default) or \"plain\" (a single word containing the");
+    println!("                        connection state)");
     println!("    --diagnostic-mode   start the client in diagnostic mode (i.e. the client");
     println!("                        will try to connect to a given Arrow Service and it");
     println!("                        will report success as its exit code; note: the");
     println!("                        \"access denied\" response from the server is also");
     println!("                        considered as a success)");
+    println!("    --log-level=level   log level (one of: debug, info, warn, error; default");
+    println!("                        value: info); log levels of particular modules can");
+    println!("                        be set using comma-separated directives, e.g.:");
+    println!("                        \"warn,net::arrow::session=debug,scanner=info\"");
     println!("    --log-stderr        send log messages into stderr instead of syslog");
     println!("    --log-stderr-pretty  send log messages into stderr instead of syslog and");
     println!("                        use colored messages");
@@ -1172,6 +3447,41 @@ pub fn usage(exit_code: i32) -> ! {
     println!("                        10240)");
     println!("    --log-file-rotations=n  number of backup files (i.e. rotations) for the");
     println!("                        log file (default value: 1)");
+    println!("    --log-file-period=p  rotate the log file also on every hour or day; one");
+    println!("                        of: \"hourly\" or \"daily\"");
+    println!("    --log-file-compress  compress rotated log files using gzip");
+    println!("    --log-file-budget=n  limit for the total size of the log file and all its");
+    println!("                        rotated files (in bytes); the oldest rotated files");
+    println!("                        are removed when the limit is exceeded");
+    println!("    --log-format=fmt    format of log messages sent into stderr or into a log");
+    println!("                        file; one of: \"text\" (default) or \"json\" (one");
+    println!("                        JSON object per line)");
+    println!("    --log-buffer-size=n  size of the in-memory log buffer that can be read by");
+    println!("                        the Arrow Service (in KiB; default value: 64; 0");
+    println!("                        disables the buffer)");
+    println!("    --syslog-host=host  send log messages to a given remote syslog collector");
+    println!("                        instead of the local syslog");
+    println!("    --syslog-port=n     port of the remote syslog collector (default value:");
+    println!("                        514 for UDP and TCP, 6514 for TLS)");
+    println!("    --syslog-transport=t  transport used for sending messages to the remote");
+    println!("                        syslog collector; one of: \"udp\" (default), \"tcp\"");
+    println!("                        or \"tls\"");
+    println!("    --syslog-protocol=p  remote syslog message format; one of: \"rfc5424\"");
+    println!("                        (default) or \"rfc3164\" (legacy BSD syslog)");
+    println!("    --syslog-facility=f  remote syslog facility (e.g. \"daemon\" or");
+    println!("                        \"local0\"; default value: user)");
+    println!("    --syslog-app-name=name  application name sent to the remote syslog");
+    println!("                        collector (default value: arrow-client)");
+    println!("    --syslog-queue-size=n  maximum number of log messages queued while the");
+    println!("                        remote syslog collector is unreachable (the oldest");
+    println!(
+        "                        ones are dropped; default value: {})",
+        remote_syslog::DEFAULT_QUEUE_SIZE
+    );
+    println!("    --syslog-ca=path    CA certificate(s) used for verifying the remote syslog");
+    println!("                        collector (TLS only; the system CA certificates are");
+    println!("                        used by default; this option can be used multiple");
+    println!("                        times)");
     if cfg!(feature = "discovery") {
         println!("    --rtsp-paths=path   alternative path to a file containing list of RTSP");
         println!("                        paths used on service discovery (default value:");
@@ -1179,11 +3489,326 @@ pub fn usage(exit_code: i32) -> ! {
         println!("    --mjpeg-paths=path  alternative path to a file containing list of MJPEG");
         println!("                        paths used on service discovery (default value:");
         println!("                        /etc/arrow/mjpeg-paths)");
-    }
+        println!("    --scan-period=n     network scan period (in seconds; default value:");
+        println!("                        {})", DEFAULT_SCAN_PERIOD);
+        println!("    --rtsp-ports=list   comma-separated list of RTSP port candidates sorted");
+        println!("                        by priority");
+        println!("    --http-ports=list   comma-separated list of HTTP port candidates sorted");
+        println!("                        by priority");
+    }
+    println!("    --connection-timeout=n  timeout for connecting to the Arrow Service (in");
+    println!(
+        "                        seconds; default value: {})",
+        DEFAULT_CONNECTION_TIMEOUT
+    );
+    println!("    --retry-timeout=n   minimum time between two connection attempts (in");
+    println!(
+        "                        seconds; default value: {})",
+        DEFAULT_RETRY_TIMEOUT
+    );
+    println!("    --proxy=host:port   connect to the Arrow Service using a given HTTP proxy");
+    println!("                        (the proxy must support the CONNECT method)");
+    println!("    --pairing-window=n  length of the pairing window (in seconds; default");
+    println!(
+        "                        value: {}); the pairing window can be",
+        DEFAULT_PAIRING_WINDOW
+    );
+    println!("                        reopened using SIGUSR1, the \"ctl pairing-window\"");
+    println!("                        command or the pairing trigger");
+    println!("    --pairing-trigger=path  reopen the pairing window whenever a given file");
+    println!("                        becomes active, i.e. it appears or its content changes");
+    println!("                        from \"0\" to anything else (e.g. a GPIO value file)");
+    println!("    --pairing-code      log a short pairing code derived from the client UUID");
+    println!("                        whenever the pairing window is opened");
+    println!("    --pairing-code-file=path  write the pairing code also into a given file");
+    println!("    --allowed-networks=list  comma-separated list of networks (in the CIDR");
+    println!("                        notation) the client is allowed to connect to; all");
+    println!("                        networks are allowed by default");
+    println!("    --allowed-ports=list  comma-separated list of service ports the client is");
+    println!("                        allowed to connect to; all ports are allowed by");
+    println!("                        default");
+    println!("    --allowed-service-types=list  comma-separated list of allowed service");
+    println!("                        types (rtsp, mjpeg, http, tcp); all service types");
+    println!("                        are allowed by default");
+    println!("    --http-path-rule=rule  allow or deny HTTP paths of proxied HTTP services;");
+    println!("                        the rule format is [addr=]allow:pattern or");
+    println!("                        [addr=]deny:pattern where pattern is a path or a path");
+    println!("                        prefix followed by \"*\"; rejected requests get a local");
+    println!("                        403 response (the option can be used multiple times)");
     println!("    --lock-file=path    make sure that there is only one instance of the");
     println!("                        process running; the file will contain also PID of the");
     println!("                        process");
+    println!("    --api-addr=addr     enable the local management HTTP API on a given");
+    println!("                        loopback address (e.g. 127.0.0.1:8901)");
+    println!("    --api-socket=path   enable the local management HTTP API on a given Unix");
+    println!("                        socket (absolute path)");
+    println!("    --control-socket[=path]");
+    println!("                        enable the control socket used by the \"ctl\" command");
+    println!("                        (default path: {})", CONTROL_SOCKET);
+    println!("    --event-hook=path   run a given executable on connection state changes,");
+    println!("                        pairing window expiration, service table changes and");
+    println!("                        scan completion; the event is passed as JSON on stdin");
+    println!("                        (the option can be used multiple times)");
+    println!("    --event-hook-timeout=n  maximum time for a single hook run (in seconds;");
+    println!(
+        "                        default value: {})",
+        DEFAULT_HOOK_TIMEOUT
+    );
+    println!("    --event-hook-concurrency=n  maximum number of concurrently running hooks");
+    println!(
+        "                        (default value: {})",
+        DEFAULT_MAX_RUNNING_HOOKS
+    );
     println!();
 
     process::exit(exit_code);
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Configuration written by the oldest versions of the application
+    /// (service IDs given by element positions, no timestamps).
+    const CONFIG_V1_LEGACY: &str = r#"{
+        "uuid": "0c4d52e4-4b7a-4c5b-9d3a-e12f4a2a8f10",
+        "passwd": "6b1a0f7e-2d3c-4e5f-8a9b-0c1d2e3f4a5b",
+        "version": 3,
+        "svc_table": {
+            "services": [
+                {"svc_type": 1, "mac": "00:11:22:33:44:55", "address": "10.0.0.2:554", "path": "/stream"},
+                {"svc_type": 4, "mac": "00:11:22:33:44:56", "address": "10.0.0.3:80", "path": ""}
+            ]
+        }
+    }"#;
+
+    /// Configuration written before schema versioning was introduced.
+    const CONFIG_V1: &str = r#"{
+        "uuid": "0c4d52e4-4b7a-4c5b-9d3a-e12f4a2a8f10",
+        "passwd": "6b1a0f7e-2d3c-4e5f-8a9b-0c1d2e3f4a5b",
+        "version": 3,
+        "svc_table": {
+            "services": [
+                {"id": 1, "svc_type": 1, "mac": "00:11:22:33:44:55", "address": "10.0.0.2:554", "path": "/stream", "static_svc": false, "last_seen": 1500000000, "active": true},
+                {"id": 2, "svc_type": 4, "mac": "00:11:22:33:44:56", "address": "10.0.0.3:80", "path": "", "static_svc": false, "last_seen": 1500000000, "active": true}
+            ]
+        }
+    }"#;
+
+    /// Configuration of the current schema version.
+    const CONFIG_V2: &str = r#"{
+        "schema_version": 2,
+        "uuid": "0c4d52e4-4b7a-4c5b-9d3a-e12f4a2a8f10",
+        "passwd": "6b1a0f7e-2d3c-4e5f-8a9b-0c1d2e3f4a5b",
+        "version": 3,
+        "svc_table": {
+            "services": [
+                {"id": 1, "svc_type": 1, "mac": "00:11:22:33:44:55", "address": "10.0.0.2:554", "path": "/stream", "static_svc": false, "last_seen": 1500000000, "active": true},
+                {"id": 2, "svc_type": 4, "mac": "00:11:22:33:44:56", "address": "10.0.0.3:80", "path": "", "static_svc": false, "last_seen": 1500000000, "active": true}
+            ]
+        }
+    }"#;
+
+    /// Configuration written by a newer version of the application.
+    const CONFIG_FUTURE: &str = r#"{
+        "schema_version": 3,
+        "uuid": "0c4d52e4-4b7a-4c5b-9d3a-e12f4a2a8f10",
+        "passwd": "6b1a0f7e-2d3c-4e5f-8a9b-0c1d2e3f4a5b",
+        "version": 3,
+        "labels": {"site": "warehouse"},
+        "svc_table": {
+            "services": [
+                {"id": 1, "svc_type": 1, "mac": "00:11:22:33:44:55", "address": "10.0.0.2:554", "path": "/stream", "static_svc": false, "last_seen": 1500000000, "active": true, "credentials": "admin"},
+                {"id": 2, "svc_type": 4, "mac": "00:11:22:33:44:56", "address": "10.0.0.3:80", "path": "", "static_svc": false, "last_seen": 1500000000, "active": true}
+            ]
+        }
+    }"#;
+
+    #[test]
+    fn test_config_fixtures() {
+        let fixtures = [
+            (CONFIG_V1_LEGACY, 1),
+            (CONFIG_V1, 1),
+            (CONFIG_V2, 2),
+            (CONFIG_FUTURE, 3),
+        ];
+
+        for &(fixture, schema_version) in &fixtures {
+            let config = PersistentConfig::from_json(json::parse(fixture).unwrap()).unwrap();
+
+            assert_eq!(config.schema_version, schema_version);
+            assert_eq!(
+                config.uuid.to_hyphenated_ref().to_string(),
+                "0c4d52e4-4b7a-4c5b-9d3a-e12f4a2a8f10"
+            );
+            assert_eq!(config.version, 3);
+
+            let services = config.svc_table.get_ref().entries();
+
+            assert_eq!(services.len(), 2);
+            assert_eq!(services[0].id, 1);
+            assert_eq!(services[0].service.path(), Some("/stream"));
+            assert_eq!(services[1].id, 2);
+
+            // the written configuration must be of the current (or newer)
+            // version and it must load again
+            let json = config.to_json();
+
+            assert_eq!(
+                json["schema_version"],
+                cmp::max(schema_version, schema::SCHEMA_VERSION)
+            );
+
+            let reloaded = PersistentConfig::from_json(json.clone()).unwrap();
+
+            assert_eq!(reloaded.to_json(), json);
+        }
+
+        // unknown fields must be preserved
+        let config = PersistentConfig::from_json(json::parse(CONFIG_FUTURE).unwrap()).unwrap();
+
+        let json = config.to_json();
+
+        assert_eq!(json["labels"]["site"], "warehouse");
+        assert_eq!(json["svc_table"]["services"][0]["credentials"], "admin");
+        assert_eq!(json, json::parse(CONFIG_FUTURE).unwrap());
+    }
+
+    #[test]
+    fn test_config_generations() {
+        let dir = env::temp_dir().join(format!("arrow-config-test-{}", process::id()));
+
+        fs::create_dir_all(&dir).unwrap();
+
+        let path = dir.join("config.json");
+        let path = path.to_str().unwrap();
+
+        let mut logger = logger::stderr::new();
+
+        let mut config = PersistentConfig::new();
+
+        for version in 0..5 {
+            config.version = version;
+            config.save_with_generations(path, 2, None).unwrap();
+        }
+
+        let version = |path: &str| PersistentConfig::load(path, None).unwrap().version;
+
+        assert_eq!(version(path), 4);
+        assert_eq!(version(&get_generation_path(path, 1)), 3);
+        assert_eq!(version(&get_generation_path(path, 2)), 2);
+        assert!(!Path::new(&get_generation_path(path, 3)).exists());
+
+        // damaged configuration file
+        fs::write(path, "{\"uuid\":").unwrap();
+
+        let recovered = PersistentConfig::load_or_recover(&mut logger, path, 2, None).unwrap();
+
+        assert_eq!(recovered.version, 3);
+
+        // damaged configuration file and the newest generation
+        fs::write(get_generation_path(path, 1), "").unwrap();
+
+        let recovered = PersistentConfig::load_or_recover(&mut logger, path, 2, None).unwrap();
+
+        assert_eq!(recovered.version, 2);
+        assert!(PersistentConfig::load_or_recover(&mut logger, path, 1, None).is_err());
+
+        // damaged files must not be kept as generations
+        config.version = 5;
+        config.save_with_generations(path, 2, None).unwrap();
+
+        assert_eq!(version(path), 5);
+        assert_eq!(version(&get_generation_path(path, 2)), 2);
+
+        fs::remove_dir_all(&dir).unwrap_or(());
+    }
+
+    #[test]
+    fn test_list_option_precedence() {
+        let options = json::parse(
+            r#"{
+            "event_hooks": ["/etc/arrow/hooks/file"],
+            "api_addresses": ["127.0.0.1:8901"],
+            "rtsp_services": ["rtsp://10.0.0.2/file"],
+            "tcp_services": ["10.0.0.5:22"]
+        }"#,
+        )
+        .unwrap();
+
+        let options = Options::from_json(options).unwrap();
+
+        let args = vec![
+            "arrow-client",
+            "--event-hook=/etc/arrow/hooks/cli1",
+            "--event-hook=/etc/arrow/hooks/cli2",
+            "--api-addr=127.0.0.1:8902",
+            "-r",
+            "rtsp://10.0.0.2/cli",
+        ];
+
+        let args = args
+            .into_iter()
+            .map(String::from)
+            .collect::<Vec<_>>()
+            .into_iter();
+
+        let mut builder = ApplicationConfigBuilder::new().unwrap();
+
+        builder.apply_options(&options, false).unwrap();
+
+        let builder = builder.parse(args).unwrap();
+
+        assert_eq!(
+            builder.event_hooks,
+            vec!["/etc/arrow/hooks/cli1", "/etc/arrow/hooks/cli2"]
+        );
+
+        let api_addresses = builder
+            .api_addresses
+            .iter()
+            .map(|addr| addr.to_string())
+            .collect::<Vec<_>>();
+
+        assert_eq!(api_addresses, vec!["127.0.0.1:8902"]);
+
+        // reloadable lists from the options file are replaced on load
+        assert_eq!(builder.get_overridden_lists(), vec!["rtsp_services"]);
+
+        let effective = builder.effective_options().unwrap();
+
+        assert_eq!(
+            effective.get_strings("event_hooks"),
+            vec!["/etc/arrow/hooks/cli1", "/etc/arrow/hooks/cli2"]
+        );
+        assert_eq!(
+            effective.get_strings("api_addresses"),
+            vec!["127.0.0.1:8902"]
+        );
+
+        // API addresses and sockets are validated in the options file as well
+        let mut builder = ApplicationConfigBuilder::new().unwrap();
+
+        let mut options = Options::default();
+
+        options.set("api_addresses", vec!["10.0.0.1:8901"]).unwrap();
+
+        assert!(builder.apply_options(&options, false).is_err());
+
+        for path in &["", "api.sock", "/"] {
+            let mut options = Options::default();
+
+            options.set("api_sockets", vec![*path]).unwrap();
+
+            assert!(builder.apply_options(&options, false).is_err());
+        }
+
+        let mut options = Options::default();
+
+        options
+            .set("api_sockets", vec!["/var/run/arrow/api.sock"])
+            .unwrap();
+
+        assert!(builder.apply_options(&options, false).is_ok());
+    }
+}
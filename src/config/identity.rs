@@ -0,0 +1,681 @@
+// Copyright 2019 click2stream, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Identity management utility. It works directly with the configuration
+//! files, so it does not need a running client.
+//!
+//! An exported identity bundle is a JSON object containing the public client
+//! UUID and the encrypted (and authenticated) client credentials:
+//!
+//! ```json
+//! {
+//!     "type": "arrow-identity",
+//!     "uuid": "0c4d52e4-4b7a-4c5b-9d3a-e12f4a2a8f10",
+//!     "data": "enc:v1:..."
+//! }
+//! ```
+
+use std::fs;
+use std::io;
+use std::iter;
+use std::process;
+
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::str::FromStr;
+
+use fs2::FileExt;
+
+use json;
+
+use uuid::Uuid;
+
+use crate::utils;
+
+use super::secrets::{KeySource, SecretsKey};
+use super::{ApplicationConfigBuilder, ConfigError, PersistentConfig};
+
+/// Type of identity bundles. It is also used as additional authenticated
+/// data of the encrypted credentials.
+const BUNDLE_TYPE: &str = "arrow-identity";
+
+/// Note printed after changing the identity.
+const RELOAD_NOTE: &str =
+    "NOTE: a running client must be reloaded (SIGHUP) to use the new identity";
+
+/// Identity commands.
+#[derive(Debug, Clone, Eq, PartialEq)]
+enum IdentityCommand {
+    Show,
+    Export(String),
+    Import(String),
+    Reset,
+    Verify,
+}
+
+/// Identity utility options.
+struct IdentityOptions {
+    command: IdentityCommand,
+    bundle_key: Option<KeySource>,
+    assume_yes: bool,
+    app_args: Vec<String>,
+}
+
+impl IdentityOptions {
+    /// Parse given command line arguments (excluding the program name and
+    /// the "identity" argument). Arguments not recognized by the identity
+    /// utility are considered to be application arguments.
+    fn parse<I>(args: I) -> Result<IdentityOptions, ConfigError>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let mut args = args.into_iter().peekable();
+
+        let command = args
+            .next()
+            .ok_or_else(|| ConfigError::from("missing command"))?;
+
+        let mut path = || {
+            args.next_if(|arg| !arg.starts_with('-'))
+                .ok_or_else(|| ConfigError::from(format!("missing file for \"{}\"", command)))
+        };
+
+        let command = match command.as_str() {
+            "show" => IdentityCommand::Show,
+            "export" => IdentityCommand::Export(path()?),
+            "import" => IdentityCommand::Import(path()?),
+            "reset" => IdentityCommand::Reset,
+            "verify" => IdentityCommand::Verify,
+            _ => {
+                return Err(ConfigError::from(format!(
+                    "invalid command: \"{}\"",
+                    command
+                )))
+            }
+        };
+
+        let mut bundle_key = None;
+        let mut assume_yes = false;
+        let mut app_args = Vec::new();
+
+        for arg in args {
+            if arg == "--yes" {
+                assume_yes = true;
+            } else if let Some(source) = arg.strip_prefix("--bundle-key=") {
+                bundle_key = Some(KeySource::from_str(source).map_err(|err| {
+                    ConfigError::from(format!("invalid value given for {}, {}", arg, err))
+                })?);
+            } else {
+                app_args.push(arg);
+            }
+        }
+
+        let res = IdentityOptions {
+            command,
+            bundle_key,
+            assume_yes,
+            app_args,
+        };
+
+        Ok(res)
+    }
+
+    /// Get the key used for identity bundles.
+    fn get_bundle_key(&self) -> Result<SecretsKey, ConfigError> {
+        let source = self
+            .bundle_key
+            .as_ref()
+            .ok_or_else(|| ConfigError::from("no bundle key given (see --bundle-key)"))?;
+
+        SecretsKey::load(source)
+    }
+}
+
+/// Run the identity utility with given arguments (excluding the program name
+/// and the "identity" argument) and exit the process.
+pub fn main<I>(args: I) -> !
+where
+    I: IntoIterator<Item = String>,
+{
+    let options = match IdentityOptions::parse(args) {
+        Ok(options) => options,
+        Err(err) => {
+            println!("ERROR: {}\n", err);
+            usage(1);
+        }
+    };
+
+    match run(&options) {
+        Ok(()) => process::exit(0),
+        Err(err) => {
+            eprintln!("ERROR: {}", err);
+            process::exit(1);
+        }
+    }
+}
+
+/// Execute a given identity command.
+fn run(options: &IdentityOptions) -> Result<(), ConfigError> {
+    // the program name is expected by the argument parser
+    let args = iter::once(String::new())
+        .chain(options.app_args.iter().cloned())
+        .collect();
+
+    let builder = ApplicationConfigBuilder::new()?.configure(args)?;
+
+    let secrets_key = builder
+        .secrets_key
+        .as_ref()
+        .map(SecretsKey::load)
+        .transpose()?;
+
+    let tool = IdentityTool {
+        builder,
+        secrets_key,
+    };
+
+    match &options.command {
+        IdentityCommand::Show => tool.show(),
+        IdentityCommand::Export(path) => tool.export(path, &options.get_bundle_key()?),
+        IdentityCommand::Import(path) => {
+            tool.import(path, &options.get_bundle_key()?, options.assume_yes)
+        }
+        IdentityCommand::Reset => tool.reset(options.assume_yes),
+        IdentityCommand::Verify => tool.verify(),
+    }
+}
+
+/// Implementation of the identity commands.
+struct IdentityTool {
+    builder: ApplicationConfigBuilder,
+    secrets_key: Option<SecretsKey>,
+}
+
+impl IdentityTool {
+    /// Print the public client identity.
+    fn show(&self) -> Result<(), ConfigError> {
+        let config = self.load()?;
+
+        println!("uuid: {}", config.uuid.to_hyphenated_ref());
+        println!("mac:  {}", self.builder.arrow_mac);
+
+        Ok(())
+    }
+
+    /// Export client credentials into a given file.
+    fn export(&self, path: &str, bundle_key: &SecretsKey) -> Result<(), ConfigError> {
+        let config = self.load()?;
+
+        let credentials = object! {
+            "uuid" => format!("{}", config.uuid.to_hyphenated_ref()),
+            "passwd" => format!("{}", config.passwd.to_hyphenated_ref())
+        };
+
+        let bundle = object! {
+            "type" => BUNDLE_TYPE,
+            "uuid" => format!("{}", config.uuid.to_hyphenated_ref()),
+            "data" => bundle_key.encrypt(BUNDLE_TYPE, &credentials.dump())?
+        };
+
+        utils::write_file_atomically(path, bundle.pretty(4).as_bytes()).map_err(|err| {
+            ConfigError::from(format!(
+                "unable to write identity bundle \"{}\": {}",
+                path, err
+            ))
+        })?;
+
+        println!(
+            "identity {} exported into \"{}\"",
+            config.uuid.to_hyphenated_ref(),
+            path
+        );
+
+        Ok(())
+    }
+
+    /// Import client credentials from a given file.
+    fn import(
+        &self,
+        path: &str,
+        bundle_key: &SecretsKey,
+        assume_yes: bool,
+    ) -> Result<(), ConfigError> {
+        let (uuid, passwd) = read_bundle(path, bundle_key)?;
+
+        let _lock = self.lock()?;
+
+        let mut config = self.load_or_create()?;
+
+        if config.uuid == uuid && config.passwd == passwd {
+            println!("identity {} is already in use", uuid.to_hyphenated_ref());
+
+            return Ok(());
+        }
+
+        let question = format!(
+            "Replace identity {} with {}?",
+            config.uuid.to_hyphenated_ref(),
+            uuid.to_hyphenated_ref()
+        );
+
+        if !confirm(&question, assume_yes) {
+            return Err(ConfigError::from("aborted"));
+        }
+
+        config.uuid = uuid;
+        config.passwd = passwd;
+
+        self.save(&config)?;
+
+        println!("identity {} imported", uuid.to_hyphenated_ref());
+        println!("{}", RELOAD_NOTE);
+
+        Ok(())
+    }
+
+    /// Generate new client credentials.
+    fn reset(&self, assume_yes: bool) -> Result<(), ConfigError> {
+        let _lock = self.lock()?;
+
+        let mut config = self.load_or_create()?;
+
+        let question = format!(
+            "Replace identity {} with a new one? The client will have to be paired again.",
+            config.uuid.to_hyphenated_ref()
+        );
+
+        if !confirm(&question, assume_yes) {
+            return Err(ConfigError::from("aborted"));
+        }
+
+        let new = PersistentConfig::new();
+
+        config.uuid = new.uuid;
+        config.passwd = new.passwd;
+
+        self.save(&config)?;
+
+        println!("new identity: {}", config.uuid.to_hyphenated_ref());
+        println!("{}", RELOAD_NOTE);
+
+        Ok(())
+    }
+
+    /// Check that the configuration file, the configuration file skeleton
+    /// and the identity file (if any) contain the same identity.
+    fn verify(&self) -> Result<(), ConfigError> {
+        let config_file = &self.builder.config_file;
+        let config_file_skel = &self.builder.config_file_skel;
+
+        let config = self.load_file(config_file)?;
+        let skeleton = self.load_file(config_file_skel)?;
+
+        let mut problems = Vec::new();
+
+        if config.uuid != skeleton.uuid {
+            problems.push(format!(
+                "UUID in \"{}\" ({}) differs from UUID in \"{}\" ({})",
+                config_file,
+                config.uuid.to_hyphenated_ref(),
+                config_file_skel,
+                skeleton.uuid.to_hyphenated_ref()
+            ));
+        } else if config.passwd != skeleton.passwd {
+            problems.push(format!(
+                "password in \"{}\" differs from password in \"{}\"",
+                config_file, config_file_skel
+            ));
+        }
+
+        if let Some(identity_file) = self.builder.identity_file.as_ref() {
+            match read_identity_file(identity_file) {
+                Ok(uuid) if uuid == config.uuid => (),
+                Ok(uuid) => problems.push(format!(
+                    "UUID in \"{}\" ({}) differs from UUID in \"{}\" ({})",
+                    identity_file,
+                    uuid.to_hyphenated_ref(),
+                    config_file,
+                    config.uuid.to_hyphenated_ref()
+                )),
+                Err(err) => problems.push(err.to_string()),
+            }
+        }
+
+        if problems.is_empty() {
+            println!("identity {} is consistent", config.uuid.to_hyphenated_ref());
+
+            return Ok(());
+        }
+
+        for problem in &problems {
+            println!("{}", problem);
+        }
+
+        Err(ConfigError::from("identity mismatch"))
+    }
+
+    /// Take the exclusive lock on the lock file (if specified). It fails if
+    /// the lock is held by a running client. The client cannot be started
+    /// while the returned file is open.
+    fn lock(&self) -> Result<Option<File>, ConfigError> {
+        let path = match self.builder.lock_file.as_ref() {
+            Some(path) => path,
+            None => return Ok(None),
+        };
+
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(path)
+            .map_err(|err| {
+                ConfigError::from(format!("unable to open lock file \"{}\": {}", path, err))
+            })?;
+
+        file.try_lock_exclusive().map_err(|_| {
+            ConfigError::from(format!(
+                "lock file \"{}\" is locked, stop the running client first",
+                path
+            ))
+        })?;
+
+        Ok(Some(file))
+    }
+
+    /// Load the configuration file or the configuration file skeleton if
+    /// the configuration file does not exist.
+    fn load(&self) -> Result<PersistentConfig, ConfigError> {
+        if Path::new(&self.builder.config_file).exists() {
+            self.load_file(&self.builder.config_file)
+        } else if Path::new(&self.builder.config_file_skel).exists() {
+            self.load_file(&self.builder.config_file_skel)
+        } else {
+            Err(ConfigError::from(format!(
+                "configuration file \"{}\" does not exist",
+                self.builder.config_file
+            )))
+        }
+    }
+
+    /// Load the current configuration or create a new one if there is no
+    /// configuration file.
+    fn load_or_create(&self) -> Result<PersistentConfig, ConfigError> {
+        let builder = &self.builder;
+
+        if Path::new(&builder.config_file).exists() || Path::new(&builder.config_file_skel).exists()
+        {
+            self.load()
+        } else {
+            Ok(PersistentConfig::new())
+        }
+    }
+
+    /// Load a given configuration file.
+    fn load_file(&self, path: &str) -> Result<PersistentConfig, ConfigError> {
+        PersistentConfig::load(path, self.secrets_key.as_ref()).map_err(|err| {
+            ConfigError::from(format!(
+                "unable to read configuration file \"{}\": {}",
+                path, err
+            ))
+        })
+    }
+
+    /// Save a given configuration into the configuration file, the
+    /// configuration file skeleton and the identity file (if any).
+    fn save(&self, config: &PersistentConfig) -> Result<(), ConfigError> {
+        let builder = &self.builder;
+        let key = self.secrets_key.as_ref();
+
+        config.save_with_generations(&builder.config_file, builder.config_generations, key)?;
+        config.to_skeleton().save(&builder.config_file_skel, key)?;
+
+        if let Some(identity_file) = builder.identity_file.as_ref() {
+            config.to_identity().save(identity_file)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Read credentials from a given identity bundle.
+fn read_bundle(path: &str, key: &SecretsKey) -> Result<(Uuid, Uuid), ConfigError> {
+    let invalid = || ConfigError::from(format!("invalid identity bundle \"{}\"", path));
+
+    let data = fs::read_to_string(path).map_err(|err| {
+        ConfigError::from(format!(
+            "unable to read identity bundle \"{}\": {}",
+            path, err
+        ))
+    })?;
+
+    let bundle = json::parse(&data).map_err(|_| invalid())?;
+
+    if bundle["type"] != BUNDLE_TYPE {
+        return Err(invalid());
+    }
+
+    let credentials = bundle["data"].as_str().ok_or_else(invalid)?;
+    let credentials = key.decrypt(BUNDLE_TYPE, credentials)?;
+    let credentials = json::parse(&credentials).map_err(|_| invalid())?;
+
+    let uuid = credentials["uuid"]
+        .as_str()
+        .and_then(|uuid| Uuid::from_str(uuid).ok())
+        .ok_or_else(invalid)?;
+    let passwd = credentials["passwd"]
+        .as_str()
+        .and_then(|passwd| Uuid::from_str(passwd).ok())
+        .ok_or_else(invalid)?;
+
+    // the public UUID is not authenticated, so it must match the encrypted one
+    if bundle["uuid"] != format!("{}", uuid.to_hyphenated_ref()) {
+        return Err(invalid());
+    }
+
+    Ok((uuid, passwd))
+}
+
+/// Read UUID from a given identity file.
+fn read_identity_file(path: &str) -> Result<Uuid, ConfigError> {
+    let invalid = || ConfigError::from(format!("invalid identity file \"{}\"", path));
+
+    let data = fs::read_to_string(path).map_err(|err| {
+        ConfigError::from(format!(
+            "unable to read identity file \"{}\": {}",
+            path, err
+        ))
+    })?;
+
+    json::parse(&data)
+        .ok()
+        .and_then(|identity| {
+            identity["uuid"]
+                .as_str()
+                .and_then(|uuid| Uuid::from_str(uuid).ok())
+        })
+        .ok_or_else(invalid)
+}
+
+/// Ask a given yes/no question on the terminal. The default answer is "no".
+fn confirm(question: &str, assume_yes: bool) -> bool {
+    if assume_yes {
+        return true;
+    }
+
+    print!("{} [y/N] ", question);
+
+    io::stdout().flush().unwrap_or(());
+
+    let mut answer = String::new();
+
+    if io::stdin().read_line(&mut answer).is_err() {
+        return false;
+    }
+
+    matches!(answer.trim().to_lowercase().as_str(), "y" | "yes")
+}
+
+/// Print usage of the identity utility and exit the process with a given
+/// exit code.
+pub fn usage(exit_code: i32) -> ! {
+    println!("USAGE: arrow-client identity command [args] [OPTIONS]\n");
+    println!("COMMANDS:\n");
+    println!("    show                show the public client identity (UUID and MAC address)");
+    println!("    export file         export the client credentials into an encrypted");
+    println!("                        identity bundle");
+    println!("    import file         replace the client credentials with the ones from a");
+    println!("                        given identity bundle");
+    println!("    reset               generate new client credentials (the client will have");
+    println!("                        to be paired again)");
+    println!("    verify              check that the configuration file, the configuration");
+    println!("                        file skeleton and the identity file contain the same");
+    println!("                        identity\n");
+    println!("OPTIONS:\n");
+    println!("    --bundle-key=src    key used for encrypting identity bundles; \"file:path\"");
+    println!("                        or \"env:VARIABLE\" (see --secrets-key)");
+    println!("    --yes               do not ask for confirmation\n");
+    println!("All application options related to the configuration files (e.g.");
+    println!("--config-file, --config-file-skel, --identity-file, --secrets-key, -i or");
+    println!("--options-file) are accepted as well. The import and reset commands refuse");
+    println!("to run while the lock file given by --lock-file is held by a running client.");
+    println!();
+
+    process::exit(exit_code);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<IdentityOptions, ConfigError> {
+        IdentityOptions::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn test_options_parsing() {
+        let options = parse(&[
+            "export",
+            "/tmp/bundle",
+            "--bundle-key=env:KEY",
+            "-i",
+            "eth0",
+        ])
+        .unwrap();
+
+        assert_eq!(
+            options.command,
+            IdentityCommand::Export("/tmp/bundle".to_string())
+        );
+        assert_eq!(options.bundle_key, Some(KeySource::Env("KEY".to_string())));
+        assert!(!options.assume_yes);
+        assert_eq!(options.app_args, vec!["-i", "eth0"]);
+
+        let options = parse(&["reset", "--yes"]).unwrap();
+
+        assert_eq!(options.command, IdentityCommand::Reset);
+        assert!(options.assume_yes);
+
+        assert!(parse(&[]).is_err());
+        assert!(parse(&["foo"]).is_err());
+        assert!(parse(&["import", "--yes"]).is_err());
+        assert!(parse(&["show", "--bundle-key=foo"]).is_err());
+    }
+
+    #[test]
+    fn test_bundle() {
+        let dir = std::env::temp_dir().join(format!("arrow-identity-test-{}", process::id()));
+
+        fs::create_dir_all(&dir).unwrap();
+
+        let path = dir.join("bundle.json");
+        let path = path.to_str().unwrap();
+
+        let key = SecretsKey::new(b"secret").unwrap();
+        let uuid = Uuid::new_v4();
+        let passwd = Uuid::new_v4();
+
+        let credentials = object! {
+            "uuid" => format!("{}", uuid.to_hyphenated_ref()),
+            "passwd" => format!("{}", passwd.to_hyphenated_ref())
+        };
+
+        let mut bundle = object! {
+            "type" => BUNDLE_TYPE,
+            "uuid" => format!("{}", uuid.to_hyphenated_ref()),
+            "data" => key.encrypt(BUNDLE_TYPE, &credentials.dump()).unwrap()
+        };
+
+        fs::write(path, bundle.dump()).unwrap();
+
+        assert_eq!(read_bundle(path, &key).unwrap(), (uuid, passwd));
+        assert!(read_bundle(path, &SecretsKey::new(b"other").unwrap()).is_err());
+
+        bundle["uuid"] = format!("{}", Uuid::new_v4().to_hyphenated_ref()).into();
+
+        fs::write(path, bundle.dump()).unwrap();
+
+        assert!(read_bundle(path, &key).is_err());
+
+        // export followed by import on another machine
+        let tool = |name: &str| {
+            let mut builder = ApplicationConfigBuilder::new().unwrap();
+
+            let file = |suffix: &str| {
+                let path = dir.join(format!("{}-{}", name, suffix));
+
+                path.to_str().unwrap().to_string()
+            };
+
+            builder.config_file = file("config.json");
+            builder.config_file_skel = file("config-skel.json");
+            builder.identity_file = Some(file("identity.json"));
+            builder.lock_file = Some(file("lock"));
+
+            IdentityTool {
+                builder,
+                secrets_key: None,
+            }
+        };
+
+        let source = tool("source");
+        let target = tool("target");
+
+        let config = PersistentConfig::new();
+
+        source.save(&config).unwrap();
+        source.export(path, &key).unwrap();
+
+        target.save(&PersistentConfig::new()).unwrap();
+
+        // the running client holds the lock
+        let lock = target.builder.create_lock_file().unwrap();
+
+        assert!(target.import(path, &key, true).is_err());
+        assert!(target.reset(true).is_err());
+
+        drop(lock);
+
+        target.import(path, &key, true).unwrap();
+
+        let imported = target.load().unwrap();
+
+        assert_eq!(imported.uuid, config.uuid);
+        assert_eq!(imported.passwd, config.passwd);
+
+        target.verify().unwrap();
+
+        fs::remove_dir_all(&dir).unwrap_or(());
+    }
+}
@@ -0,0 +1,575 @@
+// Copyright 2019 click2stream, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Declarative options file definitions.
+//!
+//! The options file is a JSON object that may contain any of the following
+//! fields (all of them are optional):
+//!
+//! ```json
+//! {
+//!     "service_address": "arr-rs.angelcam.com:8900",
+//!     "ca_certificates": ["/etc/arrow/ca.pem"],
+//!     "discovery": true,
+//!     "interface": "eth0",
+//!     "rtsp_services": ["rtsp://10.0.0.2/stream"],
+//!     "mjpeg_services": ["http://10.0.0.3/video.mjpg"],
+//!     "http_services": ["10.0.0.4:80"],
+//!     "tcp_services": ["10.0.0.5:22"],
+//!     "log_level": "info,net::arrow::session=debug",
+//!     "diagnostic_mode": false,
+//!     "config_file": "/etc/arrow/config.json",
+//!     "config_file_skel": "/etc/arrow/config-skel.json",
+//!     "config_generations": 3,
+//!     "secrets_key": "file:/etc/arrow/secrets.key",
+//!     "identity_file": "/var/lib/arrow/identity.json",
+//!     "conn_state_file": "/var/lib/arrow/state",
+//!     "conn_state_format": "json",
+//!     "rtsp_paths": "/etc/arrow/rtsp-paths",
+//!     "mjpeg_paths": "/etc/arrow/mjpeg-paths",
+//!     "log_stderr": false,
+//!     "log_stderr_pretty": false,
+//!     "log_file": "/var/log/arrow.log",
+//!     "log_file_size": 10240,
+//!     "log_file_rotations": 1,
+//!     "log_file_period": "daily",
+//!     "log_file_compress": true,
+//!     "log_file_budget": 1048576,
+//!     "log_format": "text",
+//!     "log_buffer_size": 64,
+//!     "syslog_host": "logs.local",
+//!     "syslog_port": 514,
+//!     "syslog_transport": "udp",
+//!     "syslog_protocol": "rfc5424",
+//!     "syslog_facility": "daemon",
+//!     "syslog_app_name": "arrow-client",
+//!     "syslog_queue_size": 1024,
+//!     "syslog_ca_certificates": ["/etc/arrow/syslog-ca.pem"],
+//!     "lock_file": "/var/run/arrow.pid",
+//!     "api_addresses": ["127.0.0.1:8901"],
+//!     "api_sockets": ["/var/run/arrow/api.sock"],
+//!     "control_socket": "/var/run/arrow/control.sock",
+//!     "event_hooks": ["/etc/arrow/hooks/led"],
+//!     "event_hook_timeout": 10,
+//!     "event_hook_concurrency": 4,
+//!     "scan_period": 300,
+//!     "rtsp_ports": [554, 8554],
+//!     "http_ports": [80, 8080],
+//!     "connection_timeout": 20,
+//!     "retry_timeout": 60,
+//!     "proxy": "proxy.local:3128",
+//!     "pairing_window": 1200,
+//!     "pairing_trigger": "/sys/class/gpio/gpio17/value",
+//!     "pairing_code": true,
+//!     "pairing_code_file": "/var/run/arrow/pairing-code",
+//!     "allowed_networks": ["192.168.1.0/24"],
+//!     "allowed_ports": [80, 554],
+//!     "allowed_service_types": ["rtsp", "mjpeg"],
+//!     "http_path_rules": ["10.0.0.4:80=allow:/live/*", "deny:/cgi-bin/upgrade*"]
+//! }
+//! ```
+//!
+//! Every option can be also set using an environment variable named
+//! `ARROW_<OPTION>` (e.g. `ARROW_LOG_LEVEL=debug`). Boolean values can be
+//! given as `true`/`false`, `yes`/`no` or `1`/`0` and lists are separated by
+//! commas.
+//!
+//! Command line arguments take precedence over environment variables and
+//! environment variables take precedence over the options file. A list given
+//! in a higher priority source replaces the whole list from lower priority
+//! sources.
+//!
+//! Only the CA certificates, static services, the log level, the destination
+//! policy and HTTP path rules are re-read on SIGHUP. All other options are
+//! applied on startup only.
+
+use std::cmp;
+use std::env;
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::Read;
+
+use json;
+
+use json::JsonValue;
+
+use crate::svc_table::Service;
+use crate::utils::json::{FromJson, ParseError, ToJson};
+use crate::utils::logger::filter::LogFilter;
+
+use super::ConfigError;
+
+/// Prefix of environment variables corresponding to options.
+const ENV_PREFIX: &str = "ARROW_";
+
+/// Type of an option value.
+#[derive(Copy, Clone, Eq, PartialEq)]
+enum ValueType {
+    Bool,
+    Number,
+    String,
+    Strings,
+    Ports,
+}
+
+/// All known options and their types. The order is used also when
+/// printing options.
+const OPTIONS: &[(&str, ValueType)] = &[
+    ("service_address", ValueType::String),
+    ("ca_certificates", ValueType::Strings),
+    ("discovery", ValueType::Bool),
+    ("interface", ValueType::String),
+    ("rtsp_services", ValueType::Strings),
+    ("mjpeg_services", ValueType::Strings),
+    ("http_services", ValueType::Strings),
+    ("tcp_services", ValueType::Strings),
+    ("log_level", ValueType::String),
+    ("diagnostic_mode", ValueType::Bool),
+    ("config_file", ValueType::String),
+    ("config_file_skel", ValueType::String),
+    ("config_generations", ValueType::Number),
+    ("secrets_key", ValueType::String),
+    ("identity_file", ValueType::String),
+    ("conn_state_file", ValueType::String),
+    ("conn_state_format", ValueType::String),
+    ("rtsp_paths", ValueType::String),
+    ("mjpeg_paths", ValueType::String),
+    ("log_stderr", ValueType::Bool),
+    ("log_stderr_pretty", ValueType::Bool),
+    ("log_file", ValueType::String),
+    ("log_file_size", ValueType::Number),
+    ("log_file_rotations", ValueType::Number),
+    ("log_file_period", ValueType::String),
+    ("log_file_compress", ValueType::Bool),
+    ("log_file_budget", ValueType::Number),
+    ("log_format", ValueType::String),
+    ("log_buffer_size", ValueType::Number),
+    ("syslog_host", ValueType::String),
+    ("syslog_port", ValueType::Number),
+    ("syslog_transport", ValueType::String),
+    ("syslog_protocol", ValueType::String),
+    ("syslog_facility", ValueType::String),
+    ("syslog_app_name", ValueType::String),
+    ("syslog_queue_size", ValueType::Number),
+    ("syslog_ca_certificates", ValueType::Strings),
+    ("lock_file", ValueType::String),
+    ("api_addresses", ValueType::Strings),
+    ("api_sockets", ValueType::Strings),
+    ("control_socket", ValueType::String),
+    ("event_hooks", ValueType::Strings),
+    ("event_hook_timeout", ValueType::Number),
+    ("event_hook_concurrency", ValueType::Number),
+    ("scan_period", ValueType::Number),
+    ("rtsp_ports", ValueType::Ports),
+    ("http_ports", ValueType::Ports),
+    ("connection_timeout", ValueType::Number),
+    ("retry_timeout", ValueType::Number),
+    ("proxy", ValueType::String),
+    ("pairing_window", ValueType::Number),
+    ("pairing_trigger", ValueType::String),
+    ("pairing_code", ValueType::Bool),
+    ("pairing_code_file", ValueType::String),
+    ("allowed_networks", ValueType::Strings),
+    ("allowed_ports", ValueType::Ports),
+    ("allowed_service_types", ValueType::Strings),
+    ("http_path_rules", ValueType::Strings),
+];
+
+/// Options loaded from an options file or from environment variables.
+#[derive(Clone, Default)]
+pub struct Options {
+    values: BTreeMap<&'static str, JsonValue>,
+}
+
+impl Options {
+    /// Load options from a given file.
+    pub fn load(path: &str) -> Result<Options, ConfigError> {
+        let mut file = File::open(path)?;
+        let mut data = String::new();
+
+        file.read_to_string(&mut data)?;
+
+        let object = json::parse(&data)
+            .map_err(|err| ParseError::from(format!("unable to parse options file: {}", err)))?;
+
+        let options = Options::from_json(object)?;
+
+        Ok(options)
+    }
+
+    /// Load options from environment variables.
+    pub fn from_env() -> Result<Options, ConfigError> {
+        Options::from_env_vars(env::vars())
+    }
+
+    /// Load options from given environment variables. Variables that do not
+    /// correspond to any option are ignored.
+    fn from_env_vars<I>(vars: I) -> Result<Options, ConfigError>
+    where
+        I: IntoIterator<Item = (String, String)>,
+    {
+        let vars = vars.into_iter().collect::<BTreeMap<_, _>>();
+
+        let mut res = Options::default();
+
+        for &(key, value_type) in OPTIONS {
+            let name = format!("{}{}", ENV_PREFIX, key.to_uppercase());
+
+            if let Some(value) = vars.get(&name) {
+                let value = parse_env_value(value_type, value).and_then(|value| {
+                    res.set(key, value)
+                        .map_err(|err| ConfigError::from(err.to_string()))
+                });
+
+                value.map_err(|err| {
+                    ConfigError::from(format!(
+                        "invalid value of environment variable {}: {}",
+                        name, err
+                    ))
+                })?;
+            }
+        }
+
+        Ok(res)
+    }
+
+    /// Set a given option. The value is type-checked.
+    pub fn set<T>(&mut self, key: &str, value: T) -> Result<(), ParseError>
+    where
+        T: Into<JsonValue>,
+    {
+        let (key, value_type) = OPTIONS
+            .iter()
+            .cloned()
+            .find(|&(k, _)| k == key)
+            .ok_or_else(|| unknown_option(key))?;
+
+        let value = value.into();
+
+        let valid = match value_type {
+            ValueType::Bool => value.is_boolean(),
+            ValueType::Number => value.as_u64().is_some(),
+            ValueType::String => value.is_string(),
+            ValueType::Strings => value.is_array() && value.members().all(|v| v.is_string()),
+            ValueType::Ports => value.is_array() && value.members().all(|v| v.as_u16().is_some()),
+        };
+
+        if !valid {
+            let expected = match value_type {
+                ValueType::Bool => "boolean",
+                ValueType::Number => "non-negative integer",
+                ValueType::String => "string",
+                ValueType::Strings => "array of strings",
+                ValueType::Ports => "array of port numbers",
+            };
+
+            return Err(ParseError::from(format!(
+                "invalid value of \"{}\", {} expected",
+                key, expected
+            )));
+        }
+
+        if key == "log_level" {
+            let level = value.as_str().unwrap_or("");
+
+            level
+                .parse::<LogFilter>()
+                .map_err(|err| ParseError::from(format!("{}", err)))?;
+        }
+
+        self.values.insert(key, value);
+
+        Ok(())
+    }
+
+    /// Remove a given option.
+    pub fn remove(&mut self, key: &str) {
+        self.values.remove(key);
+    }
+
+    /// Get a given boolean option.
+    pub fn get_bool(&self, key: &str) -> Option<bool> {
+        self.values.get(key).and_then(|v| v.as_bool())
+    }
+
+    /// Get a given numeric option.
+    pub fn get_u64(&self, key: &str) -> Option<u64> {
+        self.values.get(key).and_then(|v| v.as_u64())
+    }
+
+    /// Get a given string option.
+    pub fn get_str(&self, key: &str) -> Option<&str> {
+        self.values.get(key).and_then(|v| v.as_str())
+    }
+
+    /// Get a given list of strings. An empty list is returned if the option
+    /// is not set.
+    pub fn get_strings(&self, key: &str) -> Vec<String> {
+        self.values
+            .get(key)
+            .map(|v| {
+                v.members()
+                    .filter_map(|item| item.as_str())
+                    .map(|item| item.to_string())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Get a given list of port numbers.
+    pub fn get_ports(&self, key: &str) -> Option<Vec<u16>> {
+        self.values
+            .get(key)
+            .map(|v| v.members().filter_map(|item| item.as_u16()).collect())
+    }
+
+    /// Get paths to CA certificates.
+    pub fn ca_certificates(&self) -> Vec<String> {
+        self.get_strings("ca_certificates")
+    }
+
+    /// Get log level, i.e. the log filter (if specified).
+    pub fn log_level(&self) -> Option<LogFilter> {
+        self.get_str("log_level")
+            .and_then(|level| level.parse().ok())
+    }
+
+    /// Get all services specified in the options.
+    ///
+    /// Note: This method may need to resolve service addresses.
+    pub fn services(&self) -> Result<Vec<Service>, ConfigError> {
+        let mut res = Vec::new();
+
+        for url in self.get_strings("rtsp_services") {
+            res.push(super::parse_rtsp_url(&url)?);
+        }
+
+        for url in self.get_strings("mjpeg_services") {
+            res.push(super::parse_mjpeg_url(&url)?);
+        }
+
+        for addr in self.get_strings("http_services") {
+            res.push(super::parse_http_address(&addr)?);
+        }
+
+        for addr in self.get_strings("tcp_services") {
+            res.push(super::parse_tcp_address(&addr)?);
+        }
+
+        Ok(res)
+    }
+}
+
+impl ToJson for Options {
+    fn to_json(&self) -> JsonValue {
+        let mut res = JsonValue::new_object();
+
+        for &(key, _) in OPTIONS {
+            if let Some(value) = self.values.get(key) {
+                res[key] = value.clone();
+            }
+        }
+
+        res
+    }
+}
+
+impl FromJson for Options {
+    fn from_json(value: JsonValue) -> Result<Self, ParseError> {
+        let options;
+
+        if let JsonValue::Object(opts) = value {
+            options = opts;
+        } else {
+            return Err(ParseError::from("JSON object expected"));
+        }
+
+        let mut res = Options::default();
+
+        for (key, value) in options.iter() {
+            res.set(key, value.clone())?;
+        }
+
+        Ok(res)
+    }
+}
+
+/// Create an error for an unknown option including a suggestion (if there
+/// is a similar option).
+fn unknown_option(key: &str) -> ParseError {
+    let suggestion = OPTIONS
+        .iter()
+        .map(|&(k, _)| (edit_distance(key, k), k))
+        .min()
+        .filter(|&(distance, _)| distance <= 2);
+
+    if let Some((_, similar)) = suggestion {
+        ParseError::from(format!(
+            "unknown option: \"{}\" (did you mean \"{}\"?)",
+            key, similar
+        ))
+    } else {
+        ParseError::from(format!("unknown option: \"{}\"", key))
+    }
+}
+
+/// Get the Levenshtein distance of two given strings.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+
+    let mut row = (0..=b.len()).collect::<Vec<_>>();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut prev = row[0];
+
+        row[0] = i + 1;
+
+        for (j, cb) in b.iter().enumerate() {
+            let cost = if ca == *cb { 0 } else { 1 };
+            let current = cmp::min(cmp::min(row[j + 1] + 1, row[j] + 1), prev + cost);
+
+            prev = row[j + 1];
+            row[j + 1] = current;
+        }
+    }
+
+    row[b.len()]
+}
+
+/// Parse a given environment variable value.
+fn parse_env_value(value_type: ValueType, value: &str) -> Result<JsonValue, ConfigError> {
+    let list = || {
+        value
+            .split(',')
+            .map(|item| item.trim())
+            .filter(|item| !item.is_empty())
+    };
+
+    let res = match value_type {
+        ValueType::Bool => match value.to_lowercase().as_str() {
+            "1" | "true" | "yes" | "on" => JsonValue::from(true),
+            "0" | "false" | "no" | "off" => JsonValue::from(false),
+            _ => return Err(ConfigError::from("boolean expected")),
+        },
+        ValueType::Number => value
+            .parse::<u64>()
+            .map(JsonValue::from)
+            .map_err(|_| ConfigError::from("non-negative integer expected"))?,
+        ValueType::String => JsonValue::from(value),
+        ValueType::Strings => JsonValue::from(list().collect::<Vec<_>>()),
+        ValueType::Ports => {
+            let ports = list()
+                .map(|port| port.parse::<u16>())
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|_| ConfigError::from("comma-separated list of port numbers expected"))?;
+
+            JsonValue::from(ports)
+        }
+    };
+
+    Ok(res)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::utils::logger::Severity;
+
+    #[test]
+    fn test_options_parsing() {
+        let json = object! {
+            "ca_certificates" => array!["/etc/arrow/ca.pem"],
+            "tcp_services" => array!["127.0.0.1:22"],
+            "log_level" => "debug",
+            "discovery" => true,
+            "rtsp_ports" => array![554, 8554],
+            "scan_period" => 60
+        };
+
+        let options = Options::from_json(json).expect("expected valid options");
+
+        assert_eq!(
+            options.ca_certificates(),
+            vec!["/etc/arrow/ca.pem".to_string()]
+        );
+        assert_eq!(options.log_level(), Some(LogFilter::new(Severity::DEBUG)));
+        assert_eq!(options.get_bool("discovery"), Some(true));
+        assert_eq!(options.get_ports("rtsp_ports"), Some(vec![554, 8554]));
+        assert_eq!(options.get_u64("scan_period"), Some(60));
+        assert_eq!(options.get_str("proxy"), None);
+
+        let services = options.services().expect("expected valid services");
+
+        assert_eq!(services.len(), 1);
+        assert_eq!(services[0].port(), Some(22));
+    }
+
+    #[test]
+    fn test_invalid_options() {
+        let json = object! {
+            "foo" => "bar"
+        };
+
+        assert!(Options::from_json(json).is_err());
+
+        let json = object! {
+            "tcp_services" => "127.0.0.1:22"
+        };
+
+        assert!(Options::from_json(json).is_err());
+
+        let json = object! {
+            "log_level" => "verbose"
+        };
+
+        assert!(Options::from_json(json).is_err());
+
+        let json = object! {
+            "rtsp_ports" => array![554, 70000]
+        };
+
+        assert!(Options::from_json(json).is_err());
+
+        let json = object! {
+            "log_levle" => "info"
+        };
+
+        let err = Options::from_json(json).err().unwrap();
+
+        assert!(err.to_string().contains("did you mean \"log_level\"?"));
+    }
+
+    #[test]
+    fn test_env_options() {
+        let vars = vec![
+            ("ARROW_LOG_LEVEL".to_string(), "warn".to_string()),
+            ("ARROW_DISCOVERY".to_string(), "yes".to_string()),
+            ("ARROW_HTTP_PORTS".to_string(), "80, 8080".to_string()),
+            ("ARROW_EVENT".to_string(), "foo".to_string()),
+            ("PATH".to_string(), "/bin".to_string()),
+        ];
+
+        let options = Options::from_env_vars(vars).expect("expected valid options");
+
+        assert_eq!(options.log_level(), Some(LogFilter::new(Severity::WARN)));
+        assert_eq!(options.get_bool("discovery"), Some(true));
+        assert_eq!(options.get_ports("http_ports"), Some(vec![80, 8080]));
+
+        let vars = vec![("ARROW_SCAN_PERIOD".to_string(), "often".to_string())];
+
+        assert!(Options::from_env_vars(vars).is_err());
+    }
+}
@@ -0,0 +1,178 @@
+// Copyright 2019 click2stream, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Versioning of the configuration file format.
+//!
+//! Every configuration file contains a `schema_version` field. Files without
+//! the field are considered to be of version 1. Files of older versions are
+//! upgraded to the current version on load by applying all migrations in
+//! sequence. Files written by newer versions of the application are loaded
+//! as they are and all their unknown fields are preserved when the file is
+//! written back.
+//!
+//! Schema history:
+//!
+//! * version 1 - service table elements may omit the `id`, `static_svc`,
+//!   `last_seen` and `active` fields (service IDs are given by positions of
+//!   the elements in the service table)
+//! * version 2 - all service table element fields are explicit
+
+use json::JsonValue;
+
+use time;
+
+use crate::utils::json::ParseError;
+
+/// Current schema version.
+pub const SCHEMA_VERSION: u64 = 2;
+
+/// Migration from one schema version to the next one.
+type Migration = fn(&mut JsonValue) -> Result<(), ParseError>;
+
+/// All migrations. The migration at index `i` upgrades a configuration of
+/// version `i + 1` to version `i + 2`.
+const MIGRATIONS: &[Migration] = &[migrate_v1_to_v2];
+
+/// Get schema version of a given configuration.
+pub fn get_schema_version(config: &JsonValue) -> Result<u64, ParseError> {
+    let version = &config["schema_version"];
+
+    if version.is_null() {
+        Ok(1)
+    } else {
+        version
+            .as_u64()
+            .filter(|&version| version > 0)
+            .ok_or_else(|| ParseError::from("invalid schema version"))
+    }
+}
+
+/// Upgrade a given configuration to the current schema version. The original
+/// schema version is returned.
+pub fn upgrade(config: &mut JsonValue) -> Result<u64, ParseError> {
+    if !config.is_object() {
+        return Err(ParseError::from("JSON object expected"));
+    }
+
+    let version = get_schema_version(config)?;
+
+    for migration in MIGRATIONS.iter().skip(version as usize - 1) {
+        migration(config)?;
+    }
+
+    if version < SCHEMA_VERSION {
+        config["schema_version"] = SCHEMA_VERSION.into();
+    }
+
+    Ok(version)
+}
+
+/// Make all service table element fields explicit.
+fn migrate_v1_to_v2(config: &mut JsonValue) -> Result<(), ParseError> {
+    // leave reporting of a missing/invalid service table up to the parser
+    if !config["svc_table"]["services"].is_array() {
+        return Ok(());
+    }
+
+    let timestamp = time::now_utc().to_timespec().sec;
+
+    let services = config["svc_table"]["services"].members_mut();
+
+    for (index, service) in services.enumerate() {
+        if !service.is_object() {
+            return Err(ParseError::from("JSON object expected"));
+        }
+
+        if !service.has_key("id") {
+            service["id"] = (index + 1).into();
+        }
+
+        if !service.has_key("static_svc") {
+            service["static_svc"] = false.into();
+        }
+
+        if !service.has_key("last_seen") {
+            service["last_seen"] = timestamp.into();
+        }
+
+        if !service.has_key("active") {
+            service["active"] = true.into();
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_upgrade() {
+        let mut config = object! {
+            "svc_table" => object! {
+                "services" => array![
+                    object! {
+                        "svc_type" => 1,
+                        "mac" => "00:00:00:00:00:00",
+                        "address" => "0.0.0.0:0",
+                        "path" => "/1"
+                    },
+                    object! {
+                        "id" => 10,
+                        "svc_type" => 1,
+                        "mac" => "00:00:00:00:00:00",
+                        "address" => "0.0.0.0:0",
+                        "path" => "/2",
+                        "static_svc" => true,
+                        "last_seen" => 123,
+                        "active" => false
+                    }
+                ]
+            }
+        };
+
+        assert_eq!(upgrade(&mut config).unwrap(), 1);
+        assert_eq!(get_schema_version(&config).unwrap(), SCHEMA_VERSION);
+
+        let services = &config["svc_table"]["services"];
+
+        assert_eq!(services[0]["id"], 1);
+        assert_eq!(services[0]["static_svc"], false);
+        assert_eq!(services[0]["active"], true);
+        assert!(services[0]["last_seen"].is_number());
+        assert_eq!(services[1]["id"], 10);
+        assert_eq!(services[1]["static_svc"], true);
+        assert_eq!(services[1]["last_seen"], 123);
+        assert_eq!(services[1]["active"], false);
+
+        // newer versions are left untouched
+        let mut config = object! {
+            "schema_version" => SCHEMA_VERSION + 1,
+            "foo" => "bar"
+        };
+
+        let expected = config.clone();
+
+        assert_eq!(upgrade(&mut config).unwrap(), SCHEMA_VERSION + 1);
+        assert_eq!(config, expected);
+
+        let mut config = object! {
+            "schema_version" => "foo"
+        };
+
+        assert!(upgrade(&mut config).is_err());
+        assert!(upgrade(&mut JsonValue::new_array()).is_err());
+    }
+}
@@ -0,0 +1,491 @@
+// Copyright 2019 click2stream, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! At-rest encryption of secrets stored in configuration files.
+//!
+//! Secrets are encrypted using AES-256-GCM. The encryption key is derived
+//! using PBKDF2-HMAC-SHA256 from key material given by a key source and from
+//! a random salt generated for every encrypted value. Name of the
+//! configuration field is used as additional authenticated data, so an
+//! encrypted value cannot be moved into another field. Encrypted values are
+//! stored as strings in the following format:
+//!
+//! ```text
+//! enc:v1:<base64(salt | iv | tag | ciphertext)>
+//! ```
+//!
+//! The key material can be read from a file, from an environment variable
+//! or derived from the machine ID. The machine ID is world-readable, so it is
+//! combined with a random salt stored in a root-only file
+//! (`/etc/arrow/machine-id.salt`) that is created with mode 0600 on first
+//! use. In this case, the configuration can be decrypted only on the same
+//! machine and only by someone who can read the salt file.
+
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::process;
+
+use std::fmt::{Display, Formatter};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::str::FromStr;
+
+use json::JsonValue;
+
+use openssl::base64;
+use openssl::hash::MessageDigest;
+use openssl::pkcs5;
+use openssl::rand;
+use openssl::symm::{self, Cipher};
+
+use super::ConfigError;
+
+/// Configuration fields containing secrets.
+pub const SECRET_FIELDS: &[&str] = &["passwd"];
+
+/// Prefix of encrypted values.
+const PREFIX: &str = "enc:v1:";
+
+/// Files that may contain the machine ID.
+const MACHINE_ID_FILES: &[&str] = &["/etc/machine-id", "/var/lib/dbus/machine-id"];
+
+/// Root-only file containing a random salt combined with the machine ID.
+const MACHINE_SALT_FILE: &str = "/etc/arrow/machine-id.salt";
+
+/// Size of the machine ID salt.
+const MACHINE_SALT_SIZE: usize = 32;
+
+/// Number of PBKDF2 iterations.
+const KDF_ITERATIONS: usize = 10_000;
+
+const SALT_SIZE: usize = 16;
+const IV_SIZE: usize = 12;
+const TAG_SIZE: usize = 16;
+const KEY_SIZE: usize = 32;
+
+/// Source of the key material.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum KeySource {
+    /// The key material is the content of a given file.
+    File(String),
+    /// The key material is the value of a given environment variable.
+    Env(String),
+    /// The key material is the machine ID combined with a salt from a
+    /// root-only file.
+    MachineId,
+}
+
+impl Display for KeySource {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
+        match self {
+            KeySource::File(path) => write!(f, "file:{}", path),
+            KeySource::Env(name) => write!(f, "env:{}", name),
+            KeySource::MachineId => f.write_str("machine-id"),
+        }
+    }
+}
+
+impl FromStr for KeySource {
+    type Err = ConfigError;
+
+    fn from_str(s: &str) -> Result<KeySource, ConfigError> {
+        if s == "machine-id" {
+            Ok(KeySource::MachineId)
+        } else if let Some(path) = s.strip_prefix("file:").filter(|path| !path.is_empty()) {
+            Ok(KeySource::File(path.to_string()))
+        } else if let Some(name) = s.strip_prefix("env:").filter(|name| !name.is_empty()) {
+            Ok(KeySource::Env(name.to_string()))
+        } else {
+            Err(ConfigError::from(
+                "\"file:path\", \"env:VARIABLE\" or \"machine-id\" expected",
+            ))
+        }
+    }
+}
+
+/// Key material used for encrypting secrets.
+#[derive(Clone)]
+pub struct SecretsKey {
+    material: Vec<u8>,
+}
+
+impl SecretsKey {
+    /// Load key material from a given source.
+    pub fn load(source: &KeySource) -> Result<SecretsKey, ConfigError> {
+        let material = match source {
+            KeySource::File(path) => fs::read(path).map_err(|err| {
+                ConfigError::from(format!(
+                    "unable to read secrets key file \"{}\": {}",
+                    path, err
+                ))
+            })?,
+            KeySource::Env(name) => env::var(name)
+                .map_err(|_| {
+                    ConfigError::from(format!("environment variable {} is not set", name))
+                })?
+                .into_bytes(),
+            KeySource::MachineId => load_machine_key(MACHINE_ID_FILES, MACHINE_SALT_FILE)?,
+        };
+
+        SecretsKey::new(&material)
+    }
+
+    /// Create a new key from given key material. Leading and trailing
+    /// whitespace is ignored.
+    pub fn new(material: &[u8]) -> Result<SecretsKey, ConfigError> {
+        let start = material
+            .iter()
+            .position(|b| !b.is_ascii_whitespace())
+            .unwrap_or(material.len());
+        let end = material
+            .iter()
+            .rposition(|b| !b.is_ascii_whitespace())
+            .map(|pos| pos + 1)
+            .unwrap_or(start);
+
+        if start >= end {
+            return Err(ConfigError::from("empty secrets key"));
+        }
+
+        let res = SecretsKey {
+            material: material[start..end].to_vec(),
+        };
+
+        Ok(res)
+    }
+
+    /// Derive the encryption key using a given salt.
+    fn derive(&self, salt: &[u8]) -> Result<[u8; KEY_SIZE], ConfigError> {
+        let mut key = [0u8; KEY_SIZE];
+
+        pkcs5::pbkdf2_hmac(
+            &self.material,
+            salt,
+            KDF_ITERATIONS,
+            MessageDigest::sha256(),
+            &mut key,
+        )
+        .map_err(|err| ConfigError::from(format!("unable to derive secrets key: {}", err)))?;
+
+        Ok(key)
+    }
+
+    /// Encrypt a value of a given field.
+    pub fn encrypt(&self, field: &str, value: &str) -> Result<String, ConfigError> {
+        let mut salt = [0u8; SALT_SIZE];
+        let mut iv = [0u8; IV_SIZE];
+        let mut tag = [0u8; TAG_SIZE];
+
+        rand::rand_bytes(&mut salt)
+            .and_then(|_| rand::rand_bytes(&mut iv))
+            .map_err(|err| ConfigError::from(format!("unable to generate salt: {}", err)))?;
+
+        let key = self.derive(&salt)?;
+
+        let ciphertext = symm::encrypt_aead(
+            Cipher::aes_256_gcm(),
+            &key,
+            Some(&iv),
+            field.as_bytes(),
+            value.as_bytes(),
+            &mut tag,
+        )
+        .map_err(|err| ConfigError::from(format!("unable to encrypt \"{}\": {}", field, err)))?;
+
+        let mut data = Vec::with_capacity(SALT_SIZE + IV_SIZE + TAG_SIZE + ciphertext.len());
+
+        data.extend_from_slice(&salt);
+        data.extend_from_slice(&iv);
+        data.extend_from_slice(&tag);
+        data.extend_from_slice(&ciphertext);
+
+        Ok(format!("{}{}", PREFIX, base64::encode_block(&data)))
+    }
+
+    /// Decrypt a value of a given field.
+    pub fn decrypt(&self, field: &str, value: &str) -> Result<String, ConfigError> {
+        let invalid = || ConfigError::from(format!("invalid encrypted value of \"{}\"", field));
+
+        let data = value
+            .strip_prefix(PREFIX)
+            .and_then(|data| base64::decode_block(data).ok())
+            .filter(|data| data.len() >= (SALT_SIZE + IV_SIZE + TAG_SIZE))
+            .ok_or_else(invalid)?;
+
+        let (salt, rest) = data.split_at(SALT_SIZE);
+        let (iv, rest) = rest.split_at(IV_SIZE);
+        let (tag, ciphertext) = rest.split_at(TAG_SIZE);
+
+        let key = self.derive(salt)?;
+
+        let plaintext = symm::decrypt_aead(
+            Cipher::aes_256_gcm(),
+            &key,
+            Some(iv),
+            field.as_bytes(),
+            ciphertext,
+            tag,
+        )
+        .map_err(|_| {
+            ConfigError::from(format!(
+                "unable to decrypt \"{}\" (invalid secrets key?)",
+                field
+            ))
+        })?;
+
+        String::from_utf8(plaintext).map_err(|_| invalid())
+    }
+}
+
+/// Load key material derived from the machine ID (read from the first
+/// readable file of given files) and from a salt stored in a given file.
+fn load_machine_key(id_files: &[&str], salt_file: &str) -> Result<Vec<u8>, ConfigError> {
+    let machine_id = id_files
+        .iter()
+        .filter_map(|path| fs::read_to_string(path).ok())
+        .map(|id| id.trim().to_string())
+        .find(|id| !id.is_empty())
+        .ok_or_else(|| ConfigError::from("unable to read machine ID"))?;
+
+    let salt = load_machine_salt(salt_file).map_err(|err| {
+        ConfigError::from(format!(
+            "unable to load machine ID salt file \"{}\": {}",
+            salt_file, err
+        ))
+    })?;
+
+    // the salt is encoded, so that it cannot be trimmed as whitespace
+    let material = format!("{}:{}", machine_id, base64::encode_block(&salt));
+
+    Ok(material.into_bytes())
+}
+
+/// Load the machine ID salt from a given file. The file is created (with
+/// a new random salt) if it does not exist. Files readable by anyone else
+/// than the owner are refused.
+fn load_machine_salt(path: &str) -> Result<Vec<u8>, ConfigError> {
+    match fs::metadata(path) {
+        Ok(metadata) => {
+            if (metadata.permissions().mode() & 0o077) != 0 {
+                return Err(ConfigError::from(
+                    "the file must not be accessible by other users",
+                ));
+            }
+
+            let salt = fs::read(path)?;
+
+            if salt.len() < MACHINE_SALT_SIZE {
+                return Err(ConfigError::from("invalid salt"));
+            }
+
+            return Ok(salt);
+        }
+        Err(err) if err.kind() == io::ErrorKind::NotFound => (),
+        Err(err) => return Err(ConfigError::from(err)),
+    }
+
+    let mut salt = [0u8; MACHINE_SALT_SIZE];
+
+    rand::rand_bytes(&mut salt)
+        .map_err(|err| ConfigError::from(format!("unable to generate salt: {}", err)))?;
+
+    // the salt is written into a temporary file first and then linked into
+    // place, so that nobody can see an incomplete salt file
+    let tmp_path = format!("{}.{}.tmp", path, process::id());
+
+    let write_salt = || {
+        // remove a leftover of a previous crash
+        match fs::remove_file(&tmp_path) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+            _ => (),
+        }
+
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(&tmp_path)?;
+
+        file.write_all(&salt)?;
+        file.sync_all()?;
+
+        // unlike rename, the link fails if the target file already exists
+        fs::hard_link(&tmp_path, path)
+    };
+
+    let res = write_salt();
+
+    fs::remove_file(&tmp_path).unwrap_or(());
+
+    match res {
+        Ok(()) => Ok(salt.to_vec()),
+        // the file has been created by someone else in the meantime
+        Err(err) if err.kind() == io::ErrorKind::AlreadyExists => load_machine_salt(path),
+        Err(err) => Err(ConfigError::from(err)),
+    }
+}
+
+/// Check if a given value is encrypted.
+pub fn is_encrypted(value: &str) -> bool {
+    value.starts_with(PREFIX)
+}
+
+/// Encrypt all secret fields of a given configuration object. Fields that
+/// are already encrypted are left as they are.
+pub fn encrypt_fields(config: &mut JsonValue, key: &SecretsKey) -> Result<(), ConfigError> {
+    for &field in SECRET_FIELDS {
+        let encrypted = match config[field].as_str() {
+            Some(value) if !is_encrypted(value) => key.encrypt(field, value)?,
+            _ => continue,
+        };
+
+        config[field] = encrypted.into();
+    }
+
+    Ok(())
+}
+
+/// Decrypt all secret fields of a given configuration object. Plaintext
+/// fields are left as they are.
+pub fn decrypt_fields(config: &mut JsonValue, key: Option<&SecretsKey>) -> Result<(), ConfigError> {
+    for &field in SECRET_FIELDS {
+        let decrypted = match config[field].as_str() {
+            Some(value) if is_encrypted(value) => key
+                .ok_or_else(|| {
+                    ConfigError::from(format!(
+                        "\"{}\" is encrypted but no secrets key is configured",
+                        field
+                    ))
+                })?
+                .decrypt(field, value)?,
+            _ => continue,
+        };
+
+        config[field] = decrypted.into();
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::process;
+
+    #[test]
+    fn test_key_source() {
+        assert_eq!(
+            KeySource::from_str("file:/etc/arrow/key").unwrap(),
+            KeySource::File("/etc/arrow/key".to_string())
+        );
+        assert_eq!(
+            KeySource::from_str("env:ARROW_KEY").unwrap(),
+            KeySource::Env("ARROW_KEY".to_string())
+        );
+        assert_eq!(
+            KeySource::from_str("machine-id").unwrap(),
+            KeySource::MachineId
+        );
+
+        assert!(KeySource::from_str("file:").is_err());
+        assert!(KeySource::from_str("foo").is_err());
+    }
+
+    #[test]
+    fn test_encryption() {
+        let key = SecretsKey::new(b"  secret\n").unwrap();
+
+        assert!(SecretsKey::new(b" \n").is_err());
+
+        let encrypted = key.encrypt("passwd", "foo").unwrap();
+
+        assert!(is_encrypted(&encrypted));
+        assert_ne!(encrypted, key.encrypt("passwd", "foo").unwrap());
+        assert_eq!(key.decrypt("passwd", &encrypted).unwrap(), "foo");
+
+        // the value is bound to the field name and the key
+        let other_key = SecretsKey::new(b"other").unwrap();
+
+        assert!(key.decrypt("uuid", &encrypted).is_err());
+        assert!(other_key.decrypt("passwd", &encrypted).is_err());
+        assert!(key.decrypt("passwd", "enc:v1:Zm9v").is_err());
+
+        let mut config = object! {
+            "uuid" => "foo",
+            "passwd" => "bar"
+        };
+
+        encrypt_fields(&mut config, &key).unwrap();
+
+        assert_eq!(config["uuid"], "foo");
+        assert!(is_encrypted(config["passwd"].as_str().unwrap()));
+
+        assert!(decrypt_fields(&mut config.clone(), None).is_err());
+
+        decrypt_fields(&mut config, Some(&key)).unwrap();
+
+        assert_eq!(config["passwd"], "bar");
+
+        // plaintext values are accepted
+        decrypt_fields(&mut config, None).unwrap();
+
+        assert_eq!(config["passwd"], "bar");
+    }
+
+    #[test]
+    fn test_machine_key() {
+        let dir = env::temp_dir().join(format!("arrow-secrets-test-{}", process::id()));
+
+        fs::create_dir_all(&dir).unwrap();
+
+        let id_file = dir.join("machine-id");
+        let salt_file = dir.join("machine-id.salt");
+
+        let id_file = id_file.to_str().unwrap();
+        let salt_file = salt_file.to_str().unwrap();
+
+        fs::write(id_file, "0123456789abcdef\n").unwrap();
+
+        // the salt file is created on first use and it is root-only
+        let material = load_machine_key(&["/nonexistent", id_file], salt_file).unwrap();
+
+        let mode = fs::metadata(salt_file).unwrap().permissions().mode();
+
+        assert_eq!(mode & 0o777, 0o600);
+        assert!(material.starts_with(b"0123456789abcdef:"));
+
+        // the temporary salt file is removed
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+
+        // the same salt is used next time
+        assert_eq!(load_machine_key(&[id_file], salt_file).unwrap(), material);
+
+        // a different salt gives a different key
+        fs::remove_file(salt_file).unwrap();
+
+        assert_ne!(load_machine_key(&[id_file], salt_file).unwrap(), material);
+
+        // salt files readable by other users are refused
+        fs::set_permissions(salt_file, fs::Permissions::from_mode(0o644)).unwrap();
+
+        assert!(load_machine_key(&[id_file], salt_file).is_err());
+        assert!(load_machine_key(&["/nonexistent"], salt_file).is_err());
+
+        fs::remove_dir_all(&dir).unwrap_or(());
+    }
+}
@@ -16,37 +16,66 @@ use std::fmt;
 use std::io;
 
 use std::fmt::{Display, Formatter};
-use std::fs::File;
-use std::io::Write;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use futures::future::Shared;
+use futures::sync::oneshot;
+use futures::{Async, Future, Poll};
+
+use json::JsonValue;
+
+use time;
 
 use uuid::Uuid;
 
 use crate::utils;
 
-use crate::config::ApplicationConfig;
+use crate::api::ApiAddress;
+use crate::config::{ApplicationConfig, ConfigError, StateFileFormat};
+use crate::hooks::{Event, EventHooks};
+use crate::metrics::Metrics;
 use crate::net::raw::ether::MacAddr;
 use crate::net::tls::TlsConnector;
+use crate::pairing;
 use crate::scanner::ScanResult;
-use crate::svc_table::{Service, SharedServiceTableRef};
-use crate::utils::logger::{BoxLogger, Severity};
+use crate::svc_table::{DestinationPolicy, HttpPathPolicy, Service, SharedServiceTableRef};
+use crate::utils::logger::filter::LogFilter;
+use crate::utils::logger::ring::LogRingBuffer;
+use crate::utils::logger::{BoxLogger, Logger, Severity};
+use crate::utils::systemd::Notifier;
 use crate::utils::RuntimeError;
 
 /// Arrow service connection state.
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub enum ConnectionState {
+    /// The client is connecting to the Arrow Service.
+    Connecting,
+    /// The connection has been established and the client is waiting for
+    /// the Arrow Service to accept its registration.
+    Handshaking,
+    /// The client is connected to the Arrow Service.
     Connected,
+    /// The client is not connected to the Arrow Service.
     Disconnected,
+    /// The Arrow Service rejected the client (e.g. the client has not been
+    /// paired yet).
     Unauthorized,
+    /// There will be no more connection attempts until the pairing window
+    /// is reopened or until the application is restarted.
+    Suspended,
 }
 
 impl ConnectionState {
     /// Get string representation of the state.
-    fn as_str(&self) -> &str {
+    pub fn as_str(&self) -> &'static str {
         match self {
-            &ConnectionState::Connected => "connected",
-            &ConnectionState::Disconnected => "disconnected",
-            &ConnectionState::Unauthorized => "unauthorized",
+            ConnectionState::Connecting => "connecting",
+            ConnectionState::Handshaking => "handshaking",
+            ConnectionState::Connected => "connected",
+            ConnectionState::Disconnected => "disconnected",
+            ConnectionState::Unauthorized => "unauthorized",
+            ConnectionState::Suspended => "suspended",
         }
     }
 }
@@ -57,25 +86,90 @@ impl Display for ConnectionState {
     }
 }
 
+/// A future that gets resolved once the application shutdown has been
+/// requested.
+#[derive(Clone)]
+pub struct ShutdownSignal {
+    inner: Shared<oneshot::Receiver<()>>,
+}
+
+impl Future for ShutdownSignal {
+    type Item = ();
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<(), ()> {
+        match self.inner.poll() {
+            Ok(Async::NotReady) => Ok(Async::NotReady),
+            // a dropped sender is also considered as a shutdown request
+            _ => Ok(Async::Ready(())),
+        }
+    }
+}
+
 /// Internal data of the application context.
 struct ApplicationContextData {
     logger: BoxLogger,
     config: ApplicationConfig,
     scanning: bool,
     scan_result: ScanResult,
+    net_diag_running: bool,
+    net_diag_finished: Option<f64>,
+    running_probes: usize,
     conn_state: ConnectionState,
+    conn_state_since: f64,
+    last_error: Option<String>,
+    arrow_endpoint: String,
+    pairing_mode_timeout: f64,
+    notifier: Option<Notifier>,
+    shutdown_tx: Option<oneshot::Sender<()>>,
+    shutdown_signal: ShutdownSignal,
 }
 
 impl ApplicationContextData {
     /// Take a given application config and create application context data.
     fn new(config: ApplicationConfig) -> ApplicationContextData {
-        ApplicationContextData {
-            logger: config.get_logger(),
+        let (shutdown_tx, shutdown_rx) = oneshot::channel();
+
+        let arrow_endpoint = config.get_arrow_service_address().to_string();
+
+        let pairing_mode_timeout =
+            time::precise_time_s() + config.get_pairing_window().as_secs_f64();
+
+        let mut logger = config.get_logger();
+
+        let notifier = match Notifier::from_env() {
+            Some(Ok(notifier)) => Some(notifier),
+            Some(Err(err)) => {
+                log_warn!(logger, "unable to create systemd notifier: {}", err);
+                None
+            }
+            None => None,
+        };
+
+        let mut res = ApplicationContextData {
+            logger,
             config: config,
             scanning: false,
             scan_result: ScanResult::new(),
+            net_diag_running: false,
+            net_diag_finished: None,
+            running_probes: 0,
             conn_state: ConnectionState::Disconnected,
-        }
+            conn_state_since: unix_time(),
+            last_error: None,
+            arrow_endpoint,
+            pairing_mode_timeout,
+            notifier,
+            shutdown_tx: Some(shutdown_tx),
+            shutdown_signal: ShutdownSignal {
+                inner: shutdown_rx.shared(),
+            },
+        };
+
+        // the pairing window is open on startup
+        res.publish_pairing_code();
+
+        res
     }
 
     /// Get application config.
@@ -103,6 +197,48 @@ impl ApplicationContextData {
         self.scanning
     }
 
+    /// Try to mark a network diagnostic as running. The method returns false
+    /// if another network diagnostic or the network scanner is running or if
+    /// the last network diagnostic finished less than a given time ago.
+    fn start_network_diagnostic(&mut self, min_interval: Duration) -> bool {
+        if self.scanning || self.net_diag_running {
+            return false;
+        }
+
+        if let Some(finished) = self.net_diag_finished {
+            if (finished + min_interval.as_secs_f64()) > time::precise_time_s() {
+                return false;
+            }
+        }
+
+        self.net_diag_running = true;
+
+        true
+    }
+
+    /// Mark the running network diagnostic as finished.
+    fn finish_network_diagnostic(&mut self) {
+        self.net_diag_running = false;
+        self.net_diag_finished = Some(time::precise_time_s());
+    }
+
+    /// Try to mark a service probe as running. The method returns false if
+    /// a given number of service probes is already running.
+    fn start_service_probe(&mut self, max_running: usize) -> bool {
+        if self.running_probes >= max_running {
+            return false;
+        }
+
+        self.running_probes += 1;
+
+        true
+    }
+
+    /// Mark a running service probe as finished.
+    fn finish_service_probe(&mut self) {
+        self.running_probes = self.running_probes.saturating_sub(1);
+    }
+
     /// Get the last scan result.
     fn get_scan_result(&self) -> ScanResult {
         self.scan_result.clone()
@@ -115,8 +251,107 @@ impl ApplicationContextData {
 
     /// Set connection state.
     fn set_connection_state(&mut self, state: ConnectionState) {
-        self.conn_state = state;
+        if state != self.conn_state {
+            let event = Event::ConnectionStateChanged {
+                state,
+                previous_state: self.conn_state,
+                endpoint: self.arrow_endpoint.clone(),
+                last_error: self.last_error.clone(),
+            };
+
+            self.conn_state = state;
+            self.conn_state_since = unix_time();
+
+            self.config.get_event_hooks().emit(event);
+        }
+
+        self.update_connection_state_file();
+
+        if let Some(notifier) = self.notifier.as_ref() {
+            let res = notifier.status(self.conn_state.as_str());
+
+            utils::result_or_log(
+                &mut self.logger,
+                Severity::DEBUG,
+                "unable to send systemd notification",
+                res,
+            );
+        }
+    }
+
+    /// Get connection state.
+    fn get_connection_state(&self) -> ConnectionState {
+        self.conn_state
+    }
+
+    /// Get time (in seconds since the Unix epoch) of the last connection
+    /// state change.
+    fn get_connection_state_since(&self) -> f64 {
+        self.conn_state_since
+    }
+
+    /// Get the last connection error (if any).
+    fn get_last_error(&self) -> Option<String> {
+        self.last_error.clone()
+    }
+
+    /// Set the last connection error.
+    fn set_last_error(&mut self, err: &str) {
+        self.last_error = Some(err.to_string());
+    }
+
+    /// Get address of the Arrow Service the client is currently connected
+    /// (or connecting) to.
+    fn get_arrow_endpoint(&self) -> String {
+        self.arrow_endpoint.clone()
+    }
+
+    /// Set address of the Arrow Service the client is currently connected
+    /// (or connecting) to.
+    fn set_arrow_endpoint(&mut self, endpoint: &str) {
+        self.arrow_endpoint = endpoint.to_string();
+    }
+
+    /// Get the end of the pairing window (in seconds since the epoch).
+    fn get_pairing_mode_timeout(&self) -> f64 {
+        self.pairing_mode_timeout
+    }
+
+    /// Restart the pairing window.
+    fn reset_pairing_window(&mut self) {
+        self.pairing_mode_timeout =
+            time::precise_time_s() + self.config.get_pairing_window().as_secs_f64();
 
+        self.update_connection_state_file();
+        self.publish_pairing_code();
+    }
+
+    /// Log the pairing code and write it into the pairing code file (if
+    /// enabled).
+    fn publish_pairing_code(&mut self) {
+        if !self.config.is_pairing_code_enabled() {
+            return;
+        }
+
+        let code = pairing::get_pairing_code(&self.config.get_uuid());
+
+        log_info!(self.logger, "pairing code: {}", code);
+
+        if let Some(path) = self.config.get_pairing_code_file() {
+            let res = utils::write_file_atomically(path, format!("{}\n", code).as_bytes());
+
+            utils::result_or_log(
+                &mut self.logger,
+                Severity::WARN,
+                format!("unable to write pairing code file \"{}\"", path),
+                res,
+            );
+        }
+    }
+
+    /// Save connection state into the connection state file and log an
+    /// error (if any).
+    fn update_connection_state_file(&mut self) {
         let res = self.save_connection_state();
 
         utils::result_or_log(
@@ -129,14 +364,79 @@ impl ApplicationContextData {
 
     /// Save connection state into the file.
     fn save_connection_state(&self) -> Result<(), io::Error> {
-        let mut file = File::create(self.config.get_connection_state_file())?;
+        let content = match self.config.get_connection_state_format() {
+            StateFileFormat::Json => self.connection_state_json().pretty(4),
+            StateFileFormat::Plain => self.conn_state.to_string(),
+        };
+
+        utils::write_file_atomically(
+            self.config.get_connection_state_file(),
+            format!("{}\n", content).as_bytes(),
+        )
+    }
+
+    /// Get JSON representation of the connection state.
+    fn connection_state_json(&self) -> JsonValue {
+        let now = unix_time();
+
+        // the pairing window deadline is measured using a monotonic clock
+        let pairing_window_end = now + self.pairing_mode_timeout - time::precise_time_s();
+
+        let services = self
+            .config
+            .get_service_table()
+            .entries()
+            .iter()
+            .filter(|entry| entry.visible)
+            .count();
+
+        object! {
+            "state" => self.conn_state.as_str(),
+            "state_since" => self.conn_state_since as u64,
+            "state_duration" => (now - self.conn_state_since).max(0.0) as u64,
+            "endpoint" => self.arrow_endpoint.clone(),
+            "last_error" => self.last_error.clone(),
+            "pairing_window_end" => pairing_window_end as u64,
+            "services" => services
+        }
+    }
+
+    /// Get systemd notifier (if the application is running as a systemd
+    /// notify service).
+    fn get_systemd_notifier(&self) -> Option<Notifier> {
+        self.notifier.clone()
+    }
+
+    /// Request application shutdown.
+    fn shutdown(&mut self) {
+        if let Some(tx) = self.shutdown_tx.take() {
+            tx.send(()).unwrap_or(());
+
+            if let Some(notifier) = self.notifier.as_ref() {
+                notifier.stopping().unwrap_or(());
+            }
+        }
+    }
 
-        writeln!(&mut file, "{}", self.conn_state)?;
+    /// Check if the application shutdown has been requested.
+    fn is_shutting_down(&self) -> bool {
+        self.shutdown_tx.is_none()
+    }
 
-        Ok(())
+    /// Get a future that will be resolved once the application shutdown is
+    /// requested.
+    fn get_shutdown_signal(&self) -> ShutdownSignal {
+        self.shutdown_signal.clone()
     }
 }
 
+/// Get the current wall clock time in seconds since the Unix epoch.
+fn unix_time() -> f64 {
+    let now = time::get_time();
+
+    now.sec as f64 + f64::from(now.nsec) / 1_000_000_000.0
+}
+
 /// Application context.
 #[derive(Clone)]
 pub struct ApplicationContext {
@@ -206,11 +506,115 @@ impl ApplicationContext {
             .to_string()
     }
 
+    /// Get listening addresses of the local management API.
+    pub fn get_api_addresses(&self) -> Vec<ApiAddress> {
+        self.data
+            .lock()
+            .unwrap()
+            .get_config()
+            .get_api_addresses()
+            .to_vec()
+    }
+
+    /// Get application metrics registry.
+    pub fn get_metrics(&self) -> Metrics {
+        self.data.lock().unwrap().get_config().get_metrics()
+    }
+
+    /// Get the in-memory log ring buffer.
+    pub fn get_log_buffer(&self) -> LogRingBuffer {
+        self.data.lock().unwrap().get_config().get_log_buffer()
+    }
+
+    /// Get event hooks.
+    pub fn get_event_hooks(&self) -> EventHooks {
+        self.data.lock().unwrap().get_config().get_event_hooks()
+    }
+
+    /// Get network scan period.
+    pub fn get_scan_period(&self) -> Duration {
+        self.data.lock().unwrap().get_config().get_scan_period()
+    }
+
+    /// Get RTSP port candidates for the network scanner.
+    pub fn get_rtsp_port_candidates(&self) -> Vec<u16> {
+        self.data
+            .lock()
+            .unwrap()
+            .get_config()
+            .get_rtsp_port_candidates()
+            .to_vec()
+    }
+
+    /// Get HTTP port candidates for the network scanner.
+    pub fn get_http_port_candidates(&self) -> Vec<u16> {
+        self.data
+            .lock()
+            .unwrap()
+            .get_config()
+            .get_http_port_candidates()
+            .to_vec()
+    }
+
+    /// Get timeout for connecting to the Arrow Service.
+    pub fn get_connection_timeout(&self) -> Duration {
+        self.data
+            .lock()
+            .unwrap()
+            .get_config()
+            .get_connection_timeout()
+    }
+
+    /// Get minimum time between two connection attempts.
+    pub fn get_retry_timeout(&self) -> Duration {
+        self.data.lock().unwrap().get_config().get_retry_timeout()
+    }
+
+    /// Get path to the pairing trigger file (if any).
+    pub fn get_pairing_trigger(&self) -> Option<String> {
+        self.data
+            .lock()
+            .unwrap()
+            .get_config()
+            .get_pairing_trigger()
+            .map(|path| path.to_string())
+    }
+
+    /// Get HTTP proxy used for connecting to the Arrow Service.
+    pub fn get_proxy(&self) -> Option<String> {
+        self.data
+            .lock()
+            .unwrap()
+            .get_config()
+            .get_proxy()
+            .map(|proxy| proxy.to_string())
+    }
+
     /// Get application logger.
     pub fn get_logger(&self) -> BoxLogger {
         self.data.lock().unwrap().get_logger()
     }
 
+    /// Get the local policy of allowed service destinations.
+    pub fn get_destination_policy(&self) -> DestinationPolicy {
+        self.data
+            .lock()
+            .unwrap()
+            .get_config()
+            .get_destination_policy()
+            .clone()
+    }
+
+    /// Get the local policy of HTTP paths accessible through HTTP services.
+    pub fn get_http_path_policy(&self) -> HttpPathPolicy {
+        self.data
+            .lock()
+            .unwrap()
+            .get_config()
+            .get_http_path_policy()
+            .clone()
+    }
+
     /// Get TLS connector for a given server hostname.
     pub fn get_tls_connector(&self) -> Result<TlsConnector, RuntimeError> {
         self.data.lock().unwrap().get_config().get_tls_connector()
@@ -226,6 +630,32 @@ impl ApplicationContext {
         self.data.lock().unwrap().is_scanning()
     }
 
+    /// Try to mark a network diagnostic as running. The method returns false
+    /// if another network diagnostic or the network scanner is running or if
+    /// the last network diagnostic finished less than a given time ago.
+    pub fn start_network_diagnostic(&mut self, min_interval: Duration) -> bool {
+        self.data
+            .lock()
+            .unwrap()
+            .start_network_diagnostic(min_interval)
+    }
+
+    /// Mark the running network diagnostic as finished.
+    pub fn finish_network_diagnostic(&mut self) {
+        self.data.lock().unwrap().finish_network_diagnostic()
+    }
+
+    /// Try to mark a service probe as running. The method returns false if
+    /// a given number of service probes is already running.
+    pub fn start_service_probe(&mut self, max_running: usize) -> bool {
+        self.data.lock().unwrap().start_service_probe(max_running)
+    }
+
+    /// Mark a running service probe as finished.
+    pub fn finish_service_probe(&mut self) {
+        self.data.lock().unwrap().finish_service_probe()
+    }
+
     /// Get the last scan result.
     pub fn get_scan_result(&self) -> ScanResult {
         self.data.lock().unwrap().get_scan_result()
@@ -246,24 +676,145 @@ impl ApplicationContext {
     where
         I: IntoIterator<Item = Service>,
     {
-        self.data
-            .lock()
-            .unwrap()
-            .get_config_mut()
-            .update_service_table(services)
+        let mut data = self.data.lock().unwrap();
+
+        data.get_config_mut().update_service_table(services);
+        data.update_connection_state_file();
+    }
+
+    /// Reload the configuration file and the options file and apply all
+    /// changes.
+    pub fn reload_config(&mut self) -> Result<(), ConfigError> {
+        let (loader, notifier) = {
+            let data = self.data.lock().unwrap();
+
+            (data.get_config().get_loader(), data.get_systemd_notifier())
+        };
+
+        if let Some(notifier) = notifier.as_ref() {
+            notifier.reloading().unwrap_or(());
+        }
+
+        // NOTE: the context must not be locked while loading the config
+        // because it may involve DNS lookups
+        let res = loader.load().map(|config| {
+            let mut data = self.data.lock().unwrap();
+
+            let uuid = data.get_config().get_uuid();
+
+            data.get_config_mut().reload(config);
+            data.update_connection_state_file();
+
+            if data.get_config().get_uuid() != uuid {
+                data.publish_pairing_code();
+            }
+        });
+
+        if let Some(notifier) = notifier.as_ref() {
+            notifier.ready().unwrap_or(());
+        }
+
+        res
     }
 
     /// Reset service table.
     pub fn reset_service_table(&mut self) {
-        self.data
-            .lock()
-            .unwrap()
-            .get_config_mut()
-            .reset_service_table()
+        let mut data = self.data.lock().unwrap();
+
+        data.get_config_mut().reset_service_table();
+        data.update_connection_state_file();
     }
 
     /// Set connection state.
     pub fn set_connection_state(&mut self, state: ConnectionState) {
         self.data.lock().unwrap().set_connection_state(state)
     }
+
+    /// Get connection state.
+    pub fn get_connection_state(&self) -> ConnectionState {
+        self.data.lock().unwrap().get_connection_state()
+    }
+
+    /// Get time (in seconds since the Unix epoch) of the last connection
+    /// state change.
+    pub fn get_connection_state_since(&self) -> f64 {
+        self.data.lock().unwrap().get_connection_state_since()
+    }
+
+    /// Get the last connection error (if any).
+    pub fn get_last_error(&self) -> Option<String> {
+        self.data.lock().unwrap().get_last_error()
+    }
+
+    /// Set the last connection error. The error will be written into the
+    /// connection state file together with the next connection state change.
+    pub fn set_last_error(&mut self, err: &str) {
+        self.data.lock().unwrap().set_last_error(err)
+    }
+
+    /// Get address of the Arrow Service the client is currently connected
+    /// (or connecting) to. It may differ from the configured address in case
+    /// of a redirect.
+    pub fn get_arrow_endpoint(&self) -> String {
+        self.data.lock().unwrap().get_arrow_endpoint()
+    }
+
+    /// Set address of the Arrow Service the client is currently connected
+    /// (or connecting) to.
+    pub fn set_arrow_endpoint(&mut self, endpoint: &str) {
+        self.data.lock().unwrap().set_arrow_endpoint(endpoint)
+    }
+
+    /// Get the end of the pairing window (in seconds since the epoch).
+    pub fn get_pairing_mode_timeout(&self) -> f64 {
+        self.data.lock().unwrap().get_pairing_mode_timeout()
+    }
+
+    /// Check if the pairing window is open.
+    pub fn is_pairing_mode(&self) -> bool {
+        time::precise_time_s() < self.get_pairing_mode_timeout()
+    }
+
+    /// Restart the pairing window.
+    pub fn reset_pairing_window(&mut self) {
+        self.data.lock().unwrap().reset_pairing_window()
+    }
+
+    /// Get current log level.
+    pub fn get_log_level(&self) -> Severity {
+        self.data.lock().unwrap().get_logger().get_level()
+    }
+
+    /// Get current log filter.
+    pub fn get_log_filter(&self) -> LogFilter {
+        self.data.lock().unwrap().get_logger().get_filter()
+    }
+
+    /// Set log filter.
+    pub fn set_log_filter(&mut self, filter: LogFilter) {
+        self.data.lock().unwrap().get_logger().set_filter(filter)
+    }
+
+    /// Get systemd notifier (if the application is running as a systemd
+    /// notify service).
+    pub fn get_systemd_notifier(&self) -> Option<Notifier> {
+        self.data.lock().unwrap().get_systemd_notifier()
+    }
+
+    /// Request application shutdown. All components observing the shutdown
+    /// signal should terminate gracefully.
+    pub fn shutdown(&mut self) {
+        self.data.lock().unwrap().shutdown()
+    }
+
+    /// Check if the application shutdown has been requested.
+    pub fn is_shutting_down(&self) -> bool {
+        self.data.lock().unwrap().is_shutting_down()
+    }
+
+    /// Get a future that will be resolved once the application shutdown is
+    /// requested.
+    pub fn get_shutdown_signal(&self) -> ShutdownSignal {
+        self.data.lock().unwrap().get_shutdown_signal()
+    }
 }
@@ -0,0 +1,361 @@
+// Copyright 2019 click2stream, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Command line control utility. It talks to a running client over the
+//! control socket using the local management API.
+
+use std::io;
+use std::process;
+use std::str;
+
+use std::io::{Read, Write};
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+use json;
+
+use json::JsonValue;
+
+use crate::config::CONTROL_SOCKET;
+use crate::net::http::generic::ResponseHeader;
+use crate::svc_table::ServiceType;
+use crate::utils::logger::filter::LogFilter;
+use crate::utils::RuntimeError;
+
+/// Maximum time for getting a response from the client.
+const REQUEST_TIMEOUT: u64 = 30;
+
+/// Control commands.
+#[derive(Debug, Clone, Eq, PartialEq)]
+enum CtlCommand {
+    Status,
+    Services,
+    Scan,
+    ResetServices,
+    PairingWindow,
+    GetLogLevel,
+    SetLogLevel(LogFilter),
+}
+
+impl CtlCommand {
+    /// Get method, path and body of the corresponding API request.
+    fn request(&self) -> (&'static str, &'static str, String) {
+        match *self {
+            CtlCommand::Status => ("GET", "/status", String::new()),
+            CtlCommand::Services => ("GET", "/services", String::new()),
+            CtlCommand::Scan => ("POST", "/scan", String::new()),
+            CtlCommand::ResetServices => ("POST", "/services/reset", String::new()),
+            CtlCommand::PairingWindow => ("POST", "/pairing-window", String::new()),
+            CtlCommand::GetLogLevel => ("GET", "/log-level", String::new()),
+            CtlCommand::SetLogLevel(ref filter) => {
+                let body = object! {
+                    "level" => filter.to_string()
+                };
+
+                ("POST", "/log-level", body.dump())
+            }
+        }
+    }
+}
+
+/// Control utility options.
+struct CtlOptions {
+    socket: PathBuf,
+    json: bool,
+    command: CtlCommand,
+}
+
+impl CtlOptions {
+    /// Parse given command line arguments (excluding the program name and
+    /// the "ctl" argument).
+    fn parse<I>(args: I) -> Result<CtlOptions, RuntimeError>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let mut socket = PathBuf::from(CONTROL_SOCKET);
+        let mut json = false;
+        let mut rest = Vec::new();
+
+        for arg in args {
+            if arg == "--json" {
+                json = true;
+            } else if let Some(path) = arg.strip_prefix("--control-socket=") {
+                socket = PathBuf::from(path);
+            } else if arg.starts_with("--") {
+                return Err(RuntimeError::from(format!("unknown argument: \"{}\"", arg)));
+            } else {
+                rest.push(arg);
+            }
+        }
+
+        let command = match rest.first().map(|cmd| cmd.as_str()) {
+            Some("status") if rest.len() == 1 => CtlCommand::Status,
+            Some("services") if rest.len() == 1 => CtlCommand::Services,
+            Some("scan") if rest.len() == 1 => CtlCommand::Scan,
+            Some("reset-services") if rest.len() == 1 => CtlCommand::ResetServices,
+            Some("pairing-window") if rest.len() == 1 => CtlCommand::PairingWindow,
+            Some("log-level") if rest.len() == 1 => CtlCommand::GetLogLevel,
+            Some("log-level") if rest.len() == 2 => {
+                CtlCommand::SetLogLevel(LogFilter::from_str(&rest[1])?)
+            }
+            Some(cmd) => return Err(RuntimeError::from(format!("invalid command: \"{}\"", cmd))),
+            None => return Err(RuntimeError::from("missing command")),
+        };
+
+        let res = CtlOptions {
+            socket,
+            json,
+            command,
+        };
+
+        Ok(res)
+    }
+}
+
+/// Run the control utility with given arguments (excluding the program name
+/// and the "ctl" argument) and exit the process.
+pub fn main<I>(args: I) -> !
+where
+    I: IntoIterator<Item = String>,
+{
+    let options = match CtlOptions::parse(args) {
+        Ok(options) => options,
+        Err(err) => {
+            println!("ERROR: {}\n", err);
+            usage(1);
+        }
+    };
+
+    match run(&options) {
+        Ok(()) => process::exit(0),
+        Err(err) => {
+            eprintln!("ERROR: {}", err);
+            process::exit(1);
+        }
+    }
+}
+
+/// Execute a given control command.
+fn run(options: &CtlOptions) -> Result<(), RuntimeError> {
+    let (method, path, body) = options.command.request();
+
+    let response = send_request(&options.socket, method, path, &body)?;
+
+    if options.json {
+        println!("{}", response.pretty(4));
+        return Ok(());
+    }
+
+    match options.command {
+        CtlCommand::Status => print_status(&response),
+        CtlCommand::Services => print_services(&response),
+        CtlCommand::Scan => println!("network scan requested"),
+        CtlCommand::ResetServices => println!("service table reset requested"),
+        CtlCommand::PairingWindow => println!("pairing window reopened"),
+        CtlCommand::GetLogLevel => println!("{}", response["filter"]),
+        CtlCommand::SetLogLevel(ref filter) => println!("log level set to {}", filter),
+    }
+
+    Ok(())
+}
+
+/// Send a given API request over the control socket and return the parsed
+/// response body.
+fn send_request(
+    socket: &PathBuf,
+    method: &str,
+    path: &str,
+    body: &str,
+) -> Result<JsonValue, RuntimeError> {
+    let mut stream = UnixStream::connect(socket).map_err(|err| {
+        RuntimeError::from(format!(
+            "unable to connect to the control socket \"{}\": {}",
+            socket.display(),
+            err
+        ))
+    })?;
+
+    let io_error = |err: io::Error| {
+        RuntimeError::from(format!("unable to communicate with the client: {}", err))
+    };
+
+    let timeout = Some(Duration::from_secs(REQUEST_TIMEOUT));
+
+    stream.set_read_timeout(timeout).map_err(io_error)?;
+    stream.set_write_timeout(timeout).map_err(io_error)?;
+
+    let request = format!(
+        "{} {} HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        method,
+        path,
+        body.len(),
+        body
+    );
+
+    stream.write_all(request.as_bytes()).map_err(io_error)?;
+
+    let mut response = Vec::new();
+
+    stream.read_to_end(&mut response).map_err(io_error)?;
+
+    let response = str::from_utf8(&response).map_err(|_| RuntimeError::from("invalid response"))?;
+
+    let mut parts = response.splitn(2, "\r\n\r\n");
+
+    let header = parts.next().unwrap_or("");
+    let body = parts
+        .next()
+        .ok_or_else(|| RuntimeError::from("invalid response"))?;
+
+    let status_line = header.lines().next().unwrap_or("");
+
+    let header = ResponseHeader::from_str(status_line)
+        .map_err(|_| RuntimeError::from("invalid response"))?;
+
+    let body = json::parse(body).map_err(|_| RuntimeError::from("invalid response"))?;
+
+    if header.status_code() >= 400 {
+        let msg = body["error"]
+            .as_str()
+            .unwrap_or_else(|| header.status_line());
+
+        return Err(RuntimeError::from(msg.to_string()));
+    }
+
+    Ok(body)
+}
+
+/// Print the client status in a human-readable form.
+fn print_status(status: &JsonValue) {
+    let yes_no = |value: &JsonValue| {
+        if value.as_bool() == Some(true) {
+            "yes"
+        } else {
+            "no"
+        }
+    };
+
+    println!("state:          {}", status["state"]);
+
+    if let Some(err) = status["last_error"].as_str() {
+        println!("last error:     {}", err);
+    }
+
+    println!("arrow service:  {}", status["arrow_service"]);
+    println!("arrow endpoint: {}", status["arrow_endpoint"]);
+    println!("uuid:           {}", status["uuid"]);
+    println!("mac:            {}", status["mac"]);
+    println!("discovery:      {}", yes_no(&status["discovery"]));
+    println!("scanning:       {}", yes_no(&status["scanning"]));
+    println!("pairing mode:   {}", yes_no(&status["pairing_mode"]));
+    println!("log level:      {}", status["log_level"]);
+}
+
+/// Print the service table in a human-readable form.
+fn print_services(services: &JsonValue) {
+    println!(
+        "{:>5}  {:<16} {:<17} {:<21} {:<6} {:<7} PATH",
+        "ID", "TYPE", "MAC", "ADDRESS", "STATIC", "VISIBLE"
+    );
+
+    for svc in services["services"].members() {
+        let flag = |value: &JsonValue| {
+            if value.as_bool() == Some(true) {
+                "yes"
+            } else {
+                "no"
+            }
+        };
+
+        let svc_type = svc["svc_type"]
+            .as_u16()
+            .and_then(ServiceType::from_code)
+            .map(|svc_type| svc_type.as_str())
+            .unwrap_or("unknown");
+
+        println!(
+            "{:>5}  {:<16} {:<17} {:<21} {:<6} {:<7} {}",
+            svc["id"],
+            svc_type,
+            svc["mac"].as_str().unwrap_or("-"),
+            svc["address"].as_str().unwrap_or("-"),
+            flag(&svc["static_svc"]),
+            flag(&svc["visible"]),
+            svc["path"].as_str().unwrap_or("-")
+        );
+    }
+}
+
+/// Print usage of the control utility and exit the process with a given exit
+/// code.
+pub fn usage(exit_code: i32) -> ! {
+    println!("USAGE: arrow-client ctl [OPTIONS] command [args]\n");
+    println!("COMMANDS:\n");
+    println!("    status              show the client status");
+    println!("    services            show the service table");
+    println!("    scan                trigger a network scan");
+    println!("    reset-services      reset the service table");
+    println!("    pairing-window      reopen the pairing window");
+    println!("    log-level [level]   show or change the log level (one of: debug, info,");
+    println!("                        warn, error; optionally followed by per-module");
+    println!("                        directives, e.g. \"info,net::arrow::session=debug\")\n");
+    println!("OPTIONS:\n");
+    println!("    --control-socket=path");
+    println!("                        path to the control socket of a running client");
+    println!(
+        "                        (default value: {})",
+        CONTROL_SOCKET
+    );
+    println!("    --json              print the raw JSON response");
+    println!();
+
+    process::exit(exit_code);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::utils::logger::Severity;
+
+    fn parse(args: &[&str]) -> Result<CtlOptions, RuntimeError> {
+        CtlOptions::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn test_ctl_arguments() {
+        let options = parse(&["--json", "status"]).unwrap();
+
+        assert!(options.json);
+        assert_eq!(options.command, CtlCommand::Status);
+        assert_eq!(options.socket, PathBuf::from(CONTROL_SOCKET));
+
+        let options = parse(&["log-level", "debug", "--control-socket=/tmp/ctl.sock"]).unwrap();
+
+        assert!(!options.json);
+        assert_eq!(
+            options.command,
+            CtlCommand::SetLogLevel(LogFilter::new(Severity::DEBUG))
+        );
+        assert_eq!(options.socket, PathBuf::from("/tmp/ctl.sock"));
+
+        assert!(parse(&[]).is_err());
+        assert!(parse(&["foo"]).is_err());
+        assert!(parse(&["status", "foo"]).is_err());
+        assert!(parse(&["log-level", "verbose"]).is_err());
+        assert!(parse(&["--foo", "status"]).is_err());
+    }
+}
@@ -0,0 +1,774 @@
+// Copyright 2019 click2stream, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Self-test diagnostics.
+//!
+//! The `diagnose` command runs all checks necessary for a working connection
+//! to the Arrow Service step by step (network interfaces, DNS resolution,
+//! TCP connection, TLS handshake, client registration, static services and
+//! packet capture permissions) and prints a report containing results and
+//! timings of all checks. The process exits with code 0 if none of the
+//! checks failed.
+
+use std::process;
+
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+use std::time::{Duration, Instant};
+
+use futures::future;
+
+use json::JsonValue;
+
+use openssl::hash::MessageDigest;
+use openssl::x509::X509NameRef;
+
+use crate::cmd_handler;
+use crate::config::{usage, ApplicationConfig};
+use crate::context::ApplicationContext;
+use crate::net::arrow::{self, ErrorKind};
+use crate::net::http::Request as HttpRequest;
+use crate::net::raw::devices::EthernetDevice;
+use crate::net::rtsp::Request as RtspRequest;
+use crate::runtime;
+use crate::svc_table::service::{Service, ServiceType};
+use crate::utils::json::ToJson;
+use crate::utils::RuntimeError;
+
+#[cfg(feature = "discovery")]
+use crate::net::raw::pcap;
+
+/// Status of a diagnostic check.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum CheckStatus {
+    Passed,
+    Failed,
+    Skipped,
+}
+
+impl CheckStatus {
+    /// Get name of the status.
+    fn as_str(self) -> &'static str {
+        match self {
+            CheckStatus::Passed => "passed",
+            CheckStatus::Failed => "failed",
+            CheckStatus::Skipped => "skipped",
+        }
+    }
+}
+
+/// Result of a single diagnostic check.
+struct Check {
+    name: &'static str,
+    title: String,
+    status: CheckStatus,
+    duration: Duration,
+    details: JsonValue,
+    message: Option<String>,
+}
+
+impl Check {
+    /// Create a new skipped check.
+    fn skipped(name: &'static str, title: &str, reason: &str) -> Check {
+        Check {
+            name,
+            title: title.to_string(),
+            status: CheckStatus::Skipped,
+            duration: Duration::from_secs(0),
+            details: JsonValue::new_object(),
+            message: Some(reason.to_string()),
+        }
+    }
+
+    /// Get duration of the check in milliseconds.
+    fn duration_ms(&self) -> f64 {
+        let ms = self.duration.as_secs() as f64 * 1000.0
+            + f64::from(self.duration.subsec_nanos()) / 1_000_000.0;
+
+        (ms * 1000.0).round() / 1000.0
+    }
+
+    /// Format the check in a human readable form (one or more lines, each
+    /// of them terminated by a line feed).
+    fn format(&self) -> String {
+        let status = match self.status {
+            CheckStatus::Passed => "  OK  ",
+            CheckStatus::Failed => "FAILED",
+            CheckStatus::Skipped => " SKIP ",
+        };
+
+        let mut res = if self.status == CheckStatus::Skipped {
+            format!("[{}] {}\n", status, self.title)
+        } else {
+            format!(
+                "[{}] {} ({:.1} ms)\n",
+                status,
+                self.title,
+                self.duration_ms()
+            )
+        };
+
+        if let Some(ref msg) = self.message {
+            res += &format!("         {}\n", msg);
+        }
+
+        for (key, value) in self.details.entries() {
+            if let Some(value) = value.as_str() {
+                res += &format!("         {}: {}\n", key, value);
+            } else {
+                res += &format!("         {}: {}\n", key, value.dump());
+            }
+        }
+
+        res
+    }
+}
+
+impl ToJson for Check {
+    fn to_json(&self) -> JsonValue {
+        object! {
+            "name" => self.name,
+            "title" => self.title.clone(),
+            "status" => self.status.as_str(),
+            "duration_ms" => self.duration_ms(),
+            "message" => self.message.clone(),
+            "details" => self.details.clone()
+        }
+    }
+}
+
+/// Self-test diagnostics.
+struct Diagnostics {
+    context: ApplicationContext,
+    timeout: Duration,
+    checks: Vec<Check>,
+}
+
+impl Diagnostics {
+    /// Create a new diagnostics runner.
+    fn new(context: ApplicationContext) -> Diagnostics {
+        let timeout = context.get_connection_timeout();
+
+        Diagnostics {
+            context,
+            timeout,
+            checks: Vec::new(),
+        }
+    }
+
+    /// Run all checks.
+    fn run(mut self) -> Vec<Check> {
+        let mac_device = self.check_interfaces();
+
+        let proxy = self.context.get_proxy();
+
+        let addrs = self.check_dns(proxy.as_ref().map(|p| p as &str));
+
+        let stream = addrs.and_then(|addrs| self.check_tcp_connect(&addrs));
+
+        if let Some(stream) = stream {
+            if proxy.is_some() {
+                self.checks.push(Check::skipped(
+                    "tls_handshake",
+                    "TLS handshake",
+                    "the connection goes through a proxy",
+                ));
+            } else {
+                self.check_tls_handshake(stream);
+            }
+
+            self.check_register();
+        } else {
+            let reason = "the Arrow Service is not reachable";
+
+            self.checks
+                .push(Check::skipped("tls_handshake", "TLS handshake", reason));
+            self.checks
+                .push(Check::skipped("register", "client registration", reason));
+        }
+
+        self.check_services();
+        self.check_pcap(mac_device);
+
+        self.checks
+    }
+
+    /// Run a given check, measure its duration and store its result. The
+    /// check can fill in its details and it can return a value for
+    /// the subsequent checks.
+    fn check<F, T>(&mut self, name: &'static str, title: &str, f: F) -> Option<T>
+    where
+        F: FnOnce(&mut JsonValue) -> Result<T, RuntimeError>,
+    {
+        let mut details = JsonValue::new_object();
+
+        let start = Instant::now();
+
+        let res = f(&mut details);
+
+        let duration = start.elapsed();
+
+        let (status, message, res) = match res {
+            Ok(res) => (CheckStatus::Passed, None, Some(res)),
+            Err(err) => (CheckStatus::Failed, Some(err.to_string()), None),
+        };
+
+        self.checks.push(Check {
+            name,
+            title: title.to_string(),
+            status,
+            duration,
+            details,
+            message,
+        });
+
+        res
+    }
+
+    /// Check network interfaces and the client MAC address. The name of the
+    /// interface with the client MAC address is returned.
+    fn check_interfaces(&mut self) -> Option<String> {
+        let mac = self.context.get_arrow_mac_address();
+
+        self.check("interfaces", "network interfaces", |details| {
+            let devices = EthernetDevice::list();
+
+            let interfaces = devices
+                .iter()
+                .map(|dev| {
+                    object! {
+                        "name" => dev.name.clone(),
+                        "mac" => dev.mac_addr.to_string(),
+                        "ip" => dev.ip_addr.to_string()
+                    }
+                })
+                .collect::<Vec<_>>();
+
+            let device = devices
+                .iter()
+                .find(|dev| dev.mac_addr == mac)
+                .map(|dev| dev.name.clone());
+
+            details["mac"] = mac.to_string().into();
+            details["interface"] = device.clone().into();
+            details["interfaces"] = JsonValue::Array(interfaces);
+
+            if devices.is_empty() {
+                Err(RuntimeError::from("no configured network interface found"))
+            } else {
+                Ok(device)
+            }
+        })
+        .and_then(|device| device)
+    }
+
+    /// Resolve address of the Arrow Service (or address of the proxy if
+    /// a proxy is used).
+    fn check_dns(&mut self, proxy: Option<&str>) -> Option<Vec<SocketAddr>> {
+        let address = proxy
+            .map(|proxy| proxy.to_string())
+            .unwrap_or_else(|| self.context.get_arrow_service_address());
+
+        self.check("dns", "DNS resolution", |details| {
+            details["host"] = address.clone().into();
+            details["proxy"] = proxy.is_some().into();
+
+            let addrs = address
+                .to_socket_addrs()
+                .map_err(|err| format!("unable to resolve \"{}\": {}", address, err))?
+                .collect::<Vec<_>>();
+
+            let json_addrs = addrs
+                .iter()
+                .map(|addr| JsonValue::from(addr.to_string()))
+                .collect();
+
+            details["addresses"] = JsonValue::Array(json_addrs);
+
+            if addrs.is_empty() {
+                Err(RuntimeError::from(format!(
+                    "no address found for \"{}\"",
+                    address
+                )))
+            } else {
+                Ok(addrs)
+            }
+        })
+    }
+
+    /// Try to connect to any of given addresses.
+    fn check_tcp_connect(&mut self, addrs: &[SocketAddr]) -> Option<TcpStream> {
+        let timeout = self.timeout;
+
+        self.check("tcp_connect", "TCP connection", |details| {
+            let mut last_error = None;
+
+            for addr in addrs {
+                match TcpStream::connect_timeout(addr, timeout) {
+                    Ok(stream) => {
+                        details["address"] = addr.to_string().into();
+
+                        return Ok(stream);
+                    }
+                    Err(err) => {
+                        last_error = Some(format!("unable to connect to {}: {}", addr, err))
+                    }
+                }
+            }
+
+            Err(RuntimeError::from(
+                last_error.unwrap_or_else(|| "no address to connect to".to_string()),
+            ))
+        })
+    }
+
+    /// Perform a TLS handshake over a given stream.
+    fn check_tls_handshake(&mut self, stream: TcpStream) {
+        let context = self.context.clone();
+        let timeout = self.timeout;
+
+        self.check("tls_handshake", "TLS handshake", |details| {
+            stream
+                .set_read_timeout(Some(timeout))
+                .and_then(|_| stream.set_write_timeout(Some(timeout)))
+                .map_err(|err| format!("unable to set socket timeout: {}", err))?;
+
+            let stream = context
+                .get_tls_connector()?
+                .connect(stream)
+                .map_err(|err| format!("TLS handshake failed: {}", err))?;
+
+            let ssl = stream.ssl();
+
+            details["protocol"] = ssl.version_str().into();
+            details["cipher"] = ssl.current_cipher().map(|cipher| cipher.name()).into();
+
+            let cert = ssl
+                .peer_certificate()
+                .ok_or_else(|| RuntimeError::from("no peer certificate"))?;
+
+            let fingerprint = cert
+                .digest(MessageDigest::sha256())
+                .map_err(|err| format!("unable to get certificate fingerprint: {}", err))?
+                .iter()
+                .map(|b| format!("{:02X}", b))
+                .collect::<Vec<_>>()
+                .join(":");
+
+            details["subject"] = format_name(cert.subject_name()).into();
+            details["issuer"] = format_name(cert.issuer_name()).into();
+            details["not_before"] = cert.not_before().to_string().into();
+            details["not_after"] = cert.not_after().to_string().into();
+            details["sha256_fingerprint"] = fingerprint.into();
+
+            Ok(())
+        });
+    }
+
+    /// Connect to the Arrow Service and register the client.
+    fn check_register(&mut self) {
+        let context = self.context.clone();
+
+        self.check("register", "client registration", |details| {
+            let addr = context.get_arrow_service_address();
+
+            let (cmd_channel, cmd_handler) = cmd_handler::new(context.clone());
+
+            // NOTE: the client must be created within the runtime
+            let connection = future::lazy(move || arrow::connect(context, cmd_channel, &addr));
+
+            let res = runtime::block_on(connection);
+
+            drop(cmd_handler);
+
+            match res {
+                Ok(_) => {
+                    details["result"] = "accepted".into();
+                }
+                Err(err) => {
+                    if err.kind() != ErrorKind::Unauthorized {
+                        return Err(RuntimeError::from(err.to_string()));
+                    }
+
+                    details["result"] = "unauthorized".into();
+                    details["note"] = "the client is not paired with any account yet".into();
+                }
+            }
+
+            Ok(())
+        });
+    }
+
+    /// Check reachability of all static services.
+    fn check_services(&mut self) {
+        let timeout = self.timeout;
+
+        let services = self
+            .context
+            .get_service_table()
+            .entries()
+            .into_iter()
+            .filter(|entry| entry.static_service);
+
+        for entry in services {
+            let id = entry.id;
+            let service = entry.service;
+
+            let address = match service.address() {
+                Some(address) => address,
+                None => continue,
+            };
+
+            let title = format!(
+                "service {} ({} {})",
+                id,
+                service.service_type().as_str(),
+                address
+            );
+
+            self.check("service", &title, |details| {
+                details["id"] = id.into();
+                details["type"] = service.service_type().as_str().into();
+                details["address"] = address.to_string().into();
+                details["path"] = service.path().into();
+
+                check_service(&service, address, timeout, details)
+            });
+        }
+    }
+
+    /// Check if packets can be captured on the interface with the client MAC
+    /// address (necessary for network discovery).
+    #[cfg(feature = "discovery")]
+    fn check_pcap(&mut self, device: Option<String>) {
+        let title = "packet capture permissions";
+
+        let device = match device {
+            Some(device) => device,
+            None => {
+                let check = Check::skipped(
+                    "pcap",
+                    title,
+                    "no interface with the client MAC address found",
+                );
+
+                self.checks.push(check);
+
+                return;
+            }
+        };
+
+        let discovery = self.context.get_discovery();
+
+        self.check("pcap", title, |details| {
+            details["interface"] = device.clone().into();
+            details["discovery"] = discovery.into();
+
+            pcap::check_permissions(&device)
+                .map_err(|err| RuntimeError::from(format!("unable to capture packets: {}", err)))
+        });
+    }
+
+    /// Check if packets can be captured on the interface with the client MAC
+    /// address (necessary for network discovery).
+    #[cfg(not(feature = "discovery"))]
+    fn check_pcap(&mut self, _: Option<String>) {
+        self.checks.push(Check::skipped(
+            "pcap",
+            "packet capture permissions",
+            "the client was built without the network discovery feature",
+        ));
+    }
+}
+
+/// Check reachability of a given service.
+fn check_service(
+    service: &Service,
+    address: SocketAddr,
+    timeout: Duration,
+    details: &mut JsonValue,
+) -> Result<(), RuntimeError> {
+    let path = service.path().unwrap_or("/");
+
+    // NOTE: RTSP and HTTP requests are not Send, so they cannot be executed
+    // using the multi-threaded runtime
+    let mut runtime = tokio::runtime::current_thread::Runtime::new()
+        .map_err(|err| format!("unable to create a tokio runtime: {}", err))?;
+
+    match service.service_type() {
+        ServiceType::RTSP
+        | ServiceType::LockedRTSP
+        | ServiceType::UnknownRTSP
+        | ServiceType::UnsupportedRTSP => {
+            let url = format!("rtsp://{}/", address);
+
+            let request = RtspRequest::options(&url)
+                .map_err(|err| format!("invalid RTSP URL: {}", err))?
+                .set_request_timeout(Some(timeout));
+
+            let response = runtime
+                .block_on(request.send())
+                .map_err(|err| format!("RTSP OPTIONS request failed: {}", err))?;
+
+            details["options"] = response.status_line().into();
+
+            let url = format!("rtsp://{}{}", address, path);
+
+            let request = RtspRequest::describe(&url)
+                .map_err(|err| format!("invalid RTSP URL: {}", err))?
+                .set_request_timeout(Some(timeout));
+
+            let response = runtime
+                .block_on(request.send())
+                .map_err(|err| format!("RTSP DESCRIBE request failed: {}", err))?;
+
+            details["describe"] = response.status_line().into();
+
+            check_status_code(response.status_code(), details)
+        }
+        ServiceType::HTTP | ServiceType::MJPEG | ServiceType::LockedMJPEG => {
+            let url = format!("http://{}{}", address, path);
+
+            let request = HttpRequest::get_header(&url)
+                .map_err(|err| format!("invalid HTTP URL: {}", err))?
+                .set_request_timeout(Some(timeout));
+
+            let response = runtime
+                .block_on(request.send())
+                .map_err(|err| format!("HTTP GET request failed: {}", err))?;
+
+            details["get"] = response.status_line().into();
+
+            check_status_code(response.status_code(), details)
+        }
+        _ => {
+            TcpStream::connect_timeout(&address, timeout)
+                .map_err(|err| format!("unable to connect to {}: {}", address, err))?;
+
+            Ok(())
+        }
+    }
+}
+
+/// Check a given RTSP/HTTP status code. All 2xx codes and 401 (the service
+/// is reachable but it requires authentication) are accepted.
+fn check_status_code(status_code: u16, details: &mut JsonValue) -> Result<(), RuntimeError> {
+    match status_code {
+        200..=299 => Ok(()),
+        401 => {
+            details["note"] = "the service requires authentication".into();
+
+            Ok(())
+        }
+        _ => Err(RuntimeError::from(format!(
+            "unexpected status code: {}",
+            status_code
+        ))),
+    }
+}
+
+/// Check if none of given checks failed.
+fn is_success(checks: &[Check]) -> bool {
+    checks
+        .iter()
+        .all(|check| check.status != CheckStatus::Failed)
+}
+
+/// Create a JSON report of given checks.
+fn json_report(checks: &[Check]) -> JsonValue {
+    object! {
+        "success" => is_success(checks),
+        "checks" => checks.iter().map(|check| check.to_json()).collect::<Vec<_>>()
+    }
+}
+
+/// Create a human readable report of given checks.
+fn text_report(checks: &[Check]) -> String {
+    let mut res = checks
+        .iter()
+        .map(|check| check.format())
+        .collect::<String>();
+
+    if is_success(checks) {
+        res += "\nall checks passed\n";
+    } else {
+        res += "\nsome checks failed\n";
+    }
+
+    res
+}
+
+/// Format a given X.509 name (e.g. "CN=arr-rs.angelcam.com, O=Angelcam").
+fn format_name(name: &X509NameRef) -> String {
+    name.entries()
+        .map(|entry| {
+            let key = entry.object().nid().short_name().unwrap_or("?");
+
+            let value = String::from_utf8_lossy(entry.data().as_slice());
+
+            format!("{}={}", key, value)
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Run the self-test diagnostics with given arguments (excluding the program
+/// name and the "diagnose" argument) and exit the process.
+pub fn main<I>(args: I) -> !
+where
+    I: IntoIterator<Item = String>,
+{
+    let mut json = false;
+
+    let mut app_args = Vec::new();
+
+    for arg in args {
+        if arg == "--json" {
+            json = true;
+        } else {
+            app_args.push(arg);
+        }
+    }
+
+    let config = match ApplicationConfig::create_diagnostic(app_args) {
+        Ok(config) => config,
+        Err(err) => {
+            println!("ERROR: {}\n", err);
+            usage(1);
+        }
+    };
+
+    let context = ApplicationContext::new(config);
+
+    let checks = Diagnostics::new(context).run();
+
+    if json {
+        println!("{}", json_report(&checks).pretty(4));
+    } else {
+        print!("{}", text_report(&checks));
+    }
+
+    process::exit(if is_success(&checks) { 0 } else { 1 })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use openssl::x509::X509Name;
+
+    /// Create a new check with a given status.
+    fn check(name: &'static str, status: CheckStatus) -> Check {
+        Check {
+            name,
+            title: format!("{} check", name),
+            status,
+            duration: Duration::from_micros(12_345),
+            details: JsonValue::new_object(),
+            message: None,
+        }
+    }
+
+    #[test]
+    fn test_check_status_code() {
+        let mut details = JsonValue::new_object();
+
+        assert!(check_status_code(200, &mut details).is_ok());
+        assert!(check_status_code(204, &mut details).is_ok());
+        assert!(details["note"].is_null());
+
+        assert!(check_status_code(401, &mut details).is_ok());
+        assert_eq!(details["note"], "the service requires authentication");
+
+        assert!(check_status_code(199, &mut details).is_err());
+        assert!(check_status_code(301, &mut details).is_err());
+        assert!(check_status_code(403, &mut details).is_err());
+        assert!(check_status_code(500, &mut details).is_err());
+    }
+
+    #[test]
+    fn test_skipped_check() {
+        let check = Check::skipped("tls", "TLS handshake", "no TCP connection");
+
+        assert_eq!(check.status, CheckStatus::Skipped);
+        assert_eq!(check.duration_ms(), 0.0);
+        assert_eq!(check.message.as_deref(), Some("no TCP connection"));
+        assert_eq!(
+            check.format(),
+            "[ SKIP ] TLS handshake\n         no TCP connection\n"
+        );
+    }
+
+    #[test]
+    fn test_check_rendering() {
+        let mut passed = check("dns", CheckStatus::Passed);
+
+        passed.details["host"] = "arr-rs.angelcam.com".into();
+        passed.details["addresses"] = vec!["10.0.0.1:8900"].into();
+
+        assert_eq!(passed.duration_ms(), 12.345);
+
+        let json = passed.to_json();
+
+        assert_eq!(json["name"], "dns");
+        assert_eq!(json["title"], "dns check");
+        assert_eq!(json["status"], "passed");
+        assert_eq!(json["duration_ms"], 12.345);
+        assert!(json["message"].is_null());
+        assert_eq!(json["details"]["host"], "arr-rs.angelcam.com");
+
+        assert_eq!(
+            passed.format(),
+            "[  OK  ] dns check (12.3 ms)\n         host: arr-rs.angelcam.com\n         addresses: [\"10.0.0.1:8900\"]\n"
+        );
+
+        let mut failed = check("tcp", CheckStatus::Failed);
+
+        failed.message = Some("connection refused".to_string());
+
+        let checks = vec![passed, Check::skipped("pcap", "packet capture", "n/a")];
+
+        assert!(is_success(&checks));
+        assert_eq!(json_report(&checks)["success"], true);
+        assert_eq!(json_report(&checks)["checks"].len(), 2);
+        assert!(text_report(&checks).ends_with("\nall checks passed\n"));
+
+        let checks = vec![failed];
+
+        let report = json_report(&checks);
+
+        assert!(!is_success(&checks));
+        assert_eq!(report["success"], false);
+        assert_eq!(report["checks"][0]["status"], "failed");
+        assert_eq!(report["checks"][0]["message"], "connection refused");
+        assert_eq!(
+            text_report(&checks),
+            "[FAILED] tcp check (12.3 ms)\n         connection refused\n\nsome checks failed\n"
+        );
+    }
+
+    #[test]
+    fn test_format_name() {
+        let mut builder = X509Name::builder().unwrap();
+
+        builder
+            .append_entry_by_text("CN", "arr-rs.angelcam.com")
+            .unwrap();
+        builder.append_entry_by_text("O", "Angelcam").unwrap();
+
+        let name = builder.build();
+
+        assert_eq!(format_name(&name), "CN=arr-rs.angelcam.com, O=Angelcam");
+        assert_eq!(format_name(&X509Name::builder().unwrap().build()), "");
+    }
+}
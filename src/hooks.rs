@@ -0,0 +1,447 @@
+// Copyright 2019 click2stream, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Event hooks. A hook is an external executable that is run on selected
+//! application events. The hook gets a JSON description of the event on its
+//! stdin and the event name in the `ARROW_EVENT` environment variable.
+//!
+//! Hooks are executed in background threads. The number of concurrently
+//! running hooks is limited and every hook is killed (together with all
+//! processes in its process group) if it does not finish within a given
+//! timeout.
+
+use std::io;
+use std::thread;
+
+use std::collections::VecDeque;
+use std::io::Write;
+use std::os::unix::io::AsRawFd;
+use std::os::unix::process::CommandExt;
+use std::process::{Child, ChildStdin, Command, ExitStatus, Stdio};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use json::JsonValue;
+
+use time;
+
+use crate::context::ConnectionState;
+use crate::svc_table::ServiceTableEntry;
+use crate::utils::json::ToJson;
+use crate::utils::logger::{BoxLogger, Logger};
+
+/// Default maximum time a hook can run (in seconds).
+pub const DEFAULT_HOOK_TIMEOUT: u64 = 10;
+
+/// Default maximum number of concurrently running hooks.
+pub const DEFAULT_MAX_RUNNING_HOOKS: usize = 4;
+
+/// Maximum number of hook invocations waiting for execution. Any further
+/// events will be dropped.
+const MAX_PENDING_HOOKS: usize = 64;
+
+/// Interval for checking if a hook process has finished.
+const POLL_INTERVAL: u64 = 50;
+
+/// Application event passed to hooks.
+pub enum Event {
+    ConnectionStateChanged {
+        state: ConnectionState,
+        previous_state: ConnectionState,
+        endpoint: String,
+        last_error: Option<String>,
+    },
+    PairingWindowExpired,
+    ServiceAdded(ServiceTableEntry),
+    ServiceRemoved(ServiceTableEntry),
+    ScanCompleted {
+        duration: f64,
+        hosts: usize,
+        services: usize,
+    },
+}
+
+impl Event {
+    /// Get event name.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Event::ConnectionStateChanged { .. } => "connection_state_changed",
+            Event::PairingWindowExpired => "pairing_window_expired",
+            Event::ServiceAdded(_) => "service_added",
+            Event::ServiceRemoved(_) => "service_removed",
+            Event::ScanCompleted { .. } => "scan_completed",
+        }
+    }
+}
+
+impl ToJson for Event {
+    fn to_json(&self) -> JsonValue {
+        let mut res = match self {
+            Event::ConnectionStateChanged {
+                state,
+                previous_state,
+                endpoint,
+                last_error,
+            } => object! {
+                "state" => state.as_str(),
+                "previous_state" => previous_state.as_str(),
+                "endpoint" => endpoint.clone(),
+                "last_error" => last_error.clone()
+            },
+            Event::PairingWindowExpired => JsonValue::new_object(),
+            Event::ServiceAdded(entry) | Event::ServiceRemoved(entry) => object! {
+                "service" => entry.to_json()
+            },
+            Event::ScanCompleted {
+                duration,
+                hosts,
+                services,
+            } => object! {
+                "duration" => *duration,
+                "hosts" => *hosts,
+                "services" => *services
+            },
+        };
+
+        res["event"] = self.name().into();
+        res["timestamp"] = time::get_time().sec.into();
+
+        res
+    }
+}
+
+/// A single hook invocation.
+struct HookInvocation {
+    hook: String,
+    event: &'static str,
+    input: String,
+}
+
+/// Hook invocation queue.
+struct HookQueue {
+    pending: VecDeque<HookInvocation>,
+    running: usize,
+}
+
+/// Event hooks. The object is cloneable and all its copies share the same
+/// invocation queue.
+#[derive(Clone)]
+pub struct EventHooks {
+    hooks: Arc<Vec<String>>,
+    timeout: Duration,
+    max_running: usize,
+    queue: Arc<Mutex<HookQueue>>,
+    logger: BoxLogger,
+}
+
+impl EventHooks {
+    /// Create a new set of event hooks.
+    pub fn new(
+        logger: BoxLogger,
+        hooks: Vec<String>,
+        timeout: Duration,
+        max_running: usize,
+    ) -> EventHooks {
+        let queue = HookQueue {
+            pending: VecDeque::new(),
+            running: 0,
+        };
+
+        EventHooks {
+            hooks: Arc::new(hooks),
+            timeout,
+            max_running,
+            queue: Arc::new(Mutex::new(queue)),
+            logger,
+        }
+    }
+
+    /// Run all hooks for a given event. The method does not wait for the
+    /// hooks to finish.
+    pub fn emit(&self, event: Event) {
+        if self.hooks.is_empty() {
+            return;
+        }
+
+        let input = event.to_json().dump();
+
+        let mut queue = self.queue.lock().unwrap();
+
+        for hook in self.hooks.iter() {
+            if queue.pending.len() >= MAX_PENDING_HOOKS {
+                log_warn!(
+                    self.logger.clone(),
+                    "too many pending event hooks, dropping event {}",
+                    event.name()
+                );
+
+                break;
+            }
+
+            queue.pending.push_back(HookInvocation {
+                hook: hook.clone(),
+                event: event.name(),
+                input: input.clone(),
+            });
+        }
+
+        while queue.running < self.max_running && queue.running < queue.pending.len() {
+            queue.running += 1;
+
+            let hooks = self.clone();
+
+            thread::spawn(move || hooks.worker());
+        }
+    }
+
+    /// Execute pending hook invocations until the queue is empty.
+    fn worker(&self) {
+        let mut logger = self.logger.clone();
+
+        loop {
+            let invocation = {
+                let mut queue = self.queue.lock().unwrap();
+
+                match queue.pending.pop_front() {
+                    Some(invocation) => invocation,
+                    None => {
+                        queue.running -= 1;
+
+                        return;
+                    }
+                }
+            };
+
+            log_debug!(
+                logger,
+                "running event hook \"{}\" ({})",
+                invocation.hook,
+                invocation.event
+            );
+
+            match run_hook(&invocation, self.timeout) {
+                Ok(Some(status)) if status.success() => (),
+                Ok(Some(status)) => log_warn!(
+                    logger,
+                    "event hook \"{}\" failed ({})",
+                    invocation.hook,
+                    status
+                ),
+                Ok(None) => log_warn!(
+                    logger,
+                    "event hook \"{}\" killed after {} seconds",
+                    invocation.hook,
+                    self.timeout.as_secs()
+                ),
+                Err(err) => log_warn!(
+                    logger,
+                    "unable to run event hook \"{}\" ({})",
+                    invocation.hook,
+                    err
+                ),
+            }
+        }
+    }
+}
+
+/// Run a given hook invocation. The hook is killed if it does not finish
+/// within a given timeout, `None` is returned in such case.
+fn run_hook(
+    invocation: &HookInvocation,
+    timeout: Duration,
+) -> Result<Option<ExitStatus>, io::Error> {
+    // the hook runs in its own process group, so that all its children can
+    // be killed as well
+    let mut child = Command::new(&invocation.hook)
+        .env("ARROW_EVENT", invocation.event)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .process_group(0)
+        .spawn()?;
+
+    let deadline = Instant::now() + timeout;
+
+    // the input is written without blocking because the hook does not have
+    // to read it
+    let mut stdin = child.stdin.take();
+    let mut input = invocation.input.as_bytes();
+
+    if let Some(pipe) = stdin.as_ref() {
+        if let Err(err) = set_nonblocking(pipe) {
+            kill_hook(&mut child)?;
+
+            return Err(err);
+        }
+    }
+
+    loop {
+        if let Some(pipe) = stdin.as_mut() {
+            match write_input(pipe, &mut input) {
+                Ok(true) => stdin = None,
+                Ok(false) => (),
+                Err(err) => {
+                    kill_hook(&mut child)?;
+
+                    return Err(err);
+                }
+            }
+        }
+
+        if let Some(status) = child.try_wait()? {
+            return Ok(Some(status));
+        } else if Instant::now() >= deadline {
+            kill_hook(&mut child)?;
+
+            return Ok(None);
+        }
+
+        thread::sleep(Duration::from_millis(POLL_INTERVAL));
+    }
+}
+
+/// Write as much of a given input as possible into a given non-blocking
+/// pipe. The method returns true if there is nothing more to write (i.e. all
+/// input has been written or the hook has closed its stdin).
+fn write_input(pipe: &mut ChildStdin, input: &mut &[u8]) -> Result<bool, io::Error> {
+    while !input.is_empty() {
+        match pipe.write(input) {
+            Ok(len) => *input = &input[len..],
+            Err(err) => match err.kind() {
+                io::ErrorKind::WouldBlock => return Ok(false),
+                io::ErrorKind::Interrupted => (),
+                io::ErrorKind::BrokenPipe => return Ok(true),
+                _ => return Err(err),
+            },
+        }
+    }
+
+    Ok(true)
+}
+
+/// Switch a given pipe into the non-blocking mode.
+fn set_nonblocking(pipe: &ChildStdin) -> Result<(), io::Error> {
+    let fd = pipe.as_raw_fd();
+
+    let res = unsafe {
+        let flags = libc::fcntl(fd, libc::F_GETFL);
+
+        if flags < 0 {
+            flags
+        } else {
+            libc::fcntl(fd, libc::F_SETFL, flags | libc::O_NONBLOCK)
+        }
+    };
+
+    if res < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+/// Kill a given hook process together with all processes in its process
+/// group and wait for the hook process.
+fn kill_hook(child: &mut Child) -> Result<(), io::Error> {
+    // the process group ID is equal to the hook process ID
+    unsafe {
+        libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL);
+    }
+
+    child.wait()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::env;
+    use std::fs;
+    use std::process;
+
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn test_run_hook() {
+        let dir = env::temp_dir().join(format!("arrow-hooks-test-{}", process::id()));
+
+        fs::create_dir_all(&dir).unwrap();
+
+        let output = dir.join("output");
+        let hook = dir.join("hook.sh");
+        let slow_hook = dir.join("slow-hook.sh");
+
+        fs::write(
+            &hook,
+            format!(
+                "#!/bin/sh\necho \"$ARROW_EVENT\" > {0}\ncat >> {0}\n",
+                output.display()
+            ),
+        )
+        .unwrap();
+
+        // the hook does not read its input and its child keeps running
+        fs::write(
+            &slow_hook,
+            format!(
+                "#!/bin/sh\n(sleep 1; echo alive > {}) &\nsleep 10\n",
+                output.display()
+            ),
+        )
+        .unwrap();
+
+        for file in &[&hook, &slow_hook] {
+            fs::set_permissions(file, fs::Permissions::from_mode(0o755)).unwrap();
+        }
+
+        let invocation = HookInvocation {
+            hook: hook.to_str().unwrap().to_string(),
+            event: "pairing_window_expired",
+            input: "{}".to_string(),
+        };
+
+        let status = run_hook(&invocation, Duration::from_secs(10)).unwrap();
+
+        assert!(status.unwrap().success());
+        assert_eq!(
+            fs::read_to_string(&output).unwrap(),
+            "pairing_window_expired\n{}"
+        );
+
+        fs::remove_file(&output).unwrap();
+
+        // the input is larger than the pipe buffer
+        let invocation = HookInvocation {
+            hook: slow_hook.to_str().unwrap().to_string(),
+            event: "pairing_window_expired",
+            input: "x".repeat(1 << 20),
+        };
+
+        let start = Instant::now();
+
+        let status = run_hook(&invocation, Duration::from_millis(200)).unwrap();
+
+        assert!(status.is_none());
+        assert!(start.elapsed() < Duration::from_secs(5));
+
+        // children of the hook must be killed as well
+        thread::sleep(Duration::from_millis(1500));
+
+        assert!(!output.exists());
+
+        fs::remove_dir_all(&dir).unwrap_or(());
+    }
+}
@@ -27,16 +27,23 @@ extern crate lazy_static;
 #[macro_use]
 pub mod utils;
 
+pub mod api;
 pub mod cmd_handler;
 pub mod config;
 pub mod context;
+pub mod ctl;
+pub mod diagnose;
 pub mod futures_ex;
+pub mod hooks;
+pub mod metrics;
 pub mod net;
+pub mod pairing;
 pub mod runtime;
 pub mod scanner;
 pub mod svc_table;
 
 use std::process;
+use std::thread;
 
 use std::error::Error;
 use std::fmt::Debug;
@@ -47,21 +54,27 @@ use futures::{Future, Stream};
 
 use tokio::timer::{Delay, Interval};
 
+use tokio_signal::unix::{Signal, SIGHUP, SIGINT, SIGTERM, SIGUSR1};
+
 use crate::net::arrow;
 
 use crate::cmd_handler::{Command, CommandChannel};
 use crate::config::ApplicationConfig;
 use crate::context::{ApplicationContext, ConnectionState};
+use crate::hooks::Event;
 use crate::net::arrow::{ArrowError, ErrorKind};
-use crate::utils::logger::{BoxLogger, Logger};
+use crate::pairing::PairingTrigger;
+use crate::utils::logger::{BoxLogger, Logger, Severity};
+use crate::utils::systemd;
 
 use crate::config::usage;
 
-/// Connectionn retry timeout.
-const RETRY_TIMEOUT: f64 = 60.0;
+/// Maximum time (in seconds) to wait for a running network scanner on
+/// shutdown.
+const SCANNER_SHUTDOWN_TIMEOUT: u64 = 10;
 
-/// Get maximum duration of the pairing mode.
-const PAIRING_MODE_TIMEOUT: f64 = 1200.0;
+/// Interval for checking the pairing trigger file (in milliseconds).
+const PAIRING_TRIGGER_POLL_INTERVAL: u64 = 500;
 
 /// Unwrap a given result (if possible) or print the error message and exit
 /// the process printing application usage.
@@ -86,7 +99,7 @@ struct ArrowMainTask {
     default_addr: String,
     current_addr: String,
     last_attempt: f64,
-    pairing_mode_timeout: f64,
+    reconnect: bool,
     diagnostic_mode: bool,
 }
 
@@ -102,8 +115,6 @@ impl ArrowMainTask {
 
         let t = time::precise_time_s();
 
-        let pairing_mode_timeout = t + PAIRING_MODE_TIMEOUT;
-
         let task = ArrowMainTask {
             app_context: app_context,
             cmd_channel: cmd_channel,
@@ -111,16 +122,18 @@ impl ArrowMainTask {
             default_addr: addr.clone(),
             current_addr: addr,
             last_attempt: t,
-            pairing_mode_timeout: pairing_mode_timeout,
+            reconnect: false,
             diagnostic_mode: diagnostic_mode,
         };
 
         let task = Arc::new(Mutex::new(task));
 
         let connector = task.clone();
-        let rhandler = task;
+        let rhandler = task.clone();
+        let shutdown = task;
 
         futures::stream::repeat(())
+            .take_while(move |_| Ok(!shutdown.lock().unwrap().app_context.is_shutting_down()))
             .and_then(move |_| connector.lock().unwrap().connect())
             .then(move |res| rhandler.lock().unwrap().process_result(res))
             .for_each(|_| Ok(()))
@@ -130,14 +143,21 @@ impl ArrowMainTask {
     fn connect(&mut self) -> impl Future<Item = String, Error = ArrowError> {
         log_info!(
             &mut self.logger,
-            "connecting to remote Arrow Service {}",
-            self.current_addr
+            "connecting to remote Arrow Service";
+            endpoint = self.current_addr
         );
 
         self.last_attempt = time::precise_time_s();
 
+        if self.reconnect {
+            self.app_context.get_metrics().reconnect();
+        }
+
+        self.reconnect = true;
+
+        self.app_context.set_arrow_endpoint(&self.current_addr);
         self.app_context
-            .set_connection_state(ConnectionState::Connected);
+            .set_connection_state(ConnectionState::Connecting);
 
         arrow::connect(
             self.app_context.clone(),
@@ -151,7 +171,14 @@ impl ArrowMainTask {
         &mut self,
         res: Result<String, ArrowError>,
     ) -> impl Future<Item = (), Error = ()> {
-        if self.diagnostic_mode {
+        if self.app_context.is_shutting_down() {
+            log_info!(
+                &mut self.logger,
+                "disconnected from the remote Arrow Service"
+            );
+
+            Box::new(futures::future::ok(()))
+        } else if self.diagnostic_mode {
             diagnose_connection_result(&res);
         } else if let Ok(addr) = res {
             // set redirection
@@ -174,13 +201,29 @@ impl ArrowMainTask {
                 _ => ConnectionState::Disconnected,
             };
 
-            self.app_context.set_connection_state(cstate);
+            self.app_context.set_last_error(&err.to_string());
+
+            let pairing_mode_timeout = self.app_context.get_pairing_mode_timeout();
+
+            let retry_timeout = self.app_context.get_retry_timeout().as_secs() as f64;
+
+            let retry = process_connection_error(
+                err,
+                self.last_attempt,
+                retry_timeout,
+                pairing_mode_timeout,
+            );
+
+            let cstate = match retry {
+                ConnectionRetry::Suspend(_) => ConnectionState::Suspended,
+                _ => cstate,
+            };
 
-            let retry = process_connection_error(err, self.last_attempt, self.pairing_mode_timeout);
+            self.app_context.set_connection_state(cstate);
 
             self.current_addr = self.default_addr.clone();
 
-            wait_for_retry(&mut self.logger, retry)
+            wait_for_retry(&mut self.logger, retry, &self.app_context)
         } else {
             panic!("unexpected Result variant")
         }
@@ -216,6 +259,7 @@ impl SuspendReason {
 fn process_connection_error(
     connection_error: ArrowError,
     last_attempt: f64,
+    retry_timeout: f64,
     pairing_mode_timeout: f64,
 ) -> ConnectionRetry {
     let t = time::precise_time_s();
@@ -240,21 +284,31 @@ fn process_connection_error(
             ConnectionRetry::Suspend(SuspendReason::UnsupportedProtocolVersion)
         }
         // in all other cases
-        _ => ConnectionRetry::Timeout(RETRY_TIMEOUT + last_attempt - t),
+        _ => ConnectionRetry::Timeout(retry_timeout + last_attempt - t),
     }
 }
 
-/// Process a given connection retry object.
+/// Process a given connection retry object. The returned future will be
+/// resolved either after the retry timeout or on shutdown. A connection
+/// thread suspended because of the pairing window timeout will be also
+/// resumed once the pairing window is reopened.
 fn wait_for_retry(
     logger: &mut dyn Logger,
     connection_retry: ConnectionRetry,
+    app_context: &ApplicationContext,
 ) -> Box<dyn Future<Item = (), Error = ()> + Send + Sync> {
+    let shutdown = app_context.get_shutdown_signal();
+
     match connection_retry {
         ConnectionRetry::Timeout(t) if t > 0.5 => {
             log_info!(logger, "retrying in {:.3} seconds", t);
 
             let time = Duration::from_millis((t * 1000.0) as u64);
-            let sleep = Delay::new(Instant::now() + time).map_err(|_| ());
+            let sleep = Delay::new(Instant::now() + time)
+                .map_err(|_| ())
+                .select(shutdown)
+                .map(|_| ())
+                .map_err(|_| ());
 
             Box::new(sleep)
         }
@@ -263,11 +317,203 @@ fn wait_for_retry(
             log_info!(logger, "{}", reason.to_string());
             log_info!(logger, "suspending the connection thread");
 
-            Box::new(futures::future::empty())
+            match reason {
+                SuspendReason::NotInPairingMode => {
+                    app_context
+                        .get_event_hooks()
+                        .emit(Event::PairingWindowExpired);
+
+                    Box::new(wait_for_pairing_window(app_context.clone()))
+                }
+                _ => Box::new(shutdown),
+            }
+        }
+    }
+}
+
+/// Create a future that will be resolved once the pairing window is reopened
+/// or on shutdown.
+fn wait_for_pairing_window(app_context: ApplicationContext) -> impl Future<Item = (), Error = ()> {
+    let interval = Duration::from_millis(1000);
+
+    Interval::new(Instant::now() + interval, interval)
+        .map_err(|_| ())
+        .take_while(move |_| Ok(!app_context.is_pairing_mode() && !app_context.is_shutting_down()))
+        .for_each(|_| Ok(()))
+}
+
+/// Create a future handling the SIGTERM and SIGINT signals. The first signal
+/// initiates a graceful shutdown, the second one terminates the process
+/// immediately.
+fn handle_termination_signals(
+    mut app_context: ApplicationContext,
+) -> impl Future<Item = (), Error = ()> {
+    let mut logger = app_context.get_logger();
+
+    let sigterm = Signal::new(SIGTERM).flatten_stream();
+    let sigint = Signal::new(SIGINT).flatten_stream();
+
+    sigterm
+        .select(sigint)
+        .for_each(move |signal| {
+            if app_context.is_shutting_down() {
+                log_warn!(
+                    &mut logger,
+                    "signal {} received during shutdown, terminating immediately",
+                    signal
+                );
+
+                process::exit(1);
+            }
+
+            log_info!(&mut logger, "signal {} received, shutting down...", signal);
+
+            app_context.shutdown();
+
+            Ok(())
+        })
+        .map_err(|_| ())
+}
+
+/// Create a future sending the reload command on every SIGHUP.
+fn handle_reload_signal(cmd_channel: CommandChannel) -> impl Future<Item = (), Error = ()> {
+    Signal::new(SIGHUP)
+        .flatten_stream()
+        .for_each(move |_| {
+            cmd_channel.send(Command::ReloadConfig);
+
+            Ok(())
+        })
+        .map_err(|_| ())
+}
+
+/// Create a future reopening the pairing window on every SIGUSR1.
+fn handle_pairing_signal(cmd_channel: CommandChannel) -> impl Future<Item = (), Error = ()> {
+    Signal::new(SIGUSR1)
+        .flatten_stream()
+        .for_each(move |_| {
+            cmd_channel.send(Command::ResetPairingWindow);
+
+            Ok(())
+        })
+        .map_err(|_| ())
+}
+
+/// Create a future watching a given pairing trigger file and reopening the
+/// pairing window whenever the trigger fires.
+fn watch_pairing_trigger(
+    path: String,
+    cmd_channel: CommandChannel,
+    mut logger: BoxLogger,
+) -> impl Future<Item = (), Error = ()> {
+    let mut trigger = PairingTrigger::new(&path);
+
+    let interval = Duration::from_millis(PAIRING_TRIGGER_POLL_INTERVAL);
+
+    Interval::new(Instant::now() + interval, interval)
+        .for_each(move |_| {
+            if trigger.poll() {
+                log_debug!(logger, "pairing trigger \"{}\" activated", path);
+
+                cmd_channel.send(Command::ResetPairingWindow);
+            }
+
+            Ok(())
+        })
+        .map_err(|_| ())
+}
+
+/// Notify systemd (if running as a notify service) that the application
+/// startup is finished and start sending watchdog pings (if enabled). The
+/// pings are sent from the event loop, so the service manager can restart
+/// the application if the loop gets stuck.
+fn start_systemd_notifications(app_context: ApplicationContext) {
+    let mut logger = app_context.get_logger();
+
+    let notifier = match app_context.get_systemd_notifier() {
+        Some(notifier) => notifier,
+        None => return,
+    };
+
+    let status = app_context.get_connection_state();
+
+    let res = notifier
+        .ready()
+        .and_then(|_| notifier.status(&status.to_string()));
+
+    utils::result_or_log(
+        &mut logger,
+        Severity::WARN,
+        "unable to send systemd notification",
+        res,
+    );
+
+    if let Some(interval) = systemd::watchdog_interval() {
+        log_info!(
+            &mut logger,
+            "systemd watchdog enabled, sending keep-alive pings every {:.3} seconds",
+            interval.as_secs_f64()
+        );
+
+        let watchdog = Interval::new_interval(interval)
+            .for_each(move |_| {
+                notifier.watchdog().unwrap_or(());
+
+                Ok(())
+            })
+            .map_err(|_| ());
+
+        tokio::spawn(watchdog);
+    }
+}
+
+/// Start the local management API on all configured addresses.
+fn start_management_api(app_context: ApplicationContext, cmd_channel: CommandChannel) {
+    let mut logger = app_context.get_logger();
+
+    for addr in app_context.get_api_addresses() {
+        match api::new(app_context.clone(), cmd_channel.clone(), &addr) {
+            Ok(server) => {
+                log_info!(&mut logger, "management API listening on {}", addr);
+
+                tokio::spawn(server);
+            }
+            Err(err) => log_warn!(
+                &mut logger,
+                "unable to start management API on {}: {}",
+                addr,
+                err
+            ),
         }
     }
 }
 
+/// Wait (with a timeout) until a running network scanner finishes and save
+/// the final connection state.
+fn finish_shutdown(mut app_context: ApplicationContext) {
+    let mut logger = app_context.get_logger();
+
+    let deadline = Instant::now() + Duration::from_secs(SCANNER_SHUTDOWN_TIMEOUT);
+
+    if app_context.is_scanning() {
+        log_info!(&mut logger, "waiting for the network scanner to finish...");
+    }
+
+    while app_context.is_scanning() && Instant::now() < deadline {
+        thread::sleep(Duration::from_millis(100));
+    }
+
+    if app_context.is_scanning() {
+        log_warn!(&mut logger, "the network scanner did not finish in time");
+    }
+
+    // NOTE: the application context is locked while the configuration file is
+    // being saved, so this call will also wait for any pending write
+    app_context.set_connection_state(ConnectionState::Disconnected);
+
+    log_info!(&mut logger, "application terminated");
+}
+
 /// Diagnose a given connection result and exit with exit code 0 if the
 /// connection was successful or the server responded with UNAUTHORIZED,
 /// otherwise exit with exit code 1.
@@ -283,6 +529,34 @@ fn diagnose_connection_result(connection_result: &Result<String, ArrowError>) ->
 
 /// Arrow Client main function.
 fn main() {
+    let mut args = std::env::args().skip(1).peekable();
+
+    if args.peek().map(|arg| arg == "ctl").unwrap_or(false) {
+        ctl::main(args.skip(1));
+    }
+
+    if args.peek().map(|arg| arg == "diagnose").unwrap_or(false) {
+        diagnose::main(args.skip(1));
+    }
+
+    if args.peek().map(|arg| arg == "identity").unwrap_or(false) {
+        config::identity::main(args.skip(1));
+    }
+
+    if args
+        .peek()
+        .map(|arg| arg == "encrypt-secrets")
+        .unwrap_or(false)
+    {
+        match ApplicationConfig::encrypt_secrets(args.skip(1)) {
+            Ok(()) => process::exit(0),
+            Err(err) => {
+                eprintln!("ERROR: {}", err);
+                process::exit(1);
+            }
+        }
+    }
+
     let config = result_or_usage(ApplicationConfig::create());
 
     let context = ApplicationContext::new(config);
@@ -300,9 +574,19 @@ fn main() {
     let (tx, rx) = cmd_handler::new(context.clone());
 
     let cmd_channel = tx.clone();
+    let reload_channel = tx.clone();
+    let pairing_channel = tx.clone();
+    let trigger_channel = tx.clone();
+    let trigger_logger = context.get_logger();
+    let pairing_trigger = context.get_pairing_trigger();
+    let api_channel = tx.clone();
 
     // create Arrow client main task
-    let arrow_main_task = ArrowMainTask::new(context, cmd_channel);
+    let arrow_main_task = ArrowMainTask::new(context.clone(), cmd_channel);
+
+    let signal_context = context.clone();
+    let api_context = context.clone();
+    let systemd_context = context.clone();
 
     let interval = Duration::from_millis(1000);
 
@@ -318,7 +602,19 @@ fn main() {
     runtime::run(futures::future::lazy(|| {
         tokio::spawn(rx);
         tokio::spawn(periodic_network_scan);
+        tokio::spawn(handle_termination_signals(signal_context));
+        tokio::spawn(handle_reload_signal(reload_channel));
+        tokio::spawn(handle_pairing_signal(pairing_channel));
+
+        if let Some(path) = pairing_trigger {
+            tokio::spawn(watch_pairing_trigger(path, trigger_channel, trigger_logger));
+        }
+
+        start_management_api(api_context, api_channel);
+        start_systemd_notifications(systemd_context);
 
         arrow_main_task
     }));
+
+    finish_shutdown(context);
 }
@@ -0,0 +1,370 @@
+// Copyright 2019 click2stream, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Application metrics registry. The metrics can be exported in the
+//! Prometheus text format.
+
+use std::fmt;
+
+use std::fmt::Write;
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+use crate::context::ConnectionState;
+use crate::svc_table::ServiceType;
+
+/// All connection states exported as the connection state gauge.
+const CONNECTION_STATES: &[ConnectionState] = &[
+    ConnectionState::Connecting,
+    ConnectionState::Handshaking,
+    ConnectionState::Connected,
+    ConnectionState::Disconnected,
+    ConnectionState::Unauthorized,
+    ConnectionState::Suspended,
+];
+
+/// Counters of transferred bytes.
+#[derive(Default, Copy, Clone)]
+struct ByteCounters {
+    sent: u64,
+    received: u64,
+}
+
+/// Internal metrics data.
+#[derive(Default)]
+struct MetricsData {
+    reconnects: u64,
+    ack_timeouts: u64,
+    active_sessions: u64,
+    sessions_opened: u64,
+    sessions_closed: BTreeMap<u32, u64>,
+    session_errors: BTreeMap<u32, u64>,
+    service_bytes: BTreeMap<&'static str, ByteCounters>,
+    scans: u64,
+    scan_duration_sum: f64,
+    last_scan_duration: f64,
+    last_scan_hosts: usize,
+    last_scan_services: usize,
+    config_save_failures: u64,
+}
+
+impl MetricsData {
+    /// Render all metrics in the Prometheus text format.
+    fn render(&self, conn_state: ConnectionState) -> Result<String, fmt::Error> {
+        let mut res = String::new();
+
+        header(
+            &mut res,
+            "arrow_connection_state",
+            "gauge",
+            "Current state of the connection to the Arrow Service.",
+        )?;
+
+        for state in CONNECTION_STATES {
+            let value = if *state == conn_state { 1 } else { 0 };
+
+            writeln!(
+                res,
+                "arrow_connection_state{{state=\"{}\"}} {}",
+                state, value
+            )?;
+        }
+
+        header(
+            &mut res,
+            "arrow_reconnects_total",
+            "counter",
+            "Number of reconnects to the Arrow Service.",
+        )?;
+        writeln!(res, "arrow_reconnects_total {}", self.reconnects)?;
+
+        header(
+            &mut res,
+            "arrow_ack_timeouts_total",
+            "counter",
+            "Number of Arrow Service connections closed because of an ACK timeout.",
+        )?;
+        writeln!(res, "arrow_ack_timeouts_total {}", self.ack_timeouts)?;
+
+        header(
+            &mut res,
+            "arrow_active_sessions",
+            "gauge",
+            "Number of active service sessions.",
+        )?;
+        writeln!(res, "arrow_active_sessions {}", self.active_sessions)?;
+
+        header(
+            &mut res,
+            "arrow_sessions_opened_total",
+            "counter",
+            "Number of opened service sessions.",
+        )?;
+        writeln!(res, "arrow_sessions_opened_total {}", self.sessions_opened)?;
+
+        header(
+            &mut res,
+            "arrow_sessions_closed_total",
+            "counter",
+            "Number of closed service sessions by HUP error code.",
+        )?;
+
+        for (code, count) in &self.sessions_closed {
+            writeln!(
+                res,
+                "arrow_sessions_closed_total{{code=\"0x{:08x}\"}} {}",
+                code, count
+            )?;
+        }
+
+        header(
+            &mut res,
+            "arrow_session_errors_total",
+            "counter",
+            "Number of service session errors by HUP error code.",
+        )?;
+
+        for (code, count) in &self.session_errors {
+            writeln!(
+                res,
+                "arrow_session_errors_total{{code=\"0x{:08x}\"}} {}",
+                code, count
+            )?;
+        }
+
+        header(
+            &mut res,
+            "arrow_service_bytes_sent_total",
+            "counter",
+            "Number of bytes sent to local services by service type.",
+        )?;
+
+        for (svc_type, counters) in &self.service_bytes {
+            writeln!(
+                res,
+                "arrow_service_bytes_sent_total{{svc_type=\"{}\"}} {}",
+                svc_type, counters.sent
+            )?;
+        }
+
+        header(
+            &mut res,
+            "arrow_service_bytes_received_total",
+            "counter",
+            "Number of bytes received from local services by service type.",
+        )?;
+
+        for (svc_type, counters) in &self.service_bytes {
+            writeln!(
+                res,
+                "arrow_service_bytes_received_total{{svc_type=\"{}\"}} {}",
+                svc_type, counters.received
+            )?;
+        }
+
+        header(
+            &mut res,
+            "arrow_scan_duration_seconds",
+            "summary",
+            "Duration of network scans.",
+        )?;
+        writeln!(
+            res,
+            "arrow_scan_duration_seconds_sum {:.3}",
+            self.scan_duration_sum
+        )?;
+        writeln!(res, "arrow_scan_duration_seconds_count {}", self.scans)?;
+
+        header(
+            &mut res,
+            "arrow_last_scan_duration_seconds",
+            "gauge",
+            "Duration of the last network scan.",
+        )?;
+        writeln!(
+            res,
+            "arrow_last_scan_duration_seconds {:.3}",
+            self.last_scan_duration
+        )?;
+
+        header(
+            &mut res,
+            "arrow_last_scan_hosts",
+            "gauge",
+            "Number of hosts found by the last network scan.",
+        )?;
+        writeln!(res, "arrow_last_scan_hosts {}", self.last_scan_hosts)?;
+
+        header(
+            &mut res,
+            "arrow_last_scan_services",
+            "gauge",
+            "Number of services found by the last network scan.",
+        )?;
+        writeln!(res, "arrow_last_scan_services {}", self.last_scan_services)?;
+
+        header(
+            &mut res,
+            "arrow_config_save_failures_total",
+            "counter",
+            "Number of failed attempts to save the configuration file.",
+        )?;
+        writeln!(
+            res,
+            "arrow_config_save_failures_total {}",
+            self.config_save_failures
+        )?;
+
+        Ok(res)
+    }
+}
+
+/// Write HELP and TYPE lines of a given metric.
+fn header(out: &mut String, name: &str, metric_type: &str, help: &str) -> Result<(), fmt::Error> {
+    writeln!(out, "# HELP {} {}", name, help)?;
+    writeln!(out, "# TYPE {} {}", name, metric_type)
+}
+
+/// Shared metrics registry. The registry is cloneable and all its copies
+/// share the same counters.
+#[derive(Clone, Default)]
+pub struct Metrics {
+    data: Arc<Mutex<MetricsData>>,
+}
+
+impl Metrics {
+    /// Create a new metrics registry.
+    pub fn new() -> Metrics {
+        Metrics::default()
+    }
+
+    /// Record a reconnect to the Arrow Service.
+    pub fn reconnect(&self) {
+        self.data.lock().unwrap().reconnects += 1;
+    }
+
+    /// Record an ACK timeout.
+    pub fn ack_timeout(&self) {
+        self.data.lock().unwrap().ack_timeouts += 1;
+    }
+
+    /// Record a new session.
+    pub fn session_opened(&self) {
+        let mut data = self.data.lock().unwrap();
+
+        data.sessions_opened += 1;
+        data.active_sessions += 1;
+    }
+
+    /// Record a closed session.
+    pub fn session_closed(&self, error_code: u32) {
+        let mut data = self.data.lock().unwrap();
+
+        *data.sessions_closed.entry(error_code).or_insert(0) += 1;
+
+        data.active_sessions = data.active_sessions.saturating_sub(1);
+    }
+
+    /// Record a session error.
+    pub fn session_error(&self, error_code: u32) {
+        *self
+            .data
+            .lock()
+            .unwrap()
+            .session_errors
+            .entry(error_code)
+            .or_insert(0) += 1;
+    }
+
+    /// Record a given number of bytes sent to a service of a given type.
+    pub fn bytes_sent(&self, svc_type: ServiceType, count: usize) {
+        let mut data = self.data.lock().unwrap();
+
+        let counters = data.service_bytes.entry(svc_type.as_str()).or_default();
+
+        counters.sent += count as u64;
+    }
+
+    /// Record a given number of bytes received from a service of a given
+    /// type.
+    pub fn bytes_received(&self, svc_type: ServiceType, count: usize) {
+        let mut data = self.data.lock().unwrap();
+
+        let counters = data.service_bytes.entry(svc_type.as_str()).or_default();
+
+        counters.received += count as u64;
+    }
+
+    /// Record a completed network scan.
+    pub fn scan_completed(&self, duration: f64, hosts: usize, services: usize) {
+        let mut data = self.data.lock().unwrap();
+
+        data.scans += 1;
+        data.scan_duration_sum += duration;
+        data.last_scan_duration = duration;
+        data.last_scan_hosts = hosts;
+        data.last_scan_services = services;
+    }
+
+    /// Record a failed attempt to save the configuration file.
+    pub fn config_save_failed(&self) {
+        self.data.lock().unwrap().config_save_failures += 1;
+    }
+
+    /// Render all metrics in the Prometheus text format.
+    pub fn render(&self, conn_state: ConnectionState) -> String {
+        self.data
+            .lock()
+            .unwrap()
+            .render(conn_state)
+            .expect("unable to render metrics")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_metrics_rendering() {
+        let metrics = Metrics::new();
+
+        metrics.session_opened();
+        metrics.session_opened();
+        metrics.session_closed(0);
+        metrics.session_error(2);
+        metrics.bytes_sent(ServiceType::RTSP, 100);
+        metrics.bytes_received(ServiceType::RTSP, 1000);
+        metrics.scan_completed(2.5, 3, 4);
+
+        let output = metrics.render(ConnectionState::Connected);
+
+        let lines = output
+            .lines()
+            .filter(|line| !line.starts_with('#'))
+            .collect::<Vec<_>>();
+
+        assert!(lines.contains(&"arrow_connection_state{state=\"connected\"} 1"));
+        assert!(lines.contains(&"arrow_connection_state{state=\"disconnected\"} 0"));
+        assert!(lines.contains(&"arrow_active_sessions 1"));
+        assert!(lines.contains(&"arrow_sessions_opened_total 2"));
+        assert!(lines.contains(&"arrow_sessions_closed_total{code=\"0x00000000\"} 1"));
+        assert!(lines.contains(&"arrow_session_errors_total{code=\"0x00000002\"} 1"));
+        assert!(lines.contains(&"arrow_service_bytes_sent_total{svc_type=\"rtsp\"} 100"));
+        assert!(lines.contains(&"arrow_service_bytes_received_total{svc_type=\"rtsp\"} 1000"));
+        assert!(lines.contains(&"arrow_scan_duration_seconds_sum 2.500"));
+        assert!(lines.contains(&"arrow_last_scan_services 4"));
+    }
+}
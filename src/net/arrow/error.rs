@@ -34,6 +34,8 @@ pub enum ErrorKind {
     Unauthorized,
     /// An internal Arrow Server error.
     ArrowServerError,
+    /// The destination is not allowed by the local destination policy.
+    DestinationDenied,
     /// Unspecified error.
     Other,
 }
@@ -90,6 +92,14 @@ impl ArrowError {
         ArrowError::new(ErrorKind::ArrowServerError, val)
     }
 
+    /// Create a new destination denied error.
+    pub fn destination_denied<T>(val: T) -> ArrowError
+    where
+        ArrowError: From<T>,
+    {
+        ArrowError::new(ErrorKind::DestinationDenied, val)
+    }
+
     /// Create another error.
     pub fn other<T>(val: T) -> ArrowError
     where
@@ -17,6 +17,7 @@ mod proto;
 mod session;
 
 use std::collections::VecDeque;
+use std::net::{Ipv4Addr, SocketAddr};
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
 
@@ -27,6 +28,7 @@ use futures::task;
 use futures::future::Future;
 use futures::sink::Sink;
 use futures::stream::Stream;
+use futures::sync::mpsc;
 use futures::task::Task;
 use futures::{Async, AsyncSink, Poll, StartSend};
 
@@ -37,30 +39,52 @@ use tokio::net::TcpStream;
 use tokio::timer::{Interval, Timeout};
 
 use crate::cmd_handler::{Command, CommandChannel};
-use crate::context::ApplicationContext;
+use crate::context::{ApplicationContext, ConnectionState};
 use crate::futures_ex::StreamEx;
 use crate::net::arrow::proto::codec::{ArrowCodec, FromBytes};
 use crate::net::arrow::proto::msg::control::ControlMessageFactory;
 use crate::net::arrow::proto::msg::control::{
-    AckMessage, ControlMessage, ControlMessageType, HupMessage, RedirectMessage,
-    SimpleServiceTable, EC_INTERNAL_SERVER_ERROR, EC_NO_ERROR, EC_UNAUTHORIZED,
-    EC_UNSUPPORTED_PROTOCOL_VERSION, STATUS_FLAG_SCAN,
+    AckMessage, ControlMessage, ControlMessageType, GetLogsMessage, HupMessage, PingSweepMessage,
+    ProbeServiceMessage, ProbeTarget, RedirectMessage, SimpleServiceTable, TracerouteMessage,
+    EC_INTERNAL_SERVER_ERROR, EC_NO_ERROR, EC_UNAUTHORIZED, EC_UNSUPPORTED_PROTOCOL_VERSION,
+    STATUS_FLAG_SCAN,
 };
 use crate::net::arrow::proto::msg::ArrowMessage;
 use crate::net::arrow::session::SessionManager;
+use crate::net::proxy;
 use crate::net::raw::ether::MacAddr;
-use crate::svc_table::SharedServiceTableRef;
+use crate::scanner::netdiag::{DiagnosticStatus, PingSweepReport, TracerouteReport};
+use crate::scanner::{ProbeResult, ProbeStatus};
+use crate::svc_table::{ServiceTable, ServiceType, SharedServiceTableRef};
 use crate::utils::logger::{BoxLogger, Logger};
 
 pub use self::error::{ArrowError, ErrorKind};
 
+#[cfg(feature = "discovery")]
+use std::thread;
+
+#[cfg(feature = "discovery")]
+use crate::scanner::discovery;
+
+#[cfg(feature = "discovery")]
+use std::net::IpAddr;
+
+#[cfg(feature = "discovery")]
+use crate::scanner::netdiag;
+
+#[cfg(feature = "discovery")]
+use crate::svc_table::IpNetwork;
+
 use crate::net::utils::get_socket_address_async;
 
 const ACK_TIMEOUT: f64 = 20.0;
-const CONNECTION_TIMEOUT: u64 = 20;
 const PING_PERIOD: f64 = 60.0;
 const UPDATE_CHECK_PERIOD: f64 = 5.0;
 
+/// Maximum number of service probes running at the same time.
+#[cfg(feature = "discovery")]
+const MAX_RUNNING_PROBES: usize = 4;
+
 /// Arrow Protocol states.
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 enum ProtocolState {
@@ -98,11 +122,14 @@ struct ArrowClientContext {
     cmsg_factory: ControlMessageFactory,
     sessions: SessionManager,
     messages: VecDeque<ArrowMessage>,
+    async_responses_tx: mpsc::UnboundedSender<ControlMessage>,
+    async_responses_rx: mpsc::UnboundedReceiver<ControlMessage>,
     expected_acks: VecDeque<ExpectedAck>,
     state: ProtocolState,
     task: Option<Task>,
     redirect: Option<String>,
     closed: bool,
+    shutdown: bool,
     last_ping: f64,
     last_update_chck: f64,
     last_stable_ver: usize,
@@ -121,6 +148,8 @@ impl ArrowClientContext {
         let cmsg_factory = ControlMessageFactory::new();
         let session_manager = SessionManager::new(app_context.clone(), cmsg_factory.clone());
 
+        let (async_responses_tx, async_responses_rx) = mpsc::unbounded();
+
         let t = time::precise_time_s();
 
         let mut client = ArrowClientContext {
@@ -131,11 +160,14 @@ impl ArrowClientContext {
             cmsg_factory: cmsg_factory,
             sessions: session_manager,
             messages: VecDeque::new(),
+            async_responses_tx,
+            async_responses_rx,
             expected_acks: VecDeque::new(),
             state: ProtocolState::Handshake,
             task: None,
             redirect: None,
             closed: false,
+            shutdown: false,
             last_ping: t,
             last_update_chck: t,
             last_stable_ver: 0,
@@ -168,7 +200,11 @@ impl ArrowClientContext {
     fn time_event(&mut self) {
         let t = time::precise_time_s();
 
-        if self.state == ProtocolState::Established {
+        if !self.shutdown && self.app_context.is_shutting_down() {
+            self.shutdown();
+        }
+
+        if self.state == ProtocolState::Established && !self.shutdown {
             if (self.last_ping + PING_PERIOD) < t {
                 self.send_ping_message();
             }
@@ -186,6 +222,24 @@ impl ArrowClientContext {
         }
     }
 
+    /// Send HUP messages for all active sessions and close the connection
+    /// once all pending messages are sent.
+    fn shutdown(&mut self) {
+        log_info!(
+            self.logger,
+            "closing connection to the remote Arrow Service..."
+        );
+
+        self.sessions.shutdown();
+
+        self.shutdown = true;
+
+        // notify the task consuming Arrow Messages about the shutdown
+        if let Some(task) = self.task.take() {
+            task.notify();
+        }
+    }
+
     /// Check if the service table has been updated.
     fn check_for_updates(&mut self) {
         if self.last_stable_ver != self.svc_table.version() {
@@ -273,6 +327,10 @@ impl ArrowClientContext {
             ControlMessageType::REDIRECT => self.process_redirect_message(msg),
             ControlMessageType::GET_STATUS => self.process_get_status_message(msg),
             ControlMessageType::GET_SCAN_REPORT => self.process_get_scan_report_message(msg),
+            ControlMessageType::GET_LOGS => self.process_get_logs_message(msg),
+            ControlMessageType::PROBE_SERVICE => self.process_probe_service_message(msg),
+            ControlMessageType::PING_SWEEP => self.process_ping_sweep_message(msg),
+            ControlMessageType::TRACE_ROUTE => self.process_traceroute_message(msg),
             ControlMessageType::RESET_SVC_TABLE => self.process_command(Command::ResetServiceTable),
             ControlMessageType::SCAN_NETWORK => self.process_command(Command::ScanNetwork),
             ControlMessageType::UNKNOWN => Err(ArrowError::other(format!(
@@ -312,6 +370,9 @@ impl ArrowClientContext {
             // switch the protocol state into normal operation
             self.state = ProtocolState::Established;
 
+            self.app_context
+                .set_connection_state(ConnectionState::Connected);
+
             // report a fake redirect in case of the diagnostic mode
             if self.app_context.get_diagnostic_mode() {
                 self.redirect = Some(String::new());
@@ -442,6 +503,381 @@ impl ArrowClientContext {
         Ok(())
     }
 
+    /// Process a given GET_LOGS message.
+    fn process_get_logs_message(&mut self, msg: ControlMessage) -> Result<(), ArrowError> {
+        if self.state != ProtocolState::Established {
+            return Err(ArrowError::other(
+                "cannot handle GET_LOGS message in the Handshake state",
+            ));
+        }
+
+        let header = msg.header();
+
+        let get_logs = msg
+            .body::<GetLogsMessage>()
+            .expect("GET_LOGS message expected");
+
+        log_debug!(self.logger, "sending a LOGS message...");
+
+        let lines = self
+            .app_context
+            .get_log_buffer()
+            .get_lines(get_logs.min_severity);
+
+        let msg = self.cmsg_factory.logs(header.msg_id, lines);
+
+        self.send_control_message(msg);
+
+        Ok(())
+    }
+
+    /// Process a given PROBE_SERVICE message. The service is probed in
+    /// a separate thread and the PROBE_RESULT message is sent once the probe
+    /// is done. The service table is not modified.
+    fn process_probe_service_message(&mut self, msg: ControlMessage) -> Result<(), ArrowError> {
+        if self.state != ProtocolState::Established {
+            return Err(ArrowError::other(
+                "cannot handle PROBE_SERVICE message in the Handshake state",
+            ));
+        }
+
+        let header = msg.header();
+
+        let probe_service = msg
+            .body::<ProbeServiceMessage>()
+            .expect("PROBE_SERVICE message expected");
+
+        let target = match probe_service.target {
+            ProbeTarget::ServiceId(id) => self.svc_table.get(id).and_then(|svc| {
+                let path = svc.path().map(|path| path.to_string());
+
+                svc.address()
+                    .filter(|_| !svc.is_control())
+                    .map(|addr| (svc.service_type(), addr, path))
+            }),
+            ProbeTarget::Service(svc_type, addr, ref path) => Some((svc_type, addr, path.clone())),
+        };
+
+        let status = match &target {
+            Some((svc_type, addr, path)) => self
+                .check_probe_target(*svc_type, *addr, path.as_deref())
+                .err(),
+            None => Some(ProbeStatus::UnknownService),
+        };
+
+        if let Some(status) = status {
+            let result = ProbeResult::new(status);
+
+            let msg = self.cmsg_factory.probe_result(header.msg_id, result);
+
+            self.send_control_message(msg);
+        } else if let Some((svc_type, addr, path)) = target {
+            log_debug!(self.logger, "probing service {}...", addr);
+
+            self.probe_service(header.msg_id, svc_type, addr, path);
+        }
+
+        Ok(())
+    }
+
+    /// Check if a given probe target is allowed by the local destination
+    /// policy and (in case of services speaking HTTP) by the HTTP path
+    /// policy.
+    fn check_probe_target(
+        &mut self,
+        svc_type: ServiceType,
+        addr: SocketAddr,
+        path: Option<&str>,
+    ) -> Result<(), ProbeStatus> {
+        let policy = self.app_context.get_destination_policy();
+
+        if let Err(err) = policy.check(svc_type, addr) {
+            log_warn!(
+                self.logger,
+                "service probe {} denied by the destination policy: {}",
+                addr,
+                err
+            );
+
+            return Err(ProbeStatus::Denied);
+        }
+
+        let http_policy = match svc_type {
+            ServiceType::HTTP | ServiceType::MJPEG | ServiceType::LockedMJPEG => {
+                self.app_context.get_http_path_policy().service_policy(addr)
+            }
+            _ => None,
+        };
+
+        if let Some(http_policy) = http_policy {
+            // the probe uses the root path if there is no path
+            if let Err(err) = http_policy.check(path.unwrap_or("/")) {
+                log_warn!(
+                    self.logger,
+                    "service probe {} denied by the HTTP path policy: {}",
+                    addr,
+                    err
+                );
+
+                return Err(ProbeStatus::Denied);
+            }
+        }
+
+        Ok(())
+    }
+
+    #[cfg(feature = "discovery")]
+    /// Probe a given service in a separate thread.
+    fn probe_service(
+        &mut self,
+        request_id: u16,
+        svc_type: ServiceType,
+        addr: SocketAddr,
+        path: Option<String>,
+    ) {
+        if !self.app_context.start_service_probe(MAX_RUNNING_PROBES) {
+            log_warn!(
+                self.logger,
+                "service probe {} rejected, too many running probes",
+                addr
+            );
+
+            let result = ProbeResult::new(ProbeStatus::RateLimited);
+
+            let msg = self.cmsg_factory.probe_result(request_id, result);
+
+            self.send_control_message(msg);
+
+            return;
+        }
+
+        let guard = ServiceProbeGuard::new(self.app_context.clone());
+
+        let mut cmsg_factory = self.cmsg_factory.clone();
+
+        let responses = self.async_responses_tx.clone();
+
+        thread::spawn(move || {
+            let _guard = guard;
+
+            let result = discovery::probe_service(svc_type, addr, path.as_ref().map(|p| p as &str));
+
+            let msg = cmsg_factory.probe_result(request_id, result);
+
+            // the client might be already closed
+            responses.unbounded_send(msg).unwrap_or_default();
+        });
+    }
+
+    #[cfg(not(feature = "discovery"))]
+    /// Dummy service probe.
+    fn probe_service(&mut self, request_id: u16, _: ServiceType, _: SocketAddr, _: Option<String>) {
+        let result = ProbeResult::new(ProbeStatus::NotAvailable);
+
+        let msg = self.cmsg_factory.probe_result(request_id, result);
+
+        self.async_responses_tx
+            .unbounded_send(msg)
+            .unwrap_or_default();
+    }
+
+    /// Process a given PING_SWEEP message. The ping sweep runs in a separate
+    /// thread and the PING_SWEEP_REPORT message is sent once it is done.
+    fn process_ping_sweep_message(&mut self, msg: ControlMessage) -> Result<(), ArrowError> {
+        if self.state != ProtocolState::Established {
+            return Err(ArrowError::other(
+                "cannot handle PING_SWEEP message in the Handshake state",
+            ));
+        }
+
+        let header = msg.header();
+
+        let ping_sweep = msg
+            .body::<PingSweepMessage>()
+            .expect("PING_SWEEP message expected");
+
+        self.ping_sweep(header.msg_id, ping_sweep.network, ping_sweep.prefix_length);
+
+        Ok(())
+    }
+
+    #[cfg(feature = "discovery")]
+    /// Run a ping sweep of a given network in a separate thread.
+    fn ping_sweep(&mut self, request_id: u16, network: Ipv4Addr, prefix_length: u8) {
+        let policy = self.app_context.get_destination_policy();
+
+        // invalid networks are rejected by the ping sweep itself
+        let check = IpNetwork::new(IpAddr::V4(network), prefix_length)
+            .map(|network| policy.check_network(&network))
+            .unwrap_or(Ok(()));
+
+        if let Err(err) = check {
+            log_warn!(
+                self.logger,
+                "ping sweep of {}/{} denied by the destination policy: {}",
+                network,
+                prefix_length,
+                err
+            );
+
+            let report = PingSweepReport::new(DiagnosticStatus::Denied);
+
+            let msg = self.cmsg_factory.ping_sweep_report(request_id, report);
+
+            self.send_control_message(msg);
+
+            return;
+        }
+
+        if !self
+            .app_context
+            .start_network_diagnostic(netdiag::MIN_DIAGNOSTIC_INTERVAL)
+        {
+            log_debug!(self.logger, "ping sweep rejected (rate limit)");
+
+            let report = PingSweepReport::new(DiagnosticStatus::RateLimited);
+
+            let msg = self.cmsg_factory.ping_sweep_report(request_id, report);
+
+            self.send_control_message(msg);
+
+            return;
+        }
+
+        log_debug!(
+            self.logger,
+            "running ping sweep of {}/{}...",
+            network,
+            prefix_length
+        );
+
+        // the diagnostic is marked as finished even if the thread panics
+        let guard = NetworkDiagnosticGuard::new(self.app_context.clone());
+
+        let mut cmsg_factory = self.cmsg_factory.clone();
+
+        let responses = self.async_responses_tx.clone();
+
+        thread::spawn(move || {
+            let report = netdiag::ping_sweep(network, prefix_length);
+
+            drop(guard);
+
+            let msg = cmsg_factory.ping_sweep_report(request_id, report);
+
+            // the client might be already closed
+            responses.unbounded_send(msg).unwrap_or_default();
+        });
+    }
+
+    #[cfg(not(feature = "discovery"))]
+    /// Dummy ping sweep.
+    fn ping_sweep(&mut self, request_id: u16, _: Ipv4Addr, _: u8) {
+        let report = PingSweepReport::new(DiagnosticStatus::NotAvailable);
+
+        let msg = self.cmsg_factory.ping_sweep_report(request_id, report);
+
+        self.async_responses_tx
+            .unbounded_send(msg)
+            .unwrap_or_default();
+    }
+
+    /// Process a given TRACE_ROUTE message. The traceroute runs in a separate
+    /// thread and the TRACE_ROUTE_REPORT message is sent once it is done.
+    fn process_traceroute_message(&mut self, msg: ControlMessage) -> Result<(), ArrowError> {
+        if self.state != ProtocolState::Established {
+            return Err(ArrowError::other(
+                "cannot handle TRACE_ROUTE message in the Handshake state",
+            ));
+        }
+
+        let header = msg.header();
+
+        let traceroute = msg
+            .body::<TracerouteMessage>()
+            .expect("TRACE_ROUTE message expected");
+
+        self.traceroute(
+            header.msg_id,
+            traceroute.host,
+            traceroute.port,
+            traceroute.max_hops,
+        );
+
+        Ok(())
+    }
+
+    #[cfg(feature = "discovery")]
+    /// Run a TCP traceroute to a given host in a separate thread.
+    fn traceroute(&mut self, request_id: u16, host: Ipv4Addr, port: u16, max_hops: u8) {
+        let policy = self.app_context.get_destination_policy();
+
+        if let Err(err) = policy.check_address(SocketAddr::from((host, port))) {
+            log_warn!(
+                self.logger,
+                "traceroute to {}:{} denied by the destination policy: {}",
+                host,
+                port,
+                err
+            );
+
+            let report = TracerouteReport::new(DiagnosticStatus::Denied);
+
+            let msg = self.cmsg_factory.traceroute_report(request_id, report);
+
+            self.send_control_message(msg);
+
+            return;
+        }
+
+        if !self
+            .app_context
+            .start_network_diagnostic(netdiag::MIN_DIAGNOSTIC_INTERVAL)
+        {
+            log_debug!(self.logger, "traceroute rejected (rate limit)");
+
+            let report = TracerouteReport::new(DiagnosticStatus::RateLimited);
+
+            let msg = self.cmsg_factory.traceroute_report(request_id, report);
+
+            self.send_control_message(msg);
+
+            return;
+        }
+
+        log_debug!(self.logger, "running traceroute to {}:{}...", host, port);
+
+        // the diagnostic is marked as finished even if the thread panics
+        let guard = NetworkDiagnosticGuard::new(self.app_context.clone());
+
+        let mut cmsg_factory = self.cmsg_factory.clone();
+
+        let responses = self.async_responses_tx.clone();
+
+        thread::spawn(move || {
+            let report = netdiag::traceroute(host, port, max_hops);
+
+            drop(guard);
+
+            let msg = cmsg_factory.traceroute_report(request_id, report);
+
+            // the client might be already closed
+            responses.unbounded_send(msg).unwrap_or_default();
+        });
+    }
+
+    #[cfg(not(feature = "discovery"))]
+    /// Dummy traceroute.
+    fn traceroute(&mut self, request_id: u16, _: Ipv4Addr, _: u16, _: u8) {
+        let report = TracerouteReport::new(DiagnosticStatus::NotAvailable);
+
+        let msg = self.cmsg_factory.traceroute_report(request_id, report);
+
+        self.async_responses_tx
+            .unbounded_send(msg)
+            .unwrap_or_default();
+    }
+
     /// Send a given command using the underlaying command channel.
     fn process_command(&mut self, cmd: Command) -> Result<(), ArrowError> {
         if self.state != ProtocolState::Established {
@@ -475,8 +911,9 @@ impl Sink for ArrowClientContext {
     type SinkError = ArrowError;
 
     fn start_send(&mut self, msg: ArrowMessage) -> StartSend<ArrowMessage, ArrowError> {
-        // ignore the message if the client has been closed
-        if self.is_closed() {
+        // ignore the message if the client has been closed or if it is
+        // shutting down
+        if self.is_closed() || self.shutdown {
             return Ok(AsyncSink::Ready);
         }
 
@@ -513,6 +950,8 @@ impl Stream for ArrowClientContext {
 
     fn poll(&mut self) -> Poll<Option<ArrowMessage>, ArrowError> {
         if self.ack_timeout() {
+            self.app_context.get_metrics().ack_timeout();
+
             return Err(ArrowError::connection_error(
                 "Arrow Service connection timeout",
             ));
@@ -520,12 +959,17 @@ impl Stream for ArrowClientContext {
             return Ok(Async::Ready(None));
         } else if let Some(msg) = self.messages.pop_front() {
             return Ok(Async::Ready(Some(msg)));
+        } else if let Ok(Async::Ready(Some(msg))) = self.async_responses_rx.poll() {
+            return Ok(Async::Ready(Some(ArrowMessage::from(msg))));
         } else if let Async::Ready(msg) = self.sessions.poll()? {
             if msg.is_none() {
                 panic!("session manager returned end of stream")
             } else {
                 return Ok(Async::Ready(msg));
             }
+        } else if self.shutdown {
+            // all pending messages have been sent
+            return Ok(Async::Ready(None));
         }
 
         self.task = Some(task::current());
@@ -575,6 +1019,49 @@ impl ArrowClient {
     }
 }
 
+/// Guard marking a running network diagnostic as finished when it is
+/// dropped.
+#[cfg(feature = "discovery")]
+struct NetworkDiagnosticGuard {
+    app_context: ApplicationContext,
+}
+
+#[cfg(feature = "discovery")]
+impl NetworkDiagnosticGuard {
+    /// Create a new guard for a network diagnostic that has been started.
+    fn new(app_context: ApplicationContext) -> NetworkDiagnosticGuard {
+        NetworkDiagnosticGuard { app_context }
+    }
+}
+
+#[cfg(feature = "discovery")]
+impl Drop for NetworkDiagnosticGuard {
+    fn drop(&mut self) {
+        self.app_context.finish_network_diagnostic();
+    }
+}
+
+/// Guard marking a running service probe as finished when dropped.
+#[cfg(feature = "discovery")]
+struct ServiceProbeGuard {
+    app_context: ApplicationContext,
+}
+
+#[cfg(feature = "discovery")]
+impl ServiceProbeGuard {
+    /// Create a new guard for a service probe that has been started.
+    fn new(app_context: ApplicationContext) -> ServiceProbeGuard {
+        ServiceProbeGuard { app_context }
+    }
+}
+
+#[cfg(feature = "discovery")]
+impl Drop for ServiceProbeGuard {
+    fn drop(&mut self) {
+        self.app_context.finish_service_probe();
+    }
+}
+
 impl Drop for ArrowClient {
     fn drop(&mut self) {
         let mut context = self.context.lock().unwrap();
@@ -621,18 +1108,39 @@ pub fn connect(
     let addr1 = addr.clone();
     let addr2 = addr.clone();
 
+    let shutdown = app_context
+        .get_shutdown_signal()
+        .then(|_| Err(ArrowError::other("connection aborted due to shutdown")));
+
     let aclient = ArrowClient::new(app_context.clone(), cmd_channel);
 
-    let connection = get_socket_address_async(addr)
-        .map_err(move |_| {
-            ArrowError::connection_error(format!(
-                "failed to lookup Arrow Service {} address information",
-                addr1
-            ))
-        })
-        .and_then(|saddr| {
-            TcpStream::connect(&saddr).map_err(|err| ArrowError::connection_error(err))
-        })
+    let mut handshake_context = app_context.clone();
+
+    let connection_timeout = app_context.get_connection_timeout();
+
+    let socket: Box<dyn Future<Item = TcpStream, Error = ArrowError> + Send> =
+        if let Some(proxy) = app_context.get_proxy() {
+            let socket = proxy::connect(&proxy, &addr).map_err(move |err| {
+                ArrowError::connection_error(format!("proxy {} error: {}", proxy, err))
+            });
+
+            Box::new(socket)
+        } else {
+            let socket = get_socket_address_async(addr)
+                .map_err(move |_| {
+                    ArrowError::connection_error(format!(
+                        "failed to lookup Arrow Service {} address information",
+                        addr1
+                    ))
+                })
+                .and_then(|saddr| {
+                    TcpStream::connect(&saddr).map_err(|err| ArrowError::connection_error(err))
+                });
+
+            Box::new(socket)
+        };
+
+    let connection = socket
         .and_then(move |socket| {
             app_context
                 .get_tls_connector()
@@ -645,7 +1153,7 @@ pub fn connect(
                 .map_err(|err| ArrowError::connection_error(err))
         });
 
-    Timeout::new(connection, Duration::from_secs(CONNECTION_TIMEOUT))
+    Timeout::new(connection, connection_timeout)
         .map_err(move |err| {
             if err.is_elapsed() {
                 ArrowError::connection_error(format!(
@@ -661,7 +1169,12 @@ pub fn connect(
                 ArrowError::other("timer error")
             }
         })
-        .and_then(|stream| {
+        .select(shutdown)
+        .map(|(stream, _)| stream)
+        .map_err(|(err, _)| err)
+        .and_then(move |stream| {
+            handshake_context.set_connection_state(ConnectionState::Handshaking);
+
             let framed = ArrowCodec.framed(stream);
 
             let (sink, stream) = framed.split();
@@ -0,0 +1,159 @@
+// Copyright 2019 click2stream, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::mem;
+
+use bytes::BytesMut;
+
+use crate::utils;
+
+use crate::net::arrow::proto::codec::{Encode, FromBytes};
+use crate::net::arrow::proto::error::DecodeError;
+use crate::net::arrow::proto::msg::control::ControlMessageBody;
+use crate::net::arrow::proto::msg::MessageBody;
+use crate::utils::logger::Severity;
+
+/// GET_LOGS message. The message body is either empty (all buffered log
+/// lines are requested) or it contains a single byte with the minimum
+/// severity (0 - debug, 1 - info, 2 - warn, 3 - error).
+pub struct GetLogsMessage {
+    pub min_severity: Severity,
+}
+
+impl Encode for GetLogsMessage {
+    fn encode(&self, buf: &mut BytesMut) {
+        buf.extend_from_slice(&[self.min_severity as u8]);
+    }
+}
+
+impl MessageBody for GetLogsMessage {
+    fn len(&self) -> usize {
+        1
+    }
+}
+
+impl ControlMessageBody for GetLogsMessage {}
+
+impl FromBytes for GetLogsMessage {
+    fn from_bytes(bytes: &[u8]) -> Result<Option<GetLogsMessage>, DecodeError> {
+        let min_severity = match bytes {
+            [] => Severity::DEBUG,
+            [0] => Severity::DEBUG,
+            [1] => Severity::INFO,
+            [2] => Severity::WARN,
+            [3] => Severity::ERROR,
+            _ => {
+                return Err(DecodeError::from(
+                    "malformed Arrow Control Protocol GET_LOGS message",
+                ))
+            }
+        };
+
+        Ok(Some(GetLogsMessage { min_severity }))
+    }
+}
+
+#[repr(packed)]
+struct LogsMessageHeader {
+    request_id: u16,
+    line_count: u32,
+}
+
+impl Encode for LogsMessageHeader {
+    fn encode(&self, buf: &mut BytesMut) {
+        let be_header = LogsMessageHeader {
+            request_id: self.request_id.to_be(),
+            line_count: self.line_count.to_be(),
+        };
+
+        buf.extend_from_slice(utils::as_bytes(&be_header))
+    }
+}
+
+/// LOGS message. The header is followed by the log lines (UTF-8). Every
+/// line is prefixed with its length in bytes (a 32-bit unsigned integer in
+/// the network byte order), so that the lines may contain line feeds.
+pub struct LogsMessage {
+    request_id: u16,
+    lines: Vec<String>,
+}
+
+impl LogsMessage {
+    /// Create a new LOGS message for a given request ID and log lines.
+    pub fn new(request_id: u16, lines: Vec<String>) -> LogsMessage {
+        LogsMessage { request_id, lines }
+    }
+}
+
+impl Encode for LogsMessage {
+    fn encode(&self, buf: &mut BytesMut) {
+        let header = LogsMessageHeader {
+            request_id: self.request_id,
+            line_count: self.lines.len() as u32,
+        };
+
+        header.encode(buf);
+
+        for line in &self.lines {
+            buf.extend_from_slice(&(line.len() as u32).to_be_bytes());
+            buf.extend_from_slice(line.as_bytes());
+        }
+    }
+}
+
+impl MessageBody for LogsMessage {
+    fn len(&self) -> usize {
+        let lines = self
+            .lines
+            .iter()
+            .map(|line| mem::size_of::<u32>() + line.len())
+            .sum::<usize>();
+
+        mem::size_of::<LogsMessageHeader>() + lines
+    }
+}
+
+impl ControlMessageBody for LogsMessage {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_logs_message() {
+        let lines = vec![
+            "first line".to_string(),
+            "multi-line\nentry".to_string(),
+            String::new(),
+        ];
+
+        let msg = LogsMessage::new(7, lines);
+
+        let mut buf = BytesMut::new();
+
+        msg.encode(&mut buf);
+
+        assert_eq!(buf.len(), msg.len());
+
+        let mut expected = vec![0, 7, 0, 0, 0, 3];
+
+        expected.extend_from_slice(&[0, 0, 0, 10]);
+        expected.extend_from_slice(b"first line");
+        expected.extend_from_slice(&[0, 0, 0, 16]);
+        expected.extend_from_slice(b"multi-line\nentry");
+        expected.extend_from_slice(&[0, 0, 0, 0]);
+
+        assert_eq!(buf.as_ref(), expected.as_slice());
+    }
+}
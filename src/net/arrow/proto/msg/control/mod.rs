@@ -14,6 +14,9 @@
 
 mod ack;
 mod hup;
+mod logs;
+mod netdiag;
+mod probe;
 mod redirect;
 mod register;
 mod scan_report;
@@ -34,10 +37,14 @@ use crate::net::arrow::proto::codec::{Encode, FromBytes};
 use crate::net::arrow::proto::error::DecodeError;
 use crate::net::arrow::proto::msg::{ArrowMessageBody, MessageBody};
 use crate::net::raw::ether::MacAddr;
-use crate::scanner::ScanResult;
+use crate::scanner::netdiag::{PingSweepReport, TracerouteReport};
+use crate::scanner::{ProbeResult, ScanResult};
 use crate::svc_table::ServiceTable;
 use crate::utils::AsAny;
 
+use self::logs::LogsMessage;
+use self::netdiag::{PingSweepReportMessage, TracerouteReportMessage};
+use self::probe::ProbeResultMessage;
 use self::register::RegisterMessage;
 use self::scan_report::ScanReportMessage;
 use self::status::StatusMessage;
@@ -45,6 +52,9 @@ use self::update::UpdateMessage;
 
 pub use self::ack::AckMessage;
 pub use self::hup::HupMessage;
+pub use self::logs::GetLogsMessage;
+pub use self::netdiag::{PingSweepMessage, TracerouteMessage};
+pub use self::probe::{ProbeServiceMessage, ProbeTarget};
 pub use self::redirect::RedirectMessage;
 pub use self::svc_table::SimpleServiceTable;
 
@@ -56,6 +66,7 @@ pub const EC_NO_ERROR: u32 = 0x00000000;
 pub const EC_UNSUPPORTED_PROTOCOL_VERSION: u32 = 0x00000001;
 pub const EC_UNAUTHORIZED: u32 = 0x00000002;
 pub const EC_CONNECTION_ERROR: u32 = 0x00000003;
+pub const EC_DESTINATION_DENIED: u32 = 0x00000005;
 pub const EC_INTERNAL_SERVER_ERROR: u32 = 0xffffffff;
 
 // unused error codes
@@ -74,6 +85,14 @@ const CMSG_GET_STATUS: u16 = 0x0008;
 const CMSG_STATUS: u16 = 0x0009;
 const CMSG_GET_SCAN_REPORT: u16 = 0x000a;
 const CMSG_SCAN_REPORT: u16 = 0x000b;
+const CMSG_GET_LOGS: u16 = 0x000c;
+const CMSG_LOGS: u16 = 0x000d;
+const CMSG_PROBE_SERVICE: u16 = 0x000e;
+const CMSG_PROBE_RESULT: u16 = 0x000f;
+const CMSG_PING_SWEEP: u16 = 0x0010;
+const CMSG_TRACE_ROUTE: u16 = 0x0011;
+const CMSG_PING_SWEEP_REPORT: u16 = 0x0012;
+const CMSG_TRACE_ROUTE_REPORT: u16 = 0x0013;
 
 /// Arrow Control Protocol message types.
 #[allow(non_camel_case_types)]
@@ -92,6 +111,14 @@ pub enum ControlMessageType {
     UNKNOWN,
     GET_SCAN_REPORT,
     SCAN_REPORT,
+    GET_LOGS,
+    LOGS,
+    PROBE_SERVICE,
+    PROBE_RESULT,
+    PING_SWEEP,
+    TRACE_ROUTE,
+    PING_SWEEP_REPORT,
+    TRACE_ROUTE_REPORT,
 }
 
 impl ControlMessageType {
@@ -109,6 +136,14 @@ impl ControlMessageType {
             ControlMessageType::STATUS => CMSG_STATUS,
             ControlMessageType::GET_SCAN_REPORT => CMSG_GET_SCAN_REPORT,
             ControlMessageType::SCAN_REPORT => CMSG_SCAN_REPORT,
+            ControlMessageType::GET_LOGS => CMSG_GET_LOGS,
+            ControlMessageType::LOGS => CMSG_LOGS,
+            ControlMessageType::PROBE_SERVICE => CMSG_PROBE_SERVICE,
+            ControlMessageType::PROBE_RESULT => CMSG_PROBE_RESULT,
+            ControlMessageType::PING_SWEEP => CMSG_PING_SWEEP,
+            ControlMessageType::TRACE_ROUTE => CMSG_TRACE_ROUTE,
+            ControlMessageType::PING_SWEEP_REPORT => CMSG_PING_SWEEP_REPORT,
+            ControlMessageType::TRACE_ROUTE_REPORT => CMSG_TRACE_ROUTE_REPORT,
             ControlMessageType::UNKNOWN => {
                 panic!("UNKNOWN Control Protocol message type has no code")
             }
@@ -150,6 +185,14 @@ impl ControlMessageHeader {
             CMSG_STATUS => ControlMessageType::STATUS,
             CMSG_GET_SCAN_REPORT => ControlMessageType::GET_SCAN_REPORT,
             CMSG_SCAN_REPORT => ControlMessageType::SCAN_REPORT,
+            CMSG_GET_LOGS => ControlMessageType::GET_LOGS,
+            CMSG_LOGS => ControlMessageType::LOGS,
+            CMSG_PROBE_SERVICE => ControlMessageType::PROBE_SERVICE,
+            CMSG_PROBE_RESULT => ControlMessageType::PROBE_RESULT,
+            CMSG_PING_SWEEP => ControlMessageType::PING_SWEEP,
+            CMSG_TRACE_ROUTE => ControlMessageType::TRACE_ROUTE,
+            CMSG_PING_SWEEP_REPORT => ControlMessageType::PING_SWEEP_REPORT,
+            CMSG_TRACE_ROUTE_REPORT => ControlMessageType::TRACE_ROUTE_REPORT,
             _ => ControlMessageType::UNKNOWN,
         }
     }
@@ -254,6 +297,50 @@ impl ControlMessage {
         )
     }
 
+    /// Create a new LOGS Control Protocol message.
+    pub fn logs(msg_id: u16, request_id: u16, lines: Vec<String>) -> ControlMessage {
+        ControlMessage::new(
+            msg_id,
+            ControlMessageType::LOGS,
+            LogsMessage::new(request_id, lines),
+        )
+    }
+
+    /// Create a new PROBE_RESULT Control Protocol message.
+    pub fn probe_result(msg_id: u16, request_id: u16, result: ProbeResult) -> ControlMessage {
+        ControlMessage::new(
+            msg_id,
+            ControlMessageType::PROBE_RESULT,
+            ProbeResultMessage::new(request_id, result),
+        )
+    }
+
+    /// Create a new PING_SWEEP_REPORT Control Protocol message.
+    pub fn ping_sweep_report(
+        msg_id: u16,
+        request_id: u16,
+        report: PingSweepReport,
+    ) -> ControlMessage {
+        ControlMessage::new(
+            msg_id,
+            ControlMessageType::PING_SWEEP_REPORT,
+            PingSweepReportMessage::new(request_id, report),
+        )
+    }
+
+    /// Create a new TRACE_ROUTE_REPORT Control Protocol message.
+    pub fn traceroute_report(
+        msg_id: u16,
+        request_id: u16,
+        report: TracerouteReport,
+    ) -> ControlMessage {
+        ControlMessage::new(
+            msg_id,
+            ControlMessageType::TRACE_ROUTE_REPORT,
+            TracerouteReportMessage::new(request_id, report),
+        )
+    }
+
     /// Create a new PING Control Protocol message.
     pub fn ping(msg_id: u16) -> ControlMessage {
         ControlMessage::new(msg_id, ControlMessageType::PING, EmptyMessage)
@@ -319,6 +406,12 @@ impl ControlMessage {
             ControlMessageType::SCAN_NETWORK => ControlMessage::decode_empty_message(bytes),
             ControlMessageType::GET_STATUS => ControlMessage::decode_empty_message(bytes),
             ControlMessageType::GET_SCAN_REPORT => ControlMessage::decode_empty_message(bytes),
+            ControlMessageType::GET_LOGS => ControlMessage::decode_get_logs_message(bytes),
+            ControlMessageType::PROBE_SERVICE => {
+                ControlMessage::decode_probe_service_message(bytes)
+            }
+            ControlMessageType::PING_SWEEP => ControlMessage::decode_ping_sweep_message(bytes),
+            ControlMessageType::TRACE_ROUTE => ControlMessage::decode_traceroute_message(bytes),
             ControlMessageType::UNKNOWN => Err(DecodeError::from(
                 "unknown Arrow Control Protocol message type",
             )),
@@ -355,6 +448,44 @@ impl ControlMessage {
         }
     }
 
+    /// Decode a GET_LOGS message from given data.
+    fn decode_get_logs_message(bytes: &[u8]) -> Result<Box<dyn ControlMessageBody>, DecodeError> {
+        if let Some(msg) = GetLogsMessage::from_bytes(bytes)? {
+            Ok(Box::new(msg))
+        } else {
+            panic!("unable to decode an Arrow Control Protocol GET_LOGS message")
+        }
+    }
+
+    /// Decode a PROBE_SERVICE message from given data.
+    fn decode_probe_service_message(
+        bytes: &[u8],
+    ) -> Result<Box<dyn ControlMessageBody>, DecodeError> {
+        if let Some(msg) = ProbeServiceMessage::from_bytes(bytes)? {
+            Ok(Box::new(msg))
+        } else {
+            panic!("unable to decode an Arrow Control Protocol PROBE_SERVICE message")
+        }
+    }
+
+    /// Decode a PING_SWEEP message from given data.
+    fn decode_ping_sweep_message(bytes: &[u8]) -> Result<Box<dyn ControlMessageBody>, DecodeError> {
+        if let Some(msg) = PingSweepMessage::from_bytes(bytes)? {
+            Ok(Box::new(msg))
+        } else {
+            panic!("unable to decode an Arrow Control Protocol PING_SWEEP message")
+        }
+    }
+
+    /// Decode a TRACE_ROUTE message from given data.
+    fn decode_traceroute_message(bytes: &[u8]) -> Result<Box<dyn ControlMessageBody>, DecodeError> {
+        if let Some(msg) = TracerouteMessage::from_bytes(bytes)? {
+            Ok(Box::new(msg))
+        } else {
+            panic!("unable to decode an Arrow Control Protocol TRACE_ROUTE message")
+        }
+    }
+
     /// Decode an empty message from given data (i.e. just check there is no data).
     fn decode_empty_message(bytes: &[u8]) -> Result<Box<dyn ControlMessageBody>, DecodeError> {
         if bytes.len() == 0 {
@@ -460,6 +591,34 @@ impl ControlMessageFactory {
         ControlMessage::scan_report(self.next_id(), request_id, scan_result, svc_table)
     }
 
+    /// Create a new LOGS message with given log lines.
+    pub fn logs(&mut self, request_id: u16, lines: Vec<String>) -> ControlMessage {
+        ControlMessage::logs(self.next_id(), request_id, lines)
+    }
+
+    /// Create a new PROBE_RESULT message with a given probe result.
+    pub fn probe_result(&mut self, request_id: u16, result: ProbeResult) -> ControlMessage {
+        ControlMessage::probe_result(self.next_id(), request_id, result)
+    }
+
+    /// Create a new PING_SWEEP_REPORT message with a given report.
+    pub fn ping_sweep_report(
+        &mut self,
+        request_id: u16,
+        report: PingSweepReport,
+    ) -> ControlMessage {
+        ControlMessage::ping_sweep_report(self.next_id(), request_id, report)
+    }
+
+    /// Create a new TRACE_ROUTE_REPORT message with a given report.
+    pub fn traceroute_report(
+        &mut self,
+        request_id: u16,
+        report: TracerouteReport,
+    ) -> ControlMessage {
+        ControlMessage::traceroute_report(self.next_id(), request_id, report)
+    }
+
     /// Create a new PING message.
     pub fn ping(&mut self) -> ControlMessage {
         ControlMessage::ping(self.next_id())
@@ -0,0 +1,358 @@
+// Copyright 2019 click2stream, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::mem;
+
+use std::net::Ipv4Addr;
+
+use bytes::BytesMut;
+
+use crate::utils;
+
+use crate::net::arrow::proto::codec::{Encode, FromBytes};
+use crate::net::arrow::proto::error::DecodeError;
+use crate::net::arrow::proto::msg::control::ControlMessageBody;
+use crate::net::arrow::proto::msg::MessageBody;
+use crate::scanner::netdiag::{DiagnosticStatus, HopType, PingSweepReport, TracerouteReport};
+
+// network diagnostic status codes
+pub const DIAG_STATUS_OK: u8 = 0x00;
+pub const DIAG_STATUS_RATE_LIMITED: u8 = 0x01;
+pub const DIAG_STATUS_INVALID_TARGET: u8 = 0x02;
+pub const DIAG_STATUS_UNREACHABLE: u8 = 0x03;
+pub const DIAG_STATUS_ERROR: u8 = 0x04;
+pub const DIAG_STATUS_NOT_AVAILABLE: u8 = 0x05;
+pub const DIAG_STATUS_DENIED: u8 = 0x06;
+
+// traceroute hop types
+pub const HOP_TYPE_TIME_EXCEEDED: u8 = 0x00;
+pub const HOP_TYPE_PORT_OPEN: u8 = 0x01;
+pub const HOP_TYPE_PORT_CLOSED: u8 = 0x02;
+
+/// PING_SWEEP message. The message body contains an IPv4 network address
+/// followed by a single byte with the network prefix length.
+pub struct PingSweepMessage {
+    pub network: Ipv4Addr,
+    pub prefix_length: u8,
+}
+
+impl Encode for PingSweepMessage {
+    fn encode(&self, buf: &mut BytesMut) {
+        buf.extend_from_slice(&self.network.octets());
+        buf.extend_from_slice(&[self.prefix_length]);
+    }
+}
+
+impl MessageBody for PingSweepMessage {
+    fn len(&self) -> usize {
+        5
+    }
+}
+
+impl ControlMessageBody for PingSweepMessage {}
+
+impl FromBytes for PingSweepMessage {
+    fn from_bytes(bytes: &[u8]) -> Result<Option<PingSweepMessage>, DecodeError> {
+        match bytes {
+            &[a, b, c, d, prefix_length] if prefix_length <= 32 => {
+                let msg = PingSweepMessage {
+                    network: Ipv4Addr::new(a, b, c, d),
+                    prefix_length,
+                };
+
+                Ok(Some(msg))
+            }
+            _ => Err(DecodeError::from(
+                "malformed Arrow Control Protocol PING_SWEEP message",
+            )),
+        }
+    }
+}
+
+/// TRACE_ROUTE message. The message body contains an IPv4 host address,
+/// a TCP port (2 bytes) and the maximum number of hops (1 byte).
+pub struct TracerouteMessage {
+    pub host: Ipv4Addr,
+    pub port: u16,
+    pub max_hops: u8,
+}
+
+impl Encode for TracerouteMessage {
+    fn encode(&self, buf: &mut BytesMut) {
+        buf.extend_from_slice(&self.host.octets());
+        buf.extend_from_slice(&self.port.to_be_bytes());
+        buf.extend_from_slice(&[self.max_hops]);
+    }
+}
+
+impl MessageBody for TracerouteMessage {
+    fn len(&self) -> usize {
+        7
+    }
+}
+
+impl ControlMessageBody for TracerouteMessage {}
+
+impl FromBytes for TracerouteMessage {
+    fn from_bytes(bytes: &[u8]) -> Result<Option<TracerouteMessage>, DecodeError> {
+        match bytes {
+            &[a, b, c, d, p1, p2, max_hops] => {
+                let msg = TracerouteMessage {
+                    host: Ipv4Addr::new(a, b, c, d),
+                    port: u16::from_be_bytes([p1, p2]),
+                    max_hops,
+                };
+
+                Ok(Some(msg))
+            }
+            _ => Err(DecodeError::from(
+                "malformed Arrow Control Protocol TRACE_ROUTE message",
+            )),
+        }
+    }
+}
+
+/// PING_SWEEP_REPORT message header.
+#[repr(packed)]
+struct PingSweepReportMessageHeader {
+    request_id: u16,
+    status: u8,
+    host_count: u16,
+}
+
+impl Encode for PingSweepReportMessageHeader {
+    fn encode(&self, buf: &mut BytesMut) {
+        let be_header = PingSweepReportMessageHeader {
+            request_id: self.request_id.to_be(),
+            status: self.status,
+            host_count: self.host_count.to_be(),
+        };
+
+        buf.extend_from_slice(utils::as_bytes(&be_header))
+    }
+}
+
+/// PING_SWEEP_REPORT message. The header is followed by a given number of
+/// host records, each containing a MAC address (6 bytes) and an IPv4
+/// address (4 bytes).
+pub struct PingSweepReportMessage {
+    request_id: u16,
+    report: PingSweepReport,
+}
+
+impl PingSweepReportMessage {
+    /// Create a new PING_SWEEP_REPORT message for a given request ID and
+    /// report.
+    pub fn new(request_id: u16, mut report: PingSweepReport) -> PingSweepReportMessage {
+        report.hosts.truncate(u16::MAX as usize);
+
+        PingSweepReportMessage { request_id, report }
+    }
+}
+
+impl Encode for PingSweepReportMessage {
+    fn encode(&self, buf: &mut BytesMut) {
+        let header = PingSweepReportMessageHeader {
+            request_id: self.request_id,
+            status: status_code(self.report.status),
+            host_count: self.report.hosts.len() as u16,
+        };
+
+        header.encode(buf);
+
+        for (mac, ip) in &self.report.hosts {
+            buf.extend_from_slice(&mac.octets());
+            buf.extend_from_slice(&ip.octets());
+        }
+    }
+}
+
+impl MessageBody for PingSweepReportMessage {
+    fn len(&self) -> usize {
+        mem::size_of::<PingSweepReportMessageHeader>() + self.report.hosts.len() * 10
+    }
+}
+
+impl ControlMessageBody for PingSweepReportMessage {}
+
+/// TRACE_ROUTE_REPORT message header.
+#[repr(packed)]
+struct TracerouteReportMessageHeader {
+    request_id: u16,
+    status: u8,
+    hop_count: u8,
+}
+
+impl Encode for TracerouteReportMessageHeader {
+    fn encode(&self, buf: &mut BytesMut) {
+        let be_header = TracerouteReportMessageHeader {
+            request_id: self.request_id.to_be(),
+            status: self.status,
+            hop_count: self.hop_count,
+        };
+
+        buf.extend_from_slice(utils::as_bytes(&be_header))
+    }
+}
+
+/// TRACE_ROUTE_REPORT message. The header is followed by a given number of
+/// hop records, each containing the TTL (1 byte), the hop type (1 byte) and
+/// the IPv4 address of the responding host (4 bytes).
+pub struct TracerouteReportMessage {
+    request_id: u16,
+    report: TracerouteReport,
+}
+
+impl TracerouteReportMessage {
+    /// Create a new TRACE_ROUTE_REPORT message for a given request ID and
+    /// report.
+    pub fn new(request_id: u16, mut report: TracerouteReport) -> TracerouteReportMessage {
+        report.hops.truncate(u8::MAX as usize);
+
+        TracerouteReportMessage { request_id, report }
+    }
+}
+
+impl Encode for TracerouteReportMessage {
+    fn encode(&self, buf: &mut BytesMut) {
+        let header = TracerouteReportMessageHeader {
+            request_id: self.request_id,
+            status: status_code(self.report.status),
+            hop_count: self.report.hops.len() as u8,
+        };
+
+        header.encode(buf);
+
+        for hop in &self.report.hops {
+            let hop_type = match hop.hop_type {
+                HopType::TimeExceeded => HOP_TYPE_TIME_EXCEEDED,
+                HopType::PortOpen => HOP_TYPE_PORT_OPEN,
+                HopType::PortClosed => HOP_TYPE_PORT_CLOSED,
+            };
+
+            buf.extend_from_slice(&[hop.ttl, hop_type]);
+            buf.extend_from_slice(&hop.addr.octets());
+        }
+    }
+}
+
+impl MessageBody for TracerouteReportMessage {
+    fn len(&self) -> usize {
+        mem::size_of::<TracerouteReportMessageHeader>() + self.report.hops.len() * 6
+    }
+}
+
+impl ControlMessageBody for TracerouteReportMessage {}
+
+/// Get protocol code of a given network diagnostic status.
+fn status_code(status: DiagnosticStatus) -> u8 {
+    match status {
+        DiagnosticStatus::Ok => DIAG_STATUS_OK,
+        DiagnosticStatus::RateLimited => DIAG_STATUS_RATE_LIMITED,
+        DiagnosticStatus::InvalidTarget => DIAG_STATUS_INVALID_TARGET,
+        DiagnosticStatus::Unreachable => DIAG_STATUS_UNREACHABLE,
+        DiagnosticStatus::Error => DIAG_STATUS_ERROR,
+        DiagnosticStatus::NotAvailable => DIAG_STATUS_NOT_AVAILABLE,
+        DiagnosticStatus::Denied => DIAG_STATUS_DENIED,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::net::raw::ether::MacAddr;
+    use crate::scanner::netdiag::TraceHop;
+
+    #[test]
+    fn test_request_messages() {
+        let msg = PingSweepMessage::from_bytes(&[192, 168, 1, 0, 24])
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(msg.network, Ipv4Addr::new(192, 168, 1, 0));
+        assert_eq!(msg.prefix_length, 24);
+
+        assert!(PingSweepMessage::from_bytes(&[192, 168, 1, 0, 33]).is_err());
+        assert!(PingSweepMessage::from_bytes(&[192, 168, 1, 0]).is_err());
+
+        let msg = TracerouteMessage {
+            host: Ipv4Addr::new(10, 0, 0, 7),
+            port: 554,
+            max_hops: 8,
+        };
+
+        let mut buf = BytesMut::new();
+
+        msg.encode(&mut buf);
+
+        assert_eq!(buf.len(), msg.len());
+
+        let decoded = TracerouteMessage::from_bytes(&buf).unwrap().unwrap();
+
+        assert_eq!(decoded.host, msg.host);
+        assert_eq!(decoded.port, msg.port);
+        assert_eq!(decoded.max_hops, msg.max_hops);
+
+        assert!(TracerouteMessage::from_bytes(&buf[..6]).is_err());
+    }
+
+    #[test]
+    fn test_report_messages() {
+        let mut report = PingSweepReport::new(DiagnosticStatus::Ok);
+
+        report.hosts.push((
+            MacAddr::new(1, 2, 3, 4, 5, 6),
+            Ipv4Addr::new(192, 168, 1, 10),
+        ));
+
+        let msg = PingSweepReportMessage::new(3, report);
+
+        let mut buf = BytesMut::new();
+
+        msg.encode(&mut buf);
+
+        assert_eq!(buf.len(), msg.len());
+        assert_eq!(
+            buf.as_ref(),
+            &[0, 3, 0, 0, 1, 1, 2, 3, 4, 5, 6, 192, 168, 1, 10][..]
+        );
+
+        let mut report = TracerouteReport::new(DiagnosticStatus::Ok);
+
+        report.hops.push(TraceHop {
+            ttl: 1,
+            addr: Ipv4Addr::new(10, 0, 0, 1),
+            hop_type: HopType::TimeExceeded,
+        });
+
+        report.hops.push(TraceHop {
+            ttl: 2,
+            addr: Ipv4Addr::new(10, 0, 1, 7),
+            hop_type: HopType::PortClosed,
+        });
+
+        let msg = TracerouteReportMessage::new(4, report);
+
+        let mut buf = BytesMut::new();
+
+        msg.encode(&mut buf);
+
+        assert_eq!(buf.len(), msg.len());
+        assert_eq!(
+            buf.as_ref(),
+            &[0, 4, 0, 2, 1, 0, 10, 0, 0, 1, 2, 2, 10, 0, 1, 7][..]
+        );
+    }
+}
@@ -0,0 +1,327 @@
+// Copyright 2019 click2stream, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::mem;
+use std::str;
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use bytes::BytesMut;
+
+use crate::utils;
+
+use crate::net::arrow::proto::codec::{Encode, FromBytes};
+use crate::net::arrow::proto::error::DecodeError;
+use crate::net::arrow::proto::msg::control::ControlMessageBody;
+use crate::net::arrow::proto::msg::MessageBody;
+use crate::net::utils::IpAddrEx;
+use crate::scanner::{ProbeResult, ProbeStatus};
+use crate::svc_table::ServiceType;
+
+// probe status codes
+pub const PROBE_STATUS_SUPPORTED: u8 = 0x00;
+pub const PROBE_STATUS_LOCKED: u8 = 0x01;
+pub const PROBE_STATUS_UNSUPPORTED: u8 = 0x02;
+pub const PROBE_STATUS_NOT_FOUND: u8 = 0x03;
+pub const PROBE_STATUS_ERROR: u8 = 0x04;
+pub const PROBE_STATUS_UNREACHABLE: u8 = 0x05;
+pub const PROBE_STATUS_UNKNOWN_SERVICE: u8 = 0x06;
+pub const PROBE_STATUS_NOT_AVAILABLE: u8 = 0x07;
+pub const PROBE_STATUS_DENIED: u8 = 0x08;
+pub const PROBE_STATUS_RATE_LIMITED: u8 = 0x09;
+
+/// Service to be probed.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum ProbeTarget {
+    /// Service from the service table.
+    ServiceId(u16),
+    /// Service given by its type, address and path.
+    Service(ServiceType, SocketAddr, Option<String>),
+}
+
+/// PROBE_SERVICE message header.
+#[repr(packed)]
+struct ProbeServiceMessageHeader {
+    svc_id: u16,
+    svc_type: u16,
+    ip_version: u8,
+    ip_addr: [u8; 16],
+    port: u16,
+}
+
+/// PROBE_SERVICE message. The message body contains either only a (non-zero)
+/// service ID or the message header (with zero service ID) followed by
+/// a null-terminated service path.
+pub struct ProbeServiceMessage {
+    pub target: ProbeTarget,
+}
+
+impl Encode for ProbeServiceMessage {
+    fn encode(&self, buf: &mut BytesMut) {
+        match self.target {
+            ProbeTarget::ServiceId(id) => buf.extend_from_slice(&id.to_be_bytes()),
+            ProbeTarget::Service(svc_type, addr, ref path) => {
+                let ip_addr = addr.ip();
+
+                let header = ProbeServiceMessageHeader {
+                    svc_id: 0,
+                    svc_type: svc_type.code().to_be(),
+                    ip_version: ip_addr.version(),
+                    ip_addr: ip_addr.bytes(),
+                    port: addr.port().to_be(),
+                };
+
+                let path = path.as_ref().map(|p| p as &str).unwrap_or("");
+
+                buf.extend_from_slice(utils::as_bytes(&header));
+                buf.extend_from_slice(path.as_bytes());
+                buf.extend_from_slice(&[0]);
+            }
+        }
+    }
+}
+
+impl MessageBody for ProbeServiceMessage {
+    fn len(&self) -> usize {
+        match self.target {
+            ProbeTarget::ServiceId(_) => mem::size_of::<u16>(),
+            ProbeTarget::Service(_, _, ref path) => {
+                let plen = path.as_ref().map(|p| p.len()).unwrap_or(0) + 1;
+
+                mem::size_of::<ProbeServiceMessageHeader>() + plen
+            }
+        }
+    }
+}
+
+impl ControlMessageBody for ProbeServiceMessage {}
+
+impl FromBytes for ProbeServiceMessage {
+    fn from_bytes(bytes: &[u8]) -> Result<Option<ProbeServiceMessage>, DecodeError> {
+        let malformed =
+            || DecodeError::from("malformed Arrow Control Protocol PROBE_SERVICE message");
+
+        let hsize = mem::size_of::<ProbeServiceMessageHeader>();
+
+        if bytes.len() == mem::size_of::<u16>() {
+            let svc_id = u16::from_be_bytes([bytes[0], bytes[1]]);
+
+            if svc_id == 0 {
+                return Err(malformed());
+            }
+
+            let msg = ProbeServiceMessage {
+                target: ProbeTarget::ServiceId(svc_id),
+            };
+
+            return Ok(Some(msg));
+        } else if bytes.len() <= hsize || bytes[bytes.len() - 1] != 0 {
+            return Err(malformed());
+        }
+
+        let ptr = bytes.as_ptr() as *const ProbeServiceMessageHeader;
+        let header = unsafe { &*ptr };
+
+        let svc_id = u16::from_be(header.svc_id);
+
+        if svc_id != 0 {
+            let msg = ProbeServiceMessage {
+                target: ProbeTarget::ServiceId(svc_id),
+            };
+
+            return Ok(Some(msg));
+        }
+
+        let svc_type = ServiceType::from_code(u16::from_be(header.svc_type))
+            .filter(|svc_type| *svc_type != ServiceType::ControlProtocol)
+            .ok_or_else(malformed)?;
+
+        let ip_addr = header.ip_addr;
+
+        let ip_addr = match header.ip_version {
+            4 => IpAddr::V4(Ipv4Addr::new(
+                ip_addr[0], ip_addr[1], ip_addr[2], ip_addr[3],
+            )),
+            6 => IpAddr::V6(Ipv6Addr::from(ip_addr)),
+            _ => return Err(malformed()),
+        };
+
+        let addr = SocketAddr::new(ip_addr, u16::from_be(header.port));
+
+        let path = str::from_utf8(&bytes[hsize..bytes.len() - 1]).map_err(|_| malformed())?;
+
+        let path = if path.is_empty() {
+            None
+        } else {
+            Some(path.to_string())
+        };
+
+        let msg = ProbeServiceMessage {
+            target: ProbeTarget::Service(svc_type, addr, path),
+        };
+
+        Ok(Some(msg))
+    }
+}
+
+/// PROBE_RESULT message header.
+#[repr(packed)]
+struct ProbeResultMessageHeader {
+    request_id: u16,
+    status: u8,
+    status_code: u16,
+    latency: u32,
+}
+
+impl Encode for ProbeResultMessageHeader {
+    fn encode(&self, buf: &mut BytesMut) {
+        let be_header = ProbeResultMessageHeader {
+            request_id: self.request_id.to_be(),
+            status: self.status,
+            status_code: self.status_code.to_be(),
+            latency: self.latency.to_be(),
+        };
+
+        buf.extend_from_slice(utils::as_bytes(&be_header))
+    }
+}
+
+/// PROBE_RESULT message. The header (containing the latency in
+/// milliseconds) is followed by a null-terminated value of the Server header
+/// and a null-terminated comma-separated list of SDP codecs.
+pub struct ProbeResultMessage {
+    request_id: u16,
+    result: ProbeResult,
+}
+
+impl ProbeResultMessage {
+    /// Create a new PROBE_RESULT message for a given request ID and probe
+    /// result.
+    pub fn new(request_id: u16, result: ProbeResult) -> ProbeResultMessage {
+        ProbeResultMessage { request_id, result }
+    }
+
+    /// Get the comma-separated list of codecs.
+    fn codecs(&self) -> String {
+        self.result.codecs.join(",")
+    }
+}
+
+impl Encode for ProbeResultMessage {
+    fn encode(&self, buf: &mut BytesMut) {
+        let latency = self.result.latency;
+        let latency = latency.as_secs() * 1000 + u64::from(latency.subsec_millis());
+
+        let header = ProbeResultMessageHeader {
+            request_id: self.request_id,
+            status: status_code(self.result.status),
+            status_code: self.result.status_code,
+            latency: latency.min(u64::from(u32::MAX)) as u32,
+        };
+
+        header.encode(buf);
+
+        let server = self.result.server.as_ref().map(|s| s as &str).unwrap_or("");
+
+        buf.extend_from_slice(server.as_bytes());
+        buf.extend_from_slice(&[0]);
+        buf.extend_from_slice(self.codecs().as_bytes());
+        buf.extend_from_slice(&[0]);
+    }
+}
+
+impl MessageBody for ProbeResultMessage {
+    fn len(&self) -> usize {
+        let server = self.result.server.as_ref().map(|s| s.len()).unwrap_or(0);
+
+        mem::size_of::<ProbeResultMessageHeader>() + server + 1 + self.codecs().len() + 1
+    }
+}
+
+impl ControlMessageBody for ProbeResultMessage {}
+
+/// Get protocol code of a given probe status.
+fn status_code(status: ProbeStatus) -> u8 {
+    match status {
+        ProbeStatus::Supported => PROBE_STATUS_SUPPORTED,
+        ProbeStatus::Locked => PROBE_STATUS_LOCKED,
+        ProbeStatus::Unsupported => PROBE_STATUS_UNSUPPORTED,
+        ProbeStatus::NotFound => PROBE_STATUS_NOT_FOUND,
+        ProbeStatus::Error => PROBE_STATUS_ERROR,
+        ProbeStatus::Unreachable => PROBE_STATUS_UNREACHABLE,
+        ProbeStatus::UnknownService => PROBE_STATUS_UNKNOWN_SERVICE,
+        ProbeStatus::NotAvailable => PROBE_STATUS_NOT_AVAILABLE,
+        ProbeStatus::Denied => PROBE_STATUS_DENIED,
+        ProbeStatus::RateLimited => PROBE_STATUS_RATE_LIMITED,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::time::Duration;
+
+    #[test]
+    fn test_probe_service_message() {
+        let targets = vec![
+            ProbeTarget::ServiceId(12),
+            ProbeTarget::Service(
+                ServiceType::RTSP,
+                "192.168.1.10:554".parse().unwrap(),
+                Some("/stream1".to_string()),
+            ),
+            ProbeTarget::Service(ServiceType::HTTP, "[fe80::1]:80".parse().unwrap(), None),
+        ];
+
+        for target in targets {
+            let msg = ProbeServiceMessage {
+                target: target.clone(),
+            };
+
+            let mut buf = BytesMut::new();
+
+            msg.encode(&mut buf);
+
+            assert_eq!(buf.len(), msg.len());
+
+            let decoded = ProbeServiceMessage::from_bytes(&buf).unwrap().unwrap();
+
+            assert_eq!(decoded.target, target);
+        }
+
+        assert!(ProbeServiceMessage::from_bytes(&[0, 0]).is_err());
+        assert!(ProbeServiceMessage::from_bytes(&[0, 0, 0]).is_err());
+    }
+
+    #[test]
+    fn test_probe_result_message() {
+        let mut result = ProbeResult::new(ProbeStatus::Locked);
+
+        result.status_code = 401;
+        result.server = Some("GStreamer RTSP server".to_string());
+        result.codecs = vec!["H264".to_string(), "PCMA".to_string()];
+        result.latency = Duration::from_millis(1234);
+
+        let msg = ProbeResultMessage::new(7, result);
+
+        let mut buf = BytesMut::new();
+
+        msg.encode(&mut buf);
+
+        assert_eq!(buf.len(), msg.len());
+        assert_eq!(&buf[..9], &[0, 7, 1, 0x01, 0x91, 0, 0, 0x04, 0xd2]);
+        assert_eq!(&buf[9..], &b"GStreamer RTSP server\0H264,PCMA\0"[..]);
+    }
+}
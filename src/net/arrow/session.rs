@@ -14,6 +14,7 @@
 
 use std::collections::{HashMap, VecDeque};
 use std::error::Error;
+use std::net::SocketAddr;
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
@@ -34,13 +35,15 @@ use tokio::timer::Timeout;
 
 use crate::context::ApplicationContext;
 use crate::futures_ex::StreamEx;
-use crate::net::arrow::error::{ArrowError, ConnectionError};
+use crate::metrics::Metrics;
+use crate::net::arrow::error::{ArrowError, ConnectionError, ErrorKind};
 use crate::net::arrow::proto::codec::RawCodec;
 use crate::net::arrow::proto::msg::control::{
-    ControlMessageFactory, EC_CONNECTION_ERROR, EC_NO_ERROR,
+    ControlMessageFactory, EC_CONNECTION_ERROR, EC_DESTINATION_DENIED, EC_NO_ERROR,
 };
 use crate::net::arrow::proto::msg::ArrowMessage;
-use crate::svc_table::{BoxServiceTable, ServiceTable};
+use crate::net::http::filter::{Rejection, RequestFilter};
+use crate::svc_table::{BoxServiceTable, ServiceTable, ServiceType};
 use crate::utils::logger::{BoxLogger, Logger};
 
 const INPUT_BUFFER_LIMIT: usize = 32768;
@@ -59,11 +62,22 @@ struct SessionContext {
     output_ready: Option<Task>,
     closed: bool,
     error: Option<ConnectionError>,
+    svc_type: ServiceType,
+    metrics: Metrics,
+    filter: Option<RequestFilter>,
 }
 
 impl SessionContext {
     /// Create a new session context for a given service ID and session ID.
-    fn new(service_id: u16, session_id: u32) -> SessionContext {
+    /// All outgoing data will be passed through a given HTTP request filter
+    /// (if any).
+    fn new(
+        service_id: u16,
+        session_id: u32,
+        svc_type: ServiceType,
+        metrics: Metrics,
+        filter: Option<RequestFilter>,
+    ) -> SessionContext {
         SessionContext {
             service_id: service_id,
             session_id: session_id,
@@ -74,23 +88,38 @@ impl SessionContext {
             output_ready: None,
             closed: false,
             error: None,
+            svc_type,
+            metrics,
+            filter,
         }
     }
 
-    /// Extend the output buffer with data from a given Arrow Message.
-    fn push_output_message(&mut self, msg: ArrowMessage) {
+    /// Extend the output buffer with data from a given Arrow Message. An
+    /// error is returned if the data contain an HTTP request rejected by the
+    /// request filter. In such case, the local response is put into the
+    /// input buffer once all preceding responses are received and the
+    /// context gets closed.
+    fn push_output_message(&mut self, msg: ArrowMessage) -> Result<(), Rejection> {
         // ignore all incoming messages after the connection gets closed
         if self.closed {
-            return;
+            return Ok(());
         }
 
         let data = msg.payload();
 
+        let mut res = Ok(());
+
         if (self.output.len() + data.len()) > OUTPUT_BUFFER_LIMIT {
             // we cannot backpressure here, so we'll set an error state
             self.set_error(ConnectionError::from("output buffer limit exceeded"));
         } else {
-            self.output.extend_from_slice(data);
+            self.metrics.bytes_sent(self.svc_type, data.len());
+
+            if let Some(filter) = self.filter.as_mut() {
+                res = filter.filter_request(data, &mut self.output);
+            } else {
+                self.output.extend_from_slice(data);
+            }
 
             // we MUST notify any possible task consuming the output buffer that
             // there is some data available again
@@ -99,6 +128,26 @@ impl SessionContext {
                     task.notify();
                 }
             }
+
+            self.send_local_response();
+        }
+
+        res
+    }
+
+    /// Put the local response to a rejected HTTP request (if any and if it
+    /// can be sent already) into the input buffer and close the context.
+    fn send_local_response(&mut self) {
+        let response = self
+            .filter
+            .as_mut()
+            .and_then(|filter| filter.take_local_response());
+
+        if let Some(response) = response {
+            self.input.extend_from_slice(&response);
+
+            // this will also notify all tasks
+            self.close();
         }
     }
 
@@ -118,6 +167,8 @@ impl SessionContext {
         }
 
         if data.len() > 0 {
+            self.metrics.bytes_received(self.svc_type, data.len());
+
             let message = ArrowMessage::new(self.service_id, self.session_id, data);
 
             Ok(Async::Ready(Some(message)))
@@ -152,7 +203,13 @@ impl SessionContext {
             take = INPUT_BUFFER_LIMIT - self.input.len();
         }
 
-        self.input.extend_from_slice(&msg.split_to(take));
+        let data = msg.split_to(take);
+
+        if let Some(filter) = self.filter.as_mut() {
+            filter.filter_response(&data, &mut self.input);
+        } else {
+            self.input.extend_from_slice(&data);
+        }
 
         // we MUST notify any possible task consuming the input buffer that
         // there is some data available again
@@ -162,6 +219,13 @@ impl SessionContext {
             }
         }
 
+        self.send_local_response();
+
+        // the rest of the data can be dropped if the context has been closed
+        if self.closed {
+            return Ok(AsyncSink::Ready);
+        }
+
         if msg.len() > 0 {
             // save the current task and wait until there is some space in
             // the input buffer again
@@ -244,16 +308,23 @@ struct Session {
 
 impl Session {
     /// Create a new session for a given service ID and session ID.
-    fn new(service_id: u16, session_id: u32) -> Session {
-        let context = SessionContext::new(service_id, session_id);
+    fn new(
+        service_id: u16,
+        session_id: u32,
+        svc_type: ServiceType,
+        metrics: Metrics,
+        filter: Option<RequestFilter>,
+    ) -> Session {
+        let context = SessionContext::new(service_id, session_id, svc_type, metrics, filter);
 
         Session {
             context: Arc::new(Mutex::new(context)),
         }
     }
 
-    /// Push a given Arrow Message into the output buffer.
-    fn push(&mut self, msg: ArrowMessage) {
+    /// Push a given Arrow Message into the output buffer. An error is
+    /// returned if the message contains a rejected HTTP request.
+    fn push(&mut self, msg: ArrowMessage) -> Result<(), Rejection> {
         self.context.lock().unwrap().push_output_message(msg)
     }
 
@@ -340,12 +411,15 @@ impl SessionErrorHandler {
 /// Arrow session manager.
 pub struct SessionManager {
     logger: BoxLogger,
+    metrics: Metrics,
     svc_table: BoxServiceTable,
+    app_context: ApplicationContext,
     cmsg_factory: ControlMessageFactory,
     cmsg_queue: VecDeque<ArrowMessage>,
     sessions: HashMap<u32, Session>,
     poll_order: VecDeque<u32>,
     new_session: Option<Task>,
+    shutdown: bool,
 }
 
 impl SessionManager {
@@ -358,12 +432,15 @@ impl SessionManager {
 
         SessionManager {
             logger: app_context.get_logger(),
+            metrics: app_context.get_metrics(),
             svc_table: svc_table.boxed(),
+            app_context,
             cmsg_factory: cmsg_factory,
             cmsg_queue: VecDeque::new(),
             sessions: HashMap::new(),
             poll_order: VecDeque::new(),
             new_session: None,
+            shutdown: false,
         }
     }
 
@@ -380,40 +457,81 @@ impl SessionManager {
         let session = self.take_session(header.service, header.session);
 
         if let Ok(mut session) = session {
-            session.push(msg);
+            if let Err(rejection) = session.push(msg) {
+                log_warn!(
+                    self.logger,
+                    "HTTP request rejected: {}",
+                    rejection.reason();
+                    service_id = format_args!("{:04x}", { header.service }),
+                    session_id = format_args!("{:08x}", { header.session })
+                );
+            }
 
             self.sessions.insert(header.session, session);
         } else if let Err(err) = session {
             log_warn!(
                 self.logger,
                 "unable to connect to a remote service: {}",
-                err.description()
+                err.description();
+                service_id = format_args!("{:04x}", { header.service }),
+                session_id = format_args!("{:08x}", { header.session })
             );
 
-            let msg = self.create_hup_message(header.session, EC_CONNECTION_ERROR);
+            let error_code = match err.kind() {
+                ErrorKind::DestinationDenied => EC_DESTINATION_DENIED,
+                _ => EC_CONNECTION_ERROR,
+            };
+
+            let msg = self.create_hup_message(header.session, error_code);
 
             self.cmsg_queue.push_back(msg);
         }
     }
 
     /// Close a given session.
-    pub fn close(&mut self, session_id: u32, _: u32) {
+    pub fn close(&mut self, session_id: u32, error_code: u32) {
         if let Some(mut session) = self.sessions.remove(&session_id) {
             log_info!(
                 self.logger,
-                "closing service connection; session ID: {:08x}",
-                session_id
+                "closing service connection";
+                session_id = format_args!("{:08x}", session_id)
             );
 
             session.close();
+
+            self.metrics.session_closed(error_code);
+        }
+    }
+
+    /// Close all active sessions, send HUP messages for them and stop
+    /// accepting new sessions.
+    pub fn shutdown(&mut self) {
+        self.shutdown = true;
+
+        let session_ids = self.sessions.keys().cloned().collect::<Vec<_>>();
+
+        for session_id in session_ids {
+            self.close(session_id, EC_NO_ERROR);
+
+            let msg = self.create_hup_message(session_id, EC_NO_ERROR);
+
+            self.cmsg_queue.push_back(msg);
         }
     }
 
     /// Take a given session object.
     fn take_session(&mut self, service_id: u16, session_id: u32) -> Result<Session, ArrowError> {
         if !self.sessions.contains_key(&session_id) {
+            if self.shutdown {
+                return Err(ArrowError::other(
+                    "no new sessions are accepted during shutdown",
+                ));
+            }
+
             let session = self.connect(service_id, session_id)?;
 
+            self.metrics.session_opened();
+
             self.sessions.insert(session_id, session);
 
             self.poll_order.push_back(session_id);
@@ -445,15 +563,44 @@ impl SessionManager {
             service_id
         )))?;
 
+        // the policies are taken from the application context every time, so
+        // that reloaded policies apply to new sessions
+        let policy = self.app_context.get_destination_policy();
+
+        policy.check(svc.service_type(), addr).map_err(|err| {
+            ArrowError::destination_denied(format!(
+                "{} denied by the destination policy: {}",
+                addr, err
+            ))
+        })?;
+
+        let svc_type = svc.service_type();
+
+        let filter = self.create_request_filter(svc_type, addr)?;
+
         log_info!(
             self.logger,
-            "connecting to remote service: {}, service ID: {:04x}, session ID: {:08x}",
-            addr,
-            service_id,
-            session_id
+            "connecting to remote service: {}",
+            addr;
+            service_id = format_args!("{:04x}", service_id),
+            session_id = format_args!("{:08x}", session_id)
         );
 
-        let session = Session::new(service_id, session_id);
+        if filter.is_some() {
+            log_debug!(
+                self.logger,
+                "HTTP request filtering enabled";
+                session_id = format_args!("{:08x}", session_id)
+            );
+        }
+
+        let session = Session::new(
+            service_id,
+            session_id,
+            svc_type,
+            self.metrics.clone(),
+            filter,
+        );
         let transport = session.transport();
         let mut err_handler = session.error_handler();
 
@@ -493,15 +640,59 @@ impl SessionManager {
         Ok(session)
     }
 
+    /// Create HTTP request filter for a session of a given service type
+    /// connecting to a given address. Services speaking HTTP are filtered if
+    /// there are any HTTP path rules for the address. Raw TCP sessions are
+    /// denied in such case because they could bypass the rules. RTSP
+    /// sessions are denied only if there are rules given explicitly for the
+    /// address (i.e. the address is known to be an HTTP server).
+    fn create_request_filter(
+        &self,
+        svc_type: ServiceType,
+        addr: SocketAddr,
+    ) -> Result<Option<RequestFilter>, ArrowError> {
+        let http_policy = self.app_context.get_http_path_policy();
+
+        let policy = http_policy.service_policy(addr);
+
+        let denied = || {
+            ArrowError::destination_denied(format!(
+                "{} denied by the HTTP path policy: {} sessions cannot be filtered",
+                addr,
+                svc_type.as_str()
+            ))
+        };
+
+        match svc_type {
+            ServiceType::HTTP | ServiceType::MJPEG | ServiceType::LockedMJPEG => {
+                Ok(policy.map(RequestFilter::new))
+            }
+            ServiceType::TCP if policy.is_some() => Err(denied()),
+            ServiceType::RTSP
+            | ServiceType::LockedRTSP
+            | ServiceType::UnknownRTSP
+            | ServiceType::UnsupportedRTSP
+                if http_policy.has_address_rules(addr) =>
+            {
+                Err(denied())
+            }
+            _ => Ok(None),
+        }
+    }
+
     /// Create HUP message for a given session.
     fn create_hup_message(&mut self, session_id: u32, error_code: u32) -> ArrowMessage {
         log_debug!(
             self.logger,
-            "sending a HUP message (session ID: {:08x}, error_code: {:08x})...",
-            session_id,
-            error_code
+            "sending a HUP message...";
+            session_id = format_args!("{:08x}", session_id),
+            error_code = format_args!("{:08x}", error_code)
         );
 
+        if error_code != EC_NO_ERROR {
+            self.metrics.session_error(error_code);
+        }
+
         ArrowMessage::from(self.cmsg_factory.hup(session_id, error_code))
     }
 }
@@ -511,11 +702,13 @@ impl Drop for SessionManager {
         for (session_id, mut session) in self.sessions.drain() {
             log_info!(
                 self.logger,
-                "closing service connection; session ID: {:08x}",
-                session_id
+                "closing service connection";
+                session_id = format_args!("{:08x}", session_id)
             );
 
             session.close();
+
+            self.metrics.session_closed(EC_CONNECTION_ERROR);
         }
     }
 }
@@ -542,10 +735,12 @@ impl Stream for SessionManager {
                         Ok(Async::Ready(None)) => {
                             log_info!(
                                 self.logger,
-                                "service connection closed; session ID: {:08x}",
-                                session_id
+                                "service connection closed";
+                                session_id = format_args!("{:08x}", session_id)
                             );
 
+                            self.metrics.session_closed(EC_NO_ERROR);
+
                             let msg = self.create_hup_message(session_id, EC_NO_ERROR);
 
                             return Ok(Async::Ready(Some(msg)));
@@ -559,11 +754,13 @@ impl Stream for SessionManager {
                         Err(err) => {
                             log_warn!(
                                 self.logger,
-                                "service connection error; session ID: {:08x}: {}",
-                                session_id,
-                                err.description()
+                                "service connection error: {}",
+                                err.description();
+                                session_id = format_args!("{:08x}", session_id)
                             );
 
+                            self.metrics.session_closed(EC_CONNECTION_ERROR);
+
                             let msg = self.create_hup_message(session_id, EC_CONNECTION_ERROR);
 
                             return Ok(Async::Ready(Some(msg)));
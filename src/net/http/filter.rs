@@ -0,0 +1,507 @@
+// Copyright 2019 click2stream, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! HTTP request filter for proxied HTTP services.
+
+use std::collections::VecDeque;
+
+use bytes::{Bytes, BytesMut};
+
+use tokio::codec::Decoder;
+
+use crate::net::http::generic::{
+    ChunkedBodyDecoder, FixedSizeBodyDecoder, MessageBodyDecoder, RequestHeader,
+    RequestHeaderDecoder, ResponseBuilder, ResponseHeader, ResponseHeaderDecoder,
+    SimpleBodyDecoder,
+};
+use crate::svc_table::ServicePathPolicy;
+
+/// Maximum length of a request header line.
+const MAX_LINE_LENGTH: usize = 8192;
+
+/// Maximum number of request header lines.
+const MAX_HEADER_LINES: usize = 128;
+
+/// Request rejected by the filter.
+#[derive(Debug, Clone)]
+pub struct Rejection {
+    status_code: u16,
+    status_line: &'static str,
+    reason: String,
+}
+
+impl Rejection {
+    /// Create a new 400 rejection.
+    fn bad_request<T>(reason: T) -> Rejection
+    where
+        T: ToString,
+    {
+        Rejection {
+            status_code: 400,
+            status_line: "Bad Request",
+            reason: reason.to_string(),
+        }
+    }
+
+    /// Create a new 403 rejection.
+    fn forbidden<T>(reason: T) -> Rejection
+    where
+        T: ToString,
+    {
+        Rejection {
+            status_code: 403,
+            status_line: "Forbidden",
+            reason: reason.to_string(),
+        }
+    }
+
+    /// Get the reason of the rejection.
+    pub fn reason(&self) -> &str {
+        &self.reason
+    }
+
+    /// Create the local response for the rejected request. The response
+    /// closes the connection.
+    pub fn response(&self) -> Bytes {
+        let body = format!("{} {}\r\n", self.status_code, self.status_line);
+
+        let response = ResponseBuilder::new("HTTP", "1.1", self.status_code, self.status_line)
+            .set_header_field(("Content-Type", "text/plain"))
+            .set_header_field(("Content-Length", body.len()))
+            .set_header_field(("Connection", "close"))
+            .set_body(body)
+            .build();
+
+        let mut res = BytesMut::new();
+
+        res.extend_from_slice(format!("{}", response.header()).as_bytes());
+        res.extend_from_slice(response.body());
+
+        res.freeze()
+    }
+}
+
+/// HTTP request filter. It parses a stream of HTTP requests and passes only
+/// requests allowed by a given path policy. Requests switching protocols
+/// (i.e. CONNECT and requests with the Upgrade header) are always rejected
+/// because the rest of the stream could not be filtered.
+///
+/// Nothing is passed to the service after a rejected request. The filter
+/// also follows the stream of responses, so the local response to the
+/// rejected request can be sent only after responses to all requests
+/// passed before it. The connection should be closed afterwards.
+pub struct RequestFilter {
+    policy: ServicePathPolicy,
+    hdecoder: RequestHeaderDecoder,
+    bdecoder: Option<Box<dyn MessageBodyDecoder + Send>>,
+    header: BytesMut,
+    pending: VecDeque<bool>,
+    rhdecoder: ResponseHeaderDecoder,
+    rbdecoder: Option<Box<dyn MessageBodyDecoder + Send>>,
+    rejection: Option<Rejection>,
+    tracking: bool,
+    completed: bool,
+}
+
+impl RequestFilter {
+    /// Create a new request filter for a given path policy.
+    pub fn new(policy: ServicePathPolicy) -> RequestFilter {
+        RequestFilter {
+            policy,
+            hdecoder: RequestHeaderDecoder::new(MAX_LINE_LENGTH, MAX_HEADER_LINES),
+            bdecoder: None,
+            header: BytesMut::new(),
+            pending: VecDeque::new(),
+            rhdecoder: ResponseHeaderDecoder::new(MAX_LINE_LENGTH, MAX_HEADER_LINES),
+            rbdecoder: None,
+            rejection: None,
+            tracking: true,
+            completed: false,
+        }
+    }
+
+    /// Filter given request data and append all data that can be passed to
+    /// the service into a given output buffer. Data of an incomplete request
+    /// header are kept until the header is complete. An error is returned if
+    /// a request is rejected. All data following the rejected request are
+    /// dropped.
+    pub fn filter_request(&mut self, data: &[u8], output: &mut BytesMut) -> Result<(), Rejection> {
+        if self.rejection.is_some() {
+            return Ok(());
+        }
+
+        let res = self.process_requests(data, output);
+
+        if let Err(rejection) = res.as_ref() {
+            self.rejection = Some(rejection.clone());
+        }
+
+        res
+    }
+
+    /// Filter given response data and append all data that can be passed to
+    /// the client into a given output buffer. Once a request is rejected,
+    /// only responses to requests passed before it are accepted. If the
+    /// responses cannot be parsed, they are passed unchanged.
+    pub fn filter_response(&mut self, data: &[u8], output: &mut BytesMut) {
+        if !self.tracking {
+            if self.rejection.is_none() {
+                output.extend_from_slice(data);
+            }
+        } else if let Err(offset) = self.process_responses(data, output) {
+            // we are lost, so we can only pass the rest of the data
+            self.tracking = false;
+
+            if self.rejection.is_none() {
+                output.extend_from_slice(&data[offset..]);
+            }
+        }
+    }
+
+    /// Take the local response to a rejected request. The response is
+    /// returned only once and only after responses to all requests passed
+    /// before the rejected one. The connection should be closed after that.
+    /// The response is empty if it cannot be delivered safely (i.e. if the
+    /// responses could not be parsed).
+    pub fn take_local_response(&mut self) -> Option<Bytes> {
+        if self.completed {
+            return None;
+        }
+
+        let rejection = self.rejection.as_ref()?;
+
+        if !self.tracking {
+            self.completed = true;
+
+            Some(Bytes::new())
+        } else if self.pending.is_empty() && self.rbdecoder.is_none() {
+            self.completed = true;
+
+            Some(rejection.response())
+        } else {
+            None
+        }
+    }
+
+    /// Process given request data.
+    fn process_requests(&mut self, data: &[u8], output: &mut BytesMut) -> Result<(), Rejection> {
+        let mut buffer = BytesMut::from(data);
+
+        // decoders consume data only from the front of the buffer, so the
+        // consumed data are always at the current offset
+        let mut offset = 0;
+
+        while !buffer.is_empty() {
+            let available = buffer.len();
+
+            if let Some(mut bdecoder) = self.bdecoder.take() {
+                let body = bdecoder
+                    .decode(&mut buffer)
+                    .map_err(Rejection::bad_request)?;
+
+                let end = offset + available - buffer.len();
+
+                output.extend_from_slice(&data[offset..end]);
+
+                offset = end;
+
+                if body.is_none() {
+                    self.bdecoder = Some(bdecoder);
+                }
+            } else {
+                let header = self
+                    .hdecoder
+                    .decode(&mut buffer)
+                    .map_err(Rejection::bad_request)?;
+
+                let end = offset + available - buffer.len();
+
+                self.header.extend_from_slice(&data[offset..end]);
+
+                offset = end;
+
+                if let Some(header) = header {
+                    self.bdecoder = Some(self.check_request(&header)?);
+
+                    self.pending
+                        .push_back(header.method().eq_ignore_ascii_case("HEAD"));
+
+                    output.extend_from_slice(&self.header.take());
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Process given response data. The offset of unprocessed data is
+    /// returned in case of a parsing error.
+    fn process_responses(&mut self, data: &[u8], output: &mut BytesMut) -> Result<(), usize> {
+        let mut buffer = BytesMut::from(data);
+
+        let mut offset = 0;
+
+        loop {
+            // drop everything after the last expected response
+            if self.rejection.is_some() && self.pending.is_empty() && self.rbdecoder.is_none() {
+                return Ok(());
+            }
+
+            let available = buffer.len();
+
+            let res = if let Some(mut bdecoder) = self.rbdecoder.take() {
+                match bdecoder.decode(&mut buffer) {
+                    Ok(Some(_)) => {
+                        self.pending.pop_front();
+
+                        Ok(true)
+                    }
+                    Ok(None) => {
+                        self.rbdecoder = Some(bdecoder);
+
+                        // all data have been consumed
+                        Ok(false)
+                    }
+                    Err(_) => Err(()),
+                }
+            } else if buffer.is_empty() {
+                return Ok(());
+            } else {
+                match self.rhdecoder.decode(&mut buffer) {
+                    Ok(Some(header)) => self.response_body_decoder(&header).map(|bdecoder| {
+                        self.rbdecoder = bdecoder;
+
+                        true
+                    }),
+                    Ok(None) => Ok(false),
+                    Err(_) => Err(()),
+                }
+            };
+
+            let end = offset + available - buffer.len();
+
+            output.extend_from_slice(&data[offset..end]);
+
+            offset = end;
+
+            match res {
+                Ok(true) => (),
+                Ok(false) => return Ok(()),
+                Err(()) => return Err(offset),
+            }
+        }
+    }
+
+    /// Create a body decoder for a response with a given header. `None` is
+    /// returned for interim responses.
+    fn response_body_decoder(
+        &self,
+        header: &ResponseHeader,
+    ) -> Result<Option<Box<dyn MessageBodyDecoder + Send>>, ()> {
+        let status_code = header.status_code();
+
+        let head = self.pending.front().cloned().unwrap_or(false);
+
+        let tenc = header.get_header_field_value("transfer-encoding");
+        let clength = header.get_header_field_value("content-length");
+
+        let bdecoder: Box<dyn MessageBodyDecoder + Send> = if (100..200).contains(&status_code) {
+            return Ok(None);
+        } else if head || status_code == 204 || status_code == 304 {
+            Box::new(FixedSizeBodyDecoder::new(0, true))
+        } else if let Some(tenc) = tenc {
+            if tenc.trim().eq_ignore_ascii_case("chunked") {
+                Box::new(ChunkedBodyDecoder::new(MAX_LINE_LENGTH, true))
+            } else {
+                Box::new(SimpleBodyDecoder::new(true))
+            }
+        } else if let Some(clength) = clength {
+            let clength = clength.trim().parse::<usize>().map_err(|_| ())?;
+
+            Box::new(FixedSizeBodyDecoder::new(clength, true))
+        } else {
+            Box::new(SimpleBodyDecoder::new(true))
+        };
+
+        Ok(Some(bdecoder))
+    }
+
+    /// Check a given request header and create a body decoder for the
+    /// request.
+    fn check_request(
+        &self,
+        header: &RequestHeader,
+    ) -> Result<Box<dyn MessageBodyDecoder + Send>, Rejection> {
+        let method = header.method();
+        let path = header.path();
+
+        if method.eq_ignore_ascii_case("CONNECT")
+            || header.get_header_field_value("upgrade").is_some()
+        {
+            return Err(Rejection::forbidden(format!(
+                "protocol switching is not allowed ({} {})",
+                method, path
+            )));
+        }
+
+        self.policy
+            .check(path)
+            .map_err(|err| Rejection::forbidden(format!("{} {}: {}", method, path, err)))?;
+
+        // ambiguous message framing could be used for smuggling requests
+        // past the filter
+        if header.get_header_fields("transfer-encoding").len() > 1
+            || header.get_header_fields("content-length").len() > 1
+        {
+            return Err(Rejection::bad_request("ambiguous message length"));
+        }
+
+        let tenc = header.get_header_field_value("transfer-encoding");
+        let clength = header.get_header_field_value("content-length");
+
+        let bdecoder: Box<dyn MessageBodyDecoder + Send> = match (tenc, clength) {
+            (Some(_), Some(_)) => {
+                return Err(Rejection::bad_request(
+                    "both Transfer-Encoding and Content-Length given",
+                ))
+            }
+            (Some(tenc), None) if tenc.trim().eq_ignore_ascii_case("chunked") => {
+                Box::new(ChunkedBodyDecoder::new(MAX_LINE_LENGTH, true))
+            }
+            (Some(_), None) => {
+                return Err(Rejection::bad_request("unsupported transfer encoding"));
+            }
+            (None, Some(clength)) => {
+                let clength = clength
+                    .trim()
+                    .parse::<usize>()
+                    .map_err(|_| Rejection::bad_request("invalid content length"))?;
+
+                Box::new(FixedSizeBodyDecoder::new(clength, true))
+            }
+            (None, None) => Box::new(FixedSizeBodyDecoder::new(0, true)),
+        };
+
+        Ok(bdecoder)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::svc_table::{HttpPathPolicy, HttpPathRule};
+
+    /// Create a new filter allowing only paths under /live/.
+    fn create_filter() -> RequestFilter {
+        let rule = "allow:/live/*".parse::<HttpPathRule>().unwrap();
+
+        let policy = HttpPathPolicy::new(vec![rule])
+            .service_policy("10.0.0.4:80".parse().unwrap())
+            .unwrap();
+
+        RequestFilter::new(policy)
+    }
+
+    #[test]
+    fn test_allowed_requests() {
+        let mut filter = create_filter();
+
+        let mut output = BytesMut::new();
+
+        let requests = &b"POST /live/ptz HTTP/1.1\r\nContent-Length: 4\r\n\r\nleftGET /live/"[..];
+
+        filter.filter_request(requests, &mut output).unwrap();
+
+        // the incomplete header is kept until it is complete
+        assert_eq!(output.len(), requests.len() - 10);
+
+        filter
+            .filter_request(b"a.mjpg HTTP/1.1\r\n\r\n", &mut output)
+            .unwrap();
+
+        assert_eq!(
+            output.as_ref(),
+            &b"POST /live/ptz HTTP/1.1\r\nContent-Length: 4\r\n\r\nleftGET /live/a.mjpg HTTP/1.1\r\n\r\n"[..]
+        );
+
+        let responses = &b"HTTP/1.1 204 No Content\r\n\r\nHTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n2\r\nok\r\n0\r\n\r\n"[..];
+
+        let mut input = BytesMut::new();
+
+        filter.filter_response(responses, &mut input);
+
+        assert_eq!(input.as_ref(), responses);
+        assert!(filter.take_local_response().is_none());
+    }
+
+    #[test]
+    fn test_rejected_requests() {
+        let mut filter = create_filter();
+
+        let mut output = BytesMut::new();
+
+        let requests = b"HEAD /live/a.mjpg HTTP/1.1\r\n\r\nPOST /cgi-bin/upgrade.cgi HTTP/1.1\r\n\r\nGET /live/b.mjpg HTTP/1.1\r\n\r\n";
+
+        assert!(filter.filter_request(requests, &mut output).is_err());
+
+        // nothing is passed after the rejected request
+        assert_eq!(output.as_ref(), &b"HEAD /live/a.mjpg HTTP/1.1\r\n\r\n"[..]);
+
+        filter
+            .filter_request(b"GET /live/ HTTP/1.1\r\n\r\n", &mut output)
+            .unwrap();
+
+        assert_eq!(output.as_ref(), &b"HEAD /live/a.mjpg HTTP/1.1\r\n\r\n"[..]);
+
+        // the local response must wait for the response to the HEAD request
+        assert!(filter.take_local_response().is_none());
+
+        let mut input = BytesMut::new();
+
+        filter.filter_response(b"HTTP/1.1 200 OK\r\nContent-", &mut input);
+
+        assert!(filter.take_local_response().is_none());
+
+        filter.filter_response(b"Length: 1000\r\n\r\nHTTP/1.1 200 OK\r\n", &mut input);
+
+        // the body of a response to a HEAD request is empty and there is
+        // nothing more expected
+        assert_eq!(
+            input.as_ref(),
+            &b"HTTP/1.1 200 OK\r\nContent-Length: 1000\r\n\r\n"[..]
+        );
+
+        let response = filter.take_local_response().unwrap();
+
+        assert!(response.starts_with(b"HTTP/1.1 403 Forbidden\r\n"));
+        assert!(filter.take_local_response().is_none());
+
+        let requests = vec![
+            &b"GET /live/ HTTP/1.1\r\nUpgrade: websocket\r\n\r\n"[..],
+            &b"POST /live/ HTTP/1.1\r\nContent-Length: 1\r\nContent-Length: 40\r\n\r\n"[..],
+            &b"POST /live/ HTTP/1.1\r\nTransfer-Encoding: chunked\r\nContent-Length: 4\r\n\r\n"[..],
+        ];
+
+        for request in requests {
+            let mut filter = create_filter();
+
+            let mut output = BytesMut::new();
+
+            assert!(filter.filter_request(request, &mut output).is_err());
+            assert!(output.is_empty());
+            assert!(filter.take_local_response().is_some());
+        }
+    }
+}
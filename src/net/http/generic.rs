@@ -295,6 +295,68 @@ impl RequestHeader {
             header_fields: HeaderFields::new(),
         }
     }
+
+    /// Get request method.
+    pub fn method(&self) -> &str {
+        &self.method
+    }
+
+    /// Get request path.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Get protocol name.
+    pub fn protocol(&self) -> &str {
+        &self.protocol
+    }
+
+    /// Get protocol version.
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+
+    /// Get header fields corresponding to a given name.
+    pub fn get_header_fields(&self, name: &str) -> &[HeaderField] {
+        self.header_fields.get(name)
+    }
+
+    /// Get value of the last header field with a given name.
+    pub fn get_header_field_value(&self, name: &str) -> Option<&str> {
+        self.header_fields
+            .get(name)
+            .last()
+            .and_then(|field| field.value())
+    }
+}
+
+impl FromStr for RequestHeader {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<RequestHeader, Error> {
+        let mut reader = StringReader::new(s);
+
+        let method = reader.read_word();
+        let path = reader.read_word();
+
+        reader.skip_whitespace();
+
+        let protocol = reader.read_until(|c| c == '/');
+
+        reader
+            .match_char('/')
+            .map_err(|_| Error::from("invalid request header"))?;
+
+        let version = reader.read_word();
+
+        if method.is_empty() || path.is_empty() || version.is_empty() {
+            return Err(Error::from("invalid request header"));
+        }
+
+        let header = RequestHeader::new(protocol, version, method, path);
+
+        Ok(header)
+    }
 }
 
 impl Display for RequestHeader {
@@ -335,6 +397,11 @@ impl Request {
         }
     }
 
+    /// Create a new HTTP-like request from a given header and body.
+    pub fn from_parts(header: RequestHeader, body: MessageBody) -> Request {
+        Request { header, body }
+    }
+
     /// Get request header.
     pub fn header(&self) -> &RequestHeader {
         &self.header
@@ -420,6 +487,17 @@ pub struct ResponseHeader {
 }
 
 impl ResponseHeader {
+    /// Create a new HTTP-like response header.
+    fn new(protocol: &str, version: &str, status_code: u16, status_line: &str) -> ResponseHeader {
+        ResponseHeader {
+            protocol: protocol.to_string(),
+            version: version.to_string(),
+            status_code,
+            status_line: status_line.to_string(),
+            header_fields: HeaderFields::new(),
+        }
+    }
+
     /// Get protocol name.
     pub fn protocol(&self) -> &str {
         &self.protocol
@@ -499,6 +577,25 @@ impl FromStr for ResponseHeader {
     }
 }
 
+impl Display for ResponseHeader {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
+        write!(
+            f,
+            "{}/{} {} {}\r\n",
+            self.protocol, self.version, self.status_code, self.status_line
+        )?;
+
+        for field in &self.header_fields {
+            // format the header field
+            Display::fmt(field, f)?;
+
+            f.write_str("\r\n")?;
+        }
+
+        f.write_str("\r\n")
+    }
+}
+
 /// HTTP-like response.
 #[derive(Clone)]
 pub struct Response {
@@ -526,6 +623,57 @@ impl Response {
     }
 }
 
+/// Response builder.
+#[derive(Clone)]
+pub struct ResponseBuilder {
+    response: Response,
+}
+
+impl ResponseBuilder {
+    /// Create a new response builder.
+    pub fn new(
+        protocol: &str,
+        version: &str,
+        status_code: u16,
+        status_line: &str,
+    ) -> ResponseBuilder {
+        let header = ResponseHeader::new(protocol, version, status_code, status_line);
+        let response = Response::new(header, Box::new([]));
+
+        ResponseBuilder { response }
+    }
+
+    /// Replace the current list of header fields having the same name (if any)
+    /// with the given one.
+    pub fn set_header_field<T>(mut self, field: T) -> ResponseBuilder
+    where
+        HeaderField: From<T>,
+    {
+        self.response
+            .header
+            .header_fields
+            .set(HeaderField::from(field));
+        self
+    }
+
+    /// Set response body.
+    pub fn set_body<T>(mut self, body: T) -> ResponseBuilder
+    where
+        T: AsRef<[u8]>,
+    {
+        let body = body.as_ref().to_vec().into_boxed_slice();
+
+        self.response.body = body;
+
+        self
+    }
+
+    /// Build response.
+    pub fn build(self) -> Response {
+        self.response
+    }
+}
+
 /// Line decoder.
 pub struct LineDecoder {
     separator: Box<[u8]>,
@@ -696,6 +844,83 @@ impl Decoder for ResponseHeaderDecoder {
     }
 }
 
+/// Decoder for HTTP-like request headers.
+pub struct RequestHeaderDecoder {
+    ldecoder: LineDecoder,
+    header: Option<RequestHeader>,
+    field: String,
+    lines: usize,
+    max_lines: usize,
+}
+
+impl RequestHeaderDecoder {
+    /// Create a new decoder for HTTP-like request headers.
+    pub fn new(max_line_length: usize, max_lines: usize) -> RequestHeaderDecoder {
+        RequestHeaderDecoder {
+            ldecoder: LineDecoder::new(b"\r\n", max_line_length),
+            header: None,
+            field: String::new(),
+            lines: 0,
+            max_lines,
+        }
+    }
+
+    /// Reset the decoder and make it ready for parsing a new HTTP-like
+    /// request header.
+    pub fn reset(&mut self) {
+        self.ldecoder.reset();
+        self.field.clear();
+
+        self.header = None;
+        self.lines = 0;
+    }
+}
+
+impl Decoder for RequestHeaderDecoder {
+    type Item = RequestHeader;
+    type Error = Error;
+
+    fn decode(&mut self, data: &mut BytesMut) -> Result<Option<RequestHeader>, Error> {
+        while let Some(line) = self.ldecoder.decode(data)? {
+            self.lines += 1;
+
+            if self.lines > self.max_lines {
+                return Err(Error::from("maximum number of lines exceeded"));
+            }
+
+            if let Some(mut header) = self.header.take() {
+                // check if the current header field should be processed
+                let commit_header_field = line
+                    .chars()
+                    .next()
+                    .map(|c| !c.is_whitespace())
+                    .unwrap_or(true);
+
+                if commit_header_field && !self.field.is_empty() {
+                    header.header_fields.add(self.field.parse()?);
+
+                    self.field.clear();
+                }
+
+                self.field += &line;
+
+                // reset the decoder and return the header
+                if line.is_empty() {
+                    self.reset();
+
+                    return Ok(Some(header));
+                }
+
+                self.header = Some(header);
+            } else {
+                self.header = Some(line.parse()?);
+            }
+        }
+
+        Ok(None)
+    }
+}
+
 /// Common trait for HTTP-like message body decoders. This is just a helper
 /// trait for creating trait objects from structs implementing the Decoder
 /// trait where Item=MessageBody and Error=Error.
@@ -12,14 +12,11 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-#[cfg(feature = "discovery")]
-pub mod rtsp;
-
-#[cfg(feature = "discovery")]
-pub mod http;
-
 pub mod arrow;
+pub mod http;
+pub mod proxy;
 pub mod raw;
+pub mod rtsp;
 pub mod tls;
 pub mod url;
 pub mod utils;
@@ -0,0 +1,92 @@
+// Copyright 2019 click2stream, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! HTTP CONNECT proxy client.
+
+use std::io;
+use std::str;
+
+use std::str::FromStr;
+
+use futures::future::{self, Loop};
+use futures::Future;
+
+use tokio;
+
+use tokio::net::TcpStream;
+
+use crate::net::http::generic::ResponseHeader;
+use crate::net::utils::get_socket_address_async;
+
+/// Maximum size of the proxy response header.
+const MAX_RESPONSE_HEADER_SIZE: usize = 8192;
+
+/// Connect to a given target ("host:port") using a given HTTP proxy
+/// ("host:port"). The returned stream is connected to the target once the
+/// future resolves.
+pub fn connect(proxy: &str, target: &str) -> impl Future<Item = TcpStream, Error = io::Error> {
+    let request = format!("CONNECT {0} HTTP/1.1\r\nHost: {0}\r\n\r\n", target);
+
+    get_socket_address_async(proxy.to_string())
+        .map_err(|err| io::Error::other(format!("unable to resolve proxy address: {}", err)))
+        .and_then(|addr| TcpStream::connect(&addr))
+        .and_then(move |stream| tokio::io::write_all(stream, request))
+        .and_then(|(stream, _)| read_response_header(stream))
+        .and_then(|(stream, header)| {
+            let header = str::from_utf8(&header).map_err(|_| {
+                io::Error::new(io::ErrorKind::InvalidData, "invalid proxy response")
+            })?;
+
+            let status_line = header.lines().next().unwrap_or("");
+
+            let header = ResponseHeader::from_str(status_line).map_err(|_| {
+                io::Error::new(io::ErrorKind::InvalidData, "invalid proxy response")
+            })?;
+
+            let status_code = header.status_code();
+
+            if !(200..=299).contains(&status_code) {
+                return Err(io::Error::other(format!(
+                    "proxy rejected the connection ({} {})",
+                    status_code,
+                    header.status_line()
+                )));
+            }
+
+            Ok(stream)
+        })
+}
+
+/// Read the proxy response header. The header is read byte by byte in order
+/// not to consume any data following the header.
+fn read_response_header(
+    stream: TcpStream,
+) -> impl Future<Item = (TcpStream, Vec<u8>), Error = io::Error> {
+    future::loop_fn((stream, Vec::new()), |(stream, mut header)| {
+        tokio::io::read_exact(stream, [0u8; 1]).and_then(move |(stream, buffer)| {
+            header.push(buffer[0]);
+
+            if header.ends_with(b"\r\n\r\n") {
+                Ok(Loop::Break((stream, header)))
+            } else if header.len() > MAX_RESPONSE_HEADER_SIZE {
+                Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "proxy response header is too long",
+                ))
+            } else {
+                Ok(Loop::Continue((stream, header)))
+            }
+        })
+    })
+}
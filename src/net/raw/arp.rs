@@ -207,6 +207,8 @@ pub mod scanner {
     pub struct Ipv4ArpScanner {
         device: EthernetDevice,
         scanner: Scanner,
+        first: u32,
+        end: u32,
     }
 
     impl Ipv4ArpScanner {
@@ -215,26 +217,47 @@ pub mod scanner {
             Ipv4ArpScanner::new(device).scan()
         }
 
+        /// Get MAC address of a given host. (The host is expected to be
+        /// within the network of a given device.)
+        pub fn resolve(device: &EthernetDevice, host: Ipv4Addr) -> pcap::Result<Option<MacAddr>> {
+            let mut scanner = Ipv4ArpScanner::new(device);
+
+            scanner.first = host.as_u32();
+            scanner.end = scanner.first.saturating_add(1);
+
+            let res = scanner
+                .scan()?
+                .into_iter()
+                .find(|&(_, addr)| addr == host)
+                .map(|(mac, _)| mac);
+
+            Ok(res)
+        }
+
         /// Create a new scanner instance.
         fn new(device: &EthernetDevice) -> Ipv4ArpScanner {
+            let mask = device.netmask.as_u32();
+            let addr = device.ip_addr.as_u32();
+
             Ipv4ArpScanner {
                 device: device.clone(),
                 scanner: Scanner::new(&device.name),
+                first: (addr & mask) + 1,
+                end: addr | !mask,
             }
         }
 
-        /// Scan a given device and return list of all active hosts.
+        /// Scan all addresses from the range of this scanner and return list
+        /// of all active hosts.
         fn scan(&mut self) -> pcap::Result<Vec<(MacAddr, Ipv4Addr)>> {
             let bcast = MacAddr::new(0xff, 0xff, 0xff, 0xff, 0xff, 0xff);
             let hdst = MacAddr::new(0x00, 0x00, 0x00, 0x00, 0x00, 0x00);
             let hsrc = self.device.mac_addr;
             let psrc = self.device.ip_addr;
-            let mask = self.device.netmask.as_u32();
-            let addr = self.device.ip_addr.as_u32();
 
-            let end = addr | !mask;
+            let end = self.end;
 
-            let mut current = (addr & mask) + 1;
+            let mut current = self.first;
 
             let mut buffer = Vec::new();
 
@@ -27,12 +27,14 @@ use crate::net::raw::ip::{Ipv4PacketBody, Ipv4PacketHeader};
 
 const ICMP_TYPE_ECHO_REPLY: u8 = 0x00;
 const ICMP_TYPE_ECHO: u8 = 0x08;
+const ICMP_TYPE_TIME_EXCEEDED: u8 = 0x0b;
 
 /// ICMP packet type.
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub enum IcmpPacketType {
     Echo,
     EchoReply,
+    TimeExceeded,
     Unknown(u8),
 }
 
@@ -42,6 +44,7 @@ impl IcmpPacketType {
         match self {
             &IcmpPacketType::Echo => ICMP_TYPE_ECHO,
             &IcmpPacketType::EchoReply => ICMP_TYPE_ECHO_REPLY,
+            &IcmpPacketType::TimeExceeded => ICMP_TYPE_TIME_EXCEEDED,
             &IcmpPacketType::Unknown(pt) => pt,
         }
     }
@@ -52,6 +55,7 @@ impl From<u8> for IcmpPacketType {
         match code {
             ICMP_TYPE_ECHO => IcmpPacketType::Echo,
             ICMP_TYPE_ECHO_REPLY => IcmpPacketType::EchoReply,
+            ICMP_TYPE_TIME_EXCEEDED => IcmpPacketType::TimeExceeded,
             pt => IcmpPacketType::Unknown(pt),
         }
     }
@@ -111,6 +115,17 @@ impl IcmpPacket {
         }
     }
 
+    /// Get ICMP packet type.
+    pub fn packet_type(&self) -> IcmpPacketType {
+        self.icmp_type
+    }
+
+    /// Get ICMP packet body (i.e. everything after the first four bytes
+    /// of the ICMP header).
+    pub fn body(&self) -> &[u8] {
+        self.body.as_ref()
+    }
+
     /// Get raw ICMP packet header.
     fn raw_header(&self) -> RawIcmpPacketHeader {
         let checksum = self.checksum();
@@ -197,7 +212,10 @@ impl IcmpEchoPacket for IcmpPacket {
 pub mod scanner {
     use super::*;
 
+    use std::thread;
+
     use std::net::Ipv4Addr;
+    use std::time::Duration;
 
     use bytes::Bytes;
 
@@ -216,8 +234,9 @@ pub mod scanner {
     pub struct IcmpScanner {
         device: EthernetDevice,
         scanner: Scanner,
-        mask: u32,
-        network: u32,
+        first: u32,
+        end: u32,
+        interval: Option<Duration>,
     }
 
     impl IcmpScanner {
@@ -226,36 +245,70 @@ pub mod scanner {
             IcmpScanner::new(device).scan()
         }
 
+        /// Scan a given network and return list of all active hosts. The
+        /// network must be a part of the network of a given device, only
+        /// the corresponding part of the network is scanned otherwise. There
+        /// will be a given delay between every two packets sent.
+        pub fn sweep(
+            device: &EthernetDevice,
+            network: Ipv4Addr,
+            prefix_length: u8,
+            interval: Duration,
+        ) -> pcap::Result<Vec<(MacAddr, Ipv4Addr)>> {
+            let mask = match prefix_length {
+                0 => 0,
+                p if p < 32 => !0u32 << (32 - p),
+                _ => !0u32,
+            };
+
+            let first = network.as_u32() & mask;
+            let last = first | !mask;
+
+            let mut scanner = IcmpScanner::new(device);
+
+            scanner.first = scanner.first.max(first);
+            scanner.end = scanner.end.min(last.saturating_add(1));
+            scanner.interval = Some(interval);
+
+            scanner.scan()
+        }
+
         /// Create a new scanner instance.
         fn new(device: &EthernetDevice) -> IcmpScanner {
             let mask = device.netmask.as_u32();
             let addr = device.ip_addr.as_u32();
-            let network = addr & mask;
 
             IcmpScanner {
                 device: device.clone(),
                 scanner: Scanner::new(&device.name),
-                mask: mask,
-                network: network,
+                first: (addr & mask) + 1,
+                end: addr | !mask,
+                interval: None,
             }
         }
 
-        /// Scan a given device and return list of all active hosts.
+        /// Scan all addresses from the range of this scanner and return list
+        /// of all active hosts.
         fn scan(&mut self) -> pcap::Result<Vec<(MacAddr, Ipv4Addr)>> {
             let bcast = MacAddr::new(0xff, 0xff, 0xff, 0xff, 0xff, 0xff);
             let hsrc = self.device.mac_addr;
             let psrc = self.device.ip_addr;
-            let mask = self.device.netmask.as_u32();
-            let addr = self.device.ip_addr.as_u32();
-
-            let end = addr | !mask;
+            let first = self.first;
+            let end = self.end;
+            let interval = self.interval;
 
-            let mut current = (addr & mask) + 1;
+            let mut current = first;
 
             let mut buffer = Vec::new();
 
             let mut generator = move || {
                 if current < end {
+                    if let Some(interval) = interval {
+                        if current > first {
+                            thread::sleep(interval);
+                        }
+                    }
+
                     let icmp_id = (current >> 16) as u16;
                     let icmp_seq = (current & 0xff) as u16;
 
@@ -297,9 +350,9 @@ pub mod scanner {
                     let sha = eh.src;
                     let spa = iph.src;
 
-                    let nwa = spa.as_u32() & self.mask;
+                    let spa_u32 = spa.as_u32();
 
-                    if nwa == self.network {
+                    if spa_u32 >= first && spa_u32 < end {
                         hosts.push((sha, spa));
                     }
                 }
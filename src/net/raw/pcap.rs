@@ -395,6 +395,14 @@ impl Scanner {
     }
 }
 
+/// Check if packets can be captured on a given device (i.e. if the process
+/// has the necessary permissions).
+pub fn check_permissions(device: &str) -> Result<()> {
+    CaptureBuilder::new(device)?.timeout(100).activate()?;
+
+    Ok(())
+}
+
 /// Listen for incoming packets matching a given filter until a given end
 /// indicator is set.
 fn packet_listener(
@@ -208,9 +208,11 @@ pub mod scanner {
     use super::*;
 
     use std::slice;
+    use std::thread;
 
     use std::net::Ipv4Addr;
     use std::ops::Range;
+    use std::time::Duration;
 
     use bytes::Bytes;
 
@@ -219,7 +221,8 @@ pub mod scanner {
     use crate::net::raw::devices::EthernetDevice;
     use crate::net::raw::ether::packet::EtherPacket;
     use crate::net::raw::ether::MacAddr;
-    use crate::net::raw::ip::Ipv4Packet;
+    use crate::net::raw::icmp::IcmpPacket;
+    use crate::net::raw::ip::{Ipv4Packet, Ipv4PacketType};
     use crate::net::raw::pcap::Scanner;
     use crate::net::raw::utils::Serialize;
 
@@ -403,6 +406,163 @@ pub mod scanner {
         }
     }
 
+    /// Source port of the first TCP SYN packet sent by the tracer (TTL of
+    /// each packet is encoded in its source port).
+    const TRACER_SPORT_BASE: u16 = 61300;
+
+    /// Response to a TTL-limited TCP SYN packet.
+    #[derive(Debug, Copy, Clone, Eq, PartialEq)]
+    pub enum TcpTraceResponse {
+        /// ICMP time exceeded message sent by a router with a given address.
+        TimeExceeded(Ipv4Addr),
+        /// TCP packet with given flags sent by the target host.
+        Tcp(u16),
+    }
+
+    /// TCP SYN tracer.
+    pub struct TcpTracer {
+        device: EthernetDevice,
+        scanner: Scanner,
+    }
+
+    impl TcpTracer {
+        /// Send TCP SYN packets with TTL from 1 up to a given maximum to
+        /// a given port of a given IPv4 host and return the first response
+        /// for every TTL (sorted by TTL). The packets are sent to a given MAC
+        /// address (i.e. either the host itself or a router accessible
+        /// through a local Ethernet network). There will be a given delay
+        /// between every two packets sent.
+        pub fn trace(
+            device: &EthernetDevice,
+            hdst: MacAddr,
+            pdst: Ipv4Addr,
+            port: u16,
+            max_ttl: u8,
+            interval: Duration,
+        ) -> pcap::Result<Vec<(u8, TcpTraceResponse)>> {
+            TcpTracer::new(device).run(hdst, pdst, port, max_ttl, interval)
+        }
+
+        /// Create a new tracer.
+        fn new(device: &EthernetDevice) -> TcpTracer {
+            TcpTracer {
+                device: device.clone(),
+                scanner: Scanner::new(&device.name),
+            }
+        }
+
+        /// Send the TCP SYN packets and collect responses.
+        fn run(
+            &mut self,
+            hdst: MacAddr,
+            pdst: Ipv4Addr,
+            port: u16,
+            max_ttl: u8,
+            interval: Duration,
+        ) -> pcap::Result<Vec<(u8, TcpTraceResponse)>> {
+            let hsrc = self.device.mac_addr;
+            let psrc = self.device.ip_addr;
+
+            let mut ttl = 0;
+
+            let mut buffer = Vec::new();
+
+            let mut generator = move || {
+                if ttl < max_ttl {
+                    if ttl > 0 {
+                        thread::sleep(interval);
+                    }
+
+                    ttl += 1;
+
+                    let sport = TRACER_SPORT_BASE + u16::from(ttl);
+
+                    let tcpp = TcpPacket::new(sport, port, TCP_FLAG_SYN, &[]);
+                    let ipp = Ipv4Packet::tcp(psrc, pdst, ttl, tcpp);
+                    let pkt = EtherPacket::ipv4(hsrc, hdst, ipp);
+
+                    buffer.clear();
+
+                    pkt.serialize(&mut buffer).unwrap();
+
+                    Some(Bytes::from(buffer.as_slice()))
+                } else {
+                    None
+                }
+            };
+
+            let filter = format!(
+                "ip dst {} and \
+                 ((icmp and icmp[icmptype] = icmp-timxceed) or \
+                 (tcp and src host {} and src port {}))",
+                psrc, pdst, port
+            );
+            let packets = self.scanner.sr(&filter, &mut generator, 2000)?;
+
+            let mut responses: Vec<(u8, TcpTraceResponse)> = Vec::new();
+
+            for ep in packets {
+                if let Some(ip) = ep.body::<Ipv4Packet>() {
+                    let iph = ip.header();
+
+                    let response = if let Some(tcp) = ip.body::<TcpPacket>() {
+                        Some((tcp.dport, TcpTraceResponse::Tcp(tcp.flags)))
+                    } else if let Some(icmp) = ip.body::<IcmpPacket>() {
+                        get_original_sport(icmp.body(), pdst, port)
+                            .map(|sport| (sport, TcpTraceResponse::TimeExceeded(iph.src)))
+                    } else {
+                        None
+                    };
+
+                    if let Some((sport, response)) = response {
+                        if sport <= TRACER_SPORT_BASE
+                            || sport > (TRACER_SPORT_BASE + u16::from(max_ttl))
+                        {
+                            continue;
+                        }
+
+                        let ttl = (sport - TRACER_SPORT_BASE) as u8;
+
+                        if !responses.iter().any(|&(t, _)| t == ttl) {
+                            responses.push((ttl, response));
+                        }
+                    }
+                }
+            }
+
+            responses.sort_by_key(|&(ttl, _)| ttl);
+
+            Ok(responses)
+        }
+    }
+
+    /// Get source port of the original TCP packet from a given body of an
+    /// ICMP error message (i.e. the original IPv4 header followed by at least
+    /// the first 8 bytes of the original payload). None is returned if the
+    /// original packet is not a TCP packet sent to a given host and port.
+    fn get_original_sport(data: &[u8], pdst: Ipv4Addr, port: u16) -> Option<u16> {
+        if data.len() < 20 {
+            return None;
+        }
+
+        let ihl = ((data[0] & 0x0f) as usize) << 2;
+
+        if ihl < 20 || data.len() < (ihl + 4) {
+            return None;
+        }
+
+        let protocol = Ipv4PacketType::from(data[9]);
+        let dst = Ipv4Addr::new(data[16], data[17], data[18], data[19]);
+        let sport = u16::from_be_bytes([data[ihl], data[ihl + 1]]);
+        let dport = u16::from_be_bytes([data[ihl + 2], data[ihl + 3]]);
+
+        if protocol != Ipv4PacketType::TCP || dst != pdst || dport != port {
+            None
+        } else {
+            Some(sport)
+        }
+    }
+
     /// Packet generator for the TCP port scanner.
     struct TcpPortScannerPacketGenerator<'a, HI: Iterator<Item = Host>> {
         device: EthernetDevice,
@@ -16,14 +16,14 @@ use std::fmt;
 use std::io;
 
 use std::error::Error;
-use std::fmt::{Display, Formatter};
+use std::fmt::{Debug, Display, Formatter};
 use std::io::{Read, Write};
 
 use futures::{Async, Future, Poll};
 
 use openssl::error::ErrorStack as SslErrorStack;
 use openssl::ssl::Error as SslError;
-use openssl::ssl::{HandshakeError, SslConnector, SslStream};
+use openssl::ssl::{HandshakeError, SslConnector, SslRef, SslStream};
 
 use tokio::io::{AsyncRead, AsyncWrite};
 
@@ -74,6 +74,13 @@ pub struct TlsStream<S> {
     inner: SslStream<S>,
 }
 
+impl<S> TlsStream<S> {
+    /// Get the underlying SSL object (e.g. to get the peer certificate).
+    pub fn ssl(&self) -> &SslRef {
+        self.inner.ssl()
+    }
+}
+
 impl<S> Read for TlsStream<S>
 where
     S: Read + Write,
@@ -195,6 +202,22 @@ impl TlsConnector {
             handshake: Some(handshake),
         }
     }
+
+    /// Take a given blocking stream and perform a TLS handshake.
+    pub fn connect<S>(&self, stream: S) -> Result<TlsStream<S>, TlsError>
+    where
+        S: Read + Write + Debug,
+    {
+        // NOTE: see the note in connect_async()
+        let inner = self
+            .inner
+            .configure()?
+            .verify_hostname(false)
+            .connect("hostname", stream)
+            .map_err(|err| TlsError::from(format!("{}", err)))?;
+
+        Ok(TlsStream { inner })
+    }
 }
 
 impl From<SslConnector> for TlsConnector {
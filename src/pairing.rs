@@ -0,0 +1,149 @@
+// Copyright 2019 click2stream, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Local pairing helpers, i.e. the pairing code and the pairing trigger.
+
+use std::fs;
+
+use openssl::sha;
+
+use uuid::Uuid;
+
+/// Alphabet of pairing codes (Crockford's Base32, i.e. without characters
+/// that can be easily confused).
+const CODE_ALPHABET: &[u8] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+
+/// Number of characters of a pairing code.
+const CODE_LENGTH: usize = 8;
+
+/// Get a short pairing code for a given client UUID. The code is made of the
+/// first 40 bits of the SHA-256 hash of the UUID bytes encoded using
+/// Crockford's Base32 and it is split into two groups of four characters
+/// (e.g. "7K3M-Q9XD").
+pub fn get_pairing_code(uuid: &Uuid) -> String {
+    let hash = sha::sha256(uuid.as_bytes());
+
+    let bits = hash[..5]
+        .iter()
+        .fold(0u64, |acc, &byte| (acc << 8) | u64::from(byte));
+
+    let mut res = String::with_capacity(CODE_LENGTH + 1);
+
+    for i in 0..CODE_LENGTH {
+        if i == CODE_LENGTH / 2 {
+            res.push('-');
+        }
+
+        let index = (bits >> (5 * (CODE_LENGTH - i - 1))) & 0x1f;
+
+        res.push(CODE_ALPHABET[index as usize] as char);
+    }
+
+    res
+}
+
+/// Pairing trigger. The trigger is a file that is considered to be active if
+/// it exists and it does not contain "0". This allows using GPIO value files
+/// (e.g. `/sys/class/gpio/gpio17/value`) as well as files created by other
+/// processes. The trigger fires on every transition from inactive to active.
+pub struct PairingTrigger {
+    path: String,
+    active: bool,
+}
+
+impl PairingTrigger {
+    /// Create a new pairing trigger for a given file. The trigger does not
+    /// fire if the file is already active.
+    pub fn new(path: &str) -> PairingTrigger {
+        PairingTrigger {
+            path: path.to_string(),
+            active: is_active(path),
+        }
+    }
+
+    /// Check the trigger file and return true if the trigger has been
+    /// activated since the last check.
+    pub fn poll(&mut self) -> bool {
+        let active = is_active(&self.path);
+
+        let res = active && !self.active;
+
+        self.active = active;
+
+        res
+    }
+}
+
+/// Check if a given trigger file is active.
+fn is_active(path: &str) -> bool {
+    match fs::read_to_string(path) {
+        Ok(content) => content.trim() != "0",
+        Err(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::env;
+    use std::process;
+
+    use std::str::FromStr;
+
+    #[test]
+    fn test_pairing_code() {
+        let uuid = Uuid::from_str("0c4d52e4-4b7a-4c5b-9d3a-e12f4a2a8f10").unwrap();
+
+        let code = get_pairing_code(&uuid);
+
+        assert_eq!(code.len(), CODE_LENGTH + 1);
+        assert_eq!(code, get_pairing_code(&uuid));
+        assert_ne!(code, get_pairing_code(&Uuid::new_v4()));
+        assert_eq!(&code[4..5], "-");
+        assert!(code
+            .bytes()
+            .filter(|&c| c != b'-')
+            .all(|c| CODE_ALPHABET.contains(&c)));
+    }
+
+    #[test]
+    fn test_pairing_trigger() {
+        let path = env::temp_dir().join(format!("arrow-trigger-test-{}", process::id()));
+        let path = path.to_str().unwrap();
+
+        fs::remove_file(path).unwrap_or(());
+
+        let mut trigger = PairingTrigger::new(path);
+
+        assert!(!trigger.poll());
+
+        fs::write(path, "").unwrap();
+
+        assert!(trigger.poll());
+        assert!(!trigger.poll());
+
+        fs::write(path, "0\n").unwrap();
+
+        assert!(!trigger.poll());
+
+        fs::write(path, "1\n").unwrap();
+
+        assert!(trigger.poll());
+
+        fs::remove_file(path).unwrap_or(());
+
+        assert!(!trigger.poll());
+    }
+}
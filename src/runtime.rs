@@ -48,6 +48,35 @@ where
         .unwrap_or(())
 }
 
+/// Run a given future to completion using a multi-threaded runtime and
+/// return its result.
+#[cfg(feature = "threads")]
+pub fn block_on<F>(future: F) -> Result<F::Item, F::Error>
+where
+    F: 'static + Future + Send,
+    F::Item: 'static + Send,
+    F::Error: 'static + Send,
+{
+    tokio::runtime::Builder::new()
+        .build()
+        .expect("unable to create a tokio runtime")
+        .block_on(future)
+}
+
+/// Run a given future to completion using a single-threaded runtime and
+/// return its result.
+#[cfg(not(feature = "threads"))]
+pub fn block_on<F>(future: F) -> Result<F::Item, F::Error>
+where
+    F: 'static + Future + Send,
+    F::Item: 'static + Send,
+    F::Error: 'static + Send,
+{
+    tokio::runtime::current_thread::Runtime::new()
+        .expect("unable to create a tokio runtime")
+        .block_on(future)
+}
+
 /// Run a given closure as blocking.
 #[cfg(feature = "threads")]
 pub fn blocking<F, T>(f: F) -> Poll<T, ()>
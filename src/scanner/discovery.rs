@@ -22,9 +22,9 @@ use std::error::Error;
 use std::fmt::{Display, Formatter};
 use std::fs::File;
 use std::io::{BufRead, BufReader};
-use std::net::{IpAddr, SocketAddr, SocketAddrV4, SocketAddrV6};
+use std::net::{IpAddr, SocketAddr, SocketAddrV4, SocketAddrV6, TcpStream};
 use std::sync::{Arc, Mutex};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use futures::future;
 use futures::stream;
@@ -45,17 +45,11 @@ use crate::net::raw::tcp::scanner::{PortCollection, TcpPortScanner};
 use crate::net::rtsp::sdp::{FromAttribute, MediaType, RTPMap, SessionDescription};
 use crate::net::rtsp::Request as RtspRequest;
 use crate::net::rtsp::Response as RtspResponse;
+use crate::scanner::probe::{ProbeResult, ProbeStatus};
 use crate::scanner::result::{ScanResult, HR_FLAG_ARP, HR_FLAG_ICMP};
 use crate::svc_table::{Service, ServiceType};
 use crate::utils::logger::{BoxLogger, Logger};
 
-/// RTSP port candidates.
-static RTSP_PORT_CANDIDATES: &'static [u16] =
-    &[554, 88, 81, 555, 7447, 8554, 7070, 10554, 80, 6667];
-
-/// HTTP port candidates.
-static HTTP_PORT_CANDIDATES: &'static [u16] = &[80, 81, 8080, 8081, 8090];
-
 /// Discovery error.
 #[derive(Debug, Clone)]
 pub struct DiscoveryError {
@@ -101,17 +95,28 @@ impl From<io::Error> for DiscoveryError {
 /// Discovery result type alias.
 pub type Result<T> = result::Result<T, DiscoveryError>;
 
+/// RTSP/HTTP request timeout (in milliseconds).
+const REQUEST_TIMEOUT: u64 = 2000;
+
 /// Scan all local networks for RTSP and MJPEG streams and associated HTTP
-/// services.
+/// services. The port candidates are expected to be sorted by priority.
 pub fn scan_network(
     logger: BoxLogger,
     rtsp_paths_file: &str,
     mjpeg_paths_file: &str,
+    rtsp_ports: &[u16],
+    http_ports: &[u16],
 ) -> Result<ScanResult> {
     let mut runtime = tokio::runtime::current_thread::Runtime::new()
         .map_err(|err| DiscoveryError::from(format!("Asyn IO error: {}", err)))?;
 
-    let context = Context::new(logger.clone(), rtsp_paths_file, mjpeg_paths_file)?;
+    let context = Context::new(
+        logger.clone(),
+        rtsp_paths_file,
+        mjpeg_paths_file,
+        rtsp_ports,
+        http_ports,
+    )?;
 
     let rtsp_port_priorities = context.get_rtsp_port_priorities();
     let http_port_priorities = context.get_http_port_priorities();
@@ -187,6 +192,8 @@ impl ContextData {
         logger: BoxLogger,
         rtsp_paths_file: &str,
         mjpeg_paths_file: &str,
+        rtsp_ports: &[u16],
+        http_ports: &[u16],
     ) -> Result<ContextData> {
         let rtsp_paths = load_paths(rtsp_paths_file)?;
         let mjpeg_paths = load_paths(mjpeg_paths_file)?;
@@ -195,14 +202,14 @@ impl ContextData {
         let mut rtsp_port_candidates = HashSet::<u16>::new();
         let mut http_port_candidates = HashSet::<u16>::new();
 
-        port_candidates.extend(RTSP_PORT_CANDIDATES);
-        port_candidates.extend(HTTP_PORT_CANDIDATES);
+        port_candidates.extend(rtsp_ports);
+        port_candidates.extend(http_ports);
 
-        rtsp_port_candidates.extend(RTSP_PORT_CANDIDATES);
-        http_port_candidates.extend(HTTP_PORT_CANDIDATES);
+        rtsp_port_candidates.extend(rtsp_ports);
+        http_port_candidates.extend(http_ports);
 
-        let rtsp_port_priorities = get_port_priorities(RTSP_PORT_CANDIDATES);
-        let http_port_priorities = get_port_priorities(HTTP_PORT_CANDIDATES);
+        let rtsp_port_priorities = get_port_priorities(rtsp_ports);
+        let http_port_priorities = get_port_priorities(http_ports);
 
         let cdata = ContextData {
             logger: logger,
@@ -213,7 +220,7 @@ impl ContextData {
             http_port_priorities: http_port_priorities,
             rtsp_paths: Arc::new(rtsp_paths),
             mjpeg_paths: Arc::new(mjpeg_paths),
-            request_timeout: Duration::from_millis(2000),
+            request_timeout: Duration::from_millis(REQUEST_TIMEOUT),
         };
 
         Ok(cdata)
@@ -259,8 +266,20 @@ struct Context {
 
 impl Context {
     /// Create a new network scanner context.
-    fn new(logger: BoxLogger, rtsp_paths_file: &str, mjpeg_paths_file: &str) -> Result<Context> {
-        let data = ContextData::new(logger, rtsp_paths_file, mjpeg_paths_file)?;
+    fn new(
+        logger: BoxLogger,
+        rtsp_paths_file: &str,
+        mjpeg_paths_file: &str,
+        rtsp_ports: &[u16],
+        http_ports: &[u16],
+    ) -> Result<Context> {
+        let data = ContextData::new(
+            logger,
+            rtsp_paths_file,
+            mjpeg_paths_file,
+            rtsp_ports,
+            http_ports,
+        )?;
 
         let context = Context {
             data: Arc::new(data),
@@ -460,8 +479,8 @@ enum StreamType {
     Error,
 }
 
-impl From<RtspResponse> for StreamType {
-    fn from(response: RtspResponse) -> StreamType {
+impl<'a> From<&'a RtspResponse> for StreamType {
+    fn from(response: &'a RtspResponse) -> StreamType {
         let status_code = response.status_code();
 
         if status_code == 200 {
@@ -480,8 +499,8 @@ impl From<RtspResponse> for StreamType {
     }
 }
 
-impl From<HttpResponse> for StreamType {
-    fn from(response: HttpResponse) -> StreamType {
+impl<'a> From<&'a HttpResponse> for StreamType {
+    fn from(response: &'a HttpResponse) -> StreamType {
         let status_code = response.status_code();
 
         if status_code == 200 {
@@ -560,13 +579,19 @@ fn is_rtsp_service(context: Context, addr: SocketAddr) -> FutureResult<bool> {
 
 /// Check if a given service is an HTTP service.
 fn is_http_service(context: Context, addr: SocketAddr) -> FutureResult<bool> {
-    let check = get_http_response(context, addr, "/").then(|result| Ok(result.is_ok()));
+    let timeout = context.get_request_timeout();
+
+    let check = get_http_response(timeout, addr, "/").then(|result| Ok(result.is_ok()));
 
     FutureResult::new(check)
 }
 
 /// Get HTTP response for a given path from a given HTTP server.
-fn get_http_response(context: Context, addr: SocketAddr, path: &str) -> FutureResult<HttpResponse> {
+fn get_http_response(
+    timeout: Duration,
+    addr: SocketAddr,
+    path: &str,
+) -> FutureResult<HttpResponse> {
     let request = HttpRequest::get_header(&format!("http://{}{}", addr, path))
         .map_err(|err| DiscoveryError::from(format!("HTTP client error: {}", err)));
 
@@ -576,7 +601,7 @@ fn get_http_response(context: Context, addr: SocketAddr, path: &str) -> FutureRe
 
     let response = request
         .unwrap()
-        .set_request_timeout(Some(context.get_request_timeout()))
+        .set_request_timeout(Some(timeout))
         .send()
         .map_err(|err| DiscoveryError::from(format!("HTTP client error: {}", err)));
 
@@ -781,28 +806,46 @@ fn get_rtsp_stream_type(
     addr: SocketAddr,
     path: &str,
 ) -> FutureResult<StreamType> {
+    let timeout = context.get_request_timeout();
+
     let path = path.to_string();
 
-    let request = RtspRequest::describe(&format!("rtsp://{}{}", addr, path));
+    let status = get_rtsp_response(timeout, addr, &path)
+        .map(move |response| get_rtsp_response_stream_type(&path, &response))
+        .or_else(|_| Ok(StreamType::Error));
 
-    if request.is_err() {
-        return FutureResult::from(Ok(StreamType::Error));
+    FutureResult::new(status)
+}
+
+/// Get RTSP DESCRIBE response for a given path from a given RTSP server.
+fn get_rtsp_response(
+    timeout: Duration,
+    addr: SocketAddr,
+    path: &str,
+) -> FutureResult<RtspResponse> {
+    let request = RtspRequest::describe(&format!("rtsp://{}{}", addr, path))
+        .map_err(|err| DiscoveryError::from(format!("RTSP client error: {}", err)));
+
+    if let Err(err) = request {
+        return FutureResult::from(Err(err));
     }
 
-    let status = request
+    let response = request
         .unwrap()
-        .set_request_timeout(Some(context.get_request_timeout()))
+        .set_request_timeout(Some(timeout))
         .send()
-        .and_then(move |response| {
-            if is_hipcam_rtsp_response(&response) && path != "/11" && path != "/12" {
-                Ok(StreamType::NotFound)
-            } else {
-                Ok(StreamType::from(response))
-            }
-        })
-        .or_else(|_| Ok(StreamType::Error));
+        .map_err(|err| DiscoveryError::from(format!("RTSP client error: {}", err)));
 
-    FutureResult::new(status)
+    FutureResult::new(response)
+}
+
+/// Get stream type for a given RTSP DESCRIBE response.
+fn get_rtsp_response_stream_type(path: &str, response: &RtspResponse) -> StreamType {
+    if is_hipcam_rtsp_response(response) && path != "/11" && path != "/12" {
+        StreamType::NotFound
+    } else {
+        StreamType::from(response)
+    }
 }
 
 /// Check if a given RTSP response is from a buggy Hi(I)pcam RTSP server.
@@ -862,8 +905,10 @@ fn get_mjpeg_stream(
 ) -> FutureResult<Option<Service>> {
     let path = path.to_string();
 
-    let service = get_http_response(context, addr, &path)
-        .map(move |response| match StreamType::from(response) {
+    let timeout = context.get_request_timeout();
+
+    let service = get_http_response(timeout, addr, &path)
+        .map(move |response| match StreamType::from(&response) {
             StreamType::Supported => Some(Service::mjpeg(mac, addr, path)),
             StreamType::Locked => Some(Service::locked_mjpeg(mac, addr, None)),
 
@@ -874,6 +919,106 @@ fn get_mjpeg_stream(
     FutureResult::new(service)
 }
 
+/// Probe a given service using the same checks as the network scanner. The
+/// service table is not modified.
+pub fn probe_service(svc_type: ServiceType, addr: SocketAddr, path: Option<&str>) -> ProbeResult {
+    let timeout = Duration::from_millis(REQUEST_TIMEOUT);
+
+    let path = path.unwrap_or("/");
+
+    let runtime = tokio::runtime::current_thread::Runtime::new();
+
+    if runtime.is_err() {
+        return ProbeResult::new(ProbeStatus::Error);
+    }
+
+    let mut runtime = runtime.unwrap();
+
+    let start = Instant::now();
+
+    let result = match svc_type {
+        ServiceType::RTSP
+        | ServiceType::LockedRTSP
+        | ServiceType::UnknownRTSP
+        | ServiceType::UnsupportedRTSP => runtime
+            .block_on(get_rtsp_response(timeout, addr, path))
+            .map(|response| {
+                let stream_type = get_rtsp_response_stream_type(path, &response);
+
+                let mut result = ProbeResult::new(ProbeStatus::from(stream_type));
+
+                result.status_code = response.status_code();
+                result.server = response
+                    .get_header_field_value("server")
+                    .map(|server| server.to_string());
+                result.codecs = get_sdp_codecs(response.body());
+
+                result
+            }),
+        ServiceType::HTTP | ServiceType::MJPEG | ServiceType::LockedMJPEG => runtime
+            .block_on(get_http_response(timeout, addr, path))
+            .map(|response| {
+                let stream_type = match StreamType::from(&response) {
+                    // any response is fine for a general HTTP service
+                    StreamType::Unsupported if svc_type == ServiceType::HTTP => {
+                        StreamType::Supported
+                    }
+                    stream_type => stream_type,
+                };
+
+                let mut result = ProbeResult::new(ProbeStatus::from(stream_type));
+
+                result.status_code = response.status_code();
+                result.server = response
+                    .get_header_field_value("server")
+                    .map(|server| server.to_string());
+
+                result
+            }),
+        ServiceType::TCP => TcpStream::connect_timeout(&addr, timeout)
+            .map(|_| ProbeResult::new(ProbeStatus::Supported))
+            .map_err(DiscoveryError::from),
+        ServiceType::ControlProtocol => return ProbeResult::new(ProbeStatus::UnknownService),
+    };
+
+    let mut result = result.unwrap_or_else(|_| ProbeResult::new(ProbeStatus::Unreachable));
+
+    result.latency = start.elapsed();
+
+    result
+}
+
+/// Get encodings of all media streams in a given session description.
+fn get_sdp_codecs(sdp: &[u8]) -> Vec<String> {
+    let mut codecs = Vec::new();
+
+    if let Ok(sdp) = SessionDescription::parse(sdp) {
+        for md in sdp.media_descriptions {
+            for attr in md.attributes {
+                if let Ok(rtpmap) = RTPMap::from_attr(&attr) {
+                    if !codecs.contains(&rtpmap.encoding) {
+                        codecs.push(rtpmap.encoding);
+                    }
+                }
+            }
+        }
+    }
+
+    codecs
+}
+
+impl From<StreamType> for ProbeStatus {
+    fn from(stream_type: StreamType) -> ProbeStatus {
+        match stream_type {
+            StreamType::Supported => ProbeStatus::Supported,
+            StreamType::Locked => ProbeStatus::Locked,
+            StreamType::Unsupported => ProbeStatus::Unsupported,
+            StreamType::NotFound => ProbeStatus::NotFound,
+            StreamType::Error => ProbeStatus::Error,
+        }
+    }
+}
+
 /// Get a list of distinct hosts from a given list of services.
 fn get_hosts(services: &[Service]) -> Vec<IpAddr> {
     let mut hosts = HashSet::new();
@@ -15,6 +15,15 @@
 #[cfg(feature = "discovery")]
 pub mod discovery;
 
+pub mod netdiag;
+pub mod probe;
 pub mod result;
 
+/// Default RTSP port candidates (sorted by priority).
+pub const RTSP_PORT_CANDIDATES: &[u16] = &[554, 88, 81, 555, 7447, 8554, 7070, 10554, 80, 6667];
+
+/// Default HTTP port candidates (sorted by priority).
+pub const HTTP_PORT_CANDIDATES: &[u16] = &[80, 81, 8080, 8081, 8090];
+
+pub use self::probe::{ProbeResult, ProbeStatus};
 pub use self::result::{HostRecord, ScanResult, HR_FLAG_ARP, HR_FLAG_ICMP};
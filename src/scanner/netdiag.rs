@@ -0,0 +1,436 @@
+// Copyright 2019 click2stream, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Network diagnostics (ICMP ping sweep and TCP SYN traceroute) limited to
+//! hosts within local networks. A traceroute can also target hosts with
+//! private addresses that are reachable through a local router.
+
+#[cfg(feature = "discovery")]
+use std::fs;
+
+use std::net::Ipv4Addr;
+use std::time::Duration;
+
+use crate::net::raw::ether::MacAddr;
+
+#[cfg(feature = "discovery")]
+use crate::net::raw::arp::scanner::Ipv4ArpScanner;
+#[cfg(feature = "discovery")]
+use crate::net::raw::devices::EthernetDevice;
+#[cfg(feature = "discovery")]
+use crate::net::raw::icmp::scanner::IcmpScanner;
+#[cfg(feature = "discovery")]
+use crate::net::raw::tcp::scanner::{TcpTraceResponse, TcpTracer};
+#[cfg(feature = "discovery")]
+use crate::net::raw::tcp::{TCP_FLAG_ACK, TCP_FLAG_SYN};
+#[cfg(feature = "discovery")]
+use crate::net::utils::Ipv4AddrEx;
+
+/// Minimum network prefix length of a ping sweep (i.e. at most 256
+/// addresses can be pinged at once).
+pub const MIN_SWEEP_PREFIX_LENGTH: u8 = 24;
+
+/// Maximum number of hops of a traceroute.
+pub const MAX_TRACEROUTE_HOPS: u8 = 30;
+
+/// Minimum time between the end of a network diagnostic and the start of
+/// another one.
+pub const MIN_DIAGNOSTIC_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Delay between every two packets sent.
+#[cfg(feature = "discovery")]
+const PACKET_INTERVAL: Duration = Duration::from_millis(20);
+
+/// File containing the IPv4 routing table.
+#[cfg(feature = "discovery")]
+const ROUTE_TABLE_FILE: &str = "/proc/net/route";
+
+/// Route flag indicating that the route is up.
+#[cfg(any(test, feature = "discovery"))]
+const RTF_UP: u32 = 0x0001;
+
+/// Route flag indicating that the destination is reachable through
+/// a gateway.
+#[cfg(any(test, feature = "discovery"))]
+const RTF_GATEWAY: u32 = 0x0002;
+
+/// Network diagnostic status.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum DiagnosticStatus {
+    /// The diagnostic has been completed.
+    Ok,
+    /// Another network diagnostic (or a network scan) is running or the
+    /// last one finished too recently.
+    RateLimited,
+    /// The target is not within any local network (or it is not a routed
+    /// private address in case of a traceroute) or the diagnostic parameters
+    /// exceed the limits.
+    InvalidTarget,
+    /// The target host (or the router in case of a routed traceroute) did
+    /// not respond to ARP requests.
+    Unreachable,
+    /// Packets could not be sent or captured.
+    Error,
+    /// The target is not allowed by the local destination policy.
+    Denied,
+    /// Network diagnostics are not available (e.g. the client was built
+    /// without the network discovery feature).
+    NotAvailable,
+}
+
+/// Ping sweep report.
+#[derive(Debug, Clone)]
+pub struct PingSweepReport {
+    /// Diagnostic status.
+    pub status: DiagnosticStatus,
+    /// Hosts that responded to ICMP echo requests.
+    pub hosts: Vec<(MacAddr, Ipv4Addr)>,
+}
+
+impl PingSweepReport {
+    /// Create a new empty report with a given status.
+    pub fn new(status: DiagnosticStatus) -> PingSweepReport {
+        PingSweepReport {
+            status,
+            hosts: Vec::new(),
+        }
+    }
+}
+
+/// Traceroute hop type.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum HopType {
+    /// A router responded with ICMP time exceeded.
+    TimeExceeded,
+    /// The target host responded with SYN-ACK.
+    PortOpen,
+    /// The target host responded with RST.
+    PortClosed,
+}
+
+/// Traceroute hop.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct TraceHop {
+    /// TTL of the packet that caused the response.
+    pub ttl: u8,
+    /// Address of the responding host.
+    pub addr: Ipv4Addr,
+    /// Response type.
+    pub hop_type: HopType,
+}
+
+/// Traceroute report.
+#[derive(Debug, Clone)]
+pub struct TracerouteReport {
+    /// Diagnostic status.
+    pub status: DiagnosticStatus,
+    /// Hops sorted by TTL (hops that did not respond are missing).
+    pub hops: Vec<TraceHop>,
+}
+
+impl TracerouteReport {
+    /// Create a new empty report with a given status.
+    pub fn new(status: DiagnosticStatus) -> TracerouteReport {
+        TracerouteReport {
+            status,
+            hops: Vec::new(),
+        }
+    }
+}
+
+/// Ping all hosts within a given network. The network must be a part of
+/// a local network.
+#[cfg(feature = "discovery")]
+pub fn ping_sweep(network: Ipv4Addr, prefix_length: u8) -> PingSweepReport {
+    if !(MIN_SWEEP_PREFIX_LENGTH..=32).contains(&prefix_length) {
+        return PingSweepReport::new(DiagnosticStatus::InvalidTarget);
+    }
+
+    let device = EthernetDevice::list().into_iter().find(|device| {
+        let prefix = device.netmask.as_u32().count_ones() as u8;
+
+        prefix <= prefix_length && is_local_address(device, network)
+    });
+
+    let device = match device {
+        Some(device) => device,
+        None => return PingSweepReport::new(DiagnosticStatus::InvalidTarget),
+    };
+
+    match IcmpScanner::sweep(&device, network, prefix_length, PACKET_INTERVAL) {
+        Ok(hosts) => PingSweepReport {
+            status: DiagnosticStatus::Ok,
+            hosts,
+        },
+        Err(_) => PingSweepReport::new(DiagnosticStatus::Error),
+    }
+}
+
+/// Trace a given port of a given host using TTL-limited TCP SYN packets.
+/// The host must be either within a local network or it must have a private
+/// address reachable through a router within a local network.
+#[cfg(feature = "discovery")]
+pub fn traceroute(host: Ipv4Addr, port: u16, max_hops: u8) -> TracerouteReport {
+    if !(1..=MAX_TRACEROUTE_HOPS).contains(&max_hops) {
+        return TracerouteReport::new(DiagnosticStatus::InvalidTarget);
+    }
+
+    let devices = EthernetDevice::list();
+
+    let local = devices
+        .iter()
+        .find(|device| is_local_address(device, host) && device.ip_addr != host);
+
+    // packets to routed private hosts are sent to the router
+    let (device, next_hop) = if let Some(device) = local {
+        (device.clone(), host)
+    } else if host.is_private() {
+        let route = fs::read_to_string(ROUTE_TABLE_FILE)
+            .ok()
+            .and_then(|table| find_route(&table, host));
+
+        let route = match route {
+            Some(route) => route,
+            None => return TracerouteReport::new(DiagnosticStatus::InvalidTarget),
+        };
+
+        let device = devices.iter().find(|device| {
+            device.name == route.iface
+                && route
+                    .gateway
+                    .map(|gateway| is_local_address(device, gateway))
+                    .unwrap_or(false)
+        });
+
+        match (device, route.gateway) {
+            (Some(device), Some(gateway)) => (device.clone(), gateway),
+            _ => return TracerouteReport::new(DiagnosticStatus::InvalidTarget),
+        }
+    } else {
+        return TracerouteReport::new(DiagnosticStatus::InvalidTarget);
+    };
+
+    let mac = match Ipv4ArpScanner::resolve(&device, next_hop) {
+        Ok(Some(mac)) => mac,
+        Ok(None) => return TracerouteReport::new(DiagnosticStatus::Unreachable),
+        Err(_) => return TracerouteReport::new(DiagnosticStatus::Error),
+    };
+
+    let responses = match TcpTracer::trace(&device, mac, host, port, max_hops, PACKET_INTERVAL) {
+        Ok(responses) => responses,
+        Err(_) => return TracerouteReport::new(DiagnosticStatus::Error),
+    };
+
+    let syn_ack = TCP_FLAG_SYN | TCP_FLAG_ACK;
+
+    let mut hops = Vec::new();
+
+    for (ttl, response) in responses {
+        let (addr, hop_type) = match response {
+            TcpTraceResponse::TimeExceeded(addr) => (addr, HopType::TimeExceeded),
+            TcpTraceResponse::Tcp(flags) if (flags & syn_ack) == syn_ack => {
+                (host, HopType::PortOpen)
+            }
+            TcpTraceResponse::Tcp(_) => (host, HopType::PortClosed),
+        };
+
+        hops.push(TraceHop {
+            ttl,
+            addr,
+            hop_type,
+        });
+
+        // all packets with higher TTL would reach the host as well
+        if hop_type != HopType::TimeExceeded {
+            break;
+        }
+    }
+
+    TracerouteReport {
+        status: DiagnosticStatus::Ok,
+        hops,
+    }
+}
+
+/// IPv4 route.
+#[cfg(any(test, feature = "discovery"))]
+#[derive(Debug, Clone, Eq, PartialEq)]
+struct Route {
+    /// Name of the network interface.
+    iface: String,
+    /// Gateway (if the destination is not directly reachable).
+    gateway: Option<Ipv4Addr>,
+}
+
+/// Find the most specific route to a given host within a given routing
+/// table (in the /proc/net/route format).
+#[cfg(any(test, feature = "discovery"))]
+fn find_route(table: &str, host: Ipv4Addr) -> Option<Route> {
+    // addresses in the table are in the network byte order
+    let parse_hex = |field: &str| u32::from_str_radix(field, 16).ok();
+
+    let host = u32::from_ne_bytes(host.octets());
+
+    let mut best: Option<(u32, u32, Route)> = None;
+
+    for line in table.lines().skip(1) {
+        let fields = line.split_whitespace().collect::<Vec<_>>();
+
+        if fields.len() < 8 {
+            continue;
+        }
+
+        let entry = (
+            parse_hex(fields[1]),
+            parse_hex(fields[2]),
+            parse_hex(fields[3]),
+            fields[6].parse::<u32>().ok(),
+            parse_hex(fields[7]),
+        );
+
+        let (destination, gateway, flags, metric, mask) = match entry {
+            (Some(d), Some(g), Some(f), Some(m), Some(n)) => (d, g, f, m, n),
+            _ => continue,
+        };
+
+        if (flags & RTF_UP) == 0 || (host & mask) != destination {
+            continue;
+        }
+
+        let prefix_length = mask.count_ones();
+
+        if let Some((best_length, best_metric, _)) = best.as_ref() {
+            if prefix_length < *best_length
+                || (prefix_length == *best_length && metric >= *best_metric)
+            {
+                continue;
+            }
+        }
+
+        let gateway = if (flags & RTF_GATEWAY) != 0 {
+            Some(Ipv4Addr::from(gateway.to_ne_bytes()))
+        } else {
+            None
+        };
+
+        let route = Route {
+            iface: fields[0].to_string(),
+            gateway,
+        };
+
+        best = Some((prefix_length, metric, route));
+    }
+
+    best.map(|(_, _, route)| route)
+}
+
+/// Check if a given address is within the network of a given device.
+#[cfg(feature = "discovery")]
+fn is_local_address(device: &EthernetDevice, addr: Ipv4Addr) -> bool {
+    let mask = device.netmask.as_u32();
+
+    (device.ip_addr.as_u32() & mask) == (addr.as_u32() & mask)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Format a given address in the /proc/net/route format.
+    fn hex(addr: [u8; 4]) -> String {
+        format!("{:08X}", u32::from_ne_bytes(addr))
+    }
+
+    #[test]
+    fn test_find_route() {
+        let header =
+            "Iface\tDestination\tGateway\tFlags\tRefCnt\tUse\tMetric\tMask\tMTU\tWindow\tIRTT";
+
+        let routes = [
+            ("eth0", [0, 0, 0, 0], [192, 168, 1, 1], 3, 100, [0, 0, 0, 0]),
+            (
+                "eth0",
+                [192, 168, 1, 0],
+                [0, 0, 0, 0],
+                1,
+                100,
+                [255, 255, 255, 0],
+            ),
+            (
+                "eth1",
+                [10, 0, 0, 0],
+                [192, 168, 1, 254],
+                3,
+                100,
+                [255, 0, 0, 0],
+            ),
+            (
+                "eth2",
+                [10, 1, 0, 0],
+                [192, 168, 1, 253],
+                3,
+                100,
+                [255, 255, 0, 0],
+            ),
+            (
+                "eth3",
+                [10, 1, 0, 0],
+                [192, 168, 1, 252],
+                3,
+                50,
+                [255, 255, 0, 0],
+            ),
+            (
+                "eth4",
+                [172, 16, 0, 0],
+                [192, 168, 1, 251],
+                2,
+                0,
+                [255, 240, 0, 0],
+            ),
+        ];
+
+        let mut table = header.to_string();
+
+        for (iface, destination, gateway, flags, metric, mask) in routes.iter() {
+            table += &format!(
+                "\n{}\t{}\t{}\t{:04X}\t0\t0\t{}\t{}\t0\t0\t0",
+                iface,
+                hex(*destination),
+                hex(*gateway),
+                flags,
+                metric,
+                hex(*mask)
+            );
+        }
+
+        let route = |iface: &str, gateway: Option<[u8; 4]>| {
+            Some(Route {
+                iface: iface.to_string(),
+                gateway: gateway.map(Ipv4Addr::from),
+            })
+        };
+
+        let find = |addr: [u8; 4]| find_route(&table, Ipv4Addr::from(addr));
+
+        assert_eq!(find([192, 168, 1, 10]), route("eth0", None));
+        assert_eq!(find([10, 2, 0, 1]), route("eth1", Some([192, 168, 1, 254])));
+        assert_eq!(find([10, 1, 0, 1]), route("eth3", Some([192, 168, 1, 252])));
+
+        // routes that are not up are ignored
+        assert_eq!(find([172, 16, 0, 1]), route("eth0", Some([192, 168, 1, 1])));
+
+        assert_eq!(find_route(header, Ipv4Addr::new(10, 0, 0, 1)), None);
+    }
+}
@@ -0,0 +1,69 @@
+// Copyright 2019 click2stream, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::time::Duration;
+
+/// Service probe status.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ProbeStatus {
+    /// The service responded and the stream is supported.
+    Supported,
+    /// The service requires authorization.
+    Locked,
+    /// The service responded but the stream is not supported.
+    Unsupported,
+    /// The service responded but the path was not found.
+    NotFound,
+    /// The service responded with an unexpected status code.
+    Error,
+    /// The service did not respond.
+    Unreachable,
+    /// The service is not known (or it cannot be probed).
+    UnknownService,
+    /// Service probing is not available (e.g. the client was built without
+    /// the network discovery feature).
+    NotAvailable,
+    /// The service is not allowed by the local destination policy.
+    Denied,
+    /// There are too many service probes running at the moment.
+    RateLimited,
+}
+
+/// Result of a service probe.
+#[derive(Debug, Clone)]
+pub struct ProbeResult {
+    /// Probe status.
+    pub status: ProbeStatus,
+    /// RTSP/HTTP status code (0 if there is no status code).
+    pub status_code: u16,
+    /// Value of the Server header.
+    pub server: Option<String>,
+    /// Encodings of all media streams in the session description.
+    pub codecs: Vec<String>,
+    /// Time elapsed until the service responded.
+    pub latency: Duration,
+}
+
+impl ProbeResult {
+    /// Create a new probe result with a given status.
+    pub fn new(status: ProbeStatus) -> ProbeResult {
+        ProbeResult {
+            status,
+            status_code: 0,
+            server: None,
+            codecs: Vec::new(),
+            latency: Duration::from_secs(0),
+        }
+    }
+}
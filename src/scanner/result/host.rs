@@ -16,7 +16,10 @@ use std::collections::hash_set::Iter as HashSetIterator;
 use std::collections::HashSet;
 use std::net::{IpAddr, SocketAddr, SocketAddrV4, SocketAddrV6};
 
+use json::JsonValue;
+
 use crate::net::raw::ether::MacAddr;
+use crate::utils::json::ToJson;
 
 pub const HR_FLAG_ARP: u8 = 0x01;
 pub const HR_FLAG_ICMP: u8 = 0x02;
@@ -134,3 +137,19 @@ impl<'a> ExactSizeIterator for SocketAddrIterator<'a> {
         self.port_iterator.len()
     }
 }
+
+impl ToJson for HostRecord {
+    fn to_json(&self) -> JsonValue {
+        let mut ports = self.ports().collect::<Vec<_>>();
+
+        ports.sort();
+
+        object! {
+            "mac" => format!("{}", self.mac),
+            "ip" => format!("{}", self.ip),
+            "arp" => (self.flags & HR_FLAG_ARP) != 0,
+            "icmp" => (self.flags & HR_FLAG_ICMP) != 0,
+            "ports" => ports
+        }
+    }
+}
@@ -18,8 +18,11 @@ use std::collections::hash_map::Iter as HashMapIterator;
 use std::collections::HashMap;
 use std::net::{IpAddr, SocketAddr};
 
+use json::JsonValue;
+
 use crate::net::raw::ether::MacAddr;
 use crate::svc_table::{Service, ServiceIdentifier};
+use crate::utils::json::ToJson;
 
 pub use self::host::HR_FLAG_ARP;
 pub use self::host::HR_FLAG_ICMP;
@@ -200,3 +203,16 @@ impl<'a> Iterator for SocketAddrIterator<'a> {
         None
     }
 }
+
+impl ToJson for ScanResult {
+    fn to_json(&self) -> JsonValue {
+        let hosts = self.hosts().map(|host| host.to_json()).collect::<Vec<_>>();
+
+        let services = self.services().map(|svc| svc.to_json()).collect::<Vec<_>>();
+
+        object! {
+            "hosts" => hosts,
+            "services" => services
+        }
+    }
+}
@@ -12,12 +12,13 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+pub mod policy;
 pub mod service;
 
 use std;
 use std::fmt;
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fmt::{Display, Formatter};
 use std::hash::{Hash, Hasher};
 use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
@@ -32,6 +33,9 @@ use time;
 use crate::net::raw::ether::MacAddr;
 use crate::utils::json::{FromJson, ParseError, ToJson};
 
+pub use self::policy::{
+    DestinationPolicy, HttpPathPolicy, HttpPathRule, IpNetwork, PolicyError, ServicePathPolicy,
+};
 pub use self::service::{
     Service, ServiceIdentifier, ServiceType, SVC_TYPE_CONTROL_PROTOCOL, SVC_TYPE_HTTP,
     SVC_TYPE_LOCKED_MJPEG, SVC_TYPE_LOCKED_RTSP, SVC_TYPE_MJPEG, SVC_TYPE_RTSP, SVC_TYPE_TCP,
@@ -108,6 +112,19 @@ impl ServiceTable for Box<dyn ServiceTable + Send + Sync> {
     }
 }
 
+/// Fields of serialized service table elements known to this version of the
+/// application.
+const KNOWN_ELEMENT_FIELDS: &[&str] = &[
+    "id",
+    "svc_type",
+    "mac",
+    "address",
+    "path",
+    "static_svc",
+    "last_seen",
+    "active",
+];
+
 /// Service table element.
 #[derive(Clone)]
 struct ServiceTableElement {
@@ -123,6 +140,8 @@ struct ServiceTableElement {
     last_seen: i64,
     /// Active flag.
     active: bool,
+    /// Unknown fields (e.g. written by a newer version of the application).
+    extra: JsonValue,
 }
 
 impl ServiceTableElement {
@@ -140,6 +159,7 @@ impl ServiceTableElement {
             enabled: enabled,
             last_seen: get_utc_timestamp(),
             active: true,
+            extra: JsonValue::new_object(),
         }
     }
 
@@ -181,7 +201,7 @@ impl ToJson for ServiceTableElement {
         let address = self.service.address().unwrap_or(default_address);
         let path = self.service.path().unwrap_or("");
 
-        object! {
+        let mut res = object! {
             "id" => self.id,
             "svc_type" => svc_type.code(),
             "mac" => format!("{}", mac),
@@ -190,7 +210,15 @@ impl ToJson for ServiceTableElement {
             "static_svc" => self.static_service,
             "last_seen" => self.last_seen,
             "active" => self.active
+        };
+
+        for (key, value) in self.extra.entries() {
+            if !res.has_key(key) {
+                res[key] = value.clone();
+            }
         }
+
+        res
     }
 }
 
@@ -268,6 +296,14 @@ impl FromJson for ServiceTableElement {
             .and_then(|v| v.as_bool())
             .unwrap_or(true);
 
+        let mut extra = JsonValue::new_object();
+
+        for (key, value) in service.iter() {
+            if !KNOWN_ELEMENT_FIELDS.contains(&key) {
+                extra[key] = value.clone();
+            }
+        }
+
         let elem = ServiceTableElement {
             id: id,
             service: svc?,
@@ -275,12 +311,48 @@ impl FromJson for ServiceTableElement {
             last_seen: last_seen,
             active: active,
             enabled: false,
+            extra,
         };
 
         Ok(elem)
     }
 }
 
+/// Description of a service table element including its visibility.
+#[derive(Clone)]
+pub struct ServiceTableEntry {
+    pub id: u16,
+    pub service: Service,
+    pub static_service: bool,
+    pub visible: bool,
+    pub last_seen: i64,
+}
+
+impl ToJson for ServiceTableEntry {
+    fn to_json(&self) -> JsonValue {
+        let mut res = self.service.to_json();
+
+        res["id"] = self.id.into();
+        res["static_svc"] = self.static_service.into();
+        res["visible"] = self.visible.into();
+        res["last_seen"] = self.last_seen.into();
+
+        res
+    }
+}
+
+impl<'a> From<&'a ServiceTableElement> for ServiceTableEntry {
+    fn from(elem: &'a ServiceTableElement) -> ServiceTableEntry {
+        ServiceTableEntry {
+            id: elem.id,
+            service: elem.to_service(),
+            static_service: elem.static_service,
+            visible: elem.is_visible(),
+            last_seen: elem.last_seen,
+        }
+    }
+}
+
 /// Service table internal data.
 #[derive(Clone)]
 struct ServiceTableData {
@@ -352,6 +424,21 @@ impl ServiceTableData {
         ServiceTableIterator::new(visible)
     }
 
+    /// Get all services (except the Control Protocol service) including the
+    /// invisible ones. The entries are sorted by service ID.
+    fn entries(&self) -> Vec<ServiceTableEntry> {
+        let mut entries = self
+            .service_map
+            .values()
+            .filter(|elem| !elem.service.is_control())
+            .map(ServiceTableEntry::from)
+            .collect::<Vec<_>>();
+
+        entries.sort_by_key(|entry| entry.id);
+
+        entries
+    }
+
     /// Insert a new element into the table and return its ID.
     fn add_service(&mut self, svc: Service, static_svc: bool, enabled: bool) -> u16 {
         let key = svc.to_service_identifier();
@@ -421,6 +508,83 @@ impl ServiceTableData {
         }
     }
 
+    /// Replace the set of enabled static services with a given one. All other
+    /// static services will be disabled. Discovered services are not affected.
+    /// The method returns a list of newly enabled services and a list of
+    /// disabled services.
+    fn set_static_services<I>(&mut self, services: I) -> (Vec<Service>, Vec<Service>)
+    where
+        I: IntoIterator<Item = Service>,
+    {
+        let mut enabled = HashSet::new();
+        let mut added = Vec::new();
+        let mut removed = Vec::new();
+
+        for svc in services {
+            let key = svc.to_service_identifier();
+
+            let was_enabled = self
+                .identifier_map
+                .get(&key)
+                .and_then(|id| self.service_map.get(id))
+                .map(|elem| elem.static_service && elem.enabled)
+                .unwrap_or(false);
+
+            if !was_enabled {
+                added.push(svc.clone());
+            }
+
+            enabled.insert(self.update(svc, true, true));
+        }
+
+        let disabled = self
+            .service_map
+            .values()
+            .filter(|elem| elem.static_service && elem.enabled)
+            .filter(|elem| !elem.service.is_control() && !enabled.contains(&elem.id))
+            .map(|elem| (elem.id, elem.to_service()))
+            .collect::<Vec<_>>();
+
+        for (id, svc) in disabled {
+            self.update_element(id, svc.clone(), false);
+
+            removed.push(svc);
+        }
+
+        (added, removed)
+    }
+
+    /// Remove all services (except the Control Protocol service) matching
+    /// a given predicate from the table and return them.
+    fn remove_services<F>(&mut self, mut predicate: F) -> Vec<Service>
+    where
+        F: FnMut(&Service) -> bool,
+    {
+        let ids = self
+            .service_map
+            .values()
+            .filter(|elem| !elem.service.is_control() && predicate(&elem.service))
+            .map(|elem| elem.id)
+            .collect::<Vec<_>>();
+
+        let mut removed = Vec::new();
+
+        for id in ids {
+            let elem = self.service_map.remove(&id).expect("broken service table");
+
+            self.identifier_map
+                .remove(&elem.service.to_service_identifier());
+
+            if elem.is_visible() {
+                self.version += 1;
+            }
+
+            removed.push(elem.service);
+        }
+
+        removed
+    }
+
     /// Update active flags of all services.
     fn update_active_services(&mut self) {
         let timestamp = get_utc_timestamp();
@@ -437,10 +601,17 @@ impl ServiceTableData {
 
 impl ToJson for ServiceTableData {
     fn to_json(&self) -> JsonValue {
-        let services = self
+        let mut elements = self
             .service_map
             .values()
             .filter(|elem| !elem.service.is_control())
+            .collect::<Vec<_>>();
+
+        // keep the output stable
+        elements.sort_by_key(|elem| elem.id);
+
+        let services = elements
+            .into_iter()
             .map(|elem| elem.to_json())
             .collect::<Vec<_>>();
 
@@ -554,6 +725,26 @@ impl SharedServiceTable {
         self.data.lock().unwrap().update(svc, true, true)
     }
 
+    /// Replace the set of enabled static services with a given one. All other
+    /// static services will be disabled. Discovered services are not affected.
+    /// The method returns a list of newly enabled services and a list of
+    /// disabled services.
+    pub fn set_static_services<I>(&mut self, services: I) -> (Vec<Service>, Vec<Service>)
+    where
+        I: IntoIterator<Item = Service>,
+    {
+        self.data.lock().unwrap().set_static_services(services)
+    }
+
+    /// Remove all services (except the Control Protocol service) matching
+    /// a given predicate from the table and return them.
+    pub fn remove_services<F>(&mut self, predicate: F) -> Vec<Service>
+    where
+        F: FnMut(&Service) -> bool,
+    {
+        self.data.lock().unwrap().remove_services(predicate)
+    }
+
     /// Update active flags of all services.
     pub fn update_active_services(&mut self) {
         self.data.lock().unwrap().update_active_services()
@@ -631,6 +822,12 @@ impl SharedServiceTableRef {
     pub fn visible(&self) -> ServiceTableIterator {
         self.data.lock().unwrap().visible()
     }
+
+    /// Get all services (except the Control Protocol service) including the
+    /// invisible ones. The entries are sorted by service ID.
+    pub fn entries(&self) -> Vec<ServiceTableEntry> {
+        self.data.lock().unwrap().entries()
+    }
 }
 
 impl ServiceTable for SharedServiceTableRef {
@@ -880,3 +1077,78 @@ fn test_deserialization_and_initialization() {
 
     assert_eq!(internal.version(), 5);
 }
+
+#[cfg(test)]
+#[test]
+fn test_static_services_replacement() {
+    let mut table = ServiceTableData::new();
+
+    let mac = MacAddr::zero();
+    let ip = Ipv4Addr::new(0, 0, 0, 0);
+    let addr = SocketAddr::V4(SocketAddrV4::new(ip, 0));
+
+    let svc_1 = Service::rtsp(mac, addr, "/1".to_string());
+    let svc_2 = Service::rtsp(mac, addr, "/2".to_string());
+    let svc_3 = Service::rtsp(mac, addr, "/3".to_string());
+    let svc_4 = Service::rtsp(mac, addr, "/4".to_string());
+
+    table.update(svc_1.clone(), true, true);
+    table.update(svc_2.clone(), true, true);
+    table.update(svc_3.clone(), false, true);
+
+    let version = table.version();
+
+    let (added, removed) = table.set_static_services(vec![svc_2.clone(), svc_4.clone()]);
+
+    assert_eq!(added, vec![svc_4.clone()]);
+    assert_eq!(removed, vec![svc_1.clone()]);
+    assert_eq!(table.version(), version + 2);
+
+    let mut visible = table
+        .visible()
+        .map(|(_, svc)| svc)
+        .map(|svc| svc.path().unwrap().to_string())
+        .collect::<Vec<_>>();
+
+    visible.sort();
+
+    assert_eq!(visible, vec!["/2", "/3", "/4"]);
+
+    // the same set of services should not change anything
+    let (added, removed) = table.set_static_services(vec![svc_4, svc_2]);
+
+    assert!(added.is_empty());
+    assert!(removed.is_empty());
+    assert_eq!(table.version(), version + 2);
+}
+
+#[cfg(test)]
+#[test]
+fn test_services_removal() {
+    let mut table = ServiceTableData::new();
+
+    let mac = MacAddr::zero();
+    let ip = Ipv4Addr::new(0, 0, 0, 0);
+    let addr = SocketAddr::V4(SocketAddrV4::new(ip, 0));
+
+    let svc_1 = Service::rtsp(mac, addr, "/1".to_string());
+    let svc_2 = Service::rtsp(mac, addr, "/2".to_string());
+
+    table.update(svc_1.clone(), true, true);
+    let id_2 = table.update(svc_2.clone(), false, true);
+
+    let version = table.version();
+
+    let removed = table.remove_services(|svc| svc.path() == Some("/1"));
+
+    assert_eq!(removed, vec![svc_1.clone()]);
+    assert_eq!(table.version(), version + 1);
+    assert_eq!(table.get_id(&svc_1.to_service_identifier()), None);
+    assert_eq!(table.get_id(&svc_2.to_service_identifier()), Some(id_2));
+
+    // the Control Protocol service is never removed
+    let removed = table.remove_services(|_| true);
+
+    assert_eq!(removed, vec![svc_2]);
+    assert!(table.get(0).is_some());
+}
@@ -0,0 +1,647 @@
+// Copyright 2019 click2stream, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Local policy of allowed service destinations and HTTP paths.
+
+use std::fmt;
+
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::net::{IpAddr, SocketAddr};
+use std::str::FromStr;
+
+use crate::svc_table::{Service, ServiceType};
+
+/// Names of service types that can be used in the destination policy. Each
+/// name covers also all variants of the corresponding service type (e.g.
+/// "rtsp" covers also locked RTSP services).
+pub const POLICY_SERVICE_TYPES: &[&str] = &["rtsp", "mjpeg", "http", "tcp"];
+
+/// Destination policy error.
+#[derive(Debug, Clone)]
+pub struct PolicyError {
+    msg: String,
+}
+
+impl Error for PolicyError {
+    fn description(&self) -> &str {
+        &self.msg
+    }
+}
+
+impl Display for PolicyError {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
+        f.write_str(&self.msg)
+    }
+}
+
+impl From<String> for PolicyError {
+    fn from(msg: String) -> PolicyError {
+        PolicyError { msg }
+    }
+}
+
+impl<'a> From<&'a str> for PolicyError {
+    fn from(msg: &'a str) -> PolicyError {
+        PolicyError::from(msg.to_string())
+    }
+}
+
+/// IPv4 or IPv6 network given by an address and a prefix length.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct IpNetwork {
+    addr: IpAddr,
+    prefix_length: u8,
+}
+
+impl IpNetwork {
+    /// Create a new network. Host bits of the address are cleared.
+    pub fn new(addr: IpAddr, prefix_length: u8) -> Result<IpNetwork, PolicyError> {
+        let addr = match addr {
+            IpAddr::V4(addr) if prefix_length <= 32 => {
+                IpAddr::V4((u32::from(addr) & ipv4_mask(prefix_length)).into())
+            }
+            IpAddr::V6(addr) if prefix_length <= 128 => {
+                IpAddr::V6((u128::from(addr) & ipv6_mask(prefix_length)).into())
+            }
+            _ => {
+                return Err(PolicyError::from(format!(
+                    "invalid prefix length: {}",
+                    prefix_length
+                )))
+            }
+        };
+
+        let res = IpNetwork {
+            addr,
+            prefix_length,
+        };
+
+        Ok(res)
+    }
+
+    /// Check if a given address belongs to the network.
+    pub fn contains(&self, addr: IpAddr) -> bool {
+        match (self.addr, addr) {
+            (IpAddr::V4(network), IpAddr::V4(addr)) => {
+                (u32::from(addr) & ipv4_mask(self.prefix_length)) == u32::from(network)
+            }
+            (IpAddr::V6(network), IpAddr::V6(addr)) => {
+                (u128::from(addr) & ipv6_mask(self.prefix_length)) == u128::from(network)
+            }
+            _ => false,
+        }
+    }
+
+    /// Check if a given network is a subnet of this network.
+    pub fn contains_network(&self, network: &IpNetwork) -> bool {
+        self.prefix_length <= network.prefix_length && self.contains(network.addr)
+    }
+}
+
+impl FromStr for IpNetwork {
+    type Err = PolicyError;
+
+    /// Parse a network in the CIDR notation. A single address without the
+    /// prefix length is also accepted.
+    fn from_str(s: &str) -> Result<IpNetwork, PolicyError> {
+        let invalid = || PolicyError::from(format!("invalid network: \"{}\"", s));
+
+        let (addr, prefix_length) = if let Some(pos) = s.find('/') {
+            let addr = s[..pos].parse::<IpAddr>().map_err(|_| invalid())?;
+            let prefix_length = s[pos + 1..].parse::<u8>().map_err(|_| invalid())?;
+
+            (addr, prefix_length)
+        } else {
+            let addr = s.parse::<IpAddr>().map_err(|_| invalid())?;
+
+            let prefix_length = match addr {
+                IpAddr::V4(_) => 32,
+                IpAddr::V6(_) => 128,
+            };
+
+            (addr, prefix_length)
+        };
+
+        IpNetwork::new(addr, prefix_length).map_err(|_| invalid())
+    }
+}
+
+impl Display for IpNetwork {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
+        write!(f, "{}/{}", self.addr, self.prefix_length)
+    }
+}
+
+/// Local policy of service destinations. A service is allowed only if its
+/// type, address and port are allowed. An empty list of networks, ports or
+/// service types means that there is no restriction.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct DestinationPolicy {
+    networks: Vec<IpNetwork>,
+    ports: Vec<u16>,
+    service_types: Vec<String>,
+}
+
+impl DestinationPolicy {
+    /// Create a new destination policy. Service types are expected to be
+    /// from the `POLICY_SERVICE_TYPES` list.
+    pub fn new(
+        networks: Vec<IpNetwork>,
+        ports: Vec<u16>,
+        service_types: Vec<String>,
+    ) -> DestinationPolicy {
+        DestinationPolicy {
+            networks,
+            ports,
+            service_types,
+        }
+    }
+
+    /// Check if there are no restrictions.
+    pub fn is_unrestricted(&self) -> bool {
+        self.networks.is_empty() && self.ports.is_empty() && self.service_types.is_empty()
+    }
+
+    /// Check if a given service is allowed. Services without any address
+    /// (i.e. the Control Protocol service) are always allowed.
+    pub fn check_service(&self, svc: &Service) -> Result<(), PolicyError> {
+        if let Some(addr) = svc.address() {
+            self.check(svc.service_type(), addr)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Check if a given service type and address is allowed.
+    pub fn check(&self, svc_type: ServiceType, addr: SocketAddr) -> Result<(), PolicyError> {
+        if !self.service_types.is_empty() {
+            let name = policy_service_type(svc_type);
+
+            if !self.service_types.iter().any(|t| t == name) {
+                return Err(PolicyError::from(format!(
+                    "service type {} is not allowed",
+                    svc_type.as_str()
+                )));
+            }
+        }
+
+        self.check_address(addr)
+    }
+
+    /// Check if a given address is allowed (the service type is not
+    /// checked).
+    pub fn check_address(&self, addr: SocketAddr) -> Result<(), PolicyError> {
+        if !self.networks.is_empty() && !self.networks.iter().any(|n| n.contains(addr.ip())) {
+            return Err(PolicyError::from(format!(
+                "address {} is not within any allowed network",
+                addr.ip()
+            )));
+        }
+
+        if !self.ports.is_empty() && !self.ports.contains(&addr.port()) {
+            return Err(PolicyError::from(format!(
+                "port {} is not allowed",
+                addr.port()
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Check if all addresses of a given network are allowed (ports and
+    /// service types are not checked).
+    pub fn check_network(&self, network: &IpNetwork) -> Result<(), PolicyError> {
+        if !self.networks.is_empty() && !self.networks.iter().any(|n| n.contains_network(network)) {
+            return Err(PolicyError::from(format!(
+                "network {} is not within any allowed network",
+                network
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+/// Action of an HTTP path rule.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum PathAction {
+    Allow,
+    Deny,
+}
+
+/// HTTP path rule. The rule can be limited to a single HTTP service given by
+/// its address. The path pattern is either an exact path or a path prefix
+/// followed by `*`.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct HttpPathRule {
+    service: Option<SocketAddr>,
+    action: PathAction,
+    pattern: String,
+}
+
+impl HttpPathRule {
+    /// Check if the rule applies to a service with a given address.
+    fn applies_to(&self, addr: SocketAddr) -> bool {
+        self.service.map(|svc| svc == addr).unwrap_or(true)
+    }
+}
+
+impl FromStr for HttpPathRule {
+    type Err = PolicyError;
+
+    /// Parse a rule in the `[addr=]allow:pattern` or `[addr=]deny:pattern`
+    /// format.
+    fn from_str(s: &str) -> Result<HttpPathRule, PolicyError> {
+        let invalid = || PolicyError::from(format!("invalid HTTP path rule: \"{}\"", s));
+
+        let (service, rule) = if s.starts_with("allow:") || s.starts_with("deny:") {
+            (None, s)
+        } else if let Some(pos) = s.find('=') {
+            let addr = s[..pos].parse::<SocketAddr>().map_err(|_| invalid())?;
+
+            (Some(addr), &s[pos + 1..])
+        } else {
+            return Err(invalid());
+        };
+
+        let (action, pattern) = if let Some(pattern) = rule.strip_prefix("allow:") {
+            (PathAction::Allow, pattern)
+        } else if let Some(pattern) = rule.strip_prefix("deny:") {
+            (PathAction::Deny, pattern)
+        } else {
+            return Err(invalid());
+        };
+
+        if !pattern.starts_with('/') {
+            return Err(invalid());
+        }
+
+        let res = HttpPathRule {
+            service,
+            action,
+            pattern: pattern.to_string(),
+        };
+
+        Ok(res)
+    }
+}
+
+impl Display for HttpPathRule {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
+        if let Some(addr) = self.service {
+            write!(f, "{}=", addr)?;
+        }
+
+        match self.action {
+            PathAction::Allow => f.write_str("allow:")?,
+            PathAction::Deny => f.write_str("deny:")?,
+        }
+
+        f.write_str(&self.pattern)
+    }
+}
+
+/// Local policy of HTTP paths accessible through HTTP services.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct HttpPathPolicy {
+    rules: Vec<HttpPathRule>,
+}
+
+impl HttpPathPolicy {
+    /// Create a new HTTP path policy.
+    pub fn new(rules: Vec<HttpPathRule>) -> HttpPathPolicy {
+        HttpPathPolicy { rules }
+    }
+
+    /// Check if there are any rules given explicitly for a given service
+    /// address.
+    pub fn has_address_rules(&self, addr: SocketAddr) -> bool {
+        self.rules.iter().any(|rule| rule.service == Some(addr))
+    }
+
+    /// Get path policy of an HTTP service with a given address. `None` is
+    /// returned if there are no rules for the service.
+    pub fn service_policy(&self, addr: SocketAddr) -> Option<ServicePathPolicy> {
+        let mut allow = Vec::new();
+        let mut deny = Vec::new();
+
+        for rule in &self.rules {
+            if rule.applies_to(addr) {
+                match rule.action {
+                    PathAction::Allow => allow.push(rule.pattern.clone()),
+                    PathAction::Deny => deny.push(rule.pattern.clone()),
+                }
+            }
+        }
+
+        if allow.is_empty() && deny.is_empty() {
+            None
+        } else {
+            Some(ServicePathPolicy { allow, deny })
+        }
+    }
+}
+
+/// HTTP path policy of a single service. A path is denied if it matches any
+/// deny pattern. Otherwise, it is allowed if there are no allow patterns or
+/// if it matches at least one of them.
+#[derive(Debug, Clone)]
+pub struct ServicePathPolicy {
+    allow: Vec<String>,
+    deny: Vec<String>,
+}
+
+impl ServicePathPolicy {
+    /// Check if a given request target is allowed. The query string is
+    /// ignored and the path is normalized before matching (i.e.
+    /// percent-encoded characters are decoded, backslashes are replaced with
+    /// slashes and dot segments are resolved). Paths are matched
+    /// case-insensitively.
+    pub fn check(&self, target: &str) -> Result<(), PolicyError> {
+        let path = normalize_path(target)?;
+
+        if self.deny.iter().any(|p| path_matches(p, &path)) {
+            return Err(PolicyError::from(format!("path {} is denied", path)));
+        }
+
+        if !self.allow.is_empty() && !self.allow.iter().any(|p| path_matches(p, &path)) {
+            return Err(PolicyError::from(format!("path {} is not allowed", path)));
+        }
+
+        Ok(())
+    }
+}
+
+/// Check if a given path matches a given pattern. The match is
+/// case-insensitive because paths are case-insensitive on some HTTP servers.
+fn path_matches(pattern: &str, path: &str) -> bool {
+    let pattern = pattern.to_lowercase();
+    let path = path.to_lowercase();
+
+    if let Some(prefix) = pattern.strip_suffix('*') {
+        path.starts_with(prefix)
+    } else {
+        path == pattern
+    }
+}
+
+/// Check if a given string is a valid URI scheme.
+fn is_scheme(s: &str) -> bool {
+    let mut chars = s.chars();
+
+    let valid_char = |c: char| c.is_ascii_alphanumeric() || c == '+' || c == '-' || c == '.';
+
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic()) && chars.all(valid_char)
+}
+
+/// Normalize path of a given request target.
+fn normalize_path(target: &str) -> Result<String, PolicyError> {
+    let invalid = || PolicyError::from(format!("invalid request target: \"{}\"", target));
+
+    let mut path = target;
+
+    if let Some(pos) = path.find(['?', '#']) {
+        path = &path[..pos];
+    }
+
+    // strip the scheme and authority of absolute-form targets
+    if !path.starts_with('/') {
+        if let Some(pos) = path.find("://").filter(|&pos| is_scheme(&path[..pos])) {
+            path = &path[pos + 3..];
+            path = path.find('/').map(|pos| &path[pos..]).unwrap_or("/");
+        }
+    }
+
+    if !path.starts_with('/') {
+        return Err(invalid());
+    }
+
+    // backslashes are treated as path separators by some HTTP servers
+    let path = percent_decode(path).ok_or_else(invalid)?.replace('\\', "/");
+
+    let mut segments = Vec::new();
+
+    for segment in path.split('/') {
+        match segment {
+            "" | "." => (),
+            ".." => {
+                segments.pop().ok_or_else(invalid)?;
+            }
+            _ => segments.push(segment),
+        }
+    }
+
+    let mut res = String::new();
+
+    for segment in segments {
+        res.push('/');
+        res.push_str(segment);
+    }
+
+    if res.is_empty() || (path.ends_with('/') && !res.ends_with('/')) {
+        res.push('/');
+    }
+
+    Ok(res)
+}
+
+/// Decode percent-encoded characters in a given string.
+fn percent_decode(s: &str) -> Option<String> {
+    let mut res = Vec::with_capacity(s.len());
+
+    let mut bytes = s.bytes();
+
+    while let Some(b) = bytes.next() {
+        if b == b'%' {
+            let hi = bytes.next().and_then(|c| (c as char).to_digit(16))?;
+            let lo = bytes.next().and_then(|c| (c as char).to_digit(16))?;
+
+            res.push((hi << 4 | lo) as u8);
+        } else {
+            res.push(b);
+        }
+    }
+
+    String::from_utf8(res).ok()
+}
+
+/// Get policy name of a given service type.
+fn policy_service_type(svc_type: ServiceType) -> &'static str {
+    match svc_type {
+        ServiceType::ControlProtocol => "control",
+        ServiceType::RTSP => "rtsp",
+        ServiceType::LockedRTSP => "rtsp",
+        ServiceType::UnknownRTSP => "rtsp",
+        ServiceType::UnsupportedRTSP => "rtsp",
+        ServiceType::HTTP => "http",
+        ServiceType::MJPEG => "mjpeg",
+        ServiceType::LockedMJPEG => "mjpeg",
+        ServiceType::TCP => "tcp",
+    }
+}
+
+/// Get IPv4 network mask for a given prefix length.
+fn ipv4_mask(prefix_length: u8) -> u32 {
+    if prefix_length == 0 {
+        0
+    } else {
+        !0 << (32 - u32::from(prefix_length))
+    }
+}
+
+/// Get IPv6 network mask for a given prefix length.
+fn ipv6_mask(prefix_length: u8) -> u128 {
+    if prefix_length == 0 {
+        0
+    } else {
+        !0 << (128 - u32::from(prefix_length))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::net::raw::ether::MacAddr;
+
+    #[test]
+    fn test_ip_network() {
+        let network = "192.168.1.77/24".parse::<IpNetwork>().unwrap();
+
+        assert_eq!(network.to_string(), "192.168.1.0/24");
+
+        assert!(network.contains("192.168.1.1".parse().unwrap()));
+        assert!(!network.contains("192.168.2.1".parse().unwrap()));
+        assert!(!network.contains("fe80::1".parse().unwrap()));
+
+        let network = "10.0.0.1".parse::<IpNetwork>().unwrap();
+
+        assert!(network.contains("10.0.0.1".parse().unwrap()));
+        assert!(!network.contains("10.0.0.2".parse().unwrap()));
+
+        let network = "fd00::/8".parse::<IpNetwork>().unwrap();
+
+        assert!(network.contains("fd12::1".parse().unwrap()));
+        assert!(!network.contains("fe80::1".parse().unwrap()));
+
+        assert!("0.0.0.0/0".parse::<IpNetwork>().is_ok());
+        assert!("10.0.0.0/33".parse::<IpNetwork>().is_err());
+        assert!("10.0.0/8".parse::<IpNetwork>().is_err());
+    }
+
+    #[test]
+    fn test_destination_policy() {
+        let mac = MacAddr::zero();
+
+        let rtsp = Service::locked_rtsp(mac, "192.168.1.10:554".parse().unwrap(), None);
+        let http = Service::http(mac, "192.168.1.10:80".parse().unwrap());
+        let tcp = Service::tcp(mac, "10.0.0.5:554".parse().unwrap());
+
+        let policy = DestinationPolicy::default();
+
+        assert!(policy.is_unrestricted());
+        assert!(policy.check_service(&http).is_ok());
+
+        let policy = DestinationPolicy::new(
+            vec!["192.168.1.0/24".parse().unwrap()],
+            vec![554],
+            vec!["rtsp".to_string(), "tcp".to_string()],
+        );
+
+        assert!(policy.check_service(&rtsp).is_ok());
+        assert!(policy.check_service(&http).is_err());
+        assert!(policy.check_service(&tcp).is_err());
+        assert!(policy.check_service(&Service::control()).is_ok());
+
+        assert!(policy
+            .check_address("192.168.1.10:554".parse().unwrap())
+            .is_ok());
+        assert!(policy
+            .check_address("192.168.1.10:80".parse().unwrap())
+            .is_err());
+        assert!(policy
+            .check_address("10.0.0.5:554".parse().unwrap())
+            .is_err());
+
+        let network = |n: &str| n.parse::<IpNetwork>().unwrap();
+
+        assert!(policy.check_network(&network("192.168.1.0/24")).is_ok());
+        assert!(policy.check_network(&network("192.168.1.128/25")).is_ok());
+        assert!(policy.check_network(&network("192.168.0.0/16")).is_err());
+        assert!(policy.check_network(&network("10.0.0.0/24")).is_err());
+    }
+
+    #[test]
+    fn test_http_path_policy() {
+        let camera = "192.168.1.10:80".parse().unwrap();
+        let other = "192.168.1.11:80".parse().unwrap();
+
+        let rules = vec![
+            "192.168.1.10:80=allow:/live/*",
+            "192.168.1.10:80=allow:/index.html",
+            "deny:/cgi-bin/upgrade*",
+            "deny:/admin*",
+        ];
+
+        let rules = rules
+            .into_iter()
+            .map(|rule| rule.parse().unwrap())
+            .collect::<Vec<HttpPathRule>>();
+
+        assert_eq!(rules[0].to_string(), "192.168.1.10:80=allow:/live/*");
+
+        let policy = HttpPathPolicy::new(rules);
+
+        assert!(policy.has_address_rules(camera));
+        assert!(!policy.has_address_rules(other));
+
+        let camera = policy.service_policy(camera).unwrap();
+
+        assert!(camera.check("/live/stream.mjpg?res=high").is_ok());
+        assert!(camera.check("http://camera/index.html").is_ok());
+        assert!(camera.check("/index.html/").is_err());
+        assert!(camera.check("/live/../cgi-bin/reset").is_err());
+        assert!(camera.check("/live/%2e%2e/cgi-bin/reset").is_err());
+        assert!(camera.check("/live/../../etc/passwd").is_err());
+        assert!(camera
+            .check("/cgi-bin/factory_reset.cgi?a=://h/live/a")
+            .is_err());
+        assert!(camera.check("/cgi-bin/reset.cgi/x://h/live/a").is_err());
+        assert!(camera.check("x/y://h/live/a").is_err());
+        assert!(camera.check("rtsp+http://h/live/a").is_ok());
+
+        let other = policy.service_policy(other).unwrap();
+
+        assert!(other.check("/setup.html").is_ok());
+        assert!(other.check("//cgi-bin/./upgrade.cgi").is_err());
+        assert!(other.check("/cgi-bin/%75pgrade.cgi").is_err());
+        assert!(other.check("/CGI-BIN/upgrade.cgi").is_err());
+        assert!(other.check("/cgi-bin\\upgrade.cgi").is_err());
+        assert!(other.check("/live/..%5ccgi-bin%5cupgrade.cgi").is_err());
+        assert!(other.check("/admin/x://y/ok").is_err());
+        assert!(other.check("http://camera/admin/x://y/ok").is_err());
+
+        assert!(camera.check("/LIVE/a.mjpg").is_ok());
+        assert!(camera.check("/live/..\\cgi-bin\\reset").is_err());
+
+        assert!(HttpPathPolicy::default()
+            .service_policy("192.168.1.10:80".parse().unwrap())
+            .is_none());
+
+        assert!("allow:live".parse::<HttpPathRule>().is_err());
+        assert!("192.168.1.10=deny:/".parse::<HttpPathRule>().is_err());
+        assert!("[fe80::1]:80=deny:/".parse::<HttpPathRule>().is_ok());
+    }
+}
@@ -12,9 +12,15 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::fmt;
+
+use std::fmt::{Display, Formatter};
 use std::net::{IpAddr, SocketAddr};
 
+use json::JsonValue;
+
 use crate::net::raw::ether::MacAddr;
+use crate::utils::json::ToJson;
 
 pub const SVC_TYPE_CONTROL_PROTOCOL: u16 = 0x0000;
 pub const SVC_TYPE_RTSP: u16 = 0x0001;
@@ -64,6 +70,39 @@ impl ServiceType {
             &ServiceType::TCP => SVC_TYPE_TCP,
         }
     }
+
+    /// Get service type for a given code.
+    pub fn from_code(code: u16) -> Option<ServiceType> {
+        let res = match code {
+            SVC_TYPE_CONTROL_PROTOCOL => ServiceType::ControlProtocol,
+            SVC_TYPE_RTSP => ServiceType::RTSP,
+            SVC_TYPE_LOCKED_RTSP => ServiceType::LockedRTSP,
+            SVC_TYPE_UNKNOWN_RTSP => ServiceType::UnknownRTSP,
+            SVC_TYPE_UNSUPPORTED_RTSP => ServiceType::UnsupportedRTSP,
+            SVC_TYPE_HTTP => ServiceType::HTTP,
+            SVC_TYPE_MJPEG => ServiceType::MJPEG,
+            SVC_TYPE_LOCKED_MJPEG => ServiceType::LockedMJPEG,
+            SVC_TYPE_TCP => ServiceType::TCP,
+            _ => return None,
+        };
+
+        Some(res)
+    }
+
+    /// Get short name of the service type.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ServiceType::ControlProtocol => "control",
+            ServiceType::RTSP => "rtsp",
+            ServiceType::LockedRTSP => "rtsp-locked",
+            ServiceType::UnknownRTSP => "rtsp-unknown",
+            ServiceType::UnsupportedRTSP => "rtsp-unsupported",
+            ServiceType::HTTP => "http",
+            ServiceType::MJPEG => "mjpeg",
+            ServiceType::LockedMJPEG => "mjpeg-locked",
+            ServiceType::TCP => "tcp",
+        }
+    }
 }
 
 /// Arrow service identifier.
@@ -227,3 +266,30 @@ impl Service {
         }
     }
 }
+
+impl Display for Service {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
+        write!(f, "{:?}", self.svc_type)?;
+
+        if let Some(addr) = self.address {
+            write!(f, " {}", addr)?;
+        }
+
+        if let Some(ref path) = self.path {
+            f.write_str(path)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl ToJson for Service {
+    fn to_json(&self) -> JsonValue {
+        object! {
+            "svc_type" => self.svc_type.code(),
+            "mac" => self.mac.map(|mac| format!("{}", mac)),
+            "address" => self.address.map(|addr| format!("{}", addr)),
+            "path" => self.path.clone()
+        }
+    }
+}
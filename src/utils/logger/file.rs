@@ -13,27 +13,101 @@
 // limitations under the License.
 
 //! file logger definitions.
-
+//!
+//! The log file is rotated when it reaches a given size limit and,
+//! optionally, also on every hour or day. Rotated files are named
+//! `<path>.1` (the newest one), `<path>.2`, etc. and they can be compressed
+//! using gzip (in which case the `.gz` suffix is appended). The total size of
+//! all files can be limited by a disk budget. The oldest rotated files are
+//! removed when the budget is exceeded.
+
+use std::fmt;
 use std::fs;
 use std::io;
 
+use std::fmt::{Display, Formatter};
 use std::fs::{File, OpenOptions};
 use std::io::Write;
+use std::os::unix::fs::MetadataExt;
 use std::path::Path;
-use std::sync::{Arc, Mutex};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::UNIX_EPOCH;
+
+use flate2::write::GzEncoder;
+use flate2::Compression;
+
+use time::{Timespec, Tm};
 
-use time;
+use crate::utils::logger::format::LogFormat;
+use crate::utils::logger::{Field, Logger, Severity};
+use crate::utils::RuntimeError;
 
-use crate::utils::logger::{Logger, Severity};
+/// Time-based rotation period.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum RotationPeriod {
+    Hourly,
+    Daily,
+}
+
+impl RotationPeriod {
+    /// Get string representation of the period.
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            RotationPeriod::Hourly => "hourly",
+            RotationPeriod::Daily => "daily",
+        }
+    }
+
+    /// Get ID of the period containing a given (local) time.
+    fn get_period_id(&self, tm: &Tm) -> i64 {
+        let day = i64::from(tm.tm_year) * 1000 + i64::from(tm.tm_yday);
+
+        match *self {
+            RotationPeriod::Hourly => day * 100 + i64::from(tm.tm_hour),
+            RotationPeriod::Daily => day,
+        }
+    }
+}
+
+impl Display for RotationPeriod {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for RotationPeriod {
+    type Err = RuntimeError;
+
+    fn from_str(s: &str) -> Result<RotationPeriod, RuntimeError> {
+        match &s.to_ascii_lowercase() as &str {
+            "hourly" => Ok(RotationPeriod::Hourly),
+            "daily" => Ok(RotationPeriod::Daily),
+            _ => Err(RuntimeError::from(format!(
+                "unknown rotation period: \"{}\"",
+                s
+            ))),
+        }
+    }
+}
 
 /// Internal logger implementation.
 struct InternalFileLogger {
     level: Severity,
+    format: LogFormat,
     path: String,
     file: File,
     written: usize,
     limit: usize,
     rotations: usize,
+    period: Option<RotationPeriod>,
+    period_id: i64,
+    modified: i64,
+    compress: bool,
+    compression: Arc<Mutex<()>>,
+    pending: Option<String>,
+    budget: Option<u64>,
+    failed: bool,
 }
 
 impl InternalFileLogger {
@@ -44,8 +118,10 @@ impl InternalFileLogger {
 
     /// Write given data into the underlaying file and rotate as necessary.
     fn write(&mut self, data: &[u8]) -> io::Result<()> {
-        if (self.written + data.len()) > self.limit {
-            self.rotate()?;
+        self.check_file()?;
+
+        if ((self.written + data.len()) > self.limit || self.is_period_over()) && self.rotate()? {
+            self.enforce_budget(data.len())?;
         }
 
         self.file.write_all(data)?;
@@ -55,46 +131,145 @@ impl InternalFileLogger {
         self.file.flush()
     }
 
-    /// Rotate the log files.
-    fn rotate(&mut self) -> io::Result<()> {
-        for i in 0..self.rotations - 1 {
-            let from = format!("{}.{}", &self.path, self.rotations - i - 1);
-            let to = format!("{}.{}", &self.path, self.rotations - i);
+    /// Reopen the log file if it has been deleted or replaced.
+    fn check_file(&mut self) -> io::Result<()> {
+        let current = self.file.metadata()?;
 
-            if Path::new(&from).exists() {
-                fs::rename(&from, &to)?;
-            }
+        let reopen = match fs::metadata(&self.path) {
+            Ok(metadata) => metadata.dev() != current.dev() || metadata.ino() != current.ino(),
+            Err(_) => true,
+        };
+
+        if reopen {
+            let (file, written) = open_file(&self.path)?;
+
+            self.file = file;
+            self.written = written;
+        }
+
+        Ok(())
+    }
+
+    /// Check if the current rotation period is over.
+    fn is_period_over(&self) -> bool {
+        if let Some(period) = self.period {
+            period.get_period_id(&time::now()) != self.period_id
+        } else {
+            false
+        }
+    }
+
+    /// Rotate the log files. The newest rotated file is not compressed here.
+    /// It is left for the caller to compress it after releasing the logger
+    /// lock. The rotation is postponed (and `false` is returned) while the
+    /// previously rotated file is still being compressed.
+    fn rotate(&mut self) -> io::Result<bool> {
+        if self.compress && self.compression.try_lock().is_err() {
+            return Ok(false);
         }
 
         if self.rotations > 0 {
-            fs::rename(&self.path, &format!("{}.1", &self.path))?;
+            remove_file(&self.get_generation_path(self.rotations, false))?;
+            remove_file(&self.get_generation_path(self.rotations, true))?;
+
+            for i in (1..self.rotations).rev() {
+                for &compressed in &[false, true] {
+                    let from = self.get_generation_path(i, compressed);
+                    let to = self.get_generation_path(i + 1, compressed);
+
+                    if Path::new(&from).exists() {
+                        fs::rename(&from, &to)?;
+                    }
+                }
+            }
+
+            let rotated = self.get_generation_path(1, false);
+
+            match fs::rename(&self.path, &rotated) {
+                Err(ref err) if err.kind() == io::ErrorKind::NotFound => (),
+                Err(err) => return Err(err),
+                Ok(()) => {
+                    if self.compress {
+                        self.pending = Some(rotated);
+                    }
+                }
+            }
         }
 
         self.file = File::create(&self.path)?;
 
         self.written = 0;
 
+        if let Some(period) = self.period {
+            self.period_id = period.get_period_id(&time::now());
+        }
+
+        Ok(true)
+    }
+
+    /// Remove the oldest rotated files until the total size of all log
+    /// files (including a given number of bytes that are about to be
+    /// written) fits into the disk budget.
+    fn enforce_budget(&self, pending: usize) -> io::Result<()> {
+        let budget = match self.budget {
+            Some(budget) => budget,
+            None => return Ok(()),
+        };
+
+        let mut total = (self.written + pending) as u64;
+
+        for i in 1..=self.rotations {
+            for &compressed in &[false, true] {
+                let path = self.get_generation_path(i, compressed);
+
+                if let Ok(metadata) = fs::metadata(&path) {
+                    total += metadata.len();
+
+                    if total > budget {
+                        fs::remove_file(&path)?;
+                    }
+                }
+            }
+        }
+
         Ok(())
     }
+
+    /// Get path of a given generation of the log file.
+    fn get_generation_path(&self, generation: usize, compressed: bool) -> String {
+        if compressed {
+            format!("{}.{}.gz", self.path, generation)
+        } else {
+            format!("{}.{}", self.path, generation)
+        }
+    }
+
+    /// Report a given write error. Only the first error of a series of
+    /// errors is reported to avoid flooding stderr.
+    fn report_error(&mut self, err: io::Error) {
+        if !self.failed {
+            eprintln!("unable to write into log file \"{}\": {}", self.path, err);
+        }
+
+        self.failed = true;
+    }
 }
 
 impl Logger for InternalFileLogger {
     fn log(&mut self, file: &str, line: u32, s: Severity, msg: &str) {
-        let t = time::strftime("%F %T", &time::now()).unwrap();
-
-        let severity = match s {
-            Severity::DEBUG => "DEBUG",
-            Severity::INFO => "INFO",
-            Severity::WARN => "WARNING",
-            Severity::ERROR => "ERROR",
-        };
+        self.log_fields(file, line, s, msg, &[])
+    }
 
+    fn log_fields(&mut self, file: &str, line: u32, s: Severity, msg: &str, fields: &[Field]) {
         if s >= self.level {
-            self.write_line(&format!(
-                "{} {:<7} [{}:{}] {}\n",
-                t, severity, file, line, msg
-            ))
-            .unwrap();
+            let mut line = self.format.format(file, line, s, msg, fields);
+
+            line.push('\n');
+
+            match self.write_line(&line) {
+                Ok(()) => self.failed = false,
+                Err(err) => self.report_error(err),
+            }
         }
     }
 
@@ -116,24 +291,31 @@ pub struct FileLogger {
 /// Create a new file logger with a given file size limit, given number of backup files
 /// (rotations) and with log level set to INFO.
 pub fn new(path: &str, limit: usize, rotations: usize) -> io::Result<FileLogger> {
-    let written = match Path::new(path).metadata() {
-        Ok(metadata) => metadata.len(),
-        Err(_) => 0,
-    };
+    let (file, written) = open_file(path)?;
 
-    let file = OpenOptions::new()
-        .create(true)
-        .write(true)
-        .append(true)
-        .open(path)?;
+    let modified = file
+        .metadata()?
+        .modified()?
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0);
 
     let logger = InternalFileLogger {
         level: Severity::INFO,
+        format: LogFormat::Text,
         path: path.to_string(),
         file: file,
-        written: written as usize,
+        written,
         limit: limit,
         rotations: rotations,
+        period: None,
+        period_id: 0,
+        modified,
+        compress: false,
+        compression: Arc::new(Mutex::new(())),
+        pending: None,
+        budget: None,
+        failed: false,
     };
 
     let logger = FileLogger {
@@ -143,9 +325,83 @@ pub fn new(path: &str, limit: usize, rotations: usize) -> io::Result<FileLogger>
     Ok(logger)
 }
 
+impl FileLogger {
+    /// Use a given log line format.
+    pub fn with_format(self, format: LogFormat) -> FileLogger {
+        self.shared.lock().unwrap().format = format;
+        self
+    }
+
+    /// Rotate the log file also on every hour or day.
+    pub fn with_period(self, period: RotationPeriod) -> FileLogger {
+        {
+            let mut logger = self.shared.lock().unwrap();
+
+            // the file may have been created in one of the previous periods
+            let modified = time::at(Timespec::new(logger.modified, 0));
+
+            logger.period = Some(period);
+            logger.period_id = period.get_period_id(&modified);
+        }
+
+        self
+    }
+
+    /// Compress rotated files using gzip.
+    pub fn with_compression(self, compress: bool) -> FileLogger {
+        self.shared.lock().unwrap().compress = compress;
+        self
+    }
+
+    /// Limit the total size of all log files (including the rotated ones).
+    pub fn with_budget(self, budget: u64) -> FileLogger {
+        self.shared.lock().unwrap().budget = Some(budget);
+        self
+    }
+
+    /// Compress the most recently rotated file (if any) without blocking
+    /// other threads that write into the log.
+    fn compress_pending(&self, mut logger: MutexGuard<InternalFileLogger>) {
+        let path = match logger.pending.take() {
+            Some(path) => path,
+            None => return,
+        };
+
+        // the compression lock is taken before the logger lock is released,
+        // so the rotated file cannot be shifted before it is compressed
+        let compression = logger.compression.clone();
+
+        let _guard = compression.lock().unwrap();
+
+        drop(logger);
+
+        let mut logger = match compress_file(&path) {
+            Ok(()) => self.shared.lock().unwrap(),
+            Err(err) => return self.shared.lock().unwrap().report_error(err),
+        };
+
+        // the compressed file may be larger than the original one
+        if let Err(err) = logger.enforce_budget(0) {
+            logger.report_error(err);
+        }
+    }
+}
+
 impl Logger for FileLogger {
     fn log(&mut self, file: &str, line: u32, s: Severity, msg: &str) {
-        self.shared.lock().unwrap().log(file, line, s, msg)
+        let mut logger = self.shared.lock().unwrap();
+
+        logger.log(file, line, s, msg);
+
+        self.compress_pending(logger);
+    }
+
+    fn log_fields(&mut self, file: &str, line: u32, s: Severity, msg: &str, fields: &[Field]) {
+        let mut logger = self.shared.lock().unwrap();
+
+        logger.log_fields(file, line, s, msg, fields);
+
+        self.compress_pending(logger);
     }
 
     fn set_level(&mut self, s: Severity) {
@@ -157,13 +413,66 @@ impl Logger for FileLogger {
     }
 }
 
+/// Open a given log file for appending and get its current size.
+fn open_file(path: &str) -> io::Result<(File, usize)> {
+    let file = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(true)
+        .open(path)?;
+
+    let written = file.metadata()?.len();
+
+    Ok((file, written as usize))
+}
+
+/// Compress a given file using gzip and remove the original.
+fn compress_file(path: &str) -> io::Result<()> {
+    let gz_path = format!("{}.gz", path);
+
+    let compress = || {
+        let mut input = File::open(path)?;
+
+        let output = File::create(&gz_path)?;
+
+        let mut encoder = GzEncoder::new(output, Compression::default());
+
+        io::copy(&mut input, &mut encoder)?;
+
+        encoder.finish()?.sync_all()
+    };
+
+    if let Err(err) = compress() {
+        // keep the uncompressed file
+        remove_file(&gz_path)?;
+
+        return Err(err);
+    }
+
+    fs::remove_file(path)
+}
+
+/// Remove a given file if it exists.
+fn remove_file(path: &str) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(ref err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+        res => res,
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
 
+    use std::env;
     use std::ffi::OsStr;
     use std::fs;
     use std::path::Path;
+    use std::process;
+
+    use std::io::Read;
+
+    use flate2::read::GzDecoder;
 
     use crate::utils::logger::Logger;
 
@@ -229,4 +538,94 @@ mod test {
 
         remove_files();
     }
+
+    #[test]
+    fn test_rotation_options() {
+        let dir = env::temp_dir().join(format!("arrow-log-test-{}", process::id()));
+
+        fs::create_dir_all(&dir).unwrap();
+
+        let path = dir.join("log");
+        let path = path.to_str().unwrap();
+
+        let generation = |n: usize| format!("{}.{}.gz", path, n);
+
+        let mut logger = new(path, 100, 5)
+            .unwrap()
+            .with_period(RotationPeriod::Daily)
+            .with_compression(true)
+            .with_budget(300);
+
+        log_info!(logger, "foo");
+        log_info!(logger, "foo");
+
+        assert!(file_exists(&generation(1)));
+        assert!(!file_exists(&format!("{}.1", path)));
+
+        let mut content = String::new();
+
+        GzDecoder::new(File::open(generation(1)).unwrap())
+            .read_to_string(&mut content)
+            .unwrap();
+
+        assert!(content.ends_with("] foo\n"));
+
+        // a new period begins
+        logger.shared.lock().unwrap().period_id -= 1;
+
+        log_info!(logger, "bar");
+
+        assert!(file_exists(&generation(2)));
+
+        // rotation is postponed while a rotated file is being compressed
+        let compression = logger.shared.lock().unwrap().compression.clone();
+
+        {
+            let _guard = compression.lock().unwrap();
+
+            for _ in 0..5 {
+                log_info!(logger, "foo");
+            }
+
+            assert!(!file_exists(&generation(3)));
+        }
+
+        // the log file gets deleted externally
+        fs::remove_file(path).unwrap();
+
+        log_info!(logger, "baz");
+
+        assert!(fs::read_to_string(path).unwrap().ends_with("] baz\n"));
+
+        for _ in 0..20 {
+            log_info!(logger, "foo");
+        }
+
+        let total = fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().metadata().unwrap().len())
+            .sum::<u64>();
+
+        assert!(total <= 300);
+        assert!(file_exists(&generation(1)));
+        assert!(!file_exists(&generation(5)));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_period_from_mtime() {
+        remove_file("testlog-mtime");
+
+        let logger = new("testlog-mtime", 100, 1).unwrap();
+
+        // pretend the file was created a long time ago
+        logger.shared.lock().unwrap().modified = 0;
+
+        let logger = logger.with_period(RotationPeriod::Hourly);
+
+        assert!(logger.shared.lock().unwrap().is_period_over());
+
+        remove_file("testlog-mtime");
+    }
 }
@@ -0,0 +1,210 @@
+// Copyright 2019 click2stream, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Per-module log level filtering.
+//!
+//! A log filter consists of a default log level and a list of directives
+//! setting log levels of particular modules. Filters are written as
+//! comma-separated lists, e.g.:
+//!
+//! ```text
+//! warn,net::arrow::session=debug,scanner=info
+//! ```
+//!
+//! Modules are matched against source file paths given to the logging
+//! macros (e.g. `src/net/arrow/session.rs` belongs to module
+//! `net::arrow::session`). A directive applies to a given module and all
+//! its submodules. The most specific directive wins.
+
+use std::cmp;
+use std::fmt;
+
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use crate::utils::logger::Severity;
+use crate::utils::RuntimeError;
+
+/// Log filter.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct LogFilter {
+    level: Severity,
+    directives: Vec<(String, Severity)>,
+}
+
+impl LogFilter {
+    /// Create a new log filter with a given default level and no
+    /// directives.
+    pub fn new(level: Severity) -> LogFilter {
+        LogFilter {
+            level,
+            directives: Vec::new(),
+        }
+    }
+
+    /// Get the default log level.
+    pub fn level(&self) -> Severity {
+        self.level
+    }
+
+    /// Set the default log level. Directives are kept as they are.
+    pub fn set_level(&mut self, level: Severity) {
+        self.level = level;
+    }
+
+    /// Get the lowest log level enabled by the filter.
+    pub fn min_level(&self) -> Severity {
+        self.directives
+            .iter()
+            .fold(self.level, |min, &(_, level)| cmp::min(min, level))
+    }
+
+    /// Check if a message with a given severity coming from a given source
+    /// file should be logged.
+    pub fn is_enabled(&self, file: &str, s: Severity) -> bool {
+        s >= self.get_file_level(file)
+    }
+
+    /// Get log level for a given source file.
+    fn get_file_level(&self, file: &str) -> Severity {
+        if self.directives.is_empty() {
+            return self.level;
+        }
+
+        let module = get_module_path(file);
+
+        self.directives
+            .iter()
+            .filter(|(prefix, _)| is_submodule(&module, prefix))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|&(_, level)| level)
+            .unwrap_or(self.level)
+    }
+}
+
+impl From<Severity> for LogFilter {
+    fn from(level: Severity) -> LogFilter {
+        LogFilter::new(level)
+    }
+}
+
+impl Display for LogFilter {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
+        f.write_str(self.level.as_str())?;
+
+        for (module, level) in &self.directives {
+            write!(f, ",{}={}", module, level)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for LogFilter {
+    type Err = RuntimeError;
+
+    fn from_str(s: &str) -> Result<LogFilter, RuntimeError> {
+        let mut res = LogFilter::new(Severity::INFO);
+
+        let directives = s
+            .split(',')
+            .map(|directive| directive.trim())
+            .filter(|directive| !directive.is_empty());
+
+        for directive in directives {
+            if let Some(pos) = directive.find('=') {
+                let module = directive[..pos].trim();
+                let level = directive[pos + 1..].trim().parse()?;
+
+                if !is_valid_module_path(module) {
+                    return Err(RuntimeError::from(format!(
+                        "invalid module name: \"{}\"",
+                        module
+                    )));
+                }
+
+                res.directives.retain(|(m, _)| m != module);
+                res.directives.push((module.to_string(), level));
+            } else {
+                res.level = directive.parse()?;
+            }
+        }
+
+        Ok(res)
+    }
+}
+
+/// Convert a given source file path into a module path (e.g.
+/// "src/net/arrow/mod.rs" into "net::arrow").
+fn get_module_path(file: &str) -> String {
+    let path = file.strip_prefix("src/").unwrap_or(file);
+    let path = path.strip_suffix(".rs").unwrap_or(path);
+    let path = path.strip_suffix("/mod").unwrap_or(path);
+
+    path.replace('/', "::")
+}
+
+/// Check if a given module is a given parent module or its submodule.
+fn is_submodule(module: &str, parent: &str) -> bool {
+    match module.strip_prefix(parent) {
+        Some(rest) => rest.is_empty() || rest.starts_with("::"),
+        None => false,
+    }
+}
+
+/// Check if a given string is a valid module path.
+fn is_valid_module_path(path: &str) -> bool {
+    path.split("::")
+        .all(|name| !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_log_filter() {
+        let filter = LogFilter::from_str("net::arrow::session=debug, warn,net=error").unwrap();
+
+        assert_eq!(filter.level(), Severity::WARN);
+        assert_eq!(filter.min_level(), Severity::DEBUG);
+        assert_eq!(
+            filter.to_string(),
+            "warn,net::arrow::session=debug,net=error"
+        );
+
+        assert!(filter.is_enabled("src/net/arrow/session.rs", Severity::DEBUG));
+        assert!(!filter.is_enabled("src/net/arrow/mod.rs", Severity::WARN));
+        assert!(filter.is_enabled("src/net/arrow/mod.rs", Severity::ERROR));
+        assert!(!filter.is_enabled("src/net/arrow/session2.rs", Severity::WARN));
+        assert!(!filter.is_enabled("src/main.rs", Severity::INFO));
+        assert!(filter.is_enabled("src/main.rs", Severity::WARN));
+
+        let filter = LogFilter::from_str("scanner=debug").unwrap();
+
+        assert_eq!(filter.level(), Severity::INFO);
+        assert!(filter.is_enabled("src/scanner/rtsp.rs", Severity::DEBUG));
+        assert!(!filter.is_enabled("src/context.rs", Severity::DEBUG));
+
+        assert_eq!(
+            LogFilter::from_str("debug").unwrap(),
+            LogFilter::new(Severity::DEBUG)
+        );
+
+        assert!(LogFilter::from_str("foo").is_err());
+        assert!(LogFilter::from_str("net=foo").is_err());
+        assert!(LogFilter::from_str("net:arrow=debug").is_err());
+        assert!(LogFilter::from_str("=debug").is_err());
+    }
+}
@@ -0,0 +1,196 @@
+// Copyright 2019 click2stream, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Log line formatters.
+
+use std::fmt;
+
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use json::JsonValue;
+
+use time;
+
+use crate::utils::logger::{Field, Severity};
+use crate::utils::RuntimeError;
+
+/// Log line format.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum LogFormat {
+    /// Human readable log lines.
+    Text,
+    /// One JSON object per line.
+    Json,
+}
+
+impl LogFormat {
+    /// Get string representation of the format.
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            LogFormat::Text => "text",
+            LogFormat::Json => "json",
+        }
+    }
+
+    /// Format a given log record (without the trailing line separator).
+    pub fn format(
+        &self,
+        file: &str,
+        line: u32,
+        s: Severity,
+        msg: &str,
+        fields: &[Field],
+    ) -> String {
+        match *self {
+            LogFormat::Text => format_text(file, line, s, msg, fields),
+            LogFormat::Json => format_json(file, line, s, msg, fields),
+        }
+    }
+}
+
+impl Display for LogFormat {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for LogFormat {
+    type Err = RuntimeError;
+
+    fn from_str(s: &str) -> Result<LogFormat, RuntimeError> {
+        match &s.to_ascii_lowercase() as &str {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(RuntimeError::from(format!("unknown log format: \"{}\"", s))),
+        }
+    }
+}
+
+/// Format a given log record as a human readable line.
+fn format_text(file: &str, line: u32, s: Severity, msg: &str, fields: &[Field]) -> String {
+    let t = time::strftime("%F %T", &time::now()).unwrap();
+
+    let severity = match s {
+        Severity::DEBUG => "DEBUG",
+        Severity::INFO => "INFO",
+        Severity::WARN => "WARNING",
+        Severity::ERROR => "ERROR",
+    };
+
+    let mut res = format!("{} {:<7} [{}:{}] {}", t, severity, file, line, msg);
+
+    if !fields.is_empty() {
+        res.push(' ');
+        res.push_str(&format_fields(fields));
+    }
+
+    res
+}
+
+/// Format a given log record as a single-line JSON object. The object
+/// contains the following keys: `timestamp` (RFC 3339, UTC), `severity`,
+/// `source` (file:line), `message` and all the given fields.
+fn format_json(file: &str, line: u32, s: Severity, msg: &str, fields: &[Field]) -> String {
+    let now = time::now_utc();
+
+    let timestamp = format!(
+        "{}.{:03}Z",
+        time::strftime("%Y-%m-%dT%H:%M:%S", &now).unwrap(),
+        now.tm_nsec / 1_000_000
+    );
+
+    let mut res = JsonValue::new_object();
+
+    res["timestamp"] = timestamp.into();
+    res["severity"] = s.as_str().into();
+    res["source"] = format!("{}:{}", file, line).into();
+    res["message"] = msg.into();
+
+    for &(key, value) in fields {
+        // fields cannot override the common keys
+        if !res.has_key(key) {
+            res[key] = value.to_string().into();
+        }
+    }
+
+    res.dump()
+}
+
+/// Format given fields as space separated "key=value" pairs. Values
+/// containing whitespace, quotes or equal signs are quoted.
+pub fn format_fields(fields: &[Field]) -> String {
+    let fields = fields
+        .iter()
+        .map(|&(key, value)| {
+            let value = value.to_string();
+
+            let quote = value.is_empty()
+                || value
+                    .chars()
+                    .any(|c| c.is_whitespace() || c == '"' || c == '=');
+
+            if quote {
+                format!("{}={:?}", key, value)
+            } else {
+                format!("{}={}", key, value)
+            }
+        })
+        .collect::<Vec<_>>();
+
+    fields.join(" ")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_formats() {
+        let session_id = format!("{:08x}", 42);
+
+        let fields: &[Field] = &[("session_id", &session_id), ("service_id", &7)];
+
+        let line = LogFormat::Text.format("src/foo.rs", 10, Severity::WARN, "msg", fields);
+
+        assert!(line.ends_with(" WARNING [src/foo.rs:10] msg session_id=0000002a service_id=7"));
+
+        let line = LogFormat::Json.format("src/foo.rs", 10, Severity::WARN, "a \"msg\"", fields);
+
+        assert!(!line.contains('\n'));
+
+        let obj = json::parse(&line).unwrap();
+
+        assert_eq!(obj["severity"], "warn");
+        assert_eq!(obj["source"], "src/foo.rs:10");
+        assert_eq!(obj["message"], "a \"msg\"");
+        assert_eq!(obj["session_id"], "0000002a");
+        assert_eq!(obj["service_id"], "7");
+        assert!(obj["timestamp"].as_str().unwrap().ends_with('Z'));
+
+        let fields: &[Field] = &[("endpoint", &"a b"), ("message", &"foo")];
+
+        assert_eq!(format_fields(fields), "endpoint=\"a b\" message=foo");
+
+        let line = LogFormat::Json.format("src/foo.rs", 10, Severity::INFO, "msg", fields);
+
+        let obj = json::parse(&line).unwrap();
+
+        assert_eq!(obj["message"], "msg");
+        assert_eq!(obj["endpoint"], "a b");
+
+        assert_eq!(LogFormat::from_str("JSON").unwrap(), LogFormat::Json);
+        assert!(LogFormat::from_str("xml").is_err());
+    }
+}
@@ -14,34 +14,75 @@
 
 //! Logger definitions.
 
+// All the logging macros accept optional key-value fields separated from the
+// message by a semicolon, e.g.:
+//
+// log_info!(logger, "service connection closed"; session_id = id, service_id = svc);
+//
+// Keys must be identifiers, values can be anything implementing Display.
+
 macro_rules! log {
+    ($logger:expr, $severity:expr, $fmt:expr $(, $arg:expr)* ; $( $key:ident = $value:expr ),+ $(,)?) => {
+        $logger.log_fields(
+            file!(),
+            line!(),
+            $severity,
+            &format!($fmt $(, $arg)*),
+            &[$( (stringify!($key), &$value as &dyn ::std::fmt::Display) ),+],
+        )
+    };
     ($logger:expr, $severity:expr, $( $arg:tt )*) => {
         $logger.log(file!(), line!(), $severity, &format!($($arg)*))
     };
 }
 
 macro_rules! log_debug {
+    ($logger:expr, $fmt:expr $(, $arg:expr)* ; $( $fields:tt )+) => {
+        log!($logger, $crate::utils::logger::Severity::DEBUG, $fmt $(, $arg)* ; $($fields)+)
+    };
     ($logger:expr, $( $arg:tt )*) => {
         $logger.debug(file!(), line!(), &format!($($arg)*))
     };
 }
 
 macro_rules! log_info {
+    ($logger:expr, $fmt:expr $(, $arg:expr)* ; $( $fields:tt )+) => {
+        log!($logger, $crate::utils::logger::Severity::INFO, $fmt $(, $arg)* ; $($fields)+)
+    };
     ($logger:expr, $( $arg:tt )*) => {
         $logger.info(file!(), line!(), &format!($($arg)*))
     };
 }
 
 macro_rules! log_warn {
+    ($logger:expr, $fmt:expr $(, $arg:expr)* ; $( $fields:tt )+) => {
+        log!($logger, $crate::utils::logger::Severity::WARN, $fmt $(, $arg)* ; $($fields)+)
+    };
     ($logger:expr, $( $arg:tt )*) => {
         $logger.warn(file!(), line!(), &format!($($arg)*))
     };
 }
 
 pub mod file;
+pub mod filter;
+pub mod format;
+pub mod redact;
+pub mod remote_syslog;
+pub mod ring;
 pub mod stderr;
 pub mod syslog;
 
+use std::fmt;
+
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+use crate::utils::RuntimeError;
+
+use self::filter::LogFilter;
+use self::redact::Redactor;
+
 /// Log message severity.
 #[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq)]
 pub enum Severity {
@@ -51,6 +92,41 @@ pub enum Severity {
     ERROR = 3,
 }
 
+impl Severity {
+    /// Get string representation of the severity.
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            Severity::DEBUG => "debug",
+            Severity::INFO => "info",
+            Severity::WARN => "warn",
+            Severity::ERROR => "error",
+        }
+    }
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Severity {
+    type Err = RuntimeError;
+
+    fn from_str(s: &str) -> Result<Severity, RuntimeError> {
+        match &s.to_ascii_lowercase() as &str {
+            "debug" => Ok(Severity::DEBUG),
+            "info" => Ok(Severity::INFO),
+            "warn" | "warning" => Ok(Severity::WARN),
+            "error" => Ok(Severity::ERROR),
+            _ => Err(RuntimeError::from(format!("unknown log level: \"{}\"", s))),
+        }
+    }
+}
+
+/// Key-value field attached to a log message.
+pub type Field<'a> = (&'a str, &'a dyn Display);
+
 const DEBUG: Severity = Severity::DEBUG;
 const INFO: Severity = Severity::INFO;
 const WARN: Severity = Severity::WARN;
@@ -61,6 +137,19 @@ pub trait Logger: Send {
     /// Log a given message with a given severity.
     fn log(&mut self, file: &str, line: u32, s: Severity, msg: &str);
 
+    /// Log a given message with a given severity and given key-value
+    /// fields. Loggers without support for structured output append the
+    /// fields to the message.
+    fn log_fields(&mut self, file: &str, line: u32, s: Severity, msg: &str, fields: &[Field]) {
+        if fields.is_empty() {
+            self.log(file, line, s, msg)
+        } else {
+            let msg = format!("{} {}", msg, format::format_fields(fields));
+
+            self.log(file, line, s, &msg)
+        }
+    }
+
     /// Set minimum log level.
     ///
     /// Messages with lover level will be discarded.
@@ -105,39 +194,109 @@ where
     }
 }
 
-/// Abstraction from a concrete logger type.
+/// Abstraction from a concrete logger type. The boxed logger also applies
+/// a log filter and redacts secrets from all messages. All clones of the
+/// logger share the same filter and the same secrets.
 pub struct BoxLogger {
     logger: Box<dyn CloneableLogger + Send + Sync>,
+    filter: Arc<Mutex<LogFilter>>,
+    redactor: Arc<Mutex<Redactor>>,
 }
 
 impl BoxLogger {
     /// Create a new boxed logger.
     pub fn new<L: 'static + CloneableLogger + Send + Sync>(logger: L) -> BoxLogger {
+        let filter = LogFilter::new(logger.get_level());
+
         BoxLogger {
             logger: Box::new(logger),
+            filter: Arc::new(Mutex::new(filter)),
+            redactor: Arc::new(Mutex::new(Redactor::new())),
         }
     }
+
+    /// Get the current log filter.
+    pub fn get_filter(&self) -> LogFilter {
+        self.filter.lock().unwrap().clone()
+    }
+
+    /// Set a given log filter.
+    pub fn set_filter(&mut self, filter: LogFilter) {
+        let mut current = self.filter.lock().unwrap();
+
+        // the underlying logger must not discard messages enabled by the
+        // filter
+        self.logger.set_level(filter.min_level());
+
+        *current = filter;
+    }
+
+    /// Set secrets (e.g. the client password) that will be masked in all
+    /// log messages.
+    pub fn set_secrets<I>(&mut self, secrets: I)
+    where
+        I: IntoIterator<Item = String>,
+    {
+        self.redactor.lock().unwrap().set_secrets(secrets)
+    }
+
+    /// Check if a given message should be logged.
+    fn is_enabled(&self, file: &str, s: Severity) -> bool {
+        self.filter.lock().unwrap().is_enabled(file, s)
+    }
 }
 
 impl Clone for BoxLogger {
     fn clone(&self) -> BoxLogger {
         let logger = self.logger.as_ref().clone();
 
-        BoxLogger { logger: logger }
+        BoxLogger {
+            logger,
+            filter: self.filter.clone(),
+            redactor: self.redactor.clone(),
+        }
     }
 }
 
 impl Logger for BoxLogger {
     fn log(&mut self, file: &str, line: u32, s: Severity, msg: &str) {
-        self.logger.log(file, line, s, msg)
+        if self.is_enabled(file, s) {
+            let redactor = self.redactor.lock().unwrap();
+
+            self.logger.log(file, line, s, &redactor.redact(msg))
+        }
+    }
+
+    fn log_fields(&mut self, file: &str, line: u32, s: Severity, msg: &str, fields: &[Field]) {
+        if self.is_enabled(file, s) {
+            let redactor = self.redactor.lock().unwrap();
+
+            let values = fields
+                .iter()
+                .map(|(_, value)| redactor.redact(&value.to_string()).into_owned())
+                .collect::<Vec<_>>();
+
+            let fields = fields
+                .iter()
+                .zip(&values)
+                .map(|(&(key, _), value)| (key, value as &dyn Display))
+                .collect::<Vec<_>>();
+
+            self.logger
+                .log_fields(file, line, s, &redactor.redact(msg), &fields)
+        }
     }
 
     fn set_level(&mut self, s: Severity) {
-        self.logger.set_level(s);
+        let mut filter = self.filter.lock().unwrap();
+
+        filter.set_level(s);
+
+        self.logger.set_level(filter.min_level());
     }
 
     fn get_level(&self) -> Severity {
-        self.logger.get_level()
+        self.filter.lock().unwrap().level()
     }
 }
 
@@ -147,11 +306,13 @@ mod tests {
 
     struct TestLogger {
         last_severity: Severity,
+        last_msg: String,
     }
 
     impl Logger for TestLogger {
-        fn log(&mut self, _: &str, _: u32, s: Severity, _: &str) {
+        fn log(&mut self, _: &str, _: u32, s: Severity, msg: &str) {
             self.last_severity = s;
+            self.last_msg = msg.to_string();
         }
 
         fn set_level(&mut self, _: Severity) {}
@@ -164,6 +325,7 @@ mod tests {
     fn test_logger() {
         let mut logger = TestLogger {
             last_severity: Severity::DEBUG,
+            last_msg: String::new(),
         };
 
         log_warn!(logger, "msg");
@@ -172,5 +334,11 @@ mod tests {
         assert_eq!(Severity::INFO, logger.last_severity);
         log_debug!(logger, "msg");
         assert_eq!(Severity::DEBUG, logger.last_severity);
+        log_warn!(logger, "msg {}", 1; session_id = "0000002a", service_id = 2);
+        assert_eq!(Severity::WARN, logger.last_severity);
+        assert_eq!("msg 1 session_id=0000002a service_id=2", logger.last_msg);
+        log!(logger, Severity::ERROR, "msg"; endpoint = "a b",);
+        assert_eq!(Severity::ERROR, logger.last_severity);
+        assert_eq!("msg endpoint=\"a b\"", logger.last_msg);
     }
 }
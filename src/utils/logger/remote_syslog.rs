@@ -0,0 +1,865 @@
+// Copyright 2019 click2stream, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Remote syslog logger definitions.
+//!
+//! The logger sends log messages to a remote syslog collector using UDP
+//! (RFC 5426), TCP (RFC 6587) or TLS (RFC 5425). Messages are formatted
+//! either according to RFC 5424 or according to the legacy BSD syslog
+//! format (RFC 3164). Logging never blocks. Messages are put into a bounded
+//! queue and they are sent from a background thread. If the collector is
+//! unreachable, the oldest messages are dropped when the queue is full.
+//! Number of dropped messages is reported to the collector once it is
+//! reachable again.
+
+use std::cmp;
+use std::fmt;
+use std::io;
+use std::process;
+use std::thread;
+
+use std::collections::VecDeque;
+use std::fmt::{Display, Formatter};
+use std::io::Write;
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs, UdpSocket};
+use std::str::FromStr;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+
+use libc::c_char;
+
+use openssl::ssl::{SslConnector, SslMethod, SslStream};
+
+use time;
+
+use crate::utils::logger::format::format_fields;
+use crate::utils::logger::{Field, Logger, Severity};
+use crate::utils::RuntimeError;
+
+/// Default size of the message queue.
+pub const DEFAULT_QUEUE_SIZE: usize = 1024;
+
+/// Default application name.
+pub const DEFAULT_APP_NAME: &str = "arrow-client";
+
+/// Timeout for connecting to the collector and for sending messages.
+const IO_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Minimum delay between reconnect attempts.
+const MIN_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// Maximum delay between reconnect attempts.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60);
+
+/// Structured data ID used for log message fields (the enterprise number
+/// is the one reserved for documentation purposes).
+const SD_ID: &str = "fields@32473";
+
+/// Facility names as defined in RFC 5424 (facilities without a commonly
+/// used name can be given only as numbers).
+const FACILITY_NAMES: &[(&str, u8)] = &[
+    ("kern", 0),
+    ("user", 1),
+    ("mail", 2),
+    ("daemon", 3),
+    ("auth", 4),
+    ("syslog", 5),
+    ("lpr", 6),
+    ("news", 7),
+    ("uucp", 8),
+    ("cron", 9),
+    ("authpriv", 10),
+    ("ftp", 11),
+    ("local0", 16),
+    ("local1", 17),
+    ("local2", 18),
+    ("local3", 19),
+    ("local4", 20),
+    ("local5", 21),
+    ("local6", 22),
+    ("local7", 23),
+];
+
+/// Syslog transport.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Transport {
+    Udp,
+    Tcp,
+    Tls,
+}
+
+impl Transport {
+    /// Get string representation of the transport.
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            Transport::Udp => "udp",
+            Transport::Tcp => "tcp",
+            Transport::Tls => "tls",
+        }
+    }
+
+    /// Get the default port for the transport.
+    pub fn default_port(&self) -> u16 {
+        match *self {
+            Transport::Udp => 514,
+            Transport::Tcp => 514,
+            Transport::Tls => 6514,
+        }
+    }
+}
+
+impl Display for Transport {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Transport {
+    type Err = RuntimeError;
+
+    fn from_str(s: &str) -> Result<Transport, RuntimeError> {
+        match &s.to_ascii_lowercase() as &str {
+            "udp" => Ok(Transport::Udp),
+            "tcp" => Ok(Transport::Tcp),
+            "tls" => Ok(Transport::Tls),
+            _ => Err(RuntimeError::from(format!(
+                "unknown syslog transport: \"{}\"",
+                s
+            ))),
+        }
+    }
+}
+
+/// Syslog message format.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Protocol {
+    /// The syslog protocol as defined in RFC 5424.
+    Rfc5424,
+    /// The BSD syslog protocol as described in RFC 3164.
+    Rfc3164,
+}
+
+impl Protocol {
+    /// Get string representation of the protocol.
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            Protocol::Rfc5424 => "rfc5424",
+            Protocol::Rfc3164 => "rfc3164",
+        }
+    }
+}
+
+impl Display for Protocol {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Protocol {
+    type Err = RuntimeError;
+
+    fn from_str(s: &str) -> Result<Protocol, RuntimeError> {
+        match &s.to_ascii_lowercase() as &str {
+            "rfc5424" => Ok(Protocol::Rfc5424),
+            "rfc3164" => Ok(Protocol::Rfc3164),
+            _ => Err(RuntimeError::from(format!(
+                "unknown syslog protocol: \"{}\"",
+                s
+            ))),
+        }
+    }
+}
+
+/// Syslog facility.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Facility {
+    code: u8,
+}
+
+impl Facility {
+    /// The "user" facility.
+    pub const USER: Facility = Facility { code: 1 };
+}
+
+impl Display for Facility {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
+        let name = FACILITY_NAMES
+            .iter()
+            .find(|&&(_, code)| code == self.code)
+            .map(|&(name, _)| name);
+
+        if let Some(name) = name {
+            f.write_str(name)
+        } else {
+            write!(f, "{}", self.code)
+        }
+    }
+}
+
+impl FromStr for Facility {
+    type Err = RuntimeError;
+
+    fn from_str(s: &str) -> Result<Facility, RuntimeError> {
+        let name = s.to_ascii_lowercase();
+
+        let code = FACILITY_NAMES
+            .iter()
+            .find(|&&(n, _)| n == name)
+            .map(|&(_, code)| code)
+            .or_else(|| s.parse().ok().filter(|&code| code < 24));
+
+        if let Some(code) = code {
+            Ok(Facility { code })
+        } else {
+            Err(RuntimeError::from(format!(
+                "unknown syslog facility: \"{}\"",
+                s
+            )))
+        }
+    }
+}
+
+/// Syslog message formatter.
+struct MessageFormatter {
+    protocol: Protocol,
+    facility: Facility,
+    hostname: String,
+    app_name: String,
+    pid: u32,
+}
+
+impl MessageFormatter {
+    /// Format a given log record.
+    fn format(&self, file: &str, line: u32, s: Severity, msg: &str, fields: &[Field]) -> String {
+        let severity = match s {
+            Severity::DEBUG => 7,
+            Severity::INFO => 6,
+            Severity::WARN => 4,
+            Severity::ERROR => 3,
+        };
+
+        let pri = (u32::from(self.facility.code) << 3) | severity;
+
+        match self.protocol {
+            Protocol::Rfc5424 => self.format_rfc5424(pri, file, line, msg, fields),
+            Protocol::Rfc3164 => self.format_rfc3164(pri, file, line, msg, fields),
+        }
+    }
+
+    /// Format a given log record according to RFC 5424. Fields are sent as
+    /// structured data.
+    fn format_rfc5424(
+        &self,
+        pri: u32,
+        file: &str,
+        line: u32,
+        msg: &str,
+        fields: &[Field],
+    ) -> String {
+        let now = time::now_utc();
+
+        let timestamp = format!(
+            "{}.{:03}Z",
+            time::strftime("%Y-%m-%dT%H:%M:%S", &now).unwrap(),
+            now.tm_nsec / 1_000_000
+        );
+
+        let mut sd = String::new();
+
+        if fields.is_empty() {
+            sd.push('-');
+        } else {
+            sd.push('[');
+            sd.push_str(SD_ID);
+
+            for &(key, value) in fields {
+                sd.push_str(&format!(" {}=\"{}\"", key, escape_param_value(value)));
+            }
+
+            sd.push(']');
+        }
+
+        format!(
+            "<{}>1 {} {} {} {} - {} [{}:{}] {}",
+            pri, timestamp, self.hostname, self.app_name, self.pid, sd, file, line, msg
+        )
+    }
+
+    /// Format a given log record according to RFC 3164. Fields are appended
+    /// to the message.
+    fn format_rfc3164(
+        &self,
+        pri: u32,
+        file: &str,
+        line: u32,
+        msg: &str,
+        fields: &[Field],
+    ) -> String {
+        let timestamp = time::strftime("%b %e %H:%M:%S", &time::now()).unwrap();
+
+        let mut res = format!(
+            "<{}>{} {} {}[{}]: [{}:{}] {}",
+            pri, timestamp, self.hostname, self.app_name, self.pid, file, line, msg
+        );
+
+        if !fields.is_empty() {
+            res.push(' ');
+            res.push_str(&format_fields(fields));
+        }
+
+        res
+    }
+}
+
+/// Escape a given structured data parameter value.
+fn escape_param_value(value: &dyn Display) -> String {
+    let value = value.to_string();
+
+    let mut res = String::with_capacity(value.len());
+
+    for c in value.chars() {
+        if c == '"' || c == '\\' || c == ']' {
+            res.push('\\');
+        }
+
+        res.push(c);
+    }
+
+    res
+}
+
+/// Replace all characters that are not allowed in syslog header fields
+/// (i.e. everything except printable US-ASCII characters) and truncate the
+/// value to a given length.
+fn sanitize_header_field(value: &str, max_len: usize) -> String {
+    let res = value
+        .chars()
+        .map(|c| if c.is_ascii_graphic() { c } else { '_' })
+        .take(max_len)
+        .collect::<String>();
+
+    if res.is_empty() {
+        String::from("-")
+    } else {
+        res
+    }
+}
+
+/// Get hostname of this machine.
+fn get_hostname() -> String {
+    let mut buffer = [0u8; 256];
+
+    let res = unsafe { libc::gethostname(buffer.as_mut_ptr() as *mut c_char, buffer.len()) };
+
+    if res != 0 {
+        return String::from("-");
+    }
+
+    let len = buffer.iter().position(|&b| b == 0).unwrap_or(buffer.len());
+
+    let hostname = String::from_utf8_lossy(&buffer[..len]);
+
+    sanitize_header_field(&hostname, 255)
+}
+
+/// Internal queue state.
+struct QueueState {
+    messages: VecDeque<String>,
+    dropped: u64,
+}
+
+/// Bounded message queue shared between the logger and the sender thread.
+struct MessageQueue {
+    state: Mutex<QueueState>,
+    available: Condvar,
+    capacity: usize,
+}
+
+impl MessageQueue {
+    /// Create a new queue with a given capacity.
+    fn new(capacity: usize) -> MessageQueue {
+        let state = QueueState {
+            messages: VecDeque::new(),
+            dropped: 0,
+        };
+
+        MessageQueue {
+            state: Mutex::new(state),
+            available: Condvar::new(),
+            capacity: cmp::max(capacity, 1),
+        }
+    }
+
+    /// Append a given message. The oldest message is dropped if the queue
+    /// is full.
+    fn push(&self, msg: String) {
+        let mut state = self.state.lock().unwrap();
+
+        if state.messages.len() >= self.capacity {
+            state.messages.pop_front();
+            state.dropped += 1;
+        }
+
+        state.messages.push_back(msg);
+
+        self.available.notify_one();
+    }
+
+    /// Put back a given message that could not be sent. The message is
+    /// dropped if the queue is full.
+    fn push_front(&self, msg: String) {
+        let mut state = self.state.lock().unwrap();
+
+        if state.messages.len() >= self.capacity {
+            state.dropped += 1;
+        } else {
+            state.messages.push_front(msg);
+        }
+    }
+
+    /// Take the oldest message. The method blocks until there is a message
+    /// available.
+    fn pop(&self) -> String {
+        let mut state = self.state.lock().unwrap();
+
+        loop {
+            if let Some(msg) = state.messages.pop_front() {
+                return msg;
+            }
+
+            state = self.available.wait(state).unwrap();
+        }
+    }
+
+    /// Get total number of dropped messages.
+    fn dropped(&self) -> u64 {
+        self.state.lock().unwrap().dropped
+    }
+}
+
+/// Connection to a syslog collector.
+enum Connection {
+    Udp(UdpSocket),
+    Tcp(TcpStream),
+    Tls(Box<SslStream<TcpStream>>),
+}
+
+impl Connection {
+    /// Send a given message using a given protocol.
+    fn send(&mut self, protocol: Protocol, msg: &str) -> io::Result<()> {
+        // stream transports need message framing (octet counting for RFC
+        // 5424 messages and LF separators for the legacy format)
+        let frame = || match protocol {
+            Protocol::Rfc5424 => format!("{} {}", msg.len(), msg),
+            Protocol::Rfc3164 => format!("{}\n", msg),
+        };
+
+        match self {
+            Connection::Udp(socket) => socket.send(msg.as_bytes()).map(|_| ()),
+            Connection::Tcp(stream) => stream.write_all(frame().as_bytes()),
+            Connection::Tls(stream) => stream.write_all(frame().as_bytes()),
+        }
+    }
+}
+
+/// Background sender of queued messages.
+struct Sender {
+    host: String,
+    port: u16,
+    transport: Transport,
+    tls_connector: Option<SslConnector>,
+    formatter: Arc<MessageFormatter>,
+    queue: Arc<MessageQueue>,
+    connection: Option<Connection>,
+    reported_drops: u64,
+}
+
+impl Sender {
+    /// Send all queued messages. The method never returns.
+    fn run(mut self) {
+        let mut retry_delay = MIN_RETRY_DELAY;
+
+        loop {
+            let msg = self.queue.pop();
+
+            if self.send(&msg).is_ok() {
+                retry_delay = MIN_RETRY_DELAY;
+            } else {
+                self.connection = None;
+                self.queue.push_front(msg);
+
+                thread::sleep(retry_delay);
+
+                retry_delay = cmp::min(retry_delay * 2, MAX_RETRY_DELAY);
+            }
+        }
+    }
+
+    /// Send a given message (and a notice about dropped messages if there
+    /// are any).
+    fn send(&mut self, msg: &str) -> io::Result<()> {
+        let protocol = self.formatter.protocol;
+
+        let dropped = self.queue.dropped();
+
+        let notice = if dropped > self.reported_drops {
+            let notice = format!(
+                "{} log messages dropped (remote syslog queue full)",
+                dropped - self.reported_drops
+            );
+
+            Some(
+                self.formatter
+                    .format(file!(), line!(), Severity::WARN, &notice, &[]),
+            )
+        } else {
+            None
+        };
+
+        let connection = match self.connection.take() {
+            Some(connection) => connection,
+            None => self.connect()?,
+        };
+
+        let connection = self.connection.get_or_insert(connection);
+
+        if let Some(notice) = notice {
+            connection.send(protocol, &notice)?;
+
+            self.reported_drops = dropped;
+        }
+
+        connection.send(protocol, msg)
+    }
+
+    /// Connect to the collector.
+    fn connect(&self) -> io::Result<Connection> {
+        let addrs = (self.host.as_str(), self.port)
+            .to_socket_addrs()?
+            .collect::<Vec<_>>();
+
+        let mut last_error = io::Error::other("unable to resolve the syslog host");
+
+        for addr in addrs {
+            match self.connect_to(addr) {
+                Ok(connection) => return Ok(connection),
+                Err(err) => last_error = err,
+            }
+        }
+
+        Err(last_error)
+    }
+
+    /// Connect to a given address.
+    fn connect_to(&self, addr: SocketAddr) -> io::Result<Connection> {
+        if self.transport == Transport::Udp {
+            let local_addr = if addr.is_ipv4() {
+                "0.0.0.0:0"
+            } else {
+                "[::]:0"
+            };
+
+            let socket = UdpSocket::bind(local_addr)?;
+
+            socket.connect(addr)?;
+
+            return Ok(Connection::Udp(socket));
+        }
+
+        let stream = TcpStream::connect_timeout(&addr, IO_TIMEOUT)?;
+
+        stream.set_write_timeout(Some(IO_TIMEOUT))?;
+
+        if self.transport == Transport::Tcp {
+            return Ok(Connection::Tcp(stream));
+        }
+
+        let connector = self
+            .tls_connector
+            .as_ref()
+            .ok_or_else(|| io::Error::other("missing TLS connector"))?;
+
+        let stream = connector
+            .connect(&self.host, stream)
+            .map_err(|err| io::Error::other(err.to_string()))?;
+
+        Ok(Connection::Tls(Box::new(stream)))
+    }
+}
+
+/// Remote syslog logger builder.
+pub struct RemoteSyslogBuilder {
+    host: String,
+    port: Option<u16>,
+    transport: Transport,
+    protocol: Protocol,
+    facility: Facility,
+    app_name: String,
+    queue_size: usize,
+    tls_connector: Option<SslConnector>,
+}
+
+impl RemoteSyslogBuilder {
+    /// Set collector port (the default port depends on the transport).
+    pub fn port(mut self, port: u16) -> RemoteSyslogBuilder {
+        self.port = Some(port);
+        self
+    }
+
+    /// Set transport (the default is UDP).
+    pub fn transport(mut self, transport: Transport) -> RemoteSyslogBuilder {
+        self.transport = transport;
+        self
+    }
+
+    /// Set message format (the default is RFC 5424).
+    pub fn protocol(mut self, protocol: Protocol) -> RemoteSyslogBuilder {
+        self.protocol = protocol;
+        self
+    }
+
+    /// Set facility (the default is "user").
+    pub fn facility(mut self, facility: Facility) -> RemoteSyslogBuilder {
+        self.facility = facility;
+        self
+    }
+
+    /// Set application name.
+    pub fn app_name(mut self, app_name: &str) -> RemoteSyslogBuilder {
+        self.app_name = app_name.to_string();
+        self
+    }
+
+    /// Set maximum number of queued messages.
+    pub fn queue_size(mut self, queue_size: usize) -> RemoteSyslogBuilder {
+        self.queue_size = queue_size;
+        self
+    }
+
+    /// Set TLS connector used for the TLS transport. A connector using the
+    /// system CA certificates is used by default.
+    pub fn tls_connector(mut self, connector: SslConnector) -> RemoteSyslogBuilder {
+        self.tls_connector = Some(connector);
+        self
+    }
+
+    /// Create the logger and start its background sender thread.
+    pub fn build(self) -> Result<RemoteSyslog, RuntimeError> {
+        let port = self.port.unwrap_or_else(|| self.transport.default_port());
+
+        let tls_connector = match self.tls_connector {
+            Some(connector) => Some(connector),
+            None if self.transport == Transport::Tls => {
+                let connector = SslConnector::builder(SslMethod::tls())
+                    .map_err(|err| {
+                        RuntimeError::from(format!(
+                            "unable to create a TLS connection builder: {}",
+                            err
+                        ))
+                    })?
+                    .build();
+
+                Some(connector)
+            }
+            None => None,
+        };
+
+        let formatter = MessageFormatter {
+            protocol: self.protocol,
+            facility: self.facility,
+            hostname: get_hostname(),
+            app_name: sanitize_header_field(&self.app_name, 48),
+            pid: process::id(),
+        };
+
+        let formatter = Arc::new(formatter);
+        let queue = Arc::new(MessageQueue::new(self.queue_size));
+
+        let sender = Sender {
+            host: self.host,
+            port,
+            transport: self.transport,
+            tls_connector,
+            formatter: formatter.clone(),
+            queue: queue.clone(),
+            connection: None,
+            reported_drops: 0,
+        };
+
+        thread::Builder::new()
+            .name("remote-syslog".to_string())
+            .spawn(move || sender.run())
+            .map_err(|err| {
+                RuntimeError::from(format!("unable to start remote syslog thread: {}", err))
+            })?;
+
+        let logger = RemoteSyslog {
+            level: Arc::new(Mutex::new(Severity::INFO)),
+            formatter,
+            queue,
+        };
+
+        Ok(logger)
+    }
+}
+
+/// Remote syslog logger. All clones of the logger share the same log level
+/// and the same message queue.
+#[derive(Clone)]
+pub struct RemoteSyslog {
+    level: Arc<Mutex<Severity>>,
+    formatter: Arc<MessageFormatter>,
+    queue: Arc<MessageQueue>,
+}
+
+impl RemoteSyslog {
+    /// Get total number of messages dropped because the message queue was
+    /// full.
+    pub fn dropped(&self) -> u64 {
+        self.queue.dropped()
+    }
+}
+
+/// Create a new remote syslog logger builder for a given collector host.
+/// The log level of the logger will be set to INFO.
+pub fn builder(host: &str) -> RemoteSyslogBuilder {
+    RemoteSyslogBuilder {
+        host: host.to_string(),
+        port: None,
+        transport: Transport::Udp,
+        protocol: Protocol::Rfc5424,
+        facility: Facility::USER,
+        app_name: DEFAULT_APP_NAME.to_string(),
+        queue_size: DEFAULT_QUEUE_SIZE,
+        tls_connector: None,
+    }
+}
+
+impl Logger for RemoteSyslog {
+    fn log(&mut self, file: &str, line: u32, s: Severity, msg: &str) {
+        self.log_fields(file, line, s, msg, &[])
+    }
+
+    fn log_fields(&mut self, file: &str, line: u32, s: Severity, msg: &str, fields: &[Field]) {
+        if s >= self.get_level() {
+            let msg = self.formatter.format(file, line, s, msg, fields);
+
+            self.queue.push(msg);
+        }
+    }
+
+    fn set_level(&mut self, s: Severity) {
+        *self.level.lock().unwrap() = s;
+    }
+
+    fn get_level(&self) -> Severity {
+        *self.level.lock().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn formatter(protocol: Protocol) -> MessageFormatter {
+        MessageFormatter {
+            protocol,
+            facility: Facility::from_str("local3").unwrap(),
+            hostname: String::from("gw"),
+            app_name: String::from("arrow"),
+            pid: 42,
+        }
+    }
+
+    #[test]
+    fn test_format() {
+        let fields: &[Field] = &[("session_id", &"0000002a"), ("endpoint", &"a\"b]")];
+
+        let msg =
+            formatter(Protocol::Rfc5424).format("src/foo.rs", 1, Severity::WARN, "msg", fields);
+
+        assert!(msg.starts_with("<156>1 "));
+        assert!(msg.ends_with(
+            " gw arrow 42 - [fields@32473 session_id=\"0000002a\" endpoint=\"a\\\"b\\]\"] [src/foo.rs:1] msg"
+        ));
+
+        let msg = formatter(Protocol::Rfc5424).format("src/foo.rs", 1, Severity::DEBUG, "msg", &[]);
+
+        assert!(msg.starts_with("<159>1 "));
+        assert!(msg.ends_with(" gw arrow 42 - - [src/foo.rs:1] msg"));
+
+        let msg =
+            formatter(Protocol::Rfc3164).format("src/foo.rs", 1, Severity::ERROR, "msg", fields);
+
+        assert!(msg.starts_with("<155>"));
+        assert!(msg.ends_with(
+            " gw arrow[42]: [src/foo.rs:1] msg session_id=0000002a endpoint=\"a\\\"b]\""
+        ));
+
+        assert_eq!(Facility::from_str("17").unwrap().to_string(), "local1");
+        assert!(Facility::from_str("24").is_err());
+        assert_eq!(sanitize_header_field("my app", 48), "my_app");
+    }
+
+    #[test]
+    fn test_queue() {
+        let queue = MessageQueue::new(2);
+
+        queue.push(String::from("1"));
+        queue.push(String::from("2"));
+        queue.push(String::from("3"));
+
+        assert_eq!(queue.dropped(), 1);
+        assert_eq!(queue.pop(), "2");
+
+        queue.push_front(String::from("2"));
+        queue.push_front(String::from("1"));
+
+        assert_eq!(queue.dropped(), 2);
+        assert_eq!(queue.pop(), "2");
+        assert_eq!(queue.pop(), "3");
+    }
+
+    #[test]
+    fn test_udp_logger() {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+
+        socket
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+
+        let port = socket.local_addr().unwrap().port();
+
+        let mut logger = builder("127.0.0.1")
+            .port(port)
+            .app_name("arrow-test")
+            .build()
+            .unwrap();
+
+        log_debug!(logger, "debug message");
+        log_info!(logger, "info message"; service_id = 7);
+
+        let mut buffer = [0u8; 1024];
+
+        let len = socket.recv(&mut buffer).unwrap();
+
+        let msg = String::from_utf8_lossy(&buffer[..len]);
+
+        assert!(msg.starts_with("<14>1 "));
+        assert!(msg.contains(" arrow-test "));
+        assert!(msg.contains(" [fields@32473 service_id=\"7\"] [src/"));
+        assert!(msg.ends_with("] info message"));
+        assert_eq!(logger.dropped(), 0);
+    }
+}
@@ -0,0 +1,164 @@
+// Copyright 2019 click2stream, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! In-memory log ring buffer.
+//!
+//! The ring buffer logger keeps the most recent log lines in memory (up to a
+//! given total size) and passes all messages to an underlying logger. The
+//! buffered lines can be retrieved at any time, e.g. by the Arrow Service.
+//! Secrets are expected to be redacted by the enclosing `BoxLogger`.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use crate::utils::logger::format::LogFormat;
+use crate::utils::logger::{BoxLogger, Field, Logger, Severity};
+
+/// Internal log ring buffer.
+struct InternalLogRingBuffer {
+    capacity: usize,
+    size: usize,
+    lines: VecDeque<(Severity, String)>,
+}
+
+impl InternalLogRingBuffer {
+    /// Push a given line into the buffer and drop the oldest lines that do
+    /// not fit into the buffer.
+    fn push(&mut self, s: Severity, line: String) {
+        self.size += line.len();
+        self.lines.push_back((s, line));
+
+        while self.size > self.capacity {
+            if let Some((_, line)) = self.lines.pop_front() {
+                self.size -= line.len();
+            } else {
+                break;
+            }
+        }
+    }
+}
+
+/// Shared log ring buffer.
+#[derive(Clone)]
+pub struct LogRingBuffer {
+    shared: Arc<Mutex<InternalLogRingBuffer>>,
+}
+
+impl LogRingBuffer {
+    /// Create a new log ring buffer with a given capacity (in bytes).
+    pub fn new(capacity: usize) -> LogRingBuffer {
+        let buffer = InternalLogRingBuffer {
+            capacity,
+            size: 0,
+            lines: VecDeque::new(),
+        };
+
+        LogRingBuffer {
+            shared: Arc::new(Mutex::new(buffer)),
+        }
+    }
+
+    /// Push a given log line into the buffer.
+    pub fn push(&self, s: Severity, line: String) {
+        self.shared.lock().unwrap().push(s, line)
+    }
+
+    /// Get all buffered log lines with a given minimum severity (the oldest
+    /// line goes first).
+    pub fn get_lines(&self, min_severity: Severity) -> Vec<String> {
+        self.shared
+            .lock()
+            .unwrap()
+            .lines
+            .iter()
+            .filter(|(s, _)| *s >= min_severity)
+            .map(|(_, line)| line.clone())
+            .collect()
+    }
+}
+
+/// Logger wrapper storing all log lines into a ring buffer.
+#[derive(Clone)]
+pub struct RingBufferLogger {
+    logger: BoxLogger,
+    buffer: LogRingBuffer,
+}
+
+impl RingBufferLogger {
+    /// Create a new ring buffer logger storing log lines into a given
+    /// buffer and passing all messages to a given logger.
+    pub fn new(logger: BoxLogger, buffer: LogRingBuffer) -> RingBufferLogger {
+        RingBufferLogger { logger, buffer }
+    }
+}
+
+impl Logger for RingBufferLogger {
+    fn log(&mut self, file: &str, line: u32, s: Severity, msg: &str) {
+        self.log_fields(file, line, s, msg, &[])
+    }
+
+    fn log_fields(&mut self, file: &str, line: u32, s: Severity, msg: &str, fields: &[Field]) {
+        self.buffer
+            .push(s, LogFormat::Text.format(file, line, s, msg, fields));
+
+        self.logger.log_fields(file, line, s, msg, fields)
+    }
+
+    fn set_level(&mut self, s: Severity) {
+        self.logger.set_level(s)
+    }
+
+    fn get_level(&self) -> Severity {
+        self.logger.get_level()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::utils::logger::stderr;
+
+    #[test]
+    fn test_ring_buffer_logger() {
+        let buffer = LogRingBuffer::new(200);
+
+        let mut logger = RingBufferLogger::new(
+            BoxLogger::new(stderr::new().with_format(LogFormat::Json)),
+            buffer.clone(),
+        );
+
+        logger.set_level(Severity::ERROR);
+
+        log_info!(logger, "service added");
+        log_warn!(logger, "foo"; endpoint = "host");
+
+        let lines = buffer.get_lines(Severity::DEBUG);
+
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].ends_with("] service added"));
+        assert!(lines[1].ends_with("] foo endpoint=host"));
+
+        assert_eq!(buffer.get_lines(Severity::WARN).len(), 1);
+
+        for _ in 0..10 {
+            log_warn!(logger, "bar");
+        }
+
+        let lines = buffer.get_lines(Severity::DEBUG);
+
+        assert!(lines.len() < 10);
+        assert!(lines.iter().all(|line| line.ends_with("] bar")));
+    }
+}
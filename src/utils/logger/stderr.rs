@@ -16,56 +16,71 @@
 
 use std;
 use std::io::{Stderr, Write};
+use std::sync::{Arc, Mutex};
 
-use time;
+use crate::utils::logger::format::LogFormat;
+use crate::utils::logger::{Field, Logger, Severity};
 
-use crate::utils::logger::{Logger, Severity};
-
-/// stderr logger structure.
+/// stderr logger structure. All clones of the logger share the same log
+/// level.
 pub struct StderrLogger {
-    level: Severity,
+    level: Arc<Mutex<Severity>>,
     stderr: Stderr,
     pretty: bool,
+    format: LogFormat,
 }
 
 /// Create a new stderr logger with log level set to INFO.
 pub fn new() -> StderrLogger {
     StderrLogger {
-        level: Severity::INFO,
+        level: Arc::new(Mutex::new(Severity::INFO)),
         stderr: std::io::stderr(),
         pretty: false,
+        format: LogFormat::Text,
     }
 }
 
 /// Create a new stderr logger with color formatted messages.
 pub fn new_pretty() -> StderrLogger {
     StderrLogger {
-        level: Severity::INFO,
+        level: Arc::new(Mutex::new(Severity::INFO)),
         stderr: std::io::stderr(),
         pretty: true,
+        format: LogFormat::Text,
     }
 }
 
 impl Clone for StderrLogger {
     fn clone(&self) -> StderrLogger {
         StderrLogger {
-            level: self.level,
+            level: self.level.clone(),
             stderr: std::io::stderr(),
             pretty: self.pretty,
+            format: self.format,
         }
     }
 }
 
+impl StderrLogger {
+    /// Use a given log line format. Colors are used only with the text
+    /// format.
+    pub fn with_format(mut self, format: LogFormat) -> StderrLogger {
+        self.format = format;
+        self
+    }
+}
+
 impl Logger for StderrLogger {
     fn log(&mut self, file: &str, line: u32, s: Severity, msg: &str) {
-        let t = time::strftime("%F %T", &time::now()).unwrap();
+        self.log_fields(file, line, s, msg, &[])
+    }
 
-        let severity = match s {
-            Severity::DEBUG => "DEBUG",
-            Severity::INFO => "INFO",
-            Severity::WARN => "WARNING",
-            Severity::ERROR => "ERROR",
-        };
+    fn log_fields(&mut self, file: &str, line: u32, s: Severity, msg: &str, fields: &[Field]) {
+        if s < self.get_level() {
+            return;
+        }
+
+        let line = self.format.format(file, line, s, msg, fields);
 
         let color = match s {
             Severity::DEBUG => "1;30",
@@ -74,30 +89,18 @@ impl Logger for StderrLogger {
             Severity::ERROR => "0;31",
         };
 
-        if s >= self.level {
-            if self.pretty {
-                writeln!(
-                    &mut self.stderr,
-                    "\x1b[{}m{} {:<7} [{}:{}] {}\x1b[m",
-                    color, t, severity, file, line, msg
-                )
-                .unwrap();
-            } else {
-                writeln!(
-                    &mut self.stderr,
-                    "{} {:<7} [{}:{}] {}",
-                    t, severity, file, line, msg
-                )
-                .unwrap();
-            }
+        if self.pretty && self.format == LogFormat::Text {
+            writeln!(&mut self.stderr, "\x1b[{}m{}\x1b[m", color, line).unwrap();
+        } else {
+            writeln!(&mut self.stderr, "{}", line).unwrap();
         }
     }
 
     fn set_level(&mut self, s: Severity) {
-        self.level = s;
+        *self.level.lock().unwrap() = s;
     }
 
     fn get_level(&self) -> Severity {
-        self.level
+        *self.level.lock().unwrap()
     }
 }
@@ -17,7 +17,7 @@
 use std::ptr;
 
 use std::ffi::CString;
-use std::sync::{Once, ONCE_INIT};
+use std::sync::{Arc, Mutex, Once, ONCE_INIT};
 
 use libc::{c_char, c_int, c_void};
 
@@ -41,10 +41,11 @@ extern "C" {
     fn syslog(priority: c_int, format: *const c_char, ...) -> c_void;
 }
 
-/// Syslog logger structure.
+/// Syslog logger structure. All clones of the logger share the same log
+/// level.
 #[derive(Debug, Clone)]
 pub struct Syslog {
-    level: Severity,
+    level: Arc<Mutex<Severity>>,
 }
 
 /// Create a new syslog logger with log level set to INFO.
@@ -54,7 +55,7 @@ pub fn new() -> Syslog {
     });
 
     Syslog {
-        level: Severity::INFO,
+        level: Arc::new(Mutex::new(Severity::INFO)),
     }
 }
 
@@ -66,7 +67,7 @@ impl Logger for Syslog {
         let fmt_ptr = cstr_fmt.as_ptr() as *const c_char;
         let msg_ptr = cstr_msg.as_ptr() as *const c_char;
 
-        if s >= self.level {
+        if s >= self.get_level() {
             unsafe {
                 match s {
                     Severity::DEBUG => syslog(LOG_DEBUG, fmt_ptr, msg_ptr),
@@ -79,10 +80,10 @@ impl Logger for Syslog {
     }
 
     fn set_level(&mut self, s: Severity) {
-        self.level = s;
+        *self.level.lock().unwrap() = s;
     }
 
     fn get_level(&self) -> Severity {
-        self.level
+        *self.level.lock().unwrap()
     }
 }
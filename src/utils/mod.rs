@@ -20,8 +20,11 @@ pub mod json;
 pub mod logger;
 
 pub mod string;
+pub mod systemd;
 
 use std::fmt;
+use std::fs;
+use std::io;
 use std::mem;
 use std::slice;
 
@@ -29,6 +32,9 @@ use std::any::Any;
 use std::error::Error;
 use std::ffi::CStr;
 use std::fmt::{Debug, Display, Formatter};
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
 
 use crate::utils::logger::{Logger, Severity};
 
@@ -107,6 +113,49 @@ pub unsafe fn cstr_to_string(ptr: *const i8) -> String {
     slice.to_string()
 }
 
+/// Replace content of a given file atomically. The data is written into a
+/// temporary file in the same directory first, the temporary file is synced
+/// to disk and renamed afterwards, so readers will never see a partially
+/// written file.
+pub fn write_file_atomically<P>(path: P, data: &[u8]) -> Result<(), io::Error>
+where
+    P: AsRef<Path>,
+{
+    let path = path.as_ref();
+
+    let mut tmp = path.as_os_str().to_os_string();
+
+    tmp.push(".tmp");
+
+    let tmp = PathBuf::from(tmp);
+
+    let res = File::create(&tmp)
+        .and_then(|mut file| {
+            file.write_all(data)?;
+            file.sync_all()
+        })
+        .and_then(|_| fs::rename(&tmp, path));
+
+    if res.is_err() {
+        fs::remove_file(&tmp).unwrap_or(());
+    }
+
+    res?;
+
+    // make sure that the rename is persisted as well (this is a best-effort
+    // operation, not all platforms/filesystems support syncing directories)
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+
+    if let Ok(dir) = File::open(dir) {
+        dir.sync_all().unwrap_or(());
+    }
+
+    Ok(())
+}
+
 /// Unwrap a given result or log an error with a given severity and return None.
 pub fn result_or_log<L, T, E, M>(
     logger: &mut L,
@@ -178,6 +227,24 @@ mod tests {
         assert_eq!(vec, vec2);
     }
 
+    #[test]
+    fn test_write_file_atomically() {
+        let path = std::env::temp_dir().join(format!("arrow-atomic-test-{}", std::process::id()));
+
+        write_file_atomically(&path, b"foo").unwrap();
+        write_file_atomically(&path, b"bar").unwrap();
+
+        assert_eq!(fs::read(&path).unwrap(), b"bar");
+
+        let mut tmp = path.as_os_str().to_os_string();
+
+        tmp.push(".tmp");
+
+        assert!(!Path::new(&tmp).exists());
+
+        fs::remove_file(&path).unwrap();
+    }
+
     #[test]
     fn test_cstr_to_string() {
         let cstr = CString::new("hello").unwrap();
@@ -0,0 +1,214 @@
+// Copyright 2019 click2stream, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Native implementation of the systemd notification protocol (see
+//! sd_notify(3)).
+
+use std::env;
+use std::io;
+use std::mem;
+use std::process;
+
+use std::os::unix::net::UnixDatagram;
+use std::sync::Arc;
+use std::time::Duration;
+
+use libc;
+
+/// systemd service manager notifier. The notifier is cloneable and all its
+/// copies share the same socket.
+#[derive(Clone)]
+pub struct Notifier {
+    socket: Arc<UnixDatagram>,
+    addr: String,
+}
+
+impl Notifier {
+    /// Create a new notifier using the `NOTIFY_SOCKET` environment variable.
+    /// `None` is returned if the variable is not set (i.e. the process has
+    /// not been started by systemd as a notify service).
+    pub fn from_env() -> Option<Result<Notifier, io::Error>> {
+        match env::var("NOTIFY_SOCKET") {
+            Ok(ref addr) if !addr.is_empty() => Some(Notifier::new(addr)),
+            _ => None,
+        }
+    }
+
+    /// Create a new notifier sending notifications to a given socket address.
+    /// Addresses starting with '@' refer to the Linux abstract namespace.
+    pub fn new(addr: &str) -> Result<Notifier, io::Error> {
+        let socket = UnixDatagram::unbound()?;
+
+        let res = Notifier {
+            socket: Arc::new(socket),
+            addr: addr.to_string(),
+        };
+
+        Ok(res)
+    }
+
+    /// Notify the service manager that the service startup is finished.
+    pub fn ready(&self) -> Result<(), io::Error> {
+        self.notify(&format!("READY=1\nMAINPID={}", process::id()))
+    }
+
+    /// Notify the service manager that the service is being reloaded. The
+    /// service manager must be notified using the `ready()` method once the
+    /// reload is finished.
+    pub fn reloading(&self) -> Result<(), io::Error> {
+        self.notify(&format!("RELOADING=1\nMONOTONIC_USEC={}", monotonic_usec()))
+    }
+
+    /// Notify the service manager that the service is shutting down.
+    pub fn stopping(&self) -> Result<(), io::Error> {
+        self.notify("STOPPING=1")
+    }
+
+    /// Send a given status message.
+    pub fn status(&self, status: &str) -> Result<(), io::Error> {
+        // the status must be a single line
+        let status = status.replace('\n', " ");
+
+        self.notify(&format!("STATUS={}", status))
+    }
+
+    /// Send a watchdog keep-alive ping.
+    pub fn watchdog(&self) -> Result<(), io::Error> {
+        self.notify("WATCHDOG=1")
+    }
+
+    /// Send a given notification (i.e. newline-separated variable
+    /// assignments).
+    pub fn notify(&self, state: &str) -> Result<(), io::Error> {
+        self.send(state.as_bytes())?;
+
+        Ok(())
+    }
+
+    #[cfg(target_os = "linux")]
+    /// Send given data to the notification socket.
+    fn send(&self, data: &[u8]) -> Result<usize, io::Error> {
+        use std::os::linux::net::SocketAddrExt;
+        use std::os::unix::net::SocketAddr;
+
+        if self.addr.starts_with('@') {
+            let addr = SocketAddr::from_abstract_name(&self.addr.as_bytes()[1..])?;
+
+            self.socket.send_to_addr(data, &addr)
+        } else {
+            self.socket.send_to(data, &self.addr)
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    /// Send given data to the notification socket.
+    fn send(&self, data: &[u8]) -> Result<usize, io::Error> {
+        self.socket.send_to(data, &self.addr)
+    }
+}
+
+/// Get the current value of the monotonic clock in microseconds.
+fn monotonic_usec() -> u64 {
+    unsafe {
+        let mut ts: libc::timespec = mem::zeroed();
+
+        libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut ts);
+
+        (ts.tv_sec as u64) * 1_000_000 + (ts.tv_nsec as u64) / 1_000
+    }
+}
+
+/// Get the interval for sending watchdog pings. The interval is based on
+/// the `WATCHDOG_USEC` and `WATCHDOG_PID` environment variables. `None` is
+/// returned if the watchdog is not enabled for this process.
+pub fn watchdog_interval() -> Option<Duration> {
+    let usec = env::var("WATCHDOG_USEC").ok()?;
+    let pid = env::var("WATCHDOG_PID").ok();
+
+    parse_watchdog_interval(&usec, pid.as_deref(), process::id())
+}
+
+/// Parse a given watchdog timeout and get the ping interval (half of the
+/// timeout, as recommended by systemd).
+fn parse_watchdog_interval(usec: &str, pid: Option<&str>, current_pid: u32) -> Option<Duration> {
+    if let Some(pid) = pid {
+        if pid.parse::<u32>().ok()? != current_pid {
+            return None;
+        }
+    }
+
+    let usec = usec.parse::<u64>().ok()?;
+
+    if usec == 0 {
+        None
+    } else {
+        Some(Duration::from_micros(usec / 2))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::fs;
+
+    #[test]
+    fn test_notifications() {
+        let path = env::temp_dir().join(format!("arrow-notify-test-{}", process::id()));
+
+        fs::remove_file(&path).unwrap_or(());
+
+        let socket = UnixDatagram::bind(&path).unwrap();
+
+        let notifier = Notifier::new(path.to_str().unwrap()).unwrap();
+
+        notifier.ready().unwrap();
+        notifier.status("connected\nto the service").unwrap();
+        notifier.watchdog().unwrap();
+
+        let mut buffer = [0u8; 256];
+
+        let len = socket.recv(&mut buffer).unwrap();
+
+        assert_eq!(
+            &buffer[..len],
+            format!("READY=1\nMAINPID={}", process::id()).as_bytes()
+        );
+
+        let len = socket.recv(&mut buffer).unwrap();
+
+        assert_eq!(&buffer[..len], b"STATUS=connected to the service");
+
+        let len = socket.recv(&mut buffer).unwrap();
+
+        assert_eq!(&buffer[..len], b"WATCHDOG=1");
+
+        fs::remove_file(&path).unwrap_or(());
+    }
+
+    #[test]
+    fn test_watchdog_interval() {
+        assert_eq!(
+            parse_watchdog_interval("10000000", None, 10),
+            Some(Duration::from_secs(5))
+        );
+        assert_eq!(
+            parse_watchdog_interval("10000000", Some("10"), 10),
+            Some(Duration::from_secs(5))
+        );
+        assert_eq!(parse_watchdog_interval("10000000", Some("11"), 10), None);
+        assert_eq!(parse_watchdog_interval("0", None, 10), None);
+        assert_eq!(parse_watchdog_interval("foo", None, 10), None);
+    }
+}